@@ -0,0 +1,82 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Source of the current time for timestamps
+//!
+//! Code that records when something happened asks a [`Clock`] instead of
+//! calling `Utc::now()`, so tests can pin time with a [`MockClock`].
+
+use chrono::{DateTime, Duration, Utc};
+use std::sync::{Arc, Mutex};
+
+/// Source of the current time
+pub trait Clock: Send + Sync + std::fmt::Debug {
+    /// Current time in UTC
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock that only moves when told to
+///
+/// Clones share the same time, so a test can keep one and hand another to
+/// the code under test.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl MockClock {
+    /// Create a clock stopped at `now`
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { now: Arc::new(Mutex::new(now)) }
+    }
+
+    /// Jump to `now`
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    /// Move the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+}
+
+impl Default for MockClock {
+    /// A clock stopped at the Unix epoch
+    fn default() -> Self {
+        Self::new(DateTime::<Utc>::UNIX_EPOCH)
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_is_shared_between_clones() {
+        let clock = MockClock::default();
+        let shared: Arc<dyn Clock> = Arc::new(clock.clone());
+        assert_eq!(shared.now(), DateTime::<Utc>::UNIX_EPOCH);
+
+        clock.advance(Duration::seconds(90));
+        assert_eq!(shared.now(), DateTime::<Utc>::UNIX_EPOCH + Duration::seconds(90));
+        clock.set(DateTime::<Utc>::UNIX_EPOCH + Duration::days(1));
+        assert_eq!(shared.now(), DateTime::<Utc>::UNIX_EPOCH + Duration::days(1));
+    }
+}
@@ -39,9 +39,9 @@ pub enum YKeyError {
     #[error("Operation cancelled by user")]
     UserCancelled,
 
-    /// Invalid PIN provided
-    #[error("Invalid PIN: {0}")]
-    InvalidPin(String),
+    /// Invalid PIN provided, with the attempts left before the PIN blocks
+    #[error("Invalid PIN: {message}{}", describe_retries(*.retries_remaining))]
+    InvalidPin { message: String, retries_remaining: Option<u8> },
 
     /// Device is locked (too many PIN attempts)
     #[error("Device is locked")]
@@ -67,6 +67,10 @@ pub enum YKeyError {
     #[error("Unsupported protocol version: {0}")]
     UnsupportedProtocolVersion(String),
 
+    /// Operation not supported by this device or transport
+    #[error("Unsupported operation {operation}: {reason}")]
+    UnsupportedOperation { operation: String, reason: String },
+
     /// Invalid request parameters
     #[error("Invalid request parameters: {0}")]
     InvalidParameters(String),
@@ -75,6 +79,10 @@ pub enum YKeyError {
     #[error("Operation timed out after {seconds} seconds")]
     Timeout { seconds: u64 },
 
+    /// Operation refused by a policy configured on the client
+    #[error("Operation not allowed: {0}")]
+    OperationNotAllowed(String),
+
     /// Permission denied (typically OS-level permissions)
     #[error("Permission denied: {0}")]
     PermissionDenied(String),
@@ -94,6 +102,26 @@ pub enum YKeyError {
     /// Generic error with context
     #[error("Operation failed: {0}")]
     Generic(#[from] anyhow::Error),
+
+    /// Some operations of a batch failed, each listed with what it acted on
+    #[error("{} of the operations failed: {}", .0.len(), describe_failures(.0))]
+    PartialFailure(Vec<(String, YKeyError)>),
+}
+
+fn describe_retries(retries_remaining: Option<u8>) -> String {
+    match retries_remaining {
+        Some(1) => ", 1 attempt remaining".to_string(),
+        Some(retries) => format!(", {} attempts remaining", retries),
+        None => String::new(),
+    }
+}
+
+fn describe_failures(failures: &[(String, YKeyError)]) -> String {
+    failures
+        .iter()
+        .map(|(subject, error)| format!("{}: {}", subject, error))
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 impl YKeyError {
@@ -102,7 +130,13 @@ impl YKeyError {
         Self::CommunicationError(message.into())
     }
 
-    /// Create a new CTAP error with code and message
+    /// Create the error for a CTAP status code
+    ///
+    /// Codes follow the CTAP2.1 status table. Statuses with a dedicated
+    /// variant map to it: PIN invalid becomes [`YKeyError::InvalidPin`],
+    /// PUAT required [`YKeyError::PinRequired`] and UP required
+    /// [`YKeyError::UserVerificationRequired`]. Every other code becomes a
+    /// [`YKeyError::CtapError`] carrying its description.
     pub fn ctap_error(code: u8) -> Self {
         let message = match code {
             0x01 => "Invalid command".to_string(),
@@ -113,47 +147,60 @@ impl YKeyError {
             0x06 => "Channel busy".to_string(),
             0x0A => "Lock required".to_string(),
             0x0B => "Invalid channel".to_string(),
-            0x10 => "CBOR unexpected type".to_string(),
-            0x11 => "Invalid CBOR".to_string(),
-            0x12 => "Missing parameter".to_string(),
-            0x13 => "Limit exceeded".to_string(),
-            0x14 => "Unsupported extension".to_string(),
-            0x15 => "Credential excluded".to_string(),
-            0x16 => "Processing".to_string(),
-            0x17 => "Invalid credential".to_string(),
-            0x18 => "User action pending".to_string(),
-            0x19 => "Operation pending".to_string(),
-            0x1A => "No operations".to_string(),
-            0x1B => "Unsupported algorithm".to_string(),
-            0x1C => "Operation denied".to_string(),
-            0x1D => "Key store full".to_string(),
-            0x1E => "No operation pending".to_string(),
-            0x1F => "Unsupported option".to_string(),
-            0x20 => "Invalid option".to_string(),
-            0x21 => "Keep alive cancel".to_string(),
-            0x22 => "No credentials".to_string(),
-            0x23 => "User action timeout".to_string(),
-            0x24 => "Not allowed".to_string(),
-            0x25 => "PIN invalid".to_string(),
-            0x26 => "PIN blocked".to_string(),
-            0x27 => "PIN auth invalid".to_string(),
-            0x28 => "PIN auth blocked".to_string(),
-            0x29 => "PIN not set".to_string(),
-            0x2A => "PIN required".to_string(),
-            0x2B => "PIN policy violation".to_string(),
-            0x2C => "PIN token expired".to_string(),
-            0x2D => "Request too large".to_string(),
-            0x2E => "Action timeout".to_string(),
-            0x2F => "Up required".to_string(),
-            0x30 => "UV blocked".to_string(),
-            0x31 => "Integrity failure".to_string(),
-            0x32 => "Invalid subcommand".to_string(),
-            0x33 => "UV invalid".to_string(),
-            0x34 => "Unauthorized permission".to_string(),
+            0x11 => "CBOR unexpected type".to_string(),
+            0x12 => "Invalid CBOR".to_string(),
+            0x14 => "Missing parameter".to_string(),
+            0x15 => "Limit exceeded".to_string(),
+            0x16 => "Unsupported extension".to_string(),
+            0x17 => "Fingerprint database full".to_string(),
+            0x18 => "Large blob storage full".to_string(),
+            0x19 => "Credential excluded".to_string(),
+            0x21 => "Processing".to_string(),
+            0x22 => "Invalid credential".to_string(),
+            0x23 => "User action pending".to_string(),
+            0x24 => "Operation pending".to_string(),
+            0x25 => "No operations".to_string(),
+            0x26 => "Unsupported algorithm".to_string(),
+            0x27 => "Operation denied".to_string(),
+            0x28 => "Key store full".to_string(),
+            0x2A => "No operation pending".to_string(),
+            0x2B => "Unsupported option".to_string(),
+            0x2C => "Invalid option".to_string(),
+            0x2D => "Keep alive cancel".to_string(),
+            0x2E => "No credentials".to_string(),
+            0x2F => "User action timeout".to_string(),
+            0x30 => "Not allowed".to_string(),
+            0x31 => "PIN invalid".to_string(),
+            0x32 => "PIN blocked".to_string(),
+            0x33 => "PIN auth invalid".to_string(),
+            0x34 => "PIN auth blocked".to_string(),
+            0x35 => "PIN not set".to_string(),
+            0x36 => "PIN/UV auth token required".to_string(),
+            0x37 => "PIN policy violation".to_string(),
+            0x38 => "PIN token expired".to_string(),
+            0x39 => "Request too large".to_string(),
+            0x3A => "Action timeout".to_string(),
+            0x3B => "UP required".to_string(),
+            0x3C => "UV blocked".to_string(),
+            0x3D => "Integrity failure".to_string(),
+            0x3E => "Invalid subcommand".to_string(),
+            0x3F => "UV invalid".to_string(),
+            0x40 => "Unauthorized permission".to_string(),
+            0x7F => "Other error".to_string(),
             _ => format!("Unknown error code: {:#04x}", code),
         };
         
-        Self::CtapError { code, message }
+        match code {
+            0x31 => Self::InvalidPin { message, retries_remaining: None },
+            0x36 => Self::PinRequired,
+            0x3B => Self::UserVerificationRequired,
+            _ => Self::CtapError { code, message },
+        }
+    }
+
+    /// Create an invalid PIN error
+    pub fn invalid_pin<S: Into<String>>(message: S, retries_remaining: Option<u8>) -> Self {
+        Self::InvalidPin { message: message.into(), retries_remaining }
     }
 
     /// Create a timeout error
@@ -171,11 +218,24 @@ impl YKeyError {
         Self::PermissionDenied(resource.into())
     }
 
+    /// Create an unsupported operation error
+    pub fn unsupported<S: Into<String>, R: Into<String>>(operation: S, reason: R) -> Self {
+        Self::UnsupportedOperation {
+            operation: operation.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Check if this error indicates the operation is not supported
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, YKeyError::UnsupportedOperation { .. })
+    }
+
     /// Check if this error indicates the device is locked
     pub fn is_device_locked(&self) -> bool {
         matches!(
             self,
-            YKeyError::DeviceLocked | YKeyError::CtapError { code: 0x26, .. }
+            YKeyError::DeviceLocked | YKeyError::CtapError { code: 0x32, .. }
         )
     }
 
@@ -183,7 +243,7 @@ impl YKeyError {
     pub fn is_pin_required(&self) -> bool {
         matches!(
             self,
-            YKeyError::PinRequired | YKeyError::CtapError { code: 0x2A, .. }
+            YKeyError::PinRequired | YKeyError::CtapError { code: 0x36, .. }
         )
     }
 
@@ -191,7 +251,7 @@ impl YKeyError {
     pub fn is_user_verification_required(&self) -> bool {
         matches!(
             self,
-            YKeyError::UserVerificationRequired | YKeyError::CtapError { code: 0x2F, .. }
+            YKeyError::UserVerificationRequired | YKeyError::CtapError { code: 0x3B, .. }
         )
     }
 
@@ -203,7 +263,7 @@ impl YKeyError {
                 | YKeyError::Timeout { .. }
                 | YKeyError::CommunicationError(_)
                 | YKeyError::CtapError { code: 0x06, .. } // Channel busy
-                | YKeyError::CtapError { code: 0x16, .. } // Processing
+                | YKeyError::CtapError { code: 0x21, .. } // Processing
         )
     }
 }
@@ -214,17 +274,47 @@ mod tests {
 
     #[test]
     fn test_ctap_error_messages() {
-        let error = YKeyError::ctap_error(0x25);
+        let error = YKeyError::ctap_error(0x31);
         assert!(error.to_string().contains("PIN invalid"));
         
-        let error = YKeyError::ctap_error(0x26);
+        let error = YKeyError::ctap_error(0x32);
         assert!(error.to_string().contains("PIN blocked"));
         assert!(error.is_device_locked());
     }
 
+    #[test]
+    fn test_ctap_status_maps_to_specific_variant() {
+        assert!(matches!(
+            YKeyError::ctap_error(0x31),
+            YKeyError::InvalidPin { message, retries_remaining: None } if message == "PIN invalid"
+        ));
+        assert!(matches!(YKeyError::ctap_error(0x36), YKeyError::PinRequired));
+        assert!(matches!(YKeyError::ctap_error(0x3B), YKeyError::UserVerificationRequired));
+        assert!(YKeyError::ctap_error(0x3B).is_user_verification_required());
+        // Neighbouring statuses that only look alike stay generic
+        assert!(matches!(YKeyError::ctap_error(0x2A), YKeyError::CtapError { code: 0x2A, .. }));
+        assert!(matches!(YKeyError::ctap_error(0x2F), YKeyError::CtapError { code: 0x2F, .. }));
+        assert_eq!(YKeyError::ctap_error(0x3D).to_string(), "CTAP error code: 0x3d - Integrity failure");
+        assert!(matches!(YKeyError::ctap_error(0x06), YKeyError::CtapError { code: 0x06, .. }));
+        assert!(matches!(YKeyError::ctap_error(0x7F), YKeyError::CtapError { code: 0x7F, .. }));
+    }
+
+    #[test]
+    fn test_invalid_pin_names_retries() {
+        assert_eq!(YKeyError::invalid_pin("PIN invalid", None).to_string(), "Invalid PIN: PIN invalid");
+        assert_eq!(
+            YKeyError::invalid_pin("PIN invalid", Some(2)).to_string(),
+            "Invalid PIN: PIN invalid, 2 attempts remaining"
+        );
+        assert_eq!(
+            YKeyError::invalid_pin("PIN invalid", Some(1)).to_string(),
+            "Invalid PIN: PIN invalid, 1 attempt remaining"
+        );
+    }
+
     #[test]
     fn test_error_classification() {
-        let pin_error = YKeyError::ctap_error(0x2A);
+        let pin_error = YKeyError::ctap_error(0x36);
         assert!(pin_error.is_pin_required());
         assert!(!pin_error.is_device_locked());
 
@@ -246,4 +336,25 @@ mod tests {
         assert!(comm_error.is_retryable());
         assert!(comm_error.to_string().contains("Failed to send data"));
     }
+
+    #[test]
+    fn test_partial_failure_lists_each_error() {
+        let error = YKeyError::PartialFailure(vec![
+            ("hid:1".to_string(), YKeyError::timeout(5)),
+            ("nfc:2".to_string(), YKeyError::DeviceBusy("in use".to_string())),
+        ]);
+        assert_eq!(
+            error.to_string(),
+            "2 of the operations failed: hid:1: Operation timed out after 5 seconds; nfc:2: Device busy: in use"
+        );
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn test_unsupported_operation_error() {
+        let error = YKeyError::unsupported("wink", "NFC has no wink command");
+        assert!(error.is_unsupported());
+        assert!(!error.is_retryable());
+        assert_eq!(error.to_string(), "Unsupported operation wink: NFC has no wink command");
+    }
 } 
\ No newline at end of file
@@ -5,13 +5,26 @@
 // SPDX-License-Identifier: MIT
 
 //! Core library for YKey hardware security key management
+//!
+//! The data types and errors build on their own. The async traits and
+//! helpers need the `async` feature, enabled by default; build with
+//! `default-features = false` to use just the types without tokio.
 
+pub mod clock;
 pub mod error;
+pub mod prelude;
+#[cfg(feature = "async")]
+pub mod retry;
 pub mod types;
+#[cfg(feature = "async")]
 pub mod traits;
 
 // Re-export commonly used types and traits
+pub use clock::{Clock, MockClock, SystemClock};
 pub use error::{YKeyError, YKeyResult};
+#[cfg(feature = "async")]
+pub use retry::{retry_async, RetryPolicy};
+#[cfg(feature = "async")]
 pub use traits::*;
 pub use types::*;
 
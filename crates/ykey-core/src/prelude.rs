@@ -0,0 +1,28 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! The commonly used types, traits and errors in one import
+//!
+//! ```
+//! use ykey_core::prelude::*;
+//!
+//! let id: DeviceId = "usb:1050:0407".parse()?;
+//! assert_eq!(id.as_str(), "usb:1050:0407");
+//! # Ok::<(), YKeyError>(())
+//! ```
+
+pub use crate::error::{YKeyError, YKeyResult};
+pub use crate::types::{
+    Aaguid, AssertionObject, AttestationObject, AuthenticatorInfo, Capability, ConnectOptions, Credential, CredentialId,
+    DeviceEvent, DeviceId, DeviceInfo, DeviceType, GetAssertionParams, MakeCredentialParams, RelyingParty,
+    TransportType, User,
+};
+
+#[cfg(feature = "async")]
+pub use crate::retry::RetryPolicy;
+#[cfg(feature = "async")]
+pub use crate::traits::{
+    AuditLogger, ConfigManager, CredentialStore, Device, DeviceCreator, DeviceDiscovery, Fido2Protocol, Transport,
+};
+#[cfg(feature = "async")]
+pub use crate::types::DeviceEventStream;
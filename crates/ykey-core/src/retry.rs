@@ -0,0 +1,140 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Retrying operations that fail with transient errors
+//!
+//! An authenticator that is busy on another channel or still processing a
+//! request, e.g. while waiting for user presence, answers with an error that
+//! `YKeyError::is_retryable` recognises. Those are retried with exponential
+//! backoff; any other error is returned straight away.
+
+use crate::YKeyResult;
+use std::future::Future;
+use std::time::Duration;
+
+/// How often and how patiently to retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each one after
+    pub base_delay: Duration,
+    /// Upper bound for a single delay
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a policy with the given attempts and base delay
+    pub fn new(max_attempts: u32, base_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            ..Self::default()
+        }
+    }
+
+    /// Delay to wait after the given failed attempt, counting from one
+    pub fn delay_after(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// Run `operation` until it succeeds, fails with a non-retryable error, or
+/// runs out of attempts
+///
+/// The last error is returned when every attempt failed.
+pub async fn retry_async<T, F, Fut>(policy: RetryPolicy, mut operation: F) -> YKeyResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = YKeyResult<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(error) if error.is_retryable() && attempt < policy.max_attempts => {
+                tokio::time::sleep(policy.delay_after(attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::YKeyError;
+    use std::cell::Cell;
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::new(max_attempts, Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let calls = Cell::new(0);
+        let result = retry_async(fast_policy(3), || async {
+            calls.set(calls.get() + 1);
+            match calls.get() {
+                1 => Err(YKeyError::DeviceBusy("in use".to_string())),
+                2 => Err(YKeyError::ctap_error(0x21)),
+                _ => Ok("done"),
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error_is_returned_immediately() {
+        let calls = Cell::new(0);
+        let result: YKeyResult<()> = retry_async(fast_policy(5), || async {
+            calls.set(calls.get() + 1);
+            Err(YKeyError::UserCancelled)
+        })
+        .await;
+
+        assert!(matches!(result, Err(YKeyError::UserCancelled)));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let calls = Cell::new(0);
+        let result: YKeyResult<()> = retry_async(fast_policy(2), || async {
+            calls.set(calls.get() + 1);
+            Err(YKeyError::timeout(1))
+        })
+        .await;
+
+        assert!(matches!(result, Err(YKeyError::Timeout { .. })));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+        };
+        assert_eq!(policy.delay_after(1), Duration::from_millis(100));
+        assert_eq!(policy.delay_after(2), Duration::from_millis(200));
+        assert_eq!(policy.delay_after(3), Duration::from_millis(400));
+        assert_eq!(policy.delay_after(4), Duration::from_millis(500));
+        assert_eq!(policy.delay_after(40), Duration::from_millis(500));
+    }
+}
@@ -4,11 +4,21 @@
 //! Core traits for YKey device and protocol abstractions
 
 use async_trait::async_trait;
+use std::sync::Arc;
 use crate::{
-    error::YKeyResult,
+    error::{YKeyError, YKeyResult},
     types::*,
 };
 
+/// Cancels the operation pending on a device from another task
+/// 
+/// Unlike [`Device::cancel`] it needs no access to the device, so it reaches
+/// a device that is busy with the operation being cancelled.
+pub trait Canceller: Send + Sync {
+    /// Ask the device to abort its pending operation
+    fn cancel(&self);
+}
+
 /// Core device abstraction trait
 /// 
 /// Provides the fundamental interface for hardware security key communication.
@@ -21,6 +31,15 @@ pub trait Device: Send + Sync {
     /// Establish connection to the hardware device
     async fn connect(&mut self) -> YKeyResult<()>;
     
+    /// Establish connection using transport-specific options
+    /// 
+    /// Implementations read the section for their own transport and ignore the
+    /// rest. The default ignores all options.
+    async fn connect_with(&mut self, options: &ConnectOptions) -> YKeyResult<()> {
+        let _ = options;
+        self.connect().await
+    }
+    
     /// Disconnect from the hardware device
     async fn disconnect(&mut self) -> YKeyResult<()>;
     
@@ -38,10 +57,195 @@ pub trait Device: Send + Sync {
         7609 // Default CTAP2 max message size
     }
     
+    /// Message bytes carried by the first packet and by each later packet
+    /// 
+    /// `None` for transports that take a whole message in `send_raw`. Others
+    /// receive their messages already split through `send_fragments`.
+    fn packet_payload_sizes(&self) -> Option<(usize, usize)> {
+        None
+    }
+    
+    /// Send a message split to `packet_payload_sizes`, one fragment per packet
+    /// 
+    /// The default sends the fragments joined back together.
+    async fn send_fragments(&mut self, fragments: &[&[u8]]) -> YKeyResult<Vec<u8>> {
+        self.send_raw(&fragments.concat()).await
+    }
+    
     /// Get device-specific timeout for operations
     fn operation_timeout(&self) -> std::time::Duration {
         std::time::Duration::from_secs(30)
     }
+    
+    /// Abort any operation pending on the device
+    /// 
+    /// Best-effort: devices without an out-of-band cancel path have nothing to abort.
+    async fn cancel(&mut self) -> YKeyResult<()> {
+        Ok(())
+    }
+    
+    /// Get a way to cancel the pending operation without locking the device
+    /// 
+    /// `None` when the transport can only cancel through `cancel`.
+    fn canceller(&self) -> Option<Arc<dyn Canceller>> {
+        None
+    }
+    
+    /// Send data to the device and receive it echoed back
+    async fn ping(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        let _ = data;
+        Err(YKeyError::unsupported("ping", "transport has no echo command"))
+    }
+    
+    /// Make the device signal its presence, e.g. by blinking its LED
+    async fn wink(&mut self) -> YKeyResult<()> {
+        Err(YKeyError::unsupported("wink", "transport has no wink command"))
+    }
+    
+    /// Discard transport-level session state and start a fresh one
+    async fn reset_channel(&mut self) -> YKeyResult<()> {
+        Err(YKeyError::unsupported("reset_channel", "transport has no logical channels"))
+    }
+    
+    /// Send a CTAP1/U2F APDU and return the raw response, status word included
+    async fn send_u2f(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>> {
+        let _ = apdu;
+        Err(YKeyError::unsupported("send_u2f", "transport has no U2F path"))
+    }
+}
+
+#[async_trait]
+impl<T: Device + ?Sized> Device for Box<T> {
+    async fn info(&self) -> YKeyResult<DeviceInfo> {
+        (**self).info().await
+    }
+    
+    async fn connect(&mut self) -> YKeyResult<()> {
+        (**self).connect().await
+    }
+    
+    async fn connect_with(&mut self, options: &ConnectOptions) -> YKeyResult<()> {
+        (**self).connect_with(options).await
+    }
+    
+    async fn disconnect(&mut self) -> YKeyResult<()> {
+        (**self).disconnect().await
+    }
+    
+    fn is_connected(&self) -> bool {
+        (**self).is_connected()
+    }
+    
+    async fn send_raw(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        (**self).send_raw(data).await
+    }
+    
+    fn max_message_size(&self) -> usize {
+        (**self).max_message_size()
+    }
+    
+    fn packet_payload_sizes(&self) -> Option<(usize, usize)> {
+        (**self).packet_payload_sizes()
+    }
+    
+    async fn send_fragments(&mut self, fragments: &[&[u8]]) -> YKeyResult<Vec<u8>> {
+        (**self).send_fragments(fragments).await
+    }
+    
+    fn operation_timeout(&self) -> std::time::Duration {
+        (**self).operation_timeout()
+    }
+    
+    async fn cancel(&mut self) -> YKeyResult<()> {
+        (**self).cancel().await
+    }
+    
+    fn canceller(&self) -> Option<Arc<dyn Canceller>> {
+        (**self).canceller()
+    }
+    
+    async fn ping(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        (**self).ping(data).await
+    }
+    
+    async fn wink(&mut self) -> YKeyResult<()> {
+        (**self).wink().await
+    }
+    
+    async fn reset_channel(&mut self) -> YKeyResult<()> {
+        (**self).reset_channel().await
+    }
+    
+    async fn send_u2f(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>> {
+        (**self).send_u2f(apdu).await
+    }
+}
+
+#[async_trait]
+impl<T: Device + ?Sized> Device for &mut T {
+    async fn info(&self) -> YKeyResult<DeviceInfo> {
+        (**self).info().await
+    }
+    
+    async fn connect(&mut self) -> YKeyResult<()> {
+        (**self).connect().await
+    }
+    
+    async fn connect_with(&mut self, options: &ConnectOptions) -> YKeyResult<()> {
+        (**self).connect_with(options).await
+    }
+    
+    async fn disconnect(&mut self) -> YKeyResult<()> {
+        (**self).disconnect().await
+    }
+    
+    fn is_connected(&self) -> bool {
+        (**self).is_connected()
+    }
+    
+    async fn send_raw(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        (**self).send_raw(data).await
+    }
+    
+    fn max_message_size(&self) -> usize {
+        (**self).max_message_size()
+    }
+    
+    fn packet_payload_sizes(&self) -> Option<(usize, usize)> {
+        (**self).packet_payload_sizes()
+    }
+    
+    async fn send_fragments(&mut self, fragments: &[&[u8]]) -> YKeyResult<Vec<u8>> {
+        (**self).send_fragments(fragments).await
+    }
+    
+    fn operation_timeout(&self) -> std::time::Duration {
+        (**self).operation_timeout()
+    }
+    
+    async fn cancel(&mut self) -> YKeyResult<()> {
+        (**self).cancel().await
+    }
+    
+    fn canceller(&self) -> Option<Arc<dyn Canceller>> {
+        (**self).canceller()
+    }
+    
+    async fn ping(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        (**self).ping(data).await
+    }
+    
+    async fn wink(&mut self) -> YKeyResult<()> {
+        (**self).wink().await
+    }
+    
+    async fn reset_channel(&mut self) -> YKeyResult<()> {
+        (**self).reset_channel().await
+    }
+    
+    async fn send_u2f(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>> {
+        (**self).send_u2f(apdu).await
+    }
 }
 
 /// FIDO2/WebAuthn protocol trait
@@ -153,6 +357,15 @@ pub trait CredentialStore: Send + Sync {
     /// Update a credential's usage information
     async fn update_usage(&mut self, id: &CredentialId) -> YKeyResult<()>;
     
+    /// Raise a credential's signature counter to `new_counter`
+    /// 
+    /// Applied atomically, and the stored counter never moves backwards, so
+    /// concurrent updates cannot lose the highest value seen.
+    async fn increment_counter(&mut self, id: &CredentialId, new_counter: u32) -> YKeyResult<()>;
+    
+    /// Record when a credential was last used
+    async fn update_last_used(&mut self, id: &CredentialId, time: chrono::DateTime<chrono::Utc>) -> YKeyResult<()>;
+    
     /// Clear all stored credentials
     async fn clear(&mut self) -> YKeyResult<()>;
     
@@ -254,8 +467,42 @@ pub struct PinComplexity {
     pub require_special_chars: bool,
 }
 
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            auto_discovery: true,
+            default_timeout: 30,
+            log_level: "info".to_string(),
+            ui_theme: "system".to_string(),
+            security_policies: SecurityPolicies::default(),
+        }
+    }
+}
+
+impl Default for SecurityPolicies {
+    fn default() -> Self {
+        Self {
+            require_pin: false,
+            require_user_verification: false,
+            max_pin_attempts: 8,
+            pin_complexity: PinComplexity::default(),
+        }
+    }
+}
+
+impl Default for PinComplexity {
+    fn default() -> Self {
+        Self {
+            min_length: 4,
+            max_length: 8,
+            require_digits: false,
+            require_special_chars: false,
+        }
+    }
+}
+
 /// Security event for audit logging
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct SecurityEvent {
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub event_type: EventType,
@@ -265,7 +512,7 @@ pub struct SecurityEvent {
 }
 
 /// Types of security events
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum EventType {
     DeviceConnected,
     DeviceDisconnected,
@@ -299,7 +546,7 @@ pub struct LogFilter {
 }
 
 /// Log levels
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
 pub enum LogLevel {
     Trace,
     Debug,
@@ -386,6 +633,36 @@ mod tests {
         assert!(!device.is_connected());
     }
 
+    #[tokio::test]
+    async fn test_default_transport_operations_unsupported() {
+        let mut device = MockDevice {
+            connected: true,
+            info: DeviceInfo::new(
+                "mock-device".to_string(),
+                "Mock Device".to_string(),
+                "Mock Manufacturer".to_string(),
+                "Mock Product".to_string(),
+                0x1234,
+                0x5678,
+                DeviceType::Generic,
+                TransportType::Nfc,
+            ),
+        };
+
+        match device.ping(&[1, 2, 3]).await {
+            Err(YKeyError::UnsupportedOperation { operation, reason }) => {
+                assert_eq!(operation, "ping");
+                assert!(!reason.is_empty());
+            }
+            other => panic!("expected UnsupportedOperation, got {:?}", other),
+        }
+        assert!(device.wink().await.unwrap_err().is_unsupported());
+
+        // Forwarding through a box keeps the default behaviour
+        let mut boxed: Box<dyn Device> = Box::new(device);
+        assert!(boxed.reset_channel().await.unwrap_err().is_unsupported());
+    }
+
     #[test]
     fn test_storage_stats() {
         let stats = StorageStats {
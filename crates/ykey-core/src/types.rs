@@ -5,13 +5,119 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::{borrow::Borrow, fmt, str::FromStr};
 use chrono::{DateTime, Utc};
+use crate::{clock::{Clock, SystemClock}, YKeyError, YKeyResult};
+
+/// Identifier of a discovered device
+///
+/// Discoveries that only know the USB identity use the
+/// `{device_type}-{vid:04x}-{pid:04x}` form built by [`DeviceId::usb`];
+/// others pick their own stable scheme, e.g. from the HID path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DeviceId(String);
+
+impl DeviceId {
+    /// Wrap an identifier as reported by a discovery
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    /// Identifier for a device known by its type and USB IDs
+    pub fn usb(device_type: DeviceType, vendor_id: u16, product_id: u16) -> Self {
+        let device_type = device_type.as_str().to_lowercase();
+        Self(format!("{}-{:04x}-{:04x}", device_type, vendor_id, product_id))
+    }
+
+    /// Vendor and product ID of an identifier built by [`DeviceId::usb`]
+    pub fn usb_ids(&self) -> Option<(u16, u16)> {
+        let mut parts = self.0.rsplitn(3, '-');
+        let (product, vendor, device_type) = (parts.next()?, parts.next()?, parts.next()?);
+        let hex = |part: &str| (part.len() == 4).then(|| u16::from_str_radix(part, 16).ok()).flatten();
+        if device_type.is_empty() {
+            return None;
+        }
+        Some((hex(vendor)?, hex(product)?))
+    }
+
+    /// The identifier as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for DeviceId {
+    type Err = YKeyError;
+
+    /// Accept any non-empty identifier without whitespace
+    fn from_str(id: &str) -> YKeyResult<Self> {
+        if id.is_empty() || id.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(YKeyError::InvalidParameters(format!("Invalid device ID {:?}", id)));
+        }
+        Ok(Self(id.to_string()))
+    }
+}
+
+impl From<String> for DeviceId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<&str> for DeviceId {
+    fn from(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl From<DeviceId> for String {
+    fn from(id: DeviceId) -> Self {
+        id.0
+    }
+}
+
+impl AsRef<str> for DeviceId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for DeviceId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for DeviceId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for DeviceId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for DeviceId {
+    fn eq(&self, other: &String) -> bool {
+        &self.0 == other
+    }
+}
 
 /// Device information containing metadata and capabilities
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct DeviceInfo {
     /// Unique device identifier
-    pub id: String,
+    pub id: DeviceId,
     /// Human-readable device name
     pub name: String,
     /// Device manufacturer
@@ -57,6 +163,40 @@ impl Default for DeviceType {
     }
 }
 
+impl DeviceType {
+    /// Every device type
+    pub const ALL: [Self; 5] = [Self::YubiKey, Self::CanoKey, Self::Nitrokey, Self::SoloKey, Self::Generic];
+
+    /// Stable name shown to users and sent to the frontend
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::YubiKey => "YubiKey",
+            Self::CanoKey => "CanoKey",
+            Self::Nitrokey => "Nitrokey",
+            Self::SoloKey => "SoloKey",
+            Self::Generic => "Generic",
+        }
+    }
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DeviceType {
+    type Err = YKeyError;
+
+    /// Parse a name produced by [`DeviceType::as_str`], ignoring case
+    fn from_str(name: &str) -> YKeyResult<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.as_str().eq_ignore_ascii_case(name))
+            .ok_or_else(|| YKeyError::InvalidParameters(format!("Unknown device type {:?}", name)))
+    }
+}
+
 /// Communication transport methods
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum TransportType {
@@ -76,6 +216,39 @@ impl Default for TransportType {
     }
 }
 
+impl TransportType {
+    /// Every transport
+    pub const ALL: [Self; 4] = [Self::Usb, Self::Nfc, Self::Bluetooth, Self::Hybrid];
+
+    /// Stable name shown to users and sent to the frontend
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Usb => "Usb",
+            Self::Nfc => "Nfc",
+            Self::Bluetooth => "Bluetooth",
+            Self::Hybrid => "Hybrid",
+        }
+    }
+}
+
+impl fmt::Display for TransportType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TransportType {
+    type Err = YKeyError;
+
+    /// Parse a name produced by [`TransportType::as_str`], ignoring case
+    fn from_str(name: &str) -> YKeyResult<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.as_str().eq_ignore_ascii_case(name))
+            .ok_or_else(|| YKeyError::InvalidParameters(format!("Unknown transport {:?}", name)))
+    }
+}
+
 /// Device capabilities
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Capability {
@@ -93,10 +266,45 @@ pub enum Capability {
     Otp,
 }
 
+impl Capability {
+    /// Every capability
+    pub const ALL: [Self; 6] = [Self::Fido2, Self::Fido1, Self::Oath, Self::Piv, Self::OpenPgp, Self::Otp];
+
+    /// Stable name shown to users and sent to the frontend
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Fido2 => "Fido2",
+            Self::Fido1 => "Fido1",
+            Self::Oath => "Oath",
+            Self::Piv => "Piv",
+            Self::OpenPgp => "OpenPgp",
+            Self::Otp => "Otp",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = YKeyError;
+
+    /// Parse a name produced by [`Capability::as_str`], ignoring case
+    fn from_str(name: &str) -> YKeyResult<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.as_str().eq_ignore_ascii_case(name))
+            .ok_or_else(|| YKeyError::InvalidParameters(format!("Unknown capability {:?}", name)))
+    }
+}
+
 impl DeviceInfo {
     /// Create a new DeviceInfo instance
     pub fn new(
-        id: String,
+        id: impl Into<DeviceId>,
         name: String,
         manufacturer: String,
         product_name: String,
@@ -106,7 +314,7 @@ impl DeviceInfo {
         transport: TransportType,
     ) -> Self {
         Self {
-            id,
+            id: id.into(),
             name,
             manufacturer,
             product_name,
@@ -117,7 +325,7 @@ impl DeviceInfo {
             transport,
             capabilities: Vec::new(),
             firmware_version: None,
-            last_seen: Utc::now(),
+            last_seen: SystemClock.now(),
         }
     }
 
@@ -135,7 +343,79 @@ impl DeviceInfo {
 
     /// Update the last seen timestamp
     pub fn update_last_seen(&mut self) {
-        self.last_seen = Utc::now();
+        self.update_last_seen_with(&SystemClock);
+    }
+
+    /// Update the last seen timestamp from `clock`
+    pub fn update_last_seen_with(&mut self, clock: &dyn Clock) {
+        self.last_seen = clock.now();
+    }
+}
+
+/// Transport-specific parameters used when opening a device
+/// 
+/// Each transport only reads its own section; options meant for other
+/// transports are ignored.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConnectOptions {
+    /// USB HID options
+    pub hid: Option<HidConnectOptions>,
+    /// NFC options
+    pub nfc: Option<NfcConnectOptions>,
+    /// Bluetooth options
+    pub ble: Option<BleConnectOptions>,
+}
+
+/// USB HID connection parameters
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HidConnectOptions {
+    /// Open this HID device path instead of the discovered one
+    pub path: Option<String>,
+}
+
+/// NFC connection parameters
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NfcConnectOptions {
+    /// PC/SC reader to use
+    pub reader_name: Option<String>,
+}
+
+/// Bluetooth connection parameters
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BleConnectOptions {
+    /// Peripheral address to connect to
+    pub address: Option<String>,
+}
+
+impl ConnectOptions {
+    /// Create options selecting a specific NFC reader
+    pub fn nfc_reader(reader_name: impl Into<String>) -> Self {
+        Self {
+            nfc: Some(NfcConnectOptions {
+                reader_name: Some(reader_name.into()),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Create options selecting a specific HID device path
+    pub fn hid_path(path: impl Into<String>) -> Self {
+        Self {
+            hid: Some(HidConnectOptions {
+                path: Some(path.into()),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Create options selecting a specific Bluetooth address
+    pub fn ble_address(address: impl Into<String>) -> Self {
+        Self {
+            ble: Some(BleConnectOptions {
+                address: Some(address.into()),
+            }),
+            ..Default::default()
+        }
     }
 }
 
@@ -163,6 +443,9 @@ pub struct Credential {
     pub created_at: DateTime<Utc>,
     /// Last usage timestamp
     pub last_used: Option<DateTime<Utc>>,
+    /// Backup state (BS flag) when last seen, if it has been observed
+    #[serde(default)]
+    pub backup_state: Option<bool>,
 }
 
 /// FIDO2 MakeCredential parameters
@@ -186,6 +469,225 @@ pub struct MakeCredentialParams {
     pub pin_uv_auth_param: Option<Vec<u8>>,
     /// PIN/UV auth protocol version
     pub pin_uv_auth_protocol: Option<u8>,
+    /// Attestation conveyance requested by the relying party
+    #[serde(default)]
+    pub attestation_preference: AttestationConveyance,
+    /// Data to store with the credential through the credBlob extension
+    #[serde(default)]
+    pub cred_blob: Option<Vec<u8>>,
+    /// Ask for the credential's largeBlobKey
+    #[serde(default)]
+    pub large_blob_key: bool,
+    /// Enterprise attestation kind: 1 = vendor-facilitated, 2 = platform-managed
+    #[serde(default)]
+    pub enterprise_attestation: Option<u8>,
+}
+
+/// Length of a SHA-256 client data hash, as CTAP2 requires
+pub const CLIENT_DATA_HASH_LENGTH: usize = 32;
+
+impl MakeCredentialParams {
+    /// Create parameters with default options, validating the client data hash
+    pub fn new(
+        client_data_hash: Vec<u8>,
+        rp: RelyingParty,
+        user: User,
+        pub_key_cred_params: Vec<PublicKeyCredentialParameter>,
+    ) -> YKeyResult<Self> {
+        let params = Self {
+            client_data_hash,
+            rp,
+            user,
+            pub_key_cred_params,
+            exclude_list: None,
+            extensions: None,
+            options: MakeCredentialOptions::default(),
+            pin_uv_auth_param: None,
+            pin_uv_auth_protocol: None,
+            attestation_preference: AttestationConveyance::default(),
+            cred_blob: None,
+            large_blob_key: false,
+            enterprise_attestation: None,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Check that the client data hash has the length CTAP2 expects
+    pub fn validate(&self) -> YKeyResult<()> {
+        validate_client_data_hash(&self.client_data_hash)
+    }
+
+    /// Enterprise attestation kind to request, if any
+    ///
+    /// An `Enterprise` attestation preference without an explicit kind asks
+    /// for vendor-facilitated attestation.
+    pub fn requested_enterprise_attestation(&self) -> Option<u8> {
+        self.enterprise_attestation.or_else(|| {
+            (self.attestation_preference == AttestationConveyance::Enterprise)
+                .then_some(ENTERPRISE_ATTESTATION_VENDOR_FACILITATED)
+        })
+    }
+}
+
+/// Enterprise attestation chosen by the authenticator's vendor
+pub const ENTERPRISE_ATTESTATION_VENDOR_FACILITATED: u8 = 1;
+
+/// Enterprise attestation for any RP ID the platform allows
+pub const ENTERPRISE_ATTESTATION_PLATFORM_MANAGED: u8 = 2;
+
+fn validate_client_data_hash(hash: &[u8]) -> YKeyResult<()> {
+    if hash.len() != CLIENT_DATA_HASH_LENGTH {
+        return Err(YKeyError::InvalidParameters(format!(
+            "Client data hash must be {} bytes, got {}",
+            CLIENT_DATA_HASH_LENGTH,
+            hash.len()
+        )));
+    }
+    Ok(())
+}
+
+/// COSE algorithm requested when a builder is given none: ES256
+const DEFAULT_ALGORITHM: i64 = -7;
+
+/// Credential type of every WebAuthn credential
+const PUBLIC_KEY_TYPE: &str = "public-key";
+
+fn public_key_descriptor(id: Vec<u8>) -> PublicKeyCredentialDescriptor {
+    PublicKeyCredentialDescriptor {
+        cred_type: PUBLIC_KEY_TYPE.to_string(),
+        id,
+        transports: None,
+    }
+}
+
+fn required<T>(value: Option<T>, name: &str) -> YKeyResult<T> {
+    value.ok_or_else(|| YKeyError::InvalidParameters(format!("{} is required", name)))
+}
+
+/// Builder for [`MakeCredentialParams`]
+#[derive(Debug, Clone, Default)]
+pub struct MakeCredentialParamsBuilder {
+    client_data_hash: Option<Vec<u8>>,
+    rp: Option<RelyingParty>,
+    user: Option<User>,
+    algorithms: Vec<i64>,
+    exclude_list: Vec<PublicKeyCredentialDescriptor>,
+    options: MakeCredentialOptions,
+    attestation_preference: AttestationConveyance,
+    cred_blob: Option<Vec<u8>>,
+    large_blob_key: bool,
+    enterprise_attestation: Option<u8>,
+}
+
+impl MakeCredentialParams {
+    /// Start building parameters
+    pub fn builder() -> MakeCredentialParamsBuilder {
+        MakeCredentialParamsBuilder::default()
+    }
+}
+
+impl MakeCredentialParamsBuilder {
+    /// Relying party the credential is created for
+    pub fn rp_id(mut self, rp_id: impl Into<String>) -> Self {
+        self.rp = Some(RelyingParty { id: rp_id.into(), name: None, icon: None });
+        self
+    }
+
+    /// Relying party name shown by the authenticator
+    pub fn rp_name(mut self, name: impl Into<String>) -> Self {
+        if let Some(rp) = &mut self.rp {
+            rp.name = Some(name.into());
+        }
+        self
+    }
+
+    /// User account the credential belongs to
+    pub fn user(mut self, user: User) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Accept a COSE algorithm, in order of preference
+    pub fn add_algorithm(mut self, alg: i64) -> Self {
+        self.algorithms.push(alg);
+        self
+    }
+
+    /// Require a discoverable credential
+    pub fn resident_key(mut self, required: bool) -> Self {
+        self.options.rk = Some(required);
+        self
+    }
+
+    /// Require user verification
+    pub fn user_verification(mut self, required: bool) -> Self {
+        self.options.uv = Some(required);
+        self
+    }
+
+    /// Refuse to create a credential if the authenticator holds this one
+    pub fn exclude(mut self, credential_id: impl Into<Vec<u8>>) -> Self {
+        self.exclude_list.push(public_key_descriptor(credential_id.into()));
+        self
+    }
+
+    /// SHA-256 hash of the client data
+    pub fn client_data_hash(mut self, hash: impl Into<Vec<u8>>) -> Self {
+        self.client_data_hash = Some(hash.into());
+        self
+    }
+
+    /// Attestation conveyance requested by the relying party
+    pub fn attestation(mut self, preference: AttestationConveyance) -> Self {
+        self.attestation_preference = preference;
+        self
+    }
+
+    /// Store data with the credential through the credBlob extension
+    pub fn cred_blob(mut self, blob: impl Into<Vec<u8>>) -> Self {
+        self.cred_blob = Some(blob.into());
+        self
+    }
+
+    /// Ask for the credential's largeBlobKey
+    pub fn large_blob_key(mut self, requested: bool) -> Self {
+        self.large_blob_key = requested;
+        self
+    }
+
+    /// Request enterprise attestation of the given kind
+    pub fn enterprise_attestation(mut self, kind: u8) -> Self {
+        self.enterprise_attestation = Some(kind);
+        self
+    }
+
+    /// Check the required fields and build the parameters
+    ///
+    /// ES256 is requested when no algorithm was added.
+    pub fn build(self) -> YKeyResult<MakeCredentialParams> {
+        let rp = required(self.rp, "Relying party")?;
+        let user = required(self.user, "User")?;
+        let client_data_hash = required(self.client_data_hash, "Client data hash")?;
+
+        let algorithms = if self.algorithms.is_empty() { vec![DEFAULT_ALGORITHM] } else { self.algorithms };
+        let mut params = MakeCredentialParams::new(
+            client_data_hash,
+            rp,
+            user,
+            algorithms
+                .into_iter()
+                .map(|alg| PublicKeyCredentialParameter { cred_type: PUBLIC_KEY_TYPE.to_string(), alg })
+                .collect(),
+        )?;
+        params.exclude_list = (!self.exclude_list.is_empty()).then_some(self.exclude_list);
+        params.options = self.options;
+        params.attestation_preference = self.attestation_preference;
+        params.cred_blob = self.cred_blob;
+        params.large_blob_key = self.large_blob_key;
+        params.enterprise_attestation = self.enterprise_attestation;
+        Ok(params)
+    }
 }
 
 /// FIDO2 GetAssertion parameters
@@ -205,6 +707,98 @@ pub struct GetAssertionParams {
     pub pin_uv_auth_param: Option<Vec<u8>>,
     /// PIN/UV auth protocol version
     pub pin_uv_auth_protocol: Option<u8>,
+    /// Ask for the data stored through the credBlob extension
+    #[serde(default)]
+    pub get_cred_blob: bool,
+    /// Ask for the credential's largeBlobKey
+    #[serde(default)]
+    pub large_blob_key: bool,
+}
+
+/// Builder for [`GetAssertionParams`]
+#[derive(Debug, Clone, Default)]
+pub struct GetAssertionParamsBuilder {
+    rp_id: Option<String>,
+    client_data_hash: Option<Vec<u8>>,
+    allow_list: Vec<PublicKeyCredentialDescriptor>,
+    options: GetAssertionOptions,
+    get_cred_blob: bool,
+    large_blob_key: bool,
+}
+
+impl GetAssertionParams {
+    /// Start building parameters
+    pub fn builder() -> GetAssertionParamsBuilder {
+        GetAssertionParamsBuilder::default()
+    }
+
+    /// Check that the client data hash has the length CTAP2 expects
+    pub fn validate(&self) -> YKeyResult<()> {
+        validate_client_data_hash(&self.client_data_hash)
+    }
+}
+
+impl GetAssertionParamsBuilder {
+    /// Relying party to assert for
+    pub fn rp_id(mut self, rp_id: impl Into<String>) -> Self {
+        self.rp_id = Some(rp_id.into());
+        self
+    }
+
+    /// Accept an assertion from this credential
+    pub fn allow(mut self, credential_id: impl Into<Vec<u8>>) -> Self {
+        self.allow_list.push(public_key_descriptor(credential_id.into()));
+        self
+    }
+
+    /// Require user presence
+    pub fn user_presence(mut self, required: bool) -> Self {
+        self.options.up = Some(required);
+        self
+    }
+
+    /// Require user verification
+    pub fn user_verification(mut self, required: bool) -> Self {
+        self.options.uv = Some(required);
+        self
+    }
+
+    /// SHA-256 hash of the client data
+    pub fn client_data_hash(mut self, hash: impl Into<Vec<u8>>) -> Self {
+        self.client_data_hash = Some(hash.into());
+        self
+    }
+
+    /// Ask for the data stored through the credBlob extension
+    pub fn get_cred_blob(mut self, requested: bool) -> Self {
+        self.get_cred_blob = requested;
+        self
+    }
+
+    /// Ask for the credential's largeBlobKey
+    pub fn large_blob_key(mut self, requested: bool) -> Self {
+        self.large_blob_key = requested;
+        self
+    }
+
+    /// Check the required fields and build the parameters
+    pub fn build(self) -> YKeyResult<GetAssertionParams> {
+        let rp_id = required(self.rp_id, "Relying party")?;
+        let client_data_hash = required(self.client_data_hash, "Client data hash")?;
+        validate_client_data_hash(&client_data_hash)?;
+
+        Ok(GetAssertionParams {
+            rp_id,
+            client_data_hash,
+            allow_list: (!self.allow_list.is_empty()).then_some(self.allow_list),
+            extensions: None,
+            options: self.options,
+            pin_uv_auth_param: None,
+            pin_uv_auth_protocol: None,
+            get_cred_blob: self.get_cred_blob,
+            large_blob_key: self.large_blob_key,
+        })
+    }
 }
 
 /// Relying Party information
@@ -262,6 +856,24 @@ pub struct MakeCredentialOptions {
     pub up: Option<bool>,
 }
 
+/// Attestation conveyance preference, mirroring the WebAuthn `attestation` option
+/// 
+/// Authenticators always return an attestation statement, so `None` is
+/// enforced by the client after the credential is created.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttestationConveyance {
+    /// Replace the attestation with the `none` format
+    None,
+    /// Allow the client to anonymize the attestation; currently returned as is
+    Indirect,
+    /// Return the attestation as produced by the authenticator
+    #[default]
+    Direct,
+    /// Request enterprise attestation identifying the individual authenticator
+    Enterprise,
+}
+
 /// Options for GetAssertion operation
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct GetAssertionOptions {
@@ -280,6 +892,12 @@ pub struct AttestationObject {
     pub att_stmt: HashMap<String, serde_json::Value>,
     /// Authenticator data
     pub auth_data: Vec<u8>,
+    /// Whether the authenticator stored the requested credBlob
+    #[serde(default)]
+    pub cred_blob_stored: Option<bool>,
+    /// Key for the credential's entry in the large blob array
+    #[serde(default)]
+    pub large_blob_key: Option<Vec<u8>>,
 }
 
 /// Assertion object returned by GetAssertion
@@ -293,17 +911,95 @@ pub struct AssertionObject {
     pub signature: Vec<u8>,
     /// User information (for resident keys)
     pub user: Option<User>,
+    /// Number of matching credentials, only reported with the first assertion
+    #[serde(default)]
+    pub number_of_credentials: Option<u32>,
+    /// Data stored with the credential through the credBlob extension
+    #[serde(default)]
+    pub cred_blob: Option<Vec<u8>>,
+    /// Key for the credential's entry in the large blob array
+    #[serde(default)]
+    pub large_blob_key: Option<Vec<u8>>,
+}
+
+/// Authenticator Attestation GUID identifying an authenticator model
+///
+/// All zeros means the authenticator does not disclose its model. Displays
+/// in the hyphenated UUID form; `{:x}` gives plain hex.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Aaguid([u8; 16]);
+
+impl Aaguid {
+    /// Length of an AAGUID in bytes
+    pub const LEN: usize = 16;
+
+    pub const fn new(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    /// Read an AAGUID, rejecting anything other than exactly 16 bytes
+    pub fn from_slice(bytes: &[u8]) -> YKeyResult<Self> {
+        bytes.try_into().map(Self).map_err(|_| {
+            YKeyError::InvalidParameters(format!("AAGUID must be {} bytes, got {}", Self::LEN, bytes.len()))
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Whether this is the all-zero AAGUID of an anonymous authenticator
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 16]
+    }
+}
+
+impl fmt::Display for Aaguid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::LowerHex for Aaguid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl From<[u8; 16]> for Aaguid {
+    fn from(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<Aaguid> for [u8; 16] {
+    fn from(aaguid: Aaguid) -> Self {
+        aaguid.0
+    }
+}
+
+impl AsRef<[u8]> for Aaguid {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
 }
 
 /// Authenticator information from GetInfo
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AuthenticatorInfo {
     /// List of supported versions
     pub versions: Vec<String>,
     /// List of supported extensions
     pub extensions: Option<Vec<String>>,
     /// AAGUID (Authenticator Attestation GUID)
-    pub aaguid: Vec<u8>,
+    pub aaguid: Aaguid,
     /// Supported options
     pub options: Option<HashMap<String, bool>>,
     /// Maximum message size
@@ -342,8 +1038,87 @@ pub struct AuthenticatorInfo {
     pub vendor_prototype_config_commands: Option<Vec<u64>>,
 }
 
+/// Optional CTAP command sets an authenticator supports, derived from GetInfo
+///
+/// MakeCredential, GetAssertion and GetInfo are always available and not listed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupportedCommands {
+    /// CTAP1/U2F register and authenticate
+    pub u2f: bool,
+    /// authenticatorClientPIN, whether or not a PIN is set yet
+    pub client_pin: bool,
+    /// authenticatorCredentialManagement, or its CTAP 2.1 preview
+    pub credential_management: bool,
+    /// authenticatorBioEnrollment, or its CTAP 2.1 preview
+    pub bio_enrollment: bool,
+    /// authenticatorConfig
+    pub config: bool,
+    /// authenticatorLargeBlobs
+    pub large_blobs: bool,
+}
+
+impl AuthenticatorInfo {
+    /// FIDO capabilities implied by the advertised versions
+    ///
+    /// Applet capabilities such as OATH or PIV are not visible through GetInfo
+    /// and have to be probed separately.
+    pub fn capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = Vec::new();
+        if self.versions.iter().any(|v| v.starts_with("FIDO_2_")) {
+            capabilities.push(Capability::Fido2);
+        }
+        if self.versions.iter().any(|v| v == "U2F_V2") {
+            capabilities.push(Capability::Fido1);
+        }
+        capabilities
+    }
+
+    /// Whether an option is advertised and set to true
+    pub fn option_enabled(&self, name: &str) -> bool {
+        self.options.as_ref().and_then(|options| options.get(name)) == Some(&true)
+    }
+
+    /// Whether an option is advertised at all, whatever its value
+    pub fn option_present(&self, name: &str) -> bool {
+        self.options.as_ref().is_some_and(|options| options.contains_key(name))
+    }
+
+    /// Optional command sets this authenticator supports
+    ///
+    /// `clientPin` and `bioEnroll` report support through their presence,
+    /// with the value only saying whether a PIN or fingerprint is enrolled.
+    pub fn supported_commands(&self) -> SupportedCommands {
+        SupportedCommands {
+            u2f: self.versions.iter().any(|v| v == "U2F_V2"),
+            client_pin: self.option_present("clientPin"),
+            credential_management: self.option_enabled("credMgmt") || self.option_enabled("credentialMgmtPreview"),
+            bio_enrollment: self.option_present("bioEnroll") || self.option_present("userVerificationMgmtPreview"),
+            config: self.option_enabled("authnrCfg"),
+            large_blobs: self.option_enabled("largeBlobs"),
+        }
+    }
+
+    /// Built-in UV attempts a platform should make before falling back to PIN
+    ///
+    /// Authenticators that do not advertise a preference get one attempt.
+    pub fn platform_uv_attempts(&self) -> u64 {
+        self.preferred_platform_uv_attempts.filter(|&attempts| attempts > 0).unwrap_or(1)
+    }
+}
+
+impl From<&AuthenticatorInfo> for Vec<Capability> {
+    fn from(info: &AuthenticatorInfo) -> Self {
+        info.capabilities()
+    }
+}
+
 /// Device event stream item
-#[derive(Debug, Clone)]
+///
+/// Serializes tagged by `type`, e.g. `{"type":"connected","device":{...}}`,
+/// `{"type":"disconnected","device_id":"..."}` or
+/// `{"type":"error","device_id":"...","error":"..."}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "DeviceEventRepr", into = "DeviceEventRepr")]
 pub enum DeviceEvent {
     /// Device connected
     Connected(DeviceInfo),
@@ -353,13 +1128,171 @@ pub enum DeviceEvent {
     Error { device_id: String, error: String },
 }
 
+/// Wire form of [`DeviceEvent`], naming the field of every variant
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum DeviceEventRepr {
+    Connected { device: DeviceInfo },
+    Disconnected { device_id: String },
+    Error { device_id: String, error: String },
+}
+
+impl From<DeviceEventRepr> for DeviceEvent {
+    fn from(repr: DeviceEventRepr) -> Self {
+        match repr {
+            DeviceEventRepr::Connected { device } => Self::Connected(device),
+            DeviceEventRepr::Disconnected { device_id } => Self::Disconnected(device_id),
+            DeviceEventRepr::Error { device_id, error } => Self::Error { device_id, error },
+        }
+    }
+}
+
+impl From<DeviceEvent> for DeviceEventRepr {
+    fn from(event: DeviceEvent) -> Self {
+        match event {
+            DeviceEvent::Connected(device) => Self::Connected { device },
+            DeviceEvent::Disconnected(device_id) => Self::Disconnected { device_id },
+            DeviceEvent::Error { device_id, error } => Self::Error { device_id, error },
+        }
+    }
+}
+
 /// Type alias for device event stream
+#[cfg(feature = "async")]
 pub type DeviceEventStream = tokio::sync::mpsc::Receiver<DeviceEvent>;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn info_with_options(versions: &[&str], options: &[(&str, bool)]) -> AuthenticatorInfo {
+        AuthenticatorInfo {
+            versions: versions.iter().map(|v| v.to_string()).collect(),
+            options: Some(options.iter().map(|(k, v)| (k.to_string(), *v)).collect()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_supported_commands_full() {
+        let info = info_with_options(
+            &["U2F_V2", "FIDO_2_0", "FIDO_2_1"],
+            &[
+                ("clientPin", false),
+                ("credMgmt", true),
+                ("bioEnroll", false),
+                ("authnrCfg", true),
+                ("largeBlobs", true),
+            ],
+        );
+        assert_eq!(
+            info.supported_commands(),
+            SupportedCommands {
+                u2f: true,
+                client_pin: true,
+                credential_management: true,
+                bio_enrollment: true,
+                config: true,
+                large_blobs: true,
+            }
+        );
+
+        // Preview options from CTAP 2.1 drafts count as well
+        let preview = info_with_options(
+            &["FIDO_2_1_PRE"],
+            &[("credentialMgmtPreview", true), ("userVerificationMgmtPreview", false)],
+        );
+        assert!(preview.supported_commands().credential_management);
+        assert!(preview.supported_commands().bio_enrollment);
+    }
+
+    #[test]
+    fn test_supported_commands_minimal() {
+        let info = info_with_options(&["FIDO_2_0"], &[("rk", true), ("up", true), ("largeBlobs", false)]);
+        assert_eq!(info.supported_commands(), SupportedCommands::default());
+
+        let no_options = AuthenticatorInfo { versions: vec!["FIDO_2_0".to_string()], ..Default::default() };
+        assert_eq!(no_options.supported_commands(), SupportedCommands::default());
+    }
+
+    #[test]
+    fn test_display_names_are_stable() {
+        // These strings reach the frontend; renaming a variant must not change them
+        let device_types: Vec<_> = DeviceType::ALL.iter().map(DeviceType::as_str).collect();
+        assert_eq!(device_types, ["YubiKey", "CanoKey", "Nitrokey", "SoloKey", "Generic"]);
+        let transports: Vec<_> = TransportType::ALL.iter().map(TransportType::as_str).collect();
+        assert_eq!(transports, ["Usb", "Nfc", "Bluetooth", "Hybrid"]);
+        let capabilities: Vec<_> = Capability::ALL.iter().map(Capability::as_str).collect();
+        assert_eq!(capabilities, ["Fido2", "Fido1", "Oath", "Piv", "OpenPgp", "Otp"]);
+
+        for device_type in DeviceType::ALL {
+            assert_eq!(device_type.to_string().parse::<DeviceType>().unwrap(), device_type);
+        }
+        for transport in TransportType::ALL {
+            assert_eq!(transport.to_string().parse::<TransportType>().unwrap(), transport);
+        }
+        for capability in Capability::ALL {
+            assert_eq!(capability.to_string().parse::<Capability>().unwrap(), capability);
+        }
+        assert_eq!("nfc".parse::<TransportType>().unwrap(), TransportType::Nfc);
+        assert!("Smartcard".parse::<TransportType>().is_err());
+        assert_eq!(DeviceId::usb(DeviceType::YubiKey, 0x1050, 0x0407), "yubikey-1050-0407");
+    }
+
+    #[test]
+    fn test_device_event_json() {
+        let mut info = DeviceInfo::new(
+            "yubikey-1050-0407",
+            "YubiKey 5".to_string(),
+            "Yubico".to_string(),
+            "YubiKey 5 NFC".to_string(),
+            0x1050,
+            0x0407,
+            DeviceType::YubiKey,
+            TransportType::Usb,
+        );
+        info.add_capability(Capability::Fido2);
+        let connected = DeviceEvent::Connected(info.clone());
+        let json = serde_json::to_value(&connected).unwrap();
+        assert_eq!(json["type"], "connected");
+        assert_eq!(json["device"], serde_json::to_value(&info).unwrap());
+        assert_eq!(serde_json::from_value::<DeviceEvent>(json).unwrap(), connected);
+
+        let disconnected = serde_json::to_value(DeviceEvent::Disconnected("yubikey-1050-0407".to_string())).unwrap();
+        assert_eq!(disconnected, serde_json::json!({"type": "disconnected", "device_id": "yubikey-1050-0407"}));
+
+        let error = DeviceEvent::Error { device_id: "nfc-key".to_string(), error: "Reader removed".to_string() };
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json, serde_json::json!({"type": "error", "device_id": "nfc-key", "error": "Reader removed"}));
+        assert_eq!(serde_json::from_value::<DeviceEvent>(json).unwrap(), error);
+    }
+
+    #[test]
+    fn test_aaguid_from_slice() {
+        let aaguid = Aaguid::from_slice(&[0x11; 16]).unwrap();
+        assert_eq!(aaguid, Aaguid::new([0x11; 16]));
+
+        for len in [0, 15, 17] {
+            let Err(YKeyError::InvalidParameters(message)) = Aaguid::from_slice(&vec![0; len]) else {
+                panic!("expected {}-byte AAGUID to be rejected", len);
+            };
+            assert_eq!(message, format!("AAGUID must be 16 bytes, got {}", len));
+        }
+    }
+
+    #[test]
+    fn test_aaguid_zero_and_formatting() {
+        assert!(Aaguid::default().is_zero());
+        assert_eq!(Aaguid::default().to_string(), "00000000-0000-0000-0000-000000000000");
+
+        let yubikey = Aaguid::new([
+            0xee, 0x88, 0x28, 0x79, 0x72, 0x1c, 0x49, 0x13, 0x97, 0x75, 0x3d, 0xfc, 0xce, 0x97, 0x07, 0x2a,
+        ]);
+        assert!(!yubikey.is_zero());
+        assert_eq!(yubikey.to_string(), "ee882879-721c-4913-9775-3dfcce97072a");
+        assert_eq!(format!("{:x}", yubikey), "ee882879721c491397753dfcce97072a");
+    }
+
     #[test]
     fn test_device_info_creation() {
         let device = DeviceInfo::new(
@@ -379,6 +1312,27 @@ mod tests {
         assert!(device.capabilities.is_empty());
     }
 
+    #[test]
+    fn test_update_last_seen_uses_clock() {
+        let clock = crate::clock::MockClock::new(DateTime::<Utc>::UNIX_EPOCH);
+        let mut device = DeviceInfo::new(
+            "test-id",
+            "Test Device".to_string(),
+            "Test Manufacturer".to_string(),
+            "Test Product".to_string(),
+            0x1234,
+            0x5678,
+            DeviceType::Generic,
+            TransportType::Usb,
+        );
+
+        device.update_last_seen_with(&clock);
+        assert_eq!(device.last_seen, DateTime::<Utc>::UNIX_EPOCH);
+        clock.advance(chrono::Duration::minutes(5));
+        device.update_last_seen_with(&clock);
+        assert_eq!(device.last_seen, DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::minutes(5));
+    }
+
     #[test]
     fn test_device_capabilities() {
         let mut device = DeviceInfo::new(
@@ -401,6 +1355,33 @@ mod tests {
         assert_eq!(device.capabilities.len(), 1);
     }
 
+    #[test]
+    fn test_device_id_round_trip() {
+        let id = DeviceId::usb(DeviceType::YubiKey, 0x1050, 0x0407);
+        assert_eq!(id, "yubikey-1050-0407");
+        assert_eq!(id.usb_ids(), Some((0x1050, 0x0407)));
+
+        let parsed: DeviceId = id.to_string().parse().unwrap();
+        assert_eq!(parsed, id);
+
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, "\"yubikey-1050-0407\"");
+        assert_eq!(serde_json::from_str::<DeviceId>(&json).unwrap(), id);
+    }
+
+    #[test]
+    fn test_device_id_rejects_malformed() {
+        assert!("".parse::<DeviceId>().is_err());
+        assert!("yubikey 1050".parse::<DeviceId>().is_err());
+        assert!("yubikey-1050-0407\n".parse::<DeviceId>().is_err());
+
+        assert_eq!(DeviceId::from("yubikey-10500407").usb_ids(), None);
+        assert_eq!(DeviceId::from("yubikey-zzzz-0407").usb_ids(), None);
+        assert_eq!(DeviceId::from("yubikey-1050-407").usb_ids(), None);
+        assert_eq!(DeviceId::from("-1050-0407").usb_ids(), None);
+        assert_eq!(DeviceId::from("/dev/hidraw1").usb_ids(), None);
+    }
+
     #[test]
     fn test_device_type_default() {
         let default_type = DeviceType::default();
@@ -421,6 +1402,106 @@ mod tests {
         assert_eq!(options.up, None);
     }
 
+    #[test]
+    fn test_make_credential_params_validate_hash_length() {
+        let build = |hash: Vec<u8>| {
+            MakeCredentialParams::new(
+                hash,
+                RelyingParty { id: "example.com".to_string(), name: None, icon: None },
+                User {
+                    id: vec![1],
+                    name: "user".to_string(),
+                    display_name: "User".to_string(),
+                    icon: None,
+                },
+                Vec::new(),
+            )
+        };
+
+        assert!(build(vec![0; CLIENT_DATA_HASH_LENGTH]).is_ok());
+        assert!(matches!(build(vec![0; 64]), Err(YKeyError::InvalidParameters(_))));
+        assert!(matches!(build(Vec::new()), Err(YKeyError::InvalidParameters(_))));
+    }
+
+    fn test_user() -> User {
+        User {
+            id: vec![1],
+            name: "user".to_string(),
+            display_name: "User".to_string(),
+            icon: None,
+        }
+    }
+
+    #[test]
+    fn test_make_credential_params_builder() {
+        let params = MakeCredentialParams::builder()
+            .rp_id("example.com")
+            .rp_name("Example")
+            .user(test_user())
+            .resident_key(true)
+            .exclude(vec![0xAA; 16])
+            .client_data_hash(vec![0; CLIENT_DATA_HASH_LENGTH])
+            .build()
+            .unwrap();
+
+        assert_eq!(params.rp.name.as_deref(), Some("Example"));
+        assert_eq!(params.pub_key_cred_params.len(), 1);
+        assert_eq!(params.pub_key_cred_params[0].alg, -7);
+        assert_eq!(params.pub_key_cred_params[0].cred_type, "public-key");
+        assert_eq!(params.options.rk, Some(true));
+        assert_eq!(params.options.uv, None);
+        assert_eq!(params.exclude_list.unwrap()[0].id, vec![0xAA; 16]);
+
+        let params = MakeCredentialParams::builder()
+            .rp_id("example.com")
+            .user(test_user())
+            .add_algorithm(-8)
+            .add_algorithm(-7)
+            .client_data_hash(vec![0; CLIENT_DATA_HASH_LENGTH])
+            .build()
+            .unwrap();
+        let algorithms: Vec<i64> = params.pub_key_cred_params.iter().map(|p| p.alg).collect();
+        assert_eq!(algorithms, vec![-8, -7]);
+        assert!(params.exclude_list.is_none());
+    }
+
+    #[test]
+    fn test_make_credential_params_builder_requires_fields() {
+        let missing_rp = MakeCredentialParams::builder()
+            .user(test_user())
+            .client_data_hash(vec![0; CLIENT_DATA_HASH_LENGTH])
+            .build();
+        assert!(matches!(missing_rp, Err(YKeyError::InvalidParameters(m)) if m.contains("Relying party")));
+
+        let missing_hash = MakeCredentialParams::builder().rp_id("example.com").user(test_user()).build();
+        assert!(matches!(missing_hash, Err(YKeyError::InvalidParameters(m)) if m.contains("Client data hash")));
+
+        let short_hash = MakeCredentialParams::builder()
+            .rp_id("example.com")
+            .user(test_user())
+            .client_data_hash(vec![0; 16])
+            .build();
+        assert!(matches!(short_hash, Err(YKeyError::InvalidParameters(_))));
+    }
+
+    #[test]
+    fn test_get_assertion_params_builder() {
+        let params = GetAssertionParams::builder()
+            .rp_id("example.com")
+            .allow(vec![0x01; 16])
+            .user_verification(true)
+            .client_data_hash(vec![0; CLIENT_DATA_HASH_LENGTH])
+            .build()
+            .unwrap();
+        assert_eq!(params.allow_list.unwrap().len(), 1);
+        assert_eq!(params.options.uv, Some(true));
+
+        let missing_rp = GetAssertionParams::builder().client_data_hash(vec![0; CLIENT_DATA_HASH_LENGTH]).build();
+        assert!(matches!(missing_rp, Err(YKeyError::InvalidParameters(m)) if m.contains("Relying party")));
+        let missing_hash = GetAssertionParams::builder().rp_id("example.com").build();
+        assert!(matches!(missing_hash, Err(YKeyError::InvalidParameters(m)) if m.contains("Client data hash")));
+    }
+
     #[test]
     fn test_credential_creation() {
         let credential = Credential {
@@ -433,10 +1514,24 @@ mod tests {
             counter: 1,
             created_at: Utc::now(),
             last_used: None,
+            backup_state: None,
         };
 
         assert_eq!(credential.rp_id, "example.com");
         assert_eq!(credential.counter, 1);
         assert!(credential.last_used.is_none());
     }
+
+    #[test]
+    fn test_capabilities_from_authenticator_info() {
+        let info = |versions: &[&str]| AuthenticatorInfo {
+            versions: versions.iter().map(|v| v.to_string()).collect(),
+            ..Default::default()
+        };
+
+        let capabilities: Vec<Capability> = (&info(&["U2F_V2", "FIDO_2_0", "FIDO_2_1"])).into();
+        assert_eq!(capabilities, vec![Capability::Fido2, Capability::Fido1]);
+        assert_eq!(info(&["FIDO_2_1_PRE"]).capabilities(), vec![Capability::Fido2]);
+        assert!(info(&[]).capabilities().is_empty());
+    }
 }
@@ -0,0 +1,49 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! The data types must build and work without the `async` feature
+//!
+//! Run with `cargo test -p ykey-core --no-default-features --test types_only`.
+
+use chrono::{DateTime, Utc};
+use ykey_core::{Capability, Credential, DeviceInfo, DeviceType, TransportType, YKeyError};
+
+#[test]
+fn test_device_info_without_async() {
+    let mut info = DeviceInfo::new(
+        "yubikey-1050-0407".to_string(),
+        "YubiKey 5".to_string(),
+        "Yubico".to_string(),
+        "YubiKey 5 NFC".to_string(),
+        0x1050,
+        0x0407,
+        DeviceType::YubiKey,
+        TransportType::Usb,
+    );
+    info.add_capability(Capability::Fido2);
+
+    let json = serde_json::to_string(&info).unwrap();
+    let decoded: DeviceInfo = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.id, "yubikey-1050-0407");
+    assert!(decoded.has_capability(&Capability::Fido2));
+}
+
+#[test]
+fn test_credential_without_async() {
+    let credential = Credential {
+        id: vec![0x01; 16],
+        rp_id: "example.com".to_string(),
+        user_id: b"alice".to_vec(),
+        user_name: "alice".to_string(),
+        user_display_name: "Alice".to_string(),
+        public_key: vec![0xA5, 0x01, 0x02],
+        counter: 3,
+        created_at: DateTime::<Utc>::UNIX_EPOCH,
+        last_used: None,
+        backup_state: None,
+    };
+
+    let decoded: Credential = serde_json::from_str(&serde_json::to_string(&credential).unwrap()).unwrap();
+    assert_eq!(decoded, credential);
+    assert!(YKeyError::CredentialNotFound(credential.rp_id).to_string().contains("example.com"));
+}
@@ -0,0 +1,236 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Aggregation of credentials enumerated from several keys
+//!
+//! The same account commonly exists on more than one key, each time under a
+//! different credential ID. Only the credential ID identifies a credential;
+//! the relying party and user are used for grouping alone.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use ykey_core::{traits::CredentialStore, types::*, YKeyResult};
+
+/// Credential together with the key it was read from
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyCredential {
+    /// ID of the device holding the credential
+    pub device_id: String,
+    /// The credential itself
+    pub credential: Credential,
+}
+
+/// One account and the keys holding a credential for it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CredentialGroup {
+    /// Relying party identifier
+    pub rp_id: String,
+    /// User handle
+    pub user_id: Vec<u8>,
+    /// User name, from the most recently created credential
+    pub user_name: String,
+    /// User display name, from the most recently created credential
+    pub user_display_name: String,
+    /// Credentials for this account, one or more per key
+    pub credentials: Vec<KeyCredential>,
+}
+
+impl CredentialGroup {
+    /// IDs of the devices holding this account
+    pub fn device_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.credentials.iter().map(|c| c.device_id.as_str()).collect();
+        ids.dedup();
+        ids
+    }
+}
+
+/// Collects credentials from several keys without losing any
+///
+/// Adding a credential whose ID is already known merges the two records
+/// instead of adding a second entry: usage data is combined and the device
+/// seen last wins.
+#[derive(Debug, Default)]
+pub struct CredentialAggregator {
+    credentials: Vec<KeyCredential>,
+    index: HashMap<CredentialId, usize>,
+}
+
+impl CredentialAggregator {
+    /// Create an empty aggregator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the credentials enumerated from one key
+    pub fn add(&mut self, device_id: &str, credentials: impl IntoIterator<Item = Credential>) {
+        for credential in credentials {
+            match self.index.get(&credential.id) {
+                Some(&position) => {
+                    let existing = &mut self.credentials[position];
+                    existing.device_id = device_id.to_string();
+                    merge(&mut existing.credential, credential);
+                }
+                None => {
+                    self.index.insert(credential.id.clone(), self.credentials.len());
+                    self.credentials.push(KeyCredential {
+                        device_id: device_id.to_string(),
+                        credential,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Number of distinct credentials
+    pub fn len(&self) -> usize {
+        self.credentials.len()
+    }
+
+    /// Whether no credentials have been added
+    pub fn is_empty(&self) -> bool {
+        self.credentials.is_empty()
+    }
+
+    /// Distinct credentials in the order they were first seen
+    pub fn credentials(&self) -> &[KeyCredential] {
+        &self.credentials
+    }
+
+    /// Credentials grouped by relying party and user
+    ///
+    /// Groups are ordered by relying party, then by user name.
+    pub fn groups(&self) -> Vec<CredentialGroup> {
+        let mut groups: Vec<CredentialGroup> = Vec::new();
+        for entry in &self.credentials {
+            let credential = &entry.credential;
+            let group = match groups
+                .iter_mut()
+                .position(|g| g.rp_id == credential.rp_id && g.user_id == credential.user_id)
+            {
+                Some(position) => &mut groups[position],
+                None => {
+                    groups.push(CredentialGroup {
+                        rp_id: credential.rp_id.clone(),
+                        user_id: credential.user_id.clone(),
+                        user_name: credential.user_name.clone(),
+                        user_display_name: credential.user_display_name.clone(),
+                        credentials: Vec::new(),
+                    });
+                    groups.last_mut().unwrap()
+                }
+            };
+
+            let newest = group.credentials.iter().all(|c| c.credential.created_at <= credential.created_at);
+            if newest {
+                group.user_name = credential.user_name.clone();
+                group.user_display_name = credential.user_display_name.clone();
+            }
+            group.credentials.push(entry.clone());
+        }
+
+        for group in &mut groups {
+            group.credentials.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+        }
+        groups.sort_by(|a, b| (&a.rp_id, &a.user_name).cmp(&(&b.rp_id, &b.user_name)));
+        groups
+    }
+
+    /// Write every distinct credential to a store
+    pub async fn import_into(&self, store: &mut dyn CredentialStore) -> YKeyResult<usize> {
+        for entry in &self.credentials {
+            store.store(&entry.credential).await?;
+        }
+        Ok(self.credentials.len())
+    }
+}
+
+/// Fold a second sighting of the same credential into the first
+fn merge(existing: &mut Credential, update: Credential) {
+    let counter = existing.counter.max(update.counter);
+    let last_used = existing.last_used.max(update.last_used);
+    let created_at = existing.created_at.min(update.created_at);
+    *existing = Credential {
+        counter,
+        last_used,
+        created_at,
+        ..update
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, Utc};
+
+    fn credential(id: u8, rp_id: &str, user: &str, created: i64) -> Credential {
+        Credential {
+            id: vec![id; 16],
+            rp_id: rp_id.to_string(),
+            user_id: user.as_bytes().to_vec(),
+            user_name: user.to_string(),
+            user_display_name: user.to_uppercase(),
+            public_key: vec![0x04, id],
+            counter: 0,
+            created_at: DateTime::<Utc>::UNIX_EPOCH + Duration::seconds(created),
+            last_used: None,
+            backup_state: None,
+        }
+    }
+
+    #[test]
+    fn test_same_account_on_two_keys_survives() {
+        let mut aggregator = CredentialAggregator::new();
+        aggregator.add("key-a", vec![credential(1, "github.com", "alice", 10)]);
+        aggregator.add(
+            "key-b",
+            vec![credential(2, "github.com", "alice", 20), credential(3, "example.com", "alice", 5)],
+        );
+
+        assert_eq!(aggregator.len(), 3);
+
+        let groups = aggregator.groups();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].rp_id, "example.com");
+
+        let github = &groups[1];
+        assert_eq!(github.rp_id, "github.com");
+        assert_eq!(github.device_ids(), vec!["key-a", "key-b"]);
+        let ids: Vec<u8> = github.credentials.iter().map(|c| c.credential.id[0]).collect();
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_same_credential_id_is_merged() {
+        let mut first = credential(1, "github.com", "alice", 10);
+        first.counter = 7;
+        first.last_used = Some(DateTime::<Utc>::UNIX_EPOCH + Duration::seconds(100));
+        let mut second = credential(1, "github.com", "alice", 10);
+        second.counter = 3;
+        second.user_display_name = "Alice Liddell".to_string();
+
+        let mut aggregator = CredentialAggregator::new();
+        aggregator.add("key-a", vec![first.clone()]);
+        aggregator.add("key-a", vec![second]);
+
+        assert_eq!(aggregator.len(), 1);
+        let merged = &aggregator.credentials()[0].credential;
+        assert_eq!(merged.counter, 7);
+        assert_eq!(merged.last_used, first.last_used);
+        assert_eq!(merged.user_display_name, "Alice Liddell");
+    }
+
+    #[test]
+    fn test_group_uses_newest_user_details() {
+        let mut renamed = credential(2, "github.com", "alice", 20);
+        renamed.user_name = "alice@example.com".to_string();
+
+        let mut aggregator = CredentialAggregator::new();
+        aggregator.add("key-b", vec![renamed]);
+        aggregator.add("key-a", vec![credential(1, "github.com", "alice", 10)]);
+
+        let groups = aggregator.groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].user_name, "alice@example.com");
+        assert_eq!(groups[0].device_ids(), vec!["key-a", "key-b"]);
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Audit log written as JSON lines
+//!
+//! Every security event becomes one line in the log file. Writes happen on a
+//! background task fed through a channel, so `log_event` never waits for the
+//! disk; reads and cleanup are queued behind pending writes and therefore see
+//! every event logged before them.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{mpsc, oneshot},
+};
+use ykey_core::{traits::*, YKeyError, YKeyResult};
+
+/// One line of the log file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    id: String,
+    level: LogLevel,
+    #[serde(flatten)]
+    event: SecurityEvent,
+}
+
+impl Record {
+    fn matches(&self, filter: &LogFilter) -> bool {
+        filter.start_time.is_none_or(|start| self.event.timestamp >= start)
+            && filter.end_time.is_none_or(|end| self.event.timestamp <= end)
+            && filter.level.as_ref().is_none_or(|level| self.level >= *level)
+            && filter.device_id.as_ref().is_none_or(|id| self.event.device_id.as_ref() == Some(id))
+            && filter.event_type.as_ref().is_none_or(|kind| self.event.event_type == *kind)
+    }
+
+    fn into_entry(self) -> LogEntry {
+        let event = self.event;
+        let mut metadata = event.details;
+        metadata.insert("event_type".to_string(), format!("{:?}", event.event_type));
+        if let Some(device_id) = &event.device_id {
+            metadata.insert("device_id".to_string(), device_id.clone());
+        }
+        if let Some(user_id) = event.user_id {
+            metadata.insert("user_id".to_string(), user_id);
+        }
+
+        let message = match &event.device_id {
+            Some(device_id) => format!("{:?} on {}", event.event_type, device_id),
+            None => format!("{:?}", event.event_type),
+        };
+        LogEntry {
+            id: self.id,
+            timestamp: event.timestamp,
+            level: self.level,
+            message,
+            metadata,
+        }
+    }
+}
+
+/// Severity recorded for each kind of event
+fn level_of(event_type: &EventType) -> LogLevel {
+    match event_type {
+        EventType::SecurityViolation => LogLevel::Error,
+        EventType::AuthenticationFailed | EventType::DeviceReset => LogLevel::Warn,
+        _ => LogLevel::Info,
+    }
+}
+
+/// Work for the writer task
+enum Command {
+    Append(Record),
+    Flush(oneshot::Sender<YKeyResult<()>>),
+    Cleanup(DateTime<Utc>, oneshot::Sender<YKeyResult<()>>),
+}
+
+/// Audit logger appending JSON lines to a file
+pub struct JsonlAuditLogger {
+    path: PathBuf,
+    commands: mpsc::UnboundedSender<Command>,
+    sequence: AtomicU64,
+}
+
+impl JsonlAuditLogger {
+    /// Log to the file at `path`, creating it on the first event
+    ///
+    /// Spawns the writer task, so this must be called within a Tokio runtime.
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let (commands, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run_writer(path.clone(), receiver));
+        Self {
+            path,
+            commands,
+            sequence: AtomicU64::new(0),
+        }
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Queue a command and wait for the writer to answer it
+    async fn request(&self, command: impl FnOnce(oneshot::Sender<YKeyResult<()>>) -> Command) -> YKeyResult<()> {
+        let (reply, response) = oneshot::channel();
+        self.commands.send(command(reply)).map_err(|_| writer_stopped())?;
+        response.await.map_err(|_| writer_stopped())?
+    }
+}
+
+#[async_trait]
+impl AuditLogger for JsonlAuditLogger {
+    async fn log_event(&self, event: SecurityEvent) -> YKeyResult<()> {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let record = Record {
+            id: format!("{}-{}", event.timestamp.timestamp_micros(), sequence),
+            level: level_of(&event.event_type),
+            event,
+        };
+        self.commands.send(Command::Append(record)).map_err(|_| writer_stopped())
+    }
+
+    async fn get_logs(&self, filter: LogFilter) -> YKeyResult<Vec<LogEntry>> {
+        self.request(Command::Flush).await?;
+        Ok(read_records(&self.path)
+            .await?
+            .into_iter()
+            .filter(|record| record.matches(&filter))
+            .map(Record::into_entry)
+            .collect())
+    }
+
+    async fn cleanup(&self, older_than: DateTime<Utc>) -> YKeyResult<()> {
+        self.request(|reply| Command::Cleanup(older_than, reply)).await
+    }
+}
+
+/// Apply commands in order until the logger is dropped
+///
+/// A failed append is reported to the next flush or cleanup.
+async fn run_writer(path: PathBuf, mut commands: mpsc::UnboundedReceiver<Command>) {
+    let mut failure = None;
+    while let Some(command) = commands.recv().await {
+        match command {
+            Command::Append(record) => {
+                if let Err(error) = append(&path, &record).await {
+                    failure.get_or_insert(error);
+                }
+            }
+            Command::Flush(reply) => {
+                let _ = reply.send(failure.take().map_or(Ok(()), Err));
+            }
+            Command::Cleanup(cutoff, reply) => {
+                let result = match failure.take() {
+                    Some(error) => Err(error),
+                    None => rewrite_since(&path, cutoff).await,
+                };
+                let _ = reply.send(result);
+            }
+        }
+    }
+}
+
+async fn append(path: &Path, record: &Record) -> YKeyResult<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+    file.write_all(&line).await?;
+    Ok(())
+}
+
+async fn read_records(path: &Path) -> YKeyResult<Vec<Record>> {
+    let contents = match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error.into()),
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(YKeyError::from))
+        .collect()
+}
+
+/// Rewrite the log keeping only entries at or after `cutoff`
+async fn rewrite_since(path: &Path, cutoff: DateTime<Utc>) -> YKeyResult<()> {
+    let mut contents = Vec::new();
+    for record in read_records(path).await? {
+        if record.event.timestamp >= cutoff {
+            contents.extend(serde_json::to_vec(&record)?);
+            contents.push(b'\n');
+        }
+    }
+
+    // Replace the file atomically so a crash never leaves half a log
+    let temporary = path.with_extension("jsonl.tmp");
+    tokio::fs::write(&temporary, contents).await?;
+    tokio::fs::rename(&temporary, path).await?;
+    Ok(())
+}
+
+fn writer_stopped() -> YKeyError {
+    YKeyError::communication("Audit log writer has stopped")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use std::collections::HashMap;
+
+    fn event(minutes: i64, event_type: EventType, device_id: Option<&str>) -> SecurityEvent {
+        SecurityEvent {
+            timestamp: DateTime::<Utc>::UNIX_EPOCH + Duration::minutes(minutes),
+            event_type,
+            device_id: device_id.map(str::to_string),
+            user_id: None,
+            details: HashMap::from([("minute".to_string(), minutes.to_string())]),
+        }
+    }
+
+    fn all() -> LogFilter {
+        LogFilter {
+            start_time: None,
+            end_time: None,
+            level: None,
+            device_id: None,
+            event_type: None,
+        }
+    }
+
+    fn minutes(entries: &[LogEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.metadata["minute"].as_str()).collect()
+    }
+
+    fn log_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ykey-audit-{}-{}.jsonl", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    async fn logger_with_events(name: &str) -> JsonlAuditLogger {
+        let logger = JsonlAuditLogger::open(log_path(name));
+        logger.log_event(event(1, EventType::DeviceConnected, Some("key-a"))).await.unwrap();
+        logger.log_event(event(2, EventType::AuthenticationFailed, Some("key-a"))).await.unwrap();
+        logger.log_event(event(3, EventType::DeviceConnected, Some("key-b"))).await.unwrap();
+        logger.log_event(event(4, EventType::SecurityViolation, None)).await.unwrap();
+        logger
+    }
+
+    #[tokio::test]
+    async fn test_get_logs_filters() {
+        let logger = logger_with_events("filters").await;
+
+        assert_eq!(minutes(&logger.get_logs(all()).await.unwrap()), vec!["1", "2", "3", "4"]);
+
+        let range = LogFilter {
+            start_time: Some(DateTime::<Utc>::UNIX_EPOCH + Duration::minutes(2)),
+            end_time: Some(DateTime::<Utc>::UNIX_EPOCH + Duration::minutes(3)),
+            ..all()
+        };
+        assert_eq!(minutes(&logger.get_logs(range).await.unwrap()), vec!["2", "3"]);
+
+        let warnings = LogFilter { level: Some(LogLevel::Warn), ..all() };
+        assert_eq!(minutes(&logger.get_logs(warnings).await.unwrap()), vec!["2", "4"]);
+
+        let device = LogFilter { device_id: Some("key-a".to_string()), ..all() };
+        assert_eq!(minutes(&logger.get_logs(device).await.unwrap()), vec!["1", "2"]);
+
+        let connected = LogFilter { event_type: Some(EventType::DeviceConnected), ..all() };
+        let entries = logger.get_logs(connected).await.unwrap();
+        assert_eq!(minutes(&entries), vec!["1", "3"]);
+        assert_eq!(entries[1].message, "DeviceConnected on key-b");
+        assert_eq!(entries[1].metadata["device_id"], "key-b");
+
+        let _ = std::fs::remove_file(logger.path());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_drops_old_entries() {
+        let logger = logger_with_events("cleanup").await;
+
+        logger.cleanup(DateTime::<Utc>::UNIX_EPOCH + Duration::minutes(3)).await.unwrap();
+        assert_eq!(minutes(&logger.get_logs(all()).await.unwrap()), vec!["3", "4"]);
+
+        // The rewritten file keeps accepting appends
+        logger.log_event(event(5, EventType::PinChanged, None)).await.unwrap();
+        assert_eq!(minutes(&logger.get_logs(all()).await.unwrap()), vec!["3", "4", "5"]);
+        assert_eq!(std::fs::read_to_string(logger.path()).unwrap().lines().count(), 3);
+
+        let _ = std::fs::remove_file(logger.path());
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! TOML-backed application configuration
+
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use ykey_core::{traits::*, YKeyError, YKeyResult};
+
+/// Configuration manager that keeps `AppConfig` in a TOML file
+pub struct TomlConfigManager {
+    path: PathBuf,
+}
+
+impl TomlConfigManager {
+    /// Use the configuration file at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Path of the configuration file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn write(&self, config: &AppConfig) -> YKeyResult<()> {
+        let contents = toml::to_string_pretty(config).map_err(config_error)?;
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&self.path, contents).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConfigManager for TomlConfigManager {
+    /// Read the configuration, writing the defaults if the file does not exist yet
+    async fn load(&self) -> YKeyResult<AppConfig> {
+        let contents = match tokio::fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                let config = AppConfig::default();
+                self.write(&config).await?;
+                return Ok(config);
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let config: AppConfig = toml::from_str(&contents).map_err(config_error)?;
+        self.validate(&config)?;
+        Ok(config)
+    }
+
+    async fn save(&self, config: &AppConfig) -> YKeyResult<()> {
+        self.validate(config)?;
+        self.write(config).await
+    }
+
+    async fn reset(&self) -> YKeyResult<()> {
+        self.write(&AppConfig::default()).await
+    }
+
+    fn validate(&self, config: &AppConfig) -> YKeyResult<()> {
+        let complexity = &config.security_policies.pin_complexity;
+        if complexity.min_length > complexity.max_length {
+            return Err(YKeyError::InvalidParameters(format!(
+                "PIN min_length {} exceeds max_length {}",
+                complexity.min_length, complexity.max_length
+            )));
+        }
+        if config.default_timeout == 0 {
+            return Err(YKeyError::InvalidParameters("default_timeout must be nonzero".to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn config_error(error: impl std::error::Error + Send + Sync + 'static) -> YKeyError {
+    YKeyError::Generic(anyhow::Error::new(error).context("Configuration file error"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh path in the temp directory, removed again on drop
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("ykey-config-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_dir_all(&dir);
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_creates_default_file() {
+        let dir = TempPath::new("default");
+        let manager = TomlConfigManager::new(dir.0.join("config.toml"));
+
+        let config = manager.load().await.unwrap();
+        assert_eq!(config.default_timeout, AppConfig::default().default_timeout);
+
+        let written = std::fs::read_to_string(manager.path()).unwrap();
+        assert!(written.contains("[security_policies.pin_complexity]"));
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_round_trip() {
+        let dir = TempPath::new("round-trip");
+        let manager = TomlConfigManager::new(dir.0.join("config.toml"));
+
+        let mut config = AppConfig {
+            default_timeout: 90,
+            ui_theme: "dark".to_string(),
+            ..AppConfig::default()
+        };
+        config.security_policies.pin_complexity.min_length = 6;
+        manager.save(&config).await.unwrap();
+
+        let loaded = manager.load().await.unwrap();
+        assert_eq!(loaded.default_timeout, 90);
+        assert_eq!(loaded.ui_theme, "dark");
+        assert_eq!(loaded.security_policies.pin_complexity.min_length, 6);
+
+        manager.reset().await.unwrap();
+        assert_eq!(manager.load().await.unwrap().default_timeout, AppConfig::default().default_timeout);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_config_rejected() {
+        let dir = TempPath::new("invalid");
+        let manager = TomlConfigManager::new(dir.0.join("config.toml"));
+
+        let mut config = AppConfig::default();
+        config.security_policies.pin_complexity.min_length = 9;
+        config.security_policies.pin_complexity.max_length = 8;
+        assert!(matches!(manager.save(&config).await, Err(YKeyError::InvalidParameters(_))));
+
+        let config = AppConfig {
+            default_timeout: 0,
+            ..AppConfig::default()
+        };
+        assert!(matches!(manager.validate(&config), Err(YKeyError::InvalidParameters(_))));
+
+        // A hand-edited file is checked on load as well
+        let contents = toml::to_string(&config).unwrap();
+        std::fs::create_dir_all(&dir.0).unwrap();
+        std::fs::write(manager.path(), contents).unwrap();
+        assert!(matches!(manager.load().await, Err(YKeyError::InvalidParameters(_))));
+    }
+}
@@ -3,22 +3,198 @@
 
 //! Device management layer for YKey hardware security keys
 
-use ykey_core::{traits::*, types::*, YKeyResult, YKeyError};
+use ykey_core::{traits::*, types::*, RetryPolicy, YKeyResult, YKeyError};
+use ykey_protocol::Fido2Client;
 use async_trait::async_trait;
-use std::{sync::Arc, collections::HashMap};
-use tokio::sync::RwLock;
+use std::{sync::Arc, collections::HashMap, panic::AssertUnwindSafe, time::Duration};
+use tokio::sync::{mpsc, Mutex, MutexGuard, OwnedSemaphorePermit, RwLock, Semaphore};
+
+pub mod aggregate;
+pub use aggregate::{CredentialAggregator, CredentialGroup, KeyCredential};
+
+pub mod audit;
+pub use audit::JsonlAuditLogger;
+
+pub mod config;
+pub use config::TomlConfigManager;
+
+pub mod managed;
+pub use managed::{ManagedDevice, ManagedFido2Client};
+
+pub mod memory_store;
+pub use memory_store::MemoryCredentialStore;
+
+pub mod prelude;
+
+pub mod rate_limit;
+pub use rate_limit::RateLimit;
+use rate_limit::RateLimiter;
+
+pub mod snapshot;
+pub use snapshot::{debounce, DeviceSnapshot};
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
+#[cfg(feature = "sqlite")]
+pub use sqlite_store::SqliteCredentialStore;
+
+/// How long a scan result is trusted when resolving a device to connect
+pub const DEFAULT_SCAN_TTL: Duration = Duration::from_secs(2);
+
+/// Device operations allowed in flight at once across all devices by default
+pub const DEFAULT_MAX_CONCURRENT_IO: usize = 64;
+
+/// Devices found by the last scan and when it finished
+struct ScanCache {
+    devices: Vec<DeviceInfo>,
+    scanned_at: tokio::time::Instant,
+}
+
+/// Called with the error of each discovery that failed during a partial scan
+pub type ScanWarningHandler = Box<dyn Fn(&YKeyError) + Send + Sync>;
+
+/// Devices yielded by [`DeviceManager::scan_devices_stream`] as they are found
+pub type DeviceScanStream = mpsc::Receiver<YKeyResult<DeviceInfo>>;
+
+/// What a scan needs to merge its reports and publish them
+#[derive(Clone)]
+struct ScanState {
+    duplicate_policy: DuplicatePolicy,
+    reported_capabilities: Arc<RwLock<HashMap<DeviceId, Vec<Capability>>>>,
+    scan_cache: Arc<RwLock<Option<ScanCache>>>,
+}
+
+impl ScanState {
+    /// Replace discovery's capability guess with what GetInfo reported
+    async fn apply_reported(&self, devices: &mut [DeviceInfo]) {
+        let reported = self.reported_capabilities.read().await;
+        for device in devices {
+            if let Some(capabilities) = reported.get(&device.id) {
+                device.capabilities = capabilities.clone();
+            }
+        }
+    }
+
+    /// Merge and sort the reports of a completed scan, then cache them
+    async fn finish(&self, devices: Vec<DeviceInfo>) -> Vec<DeviceInfo> {
+        let mut devices = self.duplicate_policy.merge(devices);
+        devices.sort_by(|a, b| a.id.cmp(&b.id));
+        self.apply_reported(&mut devices).await;
+
+        *self.scan_cache.write().await = Some(ScanCache {
+            devices: devices.clone(),
+            scanned_at: tokio::time::Instant::now(),
+        });
+        devices
+    }
+}
+
+/// A connected device shared between concurrent operations
+type SharedDevice = Arc<ConnectedDevice>;
+
+/// A connected device with the rate limiter its operations go through
+struct ConnectedDevice {
+    device: Mutex<Box<dyn Device>>,
+    limiter: RateLimiter,
+    /// Process-wide I/O permits shared with the other devices
+    io_permits: Arc<Semaphore>,
+    /// Taken before the device is shared, so reading them needs no lock
+    canceller: Option<Arc<dyn Canceller>>,
+    max_message_size: usize,
+    packet_payload_sizes: Option<(usize, usize)>,
+    operation_timeout: Duration,
+}
+
+impl ConnectedDevice {
+    fn new(device: Box<dyn Device>, limit: Option<RateLimit>, io_permits: Arc<Semaphore>) -> Self {
+        Self {
+            canceller: device.canceller(),
+            max_message_size: device.max_message_size(),
+            packet_payload_sizes: device.packet_payload_sizes(),
+            operation_timeout: device.operation_timeout(),
+            device: Mutex::new(device),
+            limiter: RateLimiter::new(limit),
+            io_permits,
+        }
+    }
+
+    /// Cancel the pending operation, bypassing the rate limit
+    ///
+    /// Goes through the transport's canceller when it has one, so a busy
+    /// device is reached right away. Otherwise waits for the device lock,
+    /// i.e. for the operation in flight to finish.
+    async fn cancel(&self) -> YKeyResult<()> {
+        match &self.canceller {
+            Some(canceller) => {
+                canceller.cancel();
+                Ok(())
+            }
+            None => self.device.lock().await.cancel().await,
+        }
+    }
+
+    /// Lock the device for one operation, once the rate limit and the global
+    /// I/O limit allow it
+    ///
+    /// The I/O permit is taken last so waiting for a busy device does not
+    /// hold one.
+    async fn acquire(&self) -> DeviceGuard<'_> {
+        self.limiter.acquire().await;
+        let device = self.device.lock().await;
+        let permit = self.io_permits.clone().acquire_owned().await.expect("I/O semaphore is never closed");
+        DeviceGuard { device, _permit: permit }
+    }
+}
+
+/// A locked device together with its process-wide I/O permit
+struct DeviceGuard<'a> {
+    device: MutexGuard<'a, Box<dyn Device>>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl std::ops::Deref for DeviceGuard<'_> {
+    type Target = Box<dyn Device>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.device
+    }
+}
+
+impl std::ops::DerefMut for DeviceGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.device
+    }
+}
 
 /// Device factory for creating device instances
 /// 
 /// Uses the factory pattern to create appropriate device implementations
 /// based on device information and registered creators.
 pub struct DeviceFactory {
-    creators: HashMap<DeviceType, Box<dyn DeviceCreator>>,
+    creators: Vec<RegisteredCreator>,
+}
+
+/// A creator together with the device type it was registered for
+struct RegisteredCreator {
+    /// Restricts the creator to one device type; `Generic` and `None` apply to all
+    device_type: Option<DeviceType>,
+    creator: Box<dyn DeviceCreator>,
+}
+
+impl RegisteredCreator {
+    fn applies_to(&self, info: &DeviceInfo) -> bool {
+        match self.device_type {
+            None | Some(DeviceType::Generic) => true,
+            Some(device_type) => device_type == info.device_type,
+        }
+    }
 }
 
 // Placeholder device creators - these will be expanded into separate modules later
 struct YubiKeyCreator;
 struct CanoKeyCreator;
+struct NitrokeyCreator;
+struct SoloKeyCreator;
 struct GenericFidoCreator;
 
 // Implement DeviceCreator for placeholder creators
@@ -31,6 +207,10 @@ impl DeviceCreator for YubiKeyCreator {
         info.device_type == DeviceType::YubiKey
     }
     
+    fn priority(&self) -> u32 {
+        10
+    }
+    
     fn name(&self) -> &str {
         "YubiKey Creator"
     }
@@ -45,11 +225,51 @@ impl DeviceCreator for CanoKeyCreator {
         info.device_type == DeviceType::CanoKey
     }
     
+    fn priority(&self) -> u32 {
+        10
+    }
+    
     fn name(&self) -> &str {
         "CanoKey Creator"
     }
 }
 
+impl DeviceCreator for NitrokeyCreator {
+    fn create(&self, info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
+        Ok(Box::new(MockDevice::new(info.clone())))
+    }
+    
+    fn supports(&self, info: &DeviceInfo) -> bool {
+        info.device_type == DeviceType::Nitrokey
+    }
+    
+    fn priority(&self) -> u32 {
+        10
+    }
+    
+    fn name(&self) -> &str {
+        "Nitrokey Creator"
+    }
+}
+
+impl DeviceCreator for SoloKeyCreator {
+    fn create(&self, info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
+        Ok(Box::new(MockDevice::new(info.clone())))
+    }
+    
+    fn supports(&self, info: &DeviceInfo) -> bool {
+        info.device_type == DeviceType::SoloKey
+    }
+    
+    fn priority(&self) -> u32 {
+        10
+    }
+    
+    fn name(&self) -> &str {
+        "SoloKey Creator"
+    }
+}
+
 impl DeviceCreator for GenericFidoCreator {
     fn create(&self, info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
         Ok(Box::new(MockDevice::new(info.clone())))
@@ -111,54 +331,206 @@ impl DeviceFactory {
     /// Create a new device factory with default creators
     pub fn new() -> Self {
         let mut factory = Self {
-            creators: HashMap::new(),
+            creators: Vec::new(),
         };
         
         // Register built-in device creators
         factory.register(DeviceType::YubiKey, Box::new(YubiKeyCreator));
         factory.register(DeviceType::CanoKey, Box::new(CanoKeyCreator));
+        factory.register(DeviceType::Nitrokey, Box::new(NitrokeyCreator));
+        factory.register(DeviceType::SoloKey, Box::new(SoloKeyCreator));
         factory.register(DeviceType::Generic, Box::new(GenericFidoCreator));
         
         factory
     }
     
     /// Register a device creator for a specific device type
+    /// 
+    /// Replaces any creator previously registered for the same type. A
+    /// creator registered for `Generic` is considered for every device.
     pub fn register(&mut self, device_type: DeviceType, creator: Box<dyn DeviceCreator>) {
-        self.creators.insert(device_type, creator);
+        self.creators.retain(|c| c.device_type != Some(device_type));
+        self.creators.push(RegisteredCreator { device_type: Some(device_type), creator });
+    }
+    
+    /// Register a device creator that picks its devices through `supports`
+    pub fn register_creator(&mut self, creator: Box<dyn DeviceCreator>) {
+        self.creators.push(RegisteredCreator { device_type: None, creator });
     }
     
     /// Create a device instance from device information
+    /// 
+    /// Uses the highest priority creator that supports the device. Among
+    /// creators of equal priority the one registered last wins.
     pub fn create_device(&self, info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
-        if let Some(creator) = self.creators.get(&info.device_type) {
-            creator.create(info)
-        } else {
-            // Try generic creator as fallback
-            if let Some(creator) = self.creators.get(&DeviceType::Generic) {
-                creator.create(info)
-            } else {
-                Err(YKeyError::UnsupportedDevice(info.device_type.clone()))
+        let creator = self.select_creator(info).ok_or(YKeyError::UnsupportedDevice(info.device_type))?;
+        Self::invoke_creator(creator, info)
+    }
+
+    /// The creator `create_device` would use for a device
+    fn select_creator(&self, info: &DeviceInfo) -> Option<&dyn DeviceCreator> {
+        self.creators
+            .iter()
+            .filter(|c| c.applies_to(info) && Self::creator_supports(c.creator.as_ref(), info))
+            .max_by_key(|c| c.creator.priority())
+            .map(|c| c.creator.as_ref())
+    }
+    
+    /// Run a creator, turning a panic into an error instead of unwinding
+    /// 
+    /// Creators may come from third parties, so a misbehaving one must not
+    /// take down scanning or connecting for every other device.
+    fn invoke_creator(creator: &dyn DeviceCreator, info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| creator.create(info))) {
+            Ok(result) => result,
+            Err(payload) => {
+                tracing::warn!(
+                    "Device creator '{}' panicked while creating device {}: {}",
+                    creator.name(), info.id, panic_message(payload.as_ref())
+                );
+                Err(YKeyError::UnsupportedDevice(info.device_type))
+            }
+        }
+    }
+
+    /// Ask a creator whether it supports a device, a panic counting as no
+    fn creator_supports(creator: &dyn DeviceCreator, info: &DeviceInfo) -> bool {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| creator.supports(info))) {
+            Ok(supported) => supported,
+            Err(payload) => {
+                tracing::warn!(
+                    "Device creator '{}' panicked while checking device {}: {}",
+                    creator.name(), info.id, panic_message(payload.as_ref())
+                );
+                false
             }
         }
     }
     
     /// Get all registered device types
     pub fn supported_device_types(&self) -> Vec<DeviceType> {
-        self.creators.keys().cloned().collect()
+        self.creators.iter().filter_map(|c| c.device_type).collect()
     }
     
     /// Check if a device type is supported
     pub fn supports_device_type(&self, device_type: &DeviceType) -> bool {
-        self.creators.contains_key(device_type)
+        self.creators.iter().any(|c| c.device_type == Some(*device_type))
+    }
+}
+
+/// Text of a caught panic, if it carried one
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// How devices reported by several discoveries are merged
+/// 
+/// Devices sharing an ID are always merged. Serial-based policies additionally
+/// merge devices reported under different IDs; only `BySerialOrVidPid` merges
+/// devices that lack a serial number.
+///
+/// Merged reports keep the most complete one, adding the capabilities, serial
+/// and firmware version the others know about. A device seen over more than
+/// one transport is reported with [`TransportType::Hybrid`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Merge only devices with the same ID
+    ById,
+    /// Merge devices with the same serial number, falling back to the ID
+    #[default]
+    BySerial,
+    /// Merge devices with the same vendor ID, product ID and serial number,
+    /// falling back to the ID
+    ByVidPidSerial,
+    /// Merge devices with the same serial number, or with the same vendor and
+    /// product ID when either lacks a serial
+    BySerialOrVidPid,
+}
+
+impl DuplicatePolicy {
+    /// Whether two reports describe the same physical device
+    fn same_device(&self, a: &DeviceInfo, b: &DeviceInfo) -> bool {
+        let serial = |device: &DeviceInfo| device.serial_number.clone().filter(|s| !s.is_empty());
+        let same_model = a.vendor_id == b.vendor_id && a.product_id == b.product_id;
+        a.id == b.id
+            || match (self, serial(a), serial(b)) {
+                (DuplicatePolicy::ById, _, _) => false,
+                (DuplicatePolicy::BySerial, Some(x), Some(y)) => x == y,
+                (DuplicatePolicy::ByVidPidSerial, Some(x), Some(y)) => same_model && x == y,
+                (DuplicatePolicy::BySerialOrVidPid, Some(x), Some(y)) => x == y,
+                (DuplicatePolicy::BySerialOrVidPid, _, _) => same_model,
+                _ => false,
+            }
+    }
+
+    /// Merge reports of the same device, keeping the first position of each
+    fn merge(&self, devices: Vec<DeviceInfo>) -> Vec<DeviceInfo> {
+        // Each group remembers every report it absorbed, so later duplicates
+        // still match when the kept report was replaced by a more complete one
+        let mut groups: Vec<(DeviceInfo, Vec<DeviceInfo>)> = Vec::new();
+        for device in devices {
+            match groups
+                .iter_mut()
+                .find(|(_, reports)| reports.iter().any(|report| self.same_device(report, &device)))
+            {
+                Some((merged, reports)) => {
+                    merge_device_info(merged, device.clone());
+                    reports.push(device);
+                }
+                None => groups.push((device.clone(), vec![device])),
+            }
+        }
+        groups.into_iter().map(|(merged, _)| merged).collect()
     }
 }
 
+/// How much a report knows about its device
+fn completeness(device: &DeviceInfo) -> usize {
+    device.serial_number.is_some() as usize + device.firmware_version.is_some() as usize + device.capabilities.len()
+}
+
+/// Fold `other` into `kept`, keeping whichever report is more complete as the base
+fn merge_device_info(kept: &mut DeviceInfo, other: DeviceInfo) {
+    let other = if completeness(&other) > completeness(kept) {
+        std::mem::replace(kept, other)
+    } else {
+        other
+    };
+    if kept.transport != other.transport {
+        kept.transport = TransportType::Hybrid;
+    }
+    for capability in other.capabilities {
+        kept.add_capability(capability);
+    }
+    kept.serial_number = kept.serial_number.take().or(other.serial_number);
+    kept.firmware_version = kept.firmware_version.take().or(other.firmware_version);
+    kept.last_seen = kept.last_seen.max(other.last_seen);
+}
+
 /// Device manager for handling device lifecycle and connections
 /// 
 /// Manages multiple devices, handles discovery, and maintains connection state.
+/// 
+/// Locks are always acquired in the order discoveries → device map → per-device,
+/// and the device map lock is never held across an `.await` on a device: the
+/// device handle is cloned out of the map first and the map lock released.
 pub struct DeviceManager {
     factory: Arc<DeviceFactory>,
-    discoveries: Vec<Box<dyn DeviceDiscovery>>,
-    connected_devices: Arc<RwLock<HashMap<String, Box<dyn Device>>>>,
+    discoveries: Vec<Arc<dyn DeviceDiscovery>>,
+    connected_devices: Arc<RwLock<HashMap<DeviceId, SharedDevice>>>,
+    duplicate_policy: DuplicatePolicy,
+    rate_limit: Option<RateLimit>,
+    scan_cache: Arc<RwLock<Option<ScanCache>>>,
+    scan_ttl: Duration,
+    /// Capabilities confirmed through GetInfo, overriding discovery's guess
+    reported_capabilities: Arc<RwLock<HashMap<DeviceId, Vec<Capability>>>>,
+    max_concurrent_io: usize,
+    io_permits: Arc<Semaphore>,
+    scan_warning_handler: Option<ScanWarningHandler>,
 }
 
 impl DeviceManager {
@@ -168,6 +540,14 @@ impl DeviceManager {
             factory: Arc::new(DeviceFactory::new()),
             discoveries: Vec::new(),
             connected_devices: Arc::new(RwLock::new(HashMap::new())),
+            duplicate_policy: DuplicatePolicy::default(),
+            rate_limit: None,
+            scan_cache: Arc::new(RwLock::new(None)),
+            scan_ttl: DEFAULT_SCAN_TTL,
+            reported_capabilities: Arc::new(RwLock::new(HashMap::new())),
+            max_concurrent_io: DEFAULT_MAX_CONCURRENT_IO,
+            io_permits: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_IO)),
+            scan_warning_handler: None,
         }
     }
     
@@ -177,51 +557,270 @@ impl DeviceManager {
             factory: Arc::new(factory),
             discoveries: Vec::new(),
             connected_devices: Arc::new(RwLock::new(HashMap::new())),
+            duplicate_policy: DuplicatePolicy::default(),
+            rate_limit: None,
+            scan_cache: Arc::new(RwLock::new(None)),
+            scan_ttl: DEFAULT_SCAN_TTL,
+            reported_capabilities: Arc::new(RwLock::new(HashMap::new())),
+            max_concurrent_io: DEFAULT_MAX_CONCURRENT_IO,
+            io_permits: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_IO)),
+            scan_warning_handler: None,
         }
     }
     
     /// Add a device discovery mechanism
     pub fn add_discovery(&mut self, discovery: Box<dyn DeviceDiscovery>) {
-        self.discoveries.push(discovery);
+        self.discoveries.push(Arc::from(discovery));
+    }
+    
+    /// Set the handler told about discoveries that fail during a scan
+    ///
+    /// Without a handler the failures are logged as warnings through `tracing`.
+    pub fn set_scan_warning_handler(&mut self, handler: Option<ScanWarningHandler>) {
+        self.scan_warning_handler = handler;
+    }
+
+    /// Set how devices reported by several discoveries are merged
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+    
+    /// Get the current duplicate merge policy
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+    
+    /// Set the command rate limit given to devices connected from now on
+    ///
+    /// Each device gets its own token bucket. `None`, the default, leaves
+    /// devices unlimited.
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.rate_limit = limit;
+    }
+    
+    /// Get the rate limit given to newly connected devices
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit
+    }
+    
+    /// Change the command rate limit of a connected device
+    pub async fn set_device_rate_limit(&self, device_id: &DeviceId, limit: Option<RateLimit>) -> YKeyResult<()> {
+        let device = self.shared_device(device_id).await
+            .ok_or_else(|| YKeyError::DeviceNotFound(device_id.to_string()))?;
+        device.limiter.set(limit);
+        Ok(())
+    }
+    
+    /// Limit the device operations in flight at once across all devices
+    ///
+    /// Devices share the limit, so bursts over many keys are throttled
+    /// process-wide; each device still runs one operation at a time. Applies
+    /// to devices connected from now on. A limit of 0 is raised to 1.
+    pub fn set_max_concurrent_io(&mut self, limit: usize) {
+        self.max_concurrent_io = limit.max(1);
+        self.io_permits = Arc::new(Semaphore::new(self.max_concurrent_io));
+    }
+    
+    /// Get the limit on device operations in flight at once
+    pub fn max_concurrent_io(&self) -> usize {
+        self.max_concurrent_io
+    }
+    
+    /// Number of device operations in flight under the current limit
+    pub fn io_in_flight(&self) -> usize {
+        self.max_concurrent_io - self.io_permits.available_permits()
+    }
+    
+    /// Set how long scan results are reused by `connect_device`
+    pub fn set_scan_ttl(&mut self, ttl: Duration) {
+        self.scan_ttl = ttl;
+    }
+    
+    /// Get how long scan results are reused by `connect_device`
+    pub fn scan_ttl(&self) -> Duration {
+        self.scan_ttl
+    }
+    
+    /// Rescan now, replacing the cached device list
+    pub async fn refresh(&self) -> YKeyResult<Vec<DeviceInfo>> {
+        self.scan_devices().await
     }
     
     /// Scan for available devices using all registered discovery mechanisms
+    ///
+    /// Discoveries run concurrently; their reports are merged in the order
+    /// the discoveries were added. A failing discovery does not abort the
+    /// scan: its error goes to the scan warning handler and the others'
+    /// devices are returned. Only when every discovery fails is the first
+    /// error returned. Every scan also refreshes the cache `connect_device`
+    /// resolves IDs from.
     pub async fn scan_devices(&self) -> YKeyResult<Vec<DeviceInfo>> {
-        let mut all_devices = Vec::new();
-        
+        let mut scans = self.spawn_scans();
+        let mut reports = vec![Vec::new(); self.discoveries.len()];
+        let mut failures = Vec::new();
+        while let Some((index, devices)) = scans.recv().await {
+            match devices {
+                Ok(devices) => reports[index] = devices,
+                Err(e) => failures.push((index, e)),
+            }
+        }
+
+        failures.sort_by_key(|(index, _)| *index);
+        if !failures.is_empty() && failures.len() == self.discoveries.len() {
+            return Err(failures.swap_remove(0).1);
+        }
+        for (index, error) in &failures {
+            match &self.scan_warning_handler {
+                Some(handler) => handler(error),
+                None => tracing::warn!("Device discovery {} failed during scan: {}", index, error),
+            }
+        }
+        Ok(self.scan_state().finish(reports.concat()).await)
+    }
+
+    /// Scan like `scan_devices`, yielding each device as soon as its discovery reports it
+    ///
+    /// A slow discovery does not hold back the others. Only the first report
+    /// of each physical device is yielded; later duplicates are merged into
+    /// the scan cache, which is refreshed once every discovery has finished
+    /// unless all of them failed. A failing discovery yields its error and
+    /// the others carry on.
+    pub fn scan_devices_stream(&self) -> DeviceScanStream {
+        let mut scans = self.spawn_scans();
+        let state = self.scan_state();
+        let mut reports: Vec<Vec<DeviceInfo>> = vec![Vec::new(); self.discoveries.len()];
+        let (tx, rx) = mpsc::channel(32);
+        tokio::spawn(async move {
+            let mut failed = 0;
+            while let Some((index, devices)) = scans.recv().await {
+                let devices = match devices {
+                    Ok(devices) => devices,
+                    Err(e) => {
+                        failed += 1;
+                        let _ = tx.send(Err(e)).await;
+                        continue;
+                    }
+                };
+                for device in devices {
+                    let duplicate = reports
+                        .iter()
+                        .flatten()
+                        .any(|seen| state.duplicate_policy.same_device(seen, &device));
+                    reports[index].push(device.clone());
+                    if !duplicate {
+                        let mut device = [device];
+                        state.apply_reported(&mut device).await;
+                        let [device] = device;
+                        let _ = tx.send(Ok(device)).await;
+                    }
+                }
+            }
+            if failed == 0 || failed < reports.len() {
+                state.finish(reports.concat()).await;
+            }
+        });
+        rx
+    }
+
+    /// Run every discovery's scan on its own task, reporting in completion order
+    fn spawn_scans(&self) -> mpsc::Receiver<(usize, YKeyResult<Vec<DeviceInfo>>)> {
+        let (tx, rx) = mpsc::channel(self.discoveries.len().max(1));
+        for (index, discovery) in self.discoveries.iter().enumerate() {
+            let discovery = discovery.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let _ = tx.send((index, discovery.scan().await)).await;
+            });
+        }
+        rx
+    }
+
+    fn scan_state(&self) -> ScanState {
+        ScanState {
+            duplicate_policy: self.duplicate_policy,
+            reported_capabilities: self.reported_capabilities.clone(),
+            scan_cache: self.scan_cache.clone(),
+        }
+    }
+    
+    /// Information about a discovered device, connected or not
+    ///
+    /// Answered from the last scan while it is younger than the scan TTL;
+    /// otherwise, or for an ID the scan did not see, the devices are
+    /// rescanned. Unknown devices give `DeviceNotFound`.
+    pub async fn device_info(&self, device_id: &DeviceId) -> YKeyResult<DeviceInfo> {
+        self.resolve_device(device_id).await
+    }
+
+    /// Look up a device, from the cache while it is fresh and rescanning otherwise
+    async fn resolve_device(&self, device_id: &DeviceId) -> YKeyResult<DeviceInfo> {
+        {
+            let cache = self.scan_cache.read().await;
+            if let Some(cache) = cache.as_ref().filter(|cache| cache.scanned_at.elapsed() < self.scan_ttl) {
+                if let Some(info) = cache.devices.iter().find(|d| &d.id == device_id) {
+                    return Ok(info.clone());
+                }
+            }
+        }
+        self.refresh().await?
+            .into_iter()
+            .find(|d| &d.id == device_id)
+            .ok_or_else(|| YKeyError::DeviceNotFound(device_id.to_string()))
+    }
+
+    /// Merge the hotplug streams of all discoveries into one
+    pub async fn watch_devices(&self) -> YKeyResult<DeviceEventStream> {
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
         for discovery in &self.discoveries {
-            let devices = discovery.scan().await?;
-            all_devices.extend(devices);
+            let mut events = discovery.watch().await?;
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                while let Some(event) = events.recv().await {
+                    if tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
+            });
         }
-        
-        // Remove duplicates based on device ID
-        all_devices.sort_by(|a, b| a.id.cmp(&b.id));
-        all_devices.dedup_by(|a, b| a.id == b.id);
-        
-        Ok(all_devices)
+        Ok(rx)
+    }
+
+    /// Stop the hotplug monitoring of all discoveries
+    ///
+    /// Streams returned by `watch_devices` end once every discovery has
+    /// shut down its monitor.
+    pub async fn stop_watching(&self) -> YKeyResult<()> {
+        for discovery in &self.discoveries {
+            discovery.stop_watch().await?;
+        }
+        Ok(())
     }
     
     /// Connect to a specific device by ID
-    pub async fn connect_device(&self, device_id: &str) -> YKeyResult<()> {
-        let devices = self.scan_devices().await?;
-        let device_info = devices.iter()
-            .find(|d| d.id == device_id)
-            .ok_or_else(|| YKeyError::DeviceNotFound(device_id.to_string()))?;
-            
-        let mut device = self.factory.create_device(device_info)?;
-        device.connect().await?;
+    pub async fn connect_device(&self, device_id: &DeviceId) -> YKeyResult<()> {
+        self.connect_device_with(device_id, ConnectOptions::default()).await
+    }
+    
+    /// Connect to a specific device by ID with transport-specific options
+    ///
+    /// The device is looked up in the last scan if it is younger than the
+    /// scan TTL; a stale cache or an unknown ID triggers a rescan.
+    pub async fn connect_device_with(&self, device_id: &DeviceId, options: ConnectOptions) -> YKeyResult<()> {
+        let device_info = self.resolve_device(device_id).await?;
+        let mut device = self.factory.create_device(&device_info)?;
+        device.connect_with(&options).await?;
         
         let mut connected = self.connected_devices.write().await;
-        connected.insert(device_id.to_string(), device);
+        connected.insert(device_id.clone(), Arc::new(ConnectedDevice::new(device, self.rate_limit, self.io_permits.clone())));
         
         Ok(())
     }
     
     /// Disconnect a specific device by ID
-    pub async fn disconnect_device(&self, device_id: &str) -> YKeyResult<()> {
-        let mut connected = self.connected_devices.write().await;
-        if let Some(mut device) = connected.remove(device_id) {
-            device.disconnect().await?;
+    pub async fn disconnect_device(&self, device_id: &DeviceId) -> YKeyResult<()> {
+        let device = self.connected_devices.write().await.remove(device_id);
+        if let Some(device) = device {
+            device.device.lock().await.disconnect().await?;
         }
         Ok(())
     }
@@ -230,26 +829,105 @@ impl DeviceManager {
     /// 
     /// Note: This returns None instead of a reference due to lifetime constraints
     /// with async RwLock. Use `with_device` for operations on connected devices.
-    pub async fn is_device_connected(&self, device_id: &str) -> bool {
+    pub async fn is_device_connected(&self, device_id: &DeviceId) -> bool {
         let connected = self.connected_devices.read().await;
         connected.contains_key(device_id)
     }
     
     /// Execute an operation with a connected device
-    pub async fn with_device<F, R>(&self, device_id: &str, f: F) -> YKeyResult<R>
+    pub async fn with_device<F, R>(&self, device_id: &DeviceId, f: F) -> YKeyResult<R>
     where
         F: FnOnce(&mut dyn Device) -> std::pin::Pin<Box<dyn std::future::Future<Output = YKeyResult<R>> + Send + '_>>,
     {
-        let mut connected = self.connected_devices.write().await;
-        if let Some(device) = connected.get_mut(device_id) {
-            f(device.as_mut()).await
-        } else {
-            Err(YKeyError::DeviceNotFound(device_id.to_string()))
+        let device = self.shared_device(device_id).await
+            .ok_or_else(|| YKeyError::DeviceNotFound(device_id.to_string()))?;
+        let mut device = device.acquire().await;
+        f(device.as_mut()).await
+    }
+    
+    /// Get a FIDO2 client for a connected device the manager keeps owning
+    ///
+    /// The client holds its own PIN token and cached GetInfo, so keep it for
+    /// as long as the session with the device lasts. Other operations on the
+    /// device can still run between its commands.
+    pub async fn fido2_client(&self, device_id: &DeviceId) -> YKeyResult<ManagedFido2Client> {
+        let device = self.shared_device(device_id).await
+            .ok_or_else(|| YKeyError::DeviceNotFound(device_id.to_string()))?;
+        Ok(Fido2Client::new(ManagedDevice::new(device)))
+    }
+    
+    /// Replace a device's capabilities with what its GetInfo reports
+    ///
+    /// Discovery guesses capabilities from the USB IDs, so a FIDO-only key
+    /// can be listed with applets it lacks. GetInfo only speaks for the FIDO
+    /// application, so the refreshed set holds the FIDO capabilities it
+    /// confirms and nothing else. It is kept across rescans.
+    pub async fn refresh_capabilities(&self, device_id: &DeviceId) -> YKeyResult<DeviceInfo> {
+        if !self.is_device_connected(device_id).await {
+            self.connect_device(device_id).await?;
+        }
+        let capabilities = self.fido2_client(device_id).await?.get_info().await?.capabilities();
+        self.reported_capabilities.write().await.insert(device_id.clone(), capabilities.clone());
+
+        if let Some(cache) = self.scan_cache.write().await.as_mut() {
+            for device in cache.devices.iter_mut().filter(|d| &d.id == device_id) {
+                device.capabilities = capabilities.clone();
+            }
+        }
+        let mut info = self.resolve_device(device_id).await?;
+        info.capabilities = capabilities;
+        Ok(info)
+    }
+    
+    /// Factory-reset a device's FIDO application
+    ///
+    /// The device stays locked until the post-reset settle delay from the
+    /// quirk table has passed, so no other operation reaches it early. The
+    /// capabilities cached from its GetInfo and the last scan are dropped,
+    /// as the reset may have changed them.
+    pub async fn reset_fido2(&self, device_id: &DeviceId) -> YKeyResult<()> {
+        let device = self.shared_device(device_id).await
+            .ok_or_else(|| YKeyError::DeviceNotFound(device_id.to_string()))?;
+        {
+            let mut device = device.acquire().await;
+            Fido2Client::new(&mut **device).reset().await?;
+        }
+
+        self.reported_capabilities.write().await.remove(device_id);
+        *self.scan_cache.write().await = None;
+        Ok(())
+    }
+    
+    /// Execute an operation with a connected device, retrying transient failures
+    ///
+    /// The device lock is released between attempts so other operations can
+    /// run while this one backs off.
+    pub async fn with_device_retry<F, R>(&self, device_id: &DeviceId, policy: RetryPolicy, mut f: F) -> YKeyResult<R>
+    where
+        F: FnMut(&mut dyn Device) -> std::pin::Pin<Box<dyn std::future::Future<Output = YKeyResult<R>> + Send + '_>>,
+    {
+        let device = self.shared_device(device_id).await
+            .ok_or_else(|| YKeyError::DeviceNotFound(device_id.to_string()))?;
+        let mut attempt = 1;
+        loop {
+            let result = f(device.acquire().await.as_mut()).await;
+            match result {
+                Err(error) if error.is_retryable() && attempt < policy.max_attempts => {
+                    tokio::time::sleep(policy.delay_after(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
         }
     }
     
+    /// Clone a connected device handle out of the map, releasing the map lock
+    async fn shared_device(&self, device_id: &DeviceId) -> Option<SharedDevice> {
+        self.connected_devices.read().await.get(device_id).cloned()
+    }
+    
     /// Get list of connected device IDs
-    pub async fn connected_device_ids(&self) -> Vec<String> {
+    pub async fn connected_device_ids(&self) -> Vec<DeviceId> {
         let connected = self.connected_devices.read().await;
         connected.keys().cloned().collect()
     }
@@ -261,19 +939,105 @@ impl DeviceManager {
     }
     
     /// Disconnect all devices
+    /// 
+    /// Every device is removed and disconnected even if some fail; the
+    /// failures are returned together as [`YKeyError::PartialFailure`].
     pub async fn disconnect_all(&self) -> YKeyResult<()> {
-        let mut connected = self.connected_devices.write().await;
-        let device_ids: Vec<String> = connected.keys().cloned().collect();
+        let devices: Vec<(DeviceId, SharedDevice)> = self.connected_devices.write().await
+            .drain()
+            .collect();
+        
+        let mut failures = Vec::new();
+        for (device_id, device) in devices {
+            if let Err(e) = device.device.lock().await.disconnect().await {
+                failures.push((device_id.to_string(), e));
+            }
+        }
+        
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            failures.sort_by(|a, b| a.0.cmp(&b.0));
+            Err(YKeyError::PartialFailure(failures))
+        }
+    }
+    
+    /// Race a GetAssertion across several connected devices
+    /// 
+    /// The same request is issued to every candidate concurrently and the first
+    /// successful assertion wins, mirroring how browsers prompt across all
+    /// authenticators. Devices that fail (e.g. no matching credential) drop out
+    /// without aborting the race; the remaining ones are cancelled once a winner
+    /// is known. Returns the winning device ID with its assertion.
+    pub async fn race_assertion(
+        &self,
+        params: GetAssertionParams,
+        device_ids: &[DeviceId],
+    ) -> YKeyResult<(DeviceId, AssertionObject)> {
+        let candidates: Vec<(DeviceId, SharedDevice)> = {
+            let connected = self.connected_devices.read().await;
+            device_ids.iter()
+                .filter_map(|id| connected.get(id).map(|device| (id.clone(), device.clone())))
+                .collect()
+        };
+        
+        if candidates.is_empty() {
+            let ids: Vec<&str> = device_ids.iter().map(DeviceId::as_str).collect();
+            return Err(YKeyError::DeviceNotFound(ids.join(", ")));
+        }
+        
+        let mut tasks = tokio::task::JoinSet::new();
+        for (device_id, device) in candidates.iter().cloned() {
+            let params = params.clone();
+            tasks.spawn(async move {
+                let mut device = device.acquire().await;
+                let mut client = Fido2Client::new(&mut **device);
+                (device_id, client.get_assertion(params).await)
+            });
+        }
         
-        for device_id in device_ids {
-            if let Some(mut device) = connected.remove(&device_id) {
-                if let Err(e) = device.disconnect().await {
-                    eprintln!("Failed to disconnect device {}: {}", device_id, e);
+        let mut winner = None;
+        let mut finished = Vec::new();
+        let mut last_error = None;
+        while let Some(joined) = tasks.join_next().await {
+            let Ok((device_id, result)) = joined else {
+                continue; // Task panicked or was aborted
+            };
+            
+            finished.push(device_id.clone());
+            match result {
+                Ok(assertion) => {
+                    winner = Some((device_id, assertion));
+                    break;
                 }
+                Err(e) => last_error = Some(e),
             }
         }
         
-        Ok(())
+        // Losers still hold their device locks, so cancel those that can be
+        // reached without one before waiting for the tasks to stop
+        let losers: Vec<&SharedDevice> = candidates.iter()
+            .filter(|(device_id, _)| !finished.contains(device_id))
+            .map(|(_, device)| device)
+            .collect();
+        if winner.is_some() {
+            for device in losers.iter().filter(|device| device.canceller.is_some()) {
+                let _ = device.cancel().await;
+            }
+        }
+        tasks.abort_all();
+        while tasks.join_next().await.is_some() {}
+        
+        if winner.is_some() {
+            for device in losers.iter().filter(|device| device.canceller.is_none()) {
+                // Best-effort: the device may already have given up
+                let _ = device.cancel().await;
+            }
+        }
+        
+        winner.ok_or_else(|| {
+            last_error.unwrap_or_else(|| YKeyError::communication("No device completed the assertion"))
+        })
     }
 }
 
@@ -298,23 +1062,43 @@ mod tests {
     // Mock discovery for testing
     struct MockDiscovery {
         devices: Vec<DeviceInfo>,
+        /// Simulated hotplug source, handed out by `watch`
+        events: std::sync::Mutex<Option<DeviceEventStream>>,
+        /// Number of times `scan` was called
+        scans: Arc<std::sync::atomic::AtomicUsize>,
+        /// How long `scan` takes
+        latency: Duration,
     }
 
     impl MockDiscovery {
         fn new(devices: Vec<DeviceInfo>) -> Self {
-            Self { devices }
+            Self { devices, events: std::sync::Mutex::new(None), scans: Arc::default(), latency: Duration::ZERO }
+        }
+
+        fn with_latency(mut self, latency: Duration) -> Self {
+            self.latency = latency;
+            self
+        }
+
+        fn with_events(devices: Vec<DeviceInfo>) -> (Self, tokio::sync::mpsc::Sender<DeviceEvent>) {
+            let (tx, rx) = tokio::sync::mpsc::channel(10);
+            (Self { devices, events: std::sync::Mutex::new(Some(rx)), scans: Arc::default(), latency: Duration::ZERO }, tx)
         }
     }
 
     #[async_trait]
     impl DeviceDiscovery for MockDiscovery {
         async fn scan(&self) -> YKeyResult<Vec<DeviceInfo>> {
+            self.scans.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::time::sleep(self.latency).await;
             Ok(self.devices.clone())
         }
 
         async fn watch(&self) -> YKeyResult<DeviceEventStream> {
-            let (_tx, rx) = tokio::sync::mpsc::channel(10);
-            Ok(rx)
+            match self.events.lock().unwrap().take() {
+                Some(rx) => Ok(rx),
+                None => Ok(tokio::sync::mpsc::channel(10).1),
+            }
         }
 
         async fn stop_watch(&self) -> YKeyResult<()> {
@@ -326,20 +1110,74 @@ mod tests {
         }
     }
 
-    fn create_test_device_info(id: &str, device_type: DeviceType) -> DeviceInfo {
-        let mut info = DeviceInfo::new(
-            id.to_string(),
-            format!("Test Device {}", id),
-            "Test Manufacturer".to_string(),
-            "Test Product".to_string(),
-            0x1234,
-            0x5678,
-            device_type,
-            TransportType::Usb,
-        );
-        info.add_capability(Capability::Fido2);
-        info
-    }
+    /// Discovery whose backend is unavailable
+    struct FailingDiscovery;
+
+    #[async_trait]
+    impl DeviceDiscovery for FailingDiscovery {
+        async fn scan(&self) -> YKeyResult<Vec<DeviceInfo>> {
+            Err(YKeyError::communication("NFC reader unplugged"))
+        }
+
+        async fn watch(&self) -> YKeyResult<DeviceEventStream> {
+            Ok(tokio::sync::mpsc::channel(10).1)
+        }
+
+        async fn stop_watch(&self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        async fn is_device_available(&self, _device_id: &str) -> YKeyResult<bool> {
+            Ok(false)
+        }
+    }
+
+    fn create_test_device_info(id: &str, device_type: DeviceType) -> DeviceInfo {
+        let mut info = DeviceInfo::new(
+            id.to_string(),
+            format!("Test Device {}", id),
+            "Test Manufacturer".to_string(),
+            "Test Product".to_string(),
+            0x1234,
+            0x5678,
+            device_type,
+            TransportType::Usb,
+        );
+        info.add_capability(Capability::Fido2);
+        info
+    }
+
+    #[tokio::test]
+    async fn test_watch_devices_merges_discoveries() {
+        let (usb, usb_events) = MockDiscovery::with_events(vec![]);
+        let (nfc, nfc_events) = MockDiscovery::with_events(vec![]);
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(usb));
+        manager.add_discovery(Box::new(nfc));
+
+        let mut events = manager.watch_devices().await.unwrap();
+        usb_events
+            .send(DeviceEvent::Connected(create_test_device_info("usb-key", DeviceType::YubiKey)))
+            .await
+            .unwrap();
+        nfc_events.send(DeviceEvent::Disconnected("nfc-key".to_string())).await.unwrap();
+
+        let mut seen = Vec::new();
+        for _ in 0..2 {
+            match events.recv().await.unwrap() {
+                DeviceEvent::Connected(info) => seen.push(format!("+{}", info.id)),
+                DeviceEvent::Disconnected(id) => seen.push(format!("-{}", id)),
+                DeviceEvent::Error { .. } => panic!("unexpected error event"),
+            }
+        }
+        seen.sort();
+        assert_eq!(seen, vec!["+usb-key", "-nfc-key"]);
+
+        // Once every source has shut down the merged stream ends
+        drop((usb_events, nfc_events));
+        manager.stop_watching().await.unwrap();
+        assert!(events.recv().await.is_none());
+    }
 
     #[tokio::test]
     async fn test_device_factory_creation() {
@@ -347,10 +1185,87 @@ mod tests {
         
         assert!(factory.supports_device_type(&DeviceType::YubiKey));
         assert!(factory.supports_device_type(&DeviceType::CanoKey));
+        assert!(factory.supports_device_type(&DeviceType::Nitrokey));
+        assert!(factory.supports_device_type(&DeviceType::SoloKey));
         assert!(factory.supports_device_type(&DeviceType::Generic));
         
         let supported_types = factory.supported_device_types();
-        assert_eq!(supported_types.len(), 3);
+        assert_eq!(supported_types.len(), 5);
+    }
+
+    #[test]
+    fn test_vendor_creators_beat_generic() {
+        let factory = DeviceFactory::new();
+        let creator_name = |device_type| {
+            let info = create_test_device_info("key", device_type);
+            factory.select_creator(&info).unwrap().name().to_string()
+        };
+
+        assert_eq!(creator_name(DeviceType::Nitrokey), "Nitrokey Creator");
+        assert_eq!(creator_name(DeviceType::SoloKey), "SoloKey Creator");
+        assert_eq!(creator_name(DeviceType::Generic), "Generic FIDO Creator");
+        assert!(NitrokeyCreator.priority() > GenericFidoCreator.priority());
+        assert!(SoloKeyCreator.priority() > GenericFidoCreator.priority());
+    }
+
+    /// Creator standing out by name and a configurable priority
+    struct NamedCreator {
+        name: &'static str,
+        priority: u32,
+        device_type: Option<DeviceType>,
+    }
+
+    impl DeviceCreator for NamedCreator {
+        fn create(&self, info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
+            let mut info = info.clone();
+            info.name = self.name.to_string();
+            Ok(Box::new(MockDevice::new(info)))
+        }
+
+        fn supports(&self, info: &DeviceInfo) -> bool {
+            self.device_type.is_none_or(|t| t == info.device_type)
+        }
+
+        fn priority(&self) -> u32 {
+            self.priority
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+    }
+
+    async fn created_by(factory: &DeviceFactory, info: &DeviceInfo) -> String {
+        factory.create_device(info).unwrap().info().await.unwrap().name
+    }
+
+    #[tokio::test]
+    async fn test_highest_priority_creator_wins() {
+        let mut factory = DeviceFactory::new();
+        factory.register(DeviceType::Generic, Box::new(NamedCreator { name: "generic", priority: 1, device_type: None }));
+        factory.register_creator(Box::new(NamedCreator {
+            name: "yubikey",
+            priority: 100,
+            device_type: Some(DeviceType::YubiKey),
+        }));
+
+        let yubikey = create_test_device_info("yubikey", DeviceType::YubiKey);
+        assert_eq!(created_by(&factory, &yubikey).await, "yubikey");
+
+        // The specialised creator doesn't support other devices
+        let generic = create_test_device_info("generic", DeviceType::Generic);
+        assert_eq!(created_by(&factory, &generic).await, "generic");
+    }
+
+    #[tokio::test]
+    async fn test_low_priority_creator_loses_to_builtin() {
+        let mut factory = DeviceFactory::new();
+        factory.register_creator(Box::new(NamedCreator { name: "fallback", priority: 0, device_type: None }));
+
+        let yubikey = create_test_device_info("yubikey", DeviceType::YubiKey);
+        assert_ne!(created_by(&factory, &yubikey).await, "fallback");
+        let generic = create_test_device_info("generic", DeviceType::Generic);
+        assert_eq!(created_by(&factory, &generic).await, "fallback");
     }
 
     #[tokio::test]
@@ -386,20 +1301,144 @@ mod tests {
         assert_eq!(manager.device_count().await, 0);
         
         // Test connection
-        manager.connect_device("device1").await.unwrap();
+        manager.connect_device(&"device1".into()).await.unwrap();
         assert_eq!(manager.device_count().await, 1);
-        assert!(manager.is_device_connected("device1").await);
-        assert!(!manager.is_device_connected("device2").await);
+        assert!(manager.is_device_connected(&"device1".into()).await);
+        assert!(!manager.is_device_connected(&"device2".into()).await);
         
         // Test connected device IDs
         let connected_ids = manager.connected_device_ids().await;
         assert_eq!(connected_ids.len(), 1);
-        assert!(connected_ids.contains(&"device1".to_string()));
+        assert!(connected_ids.contains(&"device1".into()));
         
         // Test disconnection
-        manager.disconnect_device("device1").await.unwrap();
+        manager.disconnect_device(&"device1".into()).await.unwrap();
         assert_eq!(manager.device_count().await, 0);
-        assert!(!manager.is_device_connected("device1").await);
+        assert!(!manager.is_device_connected(&"device1".into()).await);
+    }
+
+    #[tokio::test]
+    async fn test_with_device_retry_retries_busy_device() {
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![
+            create_test_device_info("device1", DeviceType::YubiKey),
+        ])));
+        manager.connect_device(&"device1".into()).await.unwrap();
+
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let policy = RetryPolicy::new(3, std::time::Duration::from_millis(1));
+        let result = manager.with_device_retry(&"device1".into(), policy, |device| {
+            let attempt = attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                match attempt {
+                    1 => Err(YKeyError::ctap_error(0x06)),
+                    2 => Err(YKeyError::ctap_error(0x21)),
+                    _ => Ok(device.info().await?.id),
+                }
+            })
+        }).await;
+        assert_eq!(result.unwrap(), "device1");
+        assert_eq!(attempts.into_inner(), 3);
+
+        // Non-retryable errors are returned after one attempt
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result: YKeyResult<()> = manager.with_device_retry(&"device1".into(), policy, |_| {
+            attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async { Err(YKeyError::PinRequired) })
+        }).await;
+        assert!(matches!(result, Err(YKeyError::PinRequired)));
+        assert_eq!(attempts.into_inner(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connect_reuses_fresh_scan() {
+        use std::sync::atomic::Ordering;
+        
+        let mut manager = DeviceManager::new();
+        let discovery = MockDiscovery::new(vec![
+            create_test_device_info("device1", DeviceType::YubiKey),
+            create_test_device_info("device2", DeviceType::CanoKey),
+        ]);
+        let scans = discovery.scans.clone();
+        manager.add_discovery(Box::new(discovery));
+        manager.set_scan_ttl(Duration::from_secs(60));
+        
+        // The first connect has nothing cached and scans once
+        manager.connect_device(&"device1".into()).await.unwrap();
+        manager.connect_device(&"device2".into()).await.unwrap();
+        manager.disconnect_device(&"device1".into()).await.unwrap();
+        manager.connect_device(&"device1".into()).await.unwrap();
+        assert_eq!(scans.load(Ordering::SeqCst), 1);
+        
+        // Unknown IDs and explicit refreshes rescan
+        assert!(manager.connect_device(&"device3".into()).await.is_err());
+        assert_eq!(scans.load(Ordering::SeqCst), 2);
+        manager.refresh().await.unwrap();
+        assert_eq!(scans.load(Ordering::SeqCst), 3);
+        
+        // A stale cache is scanned again
+        manager.set_scan_ttl(Duration::ZERO);
+        manager.connect_device(&"device2".into()).await.unwrap();
+        assert_eq!(scans.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_device_info_without_connecting() {
+        use std::sync::atomic::Ordering;
+
+        let mut manager = DeviceManager::new();
+        let discovery = MockDiscovery::new(vec![create_test_device_info("device1", DeviceType::YubiKey)]);
+        let scans = discovery.scans.clone();
+        manager.add_discovery(Box::new(discovery));
+        manager.set_scan_ttl(Duration::from_secs(60));
+
+        manager.scan_devices().await.unwrap();
+        let info = manager.device_info(&"device1".into()).await.unwrap();
+        assert_eq!(info.id, "device1");
+        assert!(!manager.is_device_connected(&"device1".into()).await);
+        assert_eq!(scans.load(Ordering::SeqCst), 1);
+
+        // An ID missing from the cache gets a rescan before giving up
+        let missing = manager.device_info(&"device2".into()).await;
+        assert!(matches!(missing, Err(YKeyError::DeviceNotFound(_))));
+        assert_eq!(scans.load(Ordering::SeqCst), 2);
+    }
+
+    // A paused clock keeps the timings exact on a loaded machine
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_spaces_operations() {
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![
+            create_test_device_info("device1", DeviceType::YubiKey),
+            create_test_device_info("device2", DeviceType::YubiKey),
+        ])));
+        manager.connect_device(&"device2".into()).await.unwrap();
+        manager.set_rate_limit(Some(RateLimit::per_second(50.0)));
+        manager.connect_device(&"device1".into()).await.unwrap();
+
+        // One command immediately, then one every 20ms
+        let start = tokio::time::Instant::now();
+        let mut times = Vec::new();
+        for _ in 0..10 {
+            let response = manager.with_device(&"device1".into(), |device| {
+                Box::pin(async move { device.send_raw(&[0x00]).await })
+            }).await;
+            assert_eq!(response.unwrap(), vec![0x90, 0x00]);
+            times.push(start.elapsed());
+        }
+        assert!(times[0] < std::time::Duration::from_millis(15));
+        assert!(times[9] >= std::time::Duration::from_millis(175));
+        assert!(times[9] < std::time::Duration::from_secs(1));
+        assert!(times.windows(2).all(|pair| pair[1] - pair[0] >= std::time::Duration::from_millis(15)));
+
+        // Devices connected before the limit was set stay unlimited
+        let start = tokio::time::Instant::now();
+        for _ in 0..10 {
+            manager.with_device(&"device2".into(), |device| {
+                Box::pin(async move { device.send_raw(&[0x00]).await })
+            }).await.unwrap();
+        }
+        assert!(start.elapsed() < std::time::Duration::from_millis(100));
     }
 
     #[tokio::test]
@@ -416,9 +1455,9 @@ mod tests {
         manager.add_discovery(Box::new(discovery));
         
         // Connect multiple devices
-        manager.connect_device("device1").await.unwrap();
-        manager.connect_device("device2").await.unwrap();
-        manager.connect_device("device3").await.unwrap();
+        manager.connect_device(&"device1".into()).await.unwrap();
+        manager.connect_device(&"device2".into()).await.unwrap();
+        manager.connect_device(&"device3".into()).await.unwrap();
         
         assert_eq!(manager.device_count().await, 3);
         
@@ -432,12 +1471,12 @@ mod tests {
         let manager = DeviceManager::new();
         
         // Test connecting to non-existent device
-        let result = manager.connect_device("non-existent").await;
+        let result = manager.connect_device(&"non-existent".into()).await;
         assert!(result.is_err());
         assert!(matches!(result.unwrap_err(), YKeyError::DeviceNotFound(_)));
         
         // Test disconnecting non-connected device (should not error)
-        let result = manager.disconnect_device("non-existent").await;
+        let result = manager.disconnect_device(&"non-existent".into()).await;
         assert!(result.is_ok());
     }
 
@@ -499,14 +1538,892 @@ mod tests {
 
     #[tokio::test]
     async fn test_device_factory_fallback() {
-        let factory = DeviceFactory::new();
+        let mut factory = DeviceFactory::new();
         
-        // Test unsupported device type falls back to generic
+        // A device type without its own creator falls back to generic
+        factory.creators.retain(|c| c.device_type != Some(DeviceType::Nitrokey));
         let unsupported_info = create_test_device_info("unsupported", DeviceType::Nitrokey);
         let device = factory.create_device(&unsupported_info).unwrap();
         
         let info = device.info().await.unwrap();
         assert_eq!(info.device_type, DeviceType::Nitrokey);
     }
-}
 
+    /// NFC device recording the reader it was opened on
+    struct NfcReaderDevice {
+        info: DeviceInfo,
+        opened_reader: Arc<std::sync::Mutex<Option<String>>>,
+    }
+
+    #[async_trait]
+    impl Device for NfcReaderDevice {
+        async fn info(&self) -> YKeyResult<DeviceInfo> {
+            Ok(self.info.clone())
+        }
+
+        async fn connect(&mut self) -> YKeyResult<()> {
+            self.connect_with(&ConnectOptions::default()).await
+        }
+
+        async fn connect_with(&mut self, options: &ConnectOptions) -> YKeyResult<()> {
+            let reader = options.nfc.as_ref().and_then(|nfc| nfc.reader_name.clone());
+            *self.opened_reader.lock().unwrap() = Some(reader.unwrap_or_else(|| "default".to_string()));
+            Ok(())
+        }
+
+        async fn disconnect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        fn is_connected(&self) -> bool {
+            self.opened_reader.lock().unwrap().is_some()
+        }
+
+        async fn send_raw(&mut self, _data: &[u8]) -> YKeyResult<Vec<u8>> {
+            Ok(vec![0x90, 0x00])
+        }
+    }
+
+    struct NfcReaderCreator {
+        opened_reader: Arc<std::sync::Mutex<Option<String>>>,
+    }
+
+    impl DeviceCreator for NfcReaderCreator {
+        fn create(&self, info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
+            Ok(Box::new(NfcReaderDevice {
+                info: info.clone(),
+                opened_reader: self.opened_reader.clone(),
+            }))
+        }
+
+        fn supports(&self, info: &DeviceInfo) -> bool {
+            info.transport == TransportType::Nfc
+        }
+
+        fn name(&self) -> &str {
+            "NFC Reader Creator"
+        }
+    }
+
+    fn nfc_manager(opened_reader: Arc<std::sync::Mutex<Option<String>>>) -> DeviceManager {
+        let mut factory = DeviceFactory::new();
+        factory.register(DeviceType::Generic, Box::new(NfcReaderCreator { opened_reader }));
+
+        let mut info = create_test_device_info("nfc-key", DeviceType::Generic);
+        info.transport = TransportType::Nfc;
+
+        let mut manager = DeviceManager::with_factory(factory);
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![info])));
+        manager
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_nfc_reader() {
+        let opened_reader = Arc::new(std::sync::Mutex::new(None));
+        let manager = nfc_manager(opened_reader.clone());
+
+        let options = ConnectOptions::nfc_reader("ACS ACR122U 00 00");
+        manager.connect_device_with(&"nfc-key".into(), options).await.unwrap();
+
+        assert!(manager.is_device_connected(&"nfc-key".into()).await);
+        assert_eq!(opened_reader.lock().unwrap().as_deref(), Some("ACS ACR122U 00 00"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_ignores_other_transports() {
+        let opened_reader = Arc::new(std::sync::Mutex::new(None));
+        let manager = nfc_manager(opened_reader.clone());
+
+        // HID and BLE options mean nothing to an NFC device
+        let options = ConnectOptions {
+            hid: ConnectOptions::hid_path("/dev/hidraw0").hid,
+            ble: ConnectOptions::ble_address("AA:BB:CC:DD:EE:FF").ble,
+            ..Default::default()
+        };
+        manager.connect_device_with(&"nfc-key".into(), options).await.unwrap();
+
+        assert_eq!(opened_reader.lock().unwrap().as_deref(), Some("default"));
+    }
+
+    #[tokio::test]
+    async fn test_global_io_limit_across_devices() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let device_ids: Vec<DeviceId> = (0..3).map(|i| DeviceId::new(format!("device-{}", i))).collect();
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(MockDiscovery::new(
+            device_ids.iter().map(|id| create_test_device_info(id.as_str(), DeviceType::YubiKey)).collect(),
+        )));
+        manager.set_max_concurrent_io(2);
+        assert_eq!(manager.max_concurrent_io(), 2);
+        for id in &device_ids {
+            manager.connect_device(id).await.unwrap();
+        }
+        let manager = Arc::new(manager);
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let mut tasks = tokio::task::JoinSet::new();
+        for id in device_ids {
+            let (manager, running, peak) = (manager.clone(), running.clone(), peak.clone());
+            tasks.spawn(async move {
+                manager.with_device(&id, |device| {
+                    Box::pin(async move {
+                        peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        device.send_raw(&[0x00]).await
+                    })
+                }).await
+            });
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert_eq!(manager.io_in_flight(), 2);
+        while let Some(result) = tasks.join_next().await {
+            result.unwrap().unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(manager.io_in_flight(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_operations_do_not_deadlock() {
+        let device_ids: Vec<DeviceId> = (0..4).map(|i| DeviceId::new(format!("device-{}", i))).collect();
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(MockDiscovery::new(
+            device_ids.iter().map(|id| create_test_device_info(id.as_str(), DeviceType::YubiKey)).collect(),
+        )));
+        let manager = Arc::new(manager);
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for round in 0..50 {
+            let manager = manager.clone();
+            let device_id = device_ids[round % device_ids.len()].clone();
+            tasks.spawn(async move {
+                manager.scan_devices().await.unwrap();
+                manager.connect_device(&device_id).await.unwrap();
+                // The device may have been disconnected by another task meanwhile
+                let _ = manager.with_device(&device_id, |device| Box::pin(async move {
+                    tokio::task::yield_now().await;
+                    device.send_raw(&[0x04]).await
+                })).await;
+                manager.connected_device_ids().await;
+                if round % 5 == 0 {
+                    manager.disconnect_device(&device_id).await.unwrap();
+                }
+                if round % 17 == 0 {
+                    manager.disconnect_all().await.unwrap();
+                }
+            });
+        }
+
+        let finished = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while let Some(result) = tasks.join_next().await {
+                result.unwrap();
+            }
+        }).await;
+        assert!(finished.is_ok(), "device manager operations deadlocked");
+    }
+
+    /// Same physical keys reported by a HID and a PC/SC discovery under different IDs
+    fn overlapping_discoveries() -> Vec<Box<dyn DeviceDiscovery>> {
+        let with_serial = |id: &str, product_id: u16, serial: Option<&str>| {
+            let mut info = create_test_device_info(id, DeviceType::YubiKey);
+            info.vendor_id = 0x1050;
+            info.product_id = product_id;
+            info.serial_number = serial.map(str::to_string);
+            info
+        };
+
+        vec![
+            Box::new(MockDiscovery::new(vec![
+                with_serial("hid:1", 0x0407, Some("12345678")),
+                with_serial("hid:2", 0x0402, Some("87654321")),
+                with_serial("hid:3", 0x0407, None),
+            ])),
+            Box::new(MockDiscovery::new(vec![
+                with_serial("pcsc:0", 0x0407, Some("12345678")),
+                with_serial("pcsc:1", 0x0404, Some("87654321")),
+                with_serial("pcsc:2", 0x0407, None),
+                with_serial("hid:1", 0x0407, Some("12345678")),
+            ])),
+        ]
+    }
+
+    async fn scan_ids(policy: DuplicatePolicy) -> Vec<DeviceId> {
+        let mut manager = DeviceManager::new();
+        for discovery in overlapping_discoveries() {
+            manager.add_discovery(discovery);
+        }
+        manager.set_duplicate_policy(policy);
+        manager.scan_devices().await.unwrap().into_iter().map(|d| d.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_policy_by_id() {
+        assert_eq!(
+            scan_ids(DuplicatePolicy::ById).await,
+            vec!["hid:1", "hid:2", "hid:3", "pcsc:0", "pcsc:1", "pcsc:2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_policy_by_serial() {
+        assert_eq!(DeviceManager::new().duplicate_policy(), DuplicatePolicy::BySerial);
+
+        // Serial-less devices stay separate even though both lack a serial
+        assert_eq!(
+            scan_ids(DuplicatePolicy::BySerial).await,
+            vec!["hid:1", "hid:2", "hid:3", "pcsc:2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_policy_by_vid_pid_serial() {
+        // A serial shared across different products is not a duplicate
+        assert_eq!(
+            scan_ids(DuplicatePolicy::ByVidPidSerial).await,
+            vec!["hid:1", "hid:2", "hid:3", "pcsc:1", "pcsc:2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_policy_by_serial_or_vid_pid() {
+        // The serial-less reports of product 0x0407 now join hid:1
+        assert_eq!(scan_ids(DuplicatePolicy::BySerialOrVidPid).await, vec!["hid:1", "hid:2"]);
+    }
+
+    #[tokio::test]
+    async fn test_same_key_over_two_transports_is_merged() {
+        let mut hid = create_test_device_info("hid:1", DeviceType::YubiKey);
+        hid.serial_number = Some("12345678".to_string());
+        let mut nfc = create_test_device_info("nfc:reader", DeviceType::YubiKey);
+        nfc.transport = TransportType::Nfc;
+        nfc.firmware_version = Some("5.4.3".to_string());
+        nfc.add_capability(Capability::Oath);
+        nfc.add_capability(Capability::Piv);
+
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![hid])));
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![nfc])));
+
+        // Without a serial on the NFC side only the vendor and product ID can match
+        assert_eq!(manager.scan_devices().await.unwrap().len(), 2);
+        manager.set_duplicate_policy(DuplicatePolicy::BySerialOrVidPid);
+        let devices = manager.scan_devices().await.unwrap();
+        assert_eq!(devices.len(), 1);
+
+        // The NFC report knows more, so it is kept and filled in from the HID one
+        let device = &devices[0];
+        assert_eq!(device.id, "nfc:reader");
+        assert_eq!(device.transport, TransportType::Hybrid);
+        assert_eq!(device.capabilities, vec![Capability::Fido2, Capability::Oath, Capability::Piv]);
+        assert_eq!(device.serial_number.as_deref(), Some("12345678"));
+        assert_eq!(device.firmware_version.as_deref(), Some("5.4.3"));
+    }
+
+    #[tokio::test]
+    async fn test_scan_survives_failing_discovery() {
+        let warnings = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(FailingDiscovery));
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![
+            create_test_device_info("hid:1", DeviceType::YubiKey),
+        ])));
+        let seen = warnings.clone();
+        manager.set_scan_warning_handler(Some(Box::new(move |error: &YKeyError| {
+            seen.lock().unwrap().push(error.to_string());
+        })));
+
+        let devices = manager.scan_devices().await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].id, "hid:1");
+        let warnings = warnings.lock().unwrap().clone();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("NFC reader unplugged"));
+
+        let mut stream = manager.scan_devices_stream();
+        let mut items = Vec::new();
+        while let Some(item) = stream.recv().await {
+            items.push(item);
+        }
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().any(|item| matches!(item, Ok(device) if device.id == "hid:1")));
+        assert!(items.iter().any(|item| item.is_err()));
+
+        // With nothing left to fall back on the scan fails
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(FailingDiscovery));
+        assert!(manager.scan_devices().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scan_stream_yields_fast_discovery_first() {
+        let mut nfc = create_test_device_info("nfc:reader", DeviceType::YubiKey);
+        nfc.serial_number = Some("12345678".to_string());
+        let mut hid = create_test_device_info("hid:1", DeviceType::YubiKey);
+        hid.serial_number = Some("12345678".to_string());
+        let mut other = create_test_device_info("hid:2", DeviceType::YubiKey);
+        other.serial_number = Some("87654321".to_string());
+
+        let latency = Duration::from_millis(500);
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![nfc]).with_latency(latency)));
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![hid, other])));
+
+        let started = tokio::time::Instant::now();
+        let mut stream = manager.scan_devices_stream();
+        assert_eq!(stream.recv().await.unwrap().unwrap().id, "hid:1");
+        assert_eq!(stream.recv().await.unwrap().unwrap().id, "hid:2");
+        assert!(started.elapsed() < latency);
+
+        // The slow report duplicates hid:1, so it only reaches the cache
+        assert!(stream.recv().await.is_none());
+        assert!(started.elapsed() >= latency);
+        let cache = manager.scan_cache.read().await;
+        assert_eq!(cache.as_ref().unwrap().devices.len(), 2);
+    }
+
+    struct PanickingCreator;
+
+    impl DeviceCreator for PanickingCreator {
+        fn create(&self, _info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
+            panic!("creator blew up");
+        }
+
+        fn supports(&self, _info: &DeviceInfo) -> bool {
+            true
+        }
+
+        fn name(&self) -> &str {
+            "Panicking Creator"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicking_creator_returns_error() {
+        let mut factory = DeviceFactory::new();
+        factory.register(DeviceType::SoloKey, Box::new(PanickingCreator));
+
+        let info = create_test_device_info("solo", DeviceType::SoloKey);
+        let result = factory.create_device(&info);
+        assert!(matches!(result, Err(YKeyError::UnsupportedDevice(DeviceType::SoloKey))));
+
+        // Other device types are unaffected
+        let yubikey_info = create_test_device_info("yubikey", DeviceType::YubiKey);
+        assert!(factory.create_device(&yubikey_info).is_ok());
+    }
+
+    /// Creator whose device check panics
+    struct PanickingSupportCreator;
+
+    impl DeviceCreator for PanickingSupportCreator {
+        fn create(&self, _info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
+            unreachable!("never selected");
+        }
+
+        fn supports(&self, _info: &DeviceInfo) -> bool {
+            panic!("supports blew up");
+        }
+
+        fn priority(&self) -> u32 {
+            100
+        }
+
+        fn name(&self) -> &str {
+            "Panicking Support Creator"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicking_supports_counts_as_unsupported() {
+        let mut factory = DeviceFactory::new();
+        factory.register_creator(Box::new(PanickingSupportCreator));
+
+        // The built-in creator still handles the device
+        let info = create_test_device_info("yubikey", DeviceType::YubiKey);
+        assert!(factory.create_device(&info).is_ok());
+
+        let mut manager = DeviceManager::with_factory(factory);
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![info])));
+        manager.connect_device(&"yubikey".into()).await.unwrap();
+        assert!(manager.is_device_connected(&"yubikey".into()).await);
+    }
+
+    /// Device whose disconnect always fails
+    struct StuckDevice {
+        info: DeviceInfo,
+    }
+
+    #[async_trait]
+    impl Device for StuckDevice {
+        async fn info(&self) -> YKeyResult<DeviceInfo> {
+            Ok(self.info.clone())
+        }
+
+        async fn connect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        async fn disconnect(&mut self) -> YKeyResult<()> {
+            Err(YKeyError::DeviceBusy("handle still open".to_string()))
+        }
+
+        fn is_connected(&self) -> bool {
+            true
+        }
+
+        async fn send_raw(&mut self, _data: &[u8]) -> YKeyResult<Vec<u8>> {
+            Ok(vec![0x90, 0x00])
+        }
+    }
+
+    /// Creator handing out stuck devices for IDs starting with "stuck"
+    struct StuckCreator;
+
+    impl DeviceCreator for StuckCreator {
+        fn create(&self, info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
+            Ok(Box::new(StuckDevice { info: info.clone() }))
+        }
+
+        fn supports(&self, info: &DeviceInfo) -> bool {
+            info.id.as_str().starts_with("stuck")
+        }
+
+        fn priority(&self) -> u32 {
+            100
+        }
+
+        fn name(&self) -> &str {
+            "Stuck Creator"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_all_reports_failures() {
+        let mut factory = DeviceFactory::new();
+        factory.register_creator(Box::new(StuckCreator));
+        let mut manager = DeviceManager::with_factory(factory);
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![
+            create_test_device_info("device1", DeviceType::YubiKey),
+            create_test_device_info("stuck1", DeviceType::YubiKey),
+            create_test_device_info("stuck2", DeviceType::Generic),
+        ])));
+        for id in ["device1", "stuck1", "stuck2"] {
+            manager.connect_device(&id.into()).await.unwrap();
+        }
+
+        let Err(YKeyError::PartialFailure(failures)) = manager.disconnect_all().await else {
+            panic!("expected the stuck devices to be reported");
+        };
+        let failed: Vec<&str> = failures.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(failed, vec!["stuck1", "stuck2"]);
+        assert!(failures.iter().all(|(_, e)| matches!(e, YKeyError::DeviceBusy(_))));
+        assert_eq!(manager.device_count().await, 0);
+    }
+
+    /// Device answering GetAssertion after a delay, recording cancellation
+    struct ScriptedAssertionDevice {
+        info: DeviceInfo,
+        delay: std::time::Duration,
+        response: Vec<u8>,
+        cancelled: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    #[async_trait]
+    impl Device for ScriptedAssertionDevice {
+        async fn info(&self) -> YKeyResult<DeviceInfo> {
+            Ok(self.info.clone())
+        }
+
+        async fn connect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        async fn disconnect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        fn is_connected(&self) -> bool {
+            true
+        }
+
+        async fn send_raw(&mut self, _data: &[u8]) -> YKeyResult<Vec<u8>> {
+            tokio::time::sleep(self.delay).await;
+            Ok(self.response.clone())
+        }
+
+        async fn cancel(&mut self) -> YKeyResult<()> {
+            self.cancelled.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Creator handing out scripted devices keyed by device ID
+    struct ScriptedCreator {
+        scripts: HashMap<String, (std::time::Duration, Vec<u8>, Arc<std::sync::atomic::AtomicBool>)>,
+    }
+
+    impl DeviceCreator for ScriptedCreator {
+        fn create(&self, info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
+            let (delay, response, cancelled) = self.scripts.get(info.id.as_str())
+                .cloned()
+                .ok_or_else(|| YKeyError::DeviceNotFound(info.id.to_string()))?;
+            Ok(Box::new(ScriptedAssertionDevice {
+                info: info.clone(),
+                delay,
+                response,
+                cancelled,
+            }))
+        }
+
+        fn supports(&self, _info: &DeviceInfo) -> bool {
+            true
+        }
+
+        fn name(&self) -> &str {
+            "Scripted Creator"
+        }
+    }
+
+    fn assertion_response(credential_id: &[u8]) -> Vec<u8> {
+        use ykey_protocol::cbor;
+
+        let body = cbor::int_map(vec![
+            (0x01, Some(cbor::Value::Map(vec![
+                (cbor::text("id"), cbor::bytes(credential_id)),
+                (cbor::text("type"), cbor::text("public-key")),
+            ]))),
+            (0x02, Some(cbor::bytes(&[0xAA; 37]))),
+            (0x03, Some(cbor::bytes(&[0x30, 0x45]))),
+        ]);
+        let mut response = vec![0x00];
+        response.extend(cbor::encode(&body).unwrap());
+        response
+    }
+
+    #[tokio::test]
+    async fn test_race_assertion_first_success_wins() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        let holder_cancelled = Arc::new(AtomicBool::new(false));
+        let other_cancelled = Arc::new(AtomicBool::new(false));
+
+        let mut scripts = HashMap::new();
+        scripts.insert(
+            "holder".to_string(),
+            (Duration::from_millis(10), assertion_response(&[1, 2, 3]), holder_cancelled.clone()),
+        );
+        // The other key has no credential and keeps waiting for a touch
+        scripts.insert(
+            "other".to_string(),
+            (Duration::from_secs(10), vec![0x2E], other_cancelled.clone()),
+        );
+
+        let mut factory = DeviceFactory::new();
+        factory.register(DeviceType::Generic, Box::new(ScriptedCreator { scripts }));
+        let mut manager = DeviceManager::with_factory(factory);
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![
+            create_test_device_info("holder", DeviceType::Generic),
+            create_test_device_info("other", DeviceType::Generic),
+        ])));
+        manager.connect_device(&"holder".into()).await.unwrap();
+        manager.connect_device(&"other".into()).await.unwrap();
+
+        let params = GetAssertionParams {
+            rp_id: "example.com".to_string(),
+            client_data_hash: vec![0; 32],
+            allow_list: None,
+            extensions: None,
+            options: GetAssertionOptions::default(),
+            pin_uv_auth_param: None,
+            pin_uv_auth_protocol: None,
+            get_cred_blob: false,
+            large_blob_key: false,
+        };
+        let ids = vec![DeviceId::from("holder"), DeviceId::from("other")];
+
+        let (winner, assertion) = tokio::time::timeout(
+            Duration::from_secs(2),
+            manager.race_assertion(params, &ids),
+        ).await.unwrap().unwrap();
+
+        assert_eq!(winner, "holder");
+        assert_eq!(assertion.credential_id, Some(vec![1, 2, 3]));
+        assert!(other_cancelled.load(Ordering::SeqCst));
+        assert!(!holder_cancelled.load(Ordering::SeqCst));
+    }
+
+    /// Cancels a [`BlockingAssertionDevice`] by raising its flag
+    struct FlagCanceller(Arc<std::sync::atomic::AtomicBool>);
+
+    impl Canceller for FlagCanceller {
+        fn cancel(&self) {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    /// Device whose requests block their thread until cancelled, like HID
+    /// report reads, so aborting the task waiting on them does not stop them
+    struct BlockingAssertionDevice {
+        info: DeviceInfo,
+        cancelled: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    #[async_trait]
+    impl Device for BlockingAssertionDevice {
+        async fn info(&self) -> YKeyResult<DeviceInfo> {
+            Ok(self.info.clone())
+        }
+
+        async fn connect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        async fn disconnect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        fn is_connected(&self) -> bool {
+            true
+        }
+
+        async fn send_raw(&mut self, _data: &[u8]) -> YKeyResult<Vec<u8>> {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+            while !self.cancelled.load(std::sync::atomic::Ordering::SeqCst) {
+                if std::time::Instant::now() > deadline {
+                    return Err(YKeyError::timeout(5));
+                }
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            Ok(vec![0x2D]) // CTAP2_ERR_KEEPALIVE_CANCEL
+        }
+
+        fn canceller(&self) -> Option<Arc<dyn Canceller>> {
+            Some(Arc::new(FlagCanceller(self.cancelled.clone())))
+        }
+    }
+
+    struct BlockingCreator {
+        cancelled: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl DeviceCreator for BlockingCreator {
+        fn create(&self, info: &DeviceInfo) -> YKeyResult<Box<dyn Device>> {
+            Ok(Box::new(BlockingAssertionDevice { info: info.clone(), cancelled: self.cancelled.clone() }))
+        }
+
+        fn supports(&self, _info: &DeviceInfo) -> bool {
+            true
+        }
+
+        fn name(&self) -> &str {
+            "Blocking Creator"
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_race_assertion_cancels_blocked_losers() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        let mut scripts = HashMap::new();
+        scripts.insert(
+            "holder".to_string(),
+            (Duration::from_millis(10), assertion_response(&[1, 2, 3]), Arc::new(AtomicBool::new(false))),
+        );
+        let blocker_cancelled = Arc::new(AtomicBool::new(false));
+
+        let mut factory = DeviceFactory::new();
+        factory.register(DeviceType::Generic, Box::new(ScriptedCreator { scripts }));
+        factory.register(DeviceType::SoloKey, Box::new(BlockingCreator { cancelled: blocker_cancelled.clone() }));
+        let mut manager = DeviceManager::with_factory(factory);
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![
+            create_test_device_info("holder", DeviceType::Generic),
+            create_test_device_info("blocker", DeviceType::SoloKey),
+        ])));
+        manager.connect_device(&"holder".into()).await.unwrap();
+        manager.connect_device(&"blocker".into()).await.unwrap();
+
+        let params = GetAssertionParams::builder()
+            .rp_id("example.com")
+            .client_data_hash(vec![0; 32])
+            .build()
+            .unwrap();
+        let ids = vec![DeviceId::from("holder"), DeviceId::from("blocker")];
+
+        // Joining the blocked loser only finishes once it has been cancelled
+        let (winner, _) = tokio::time::timeout(
+            Duration::from_secs(2),
+            manager.race_assertion(params, &ids),
+        ).await.unwrap().unwrap();
+
+        assert_eq!(winner, "holder");
+        assert!(blocker_cancelled.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_managed_device_cancels_busy_device() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut factory = DeviceFactory::new();
+        factory.register(DeviceType::Generic, Box::new(BlockingCreator { cancelled: cancelled.clone() }));
+        let mut manager = DeviceManager::with_factory(factory);
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![
+            create_test_device_info("blocker", DeviceType::Generic),
+        ])));
+        let id = DeviceId::from("blocker");
+        manager.connect_device(&id).await.unwrap();
+
+        let mut busy = manager.fido2_client(&id).await.unwrap();
+        let pending = tokio::spawn(async move { busy.device_mut().send_raw(&[0x02]).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!pending.is_finished());
+
+        // The pending request holds the device lock while it waits
+        let mut other = manager.fido2_client(&id).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), other.device_mut().cancel()).await.unwrap().unwrap();
+        assert!(cancelled.load(Ordering::SeqCst));
+        let response = tokio::time::timeout(Duration::from_secs(1), pending).await.unwrap().unwrap();
+        assert_eq!(response.unwrap(), vec![0x2D]);
+    }
+
+    #[tokio::test]
+    async fn test_fido2_client_for_managed_device() {
+        use ykey_protocol::cbor;
+
+        let info = cbor::int_map(vec![
+            (0x01, Some(cbor::Value::Array(vec![cbor::text("FIDO_2_1")]))),
+            (0x03, Some(cbor::bytes(&[0x42; 16]))),
+        ]);
+        let response = [vec![0x00], cbor::encode(&info).unwrap()].concat();
+        let mut scripts = HashMap::new();
+        scripts.insert(
+            "managed".to_string(),
+            (std::time::Duration::ZERO, response, Arc::new(std::sync::atomic::AtomicBool::new(false))),
+        );
+
+        let mut factory = DeviceFactory::new();
+        factory.register(DeviceType::Generic, Box::new(ScriptedCreator { scripts }));
+        let mut manager = DeviceManager::with_factory(factory);
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![
+            create_test_device_info("managed", DeviceType::Generic),
+        ])));
+        let id = DeviceId::from("managed");
+        assert!(matches!(manager.fido2_client(&id).await, Err(YKeyError::DeviceNotFound(_))));
+        manager.connect_device(&id).await.unwrap();
+
+        let mut client = manager.fido2_client(&id).await.unwrap();
+        let info = client.get_info().await.unwrap();
+        assert_eq!(info.versions, vec!["FIDO_2_1"]);
+        assert_eq!(info.aaguid, Aaguid::new([0x42; 16]));
+
+        // The manager still owns the device and can use it alongside the client
+        assert!(manager.is_device_connected(&id).await);
+        let ping = manager.with_device(&id, |device| Box::pin(async move { device.send_raw(&[0x04]).await })).await;
+        assert!(ping.is_ok());
+        assert!(client.get_info().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_capabilities_from_get_info() {
+        use ykey_protocol::cbor;
+
+        let info = cbor::int_map(vec![
+            (0x01, Some(cbor::Value::Array(vec![cbor::text("U2F_V2"), cbor::text("FIDO_2_0")]))),
+            (0x03, Some(cbor::bytes(&[0x42; 16]))),
+        ]);
+        let response = [vec![0x00], cbor::encode(&info).unwrap()].concat();
+        let mut scripts = HashMap::new();
+        scripts.insert(
+            "fido-only".to_string(),
+            (std::time::Duration::ZERO, response, Arc::new(std::sync::atomic::AtomicBool::new(false))),
+        );
+
+        // Discovery's static table claims applets the key does not have
+        let mut listed = create_test_device_info("fido-only", DeviceType::Generic);
+        for capability in [Capability::Fido2, Capability::Oath, Capability::Piv, Capability::Otp] {
+            listed.add_capability(capability);
+        }
+        let mut factory = DeviceFactory::new();
+        factory.register(DeviceType::Generic, Box::new(ScriptedCreator { scripts }));
+        let mut manager = DeviceManager::with_factory(factory);
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![listed])));
+        let id = DeviceId::from("fido-only");
+
+        let refreshed = manager.refresh_capabilities(&id).await.unwrap();
+        assert_eq!(refreshed.capabilities, vec![Capability::Fido2, Capability::Fido1]);
+        assert!(!refreshed.has_capability(&Capability::Piv));
+        assert!(manager.is_device_connected(&id).await);
+
+        let rescanned = manager.scan_devices().await.unwrap();
+        assert_eq!(rescanned[0].capabilities, vec![Capability::Fido2, Capability::Fido1]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reset_fido2_settles_and_clears_caches() {
+        use std::time::Duration;
+
+        let mut scripts = HashMap::new();
+        scripts.insert(
+            "yubikey".to_string(),
+            (Duration::ZERO, vec![0x00], Arc::new(std::sync::atomic::AtomicBool::new(false))),
+        );
+        // The built-in quirk table gives YubiKeys a settle delay
+        let mut listed = create_test_device_info("yubikey", DeviceType::Generic);
+        listed.vendor_id = 0x1050;
+        let settle = ykey_protocol::quirks::QuirkTable::new()
+            .lookup(&ykey_protocol::quirks::DeviceIdentity { vendor_id: Some(0x1050), ..Default::default() })
+            .post_reset_delay
+            .unwrap();
+
+        let mut factory = DeviceFactory::new();
+        factory.register(DeviceType::Generic, Box::new(ScriptedCreator { scripts }));
+        let mut manager = DeviceManager::with_factory(factory);
+        manager.add_discovery(Box::new(MockDiscovery::new(vec![listed])));
+        let id = DeviceId::from("yubikey");
+        manager.scan_devices().await.unwrap();
+        manager.connect_device(&id).await.unwrap();
+        manager.reported_capabilities.write().await.insert(id.clone(), vec![Capability::Fido2]);
+        let manager = Arc::new(manager);
+
+        let start = tokio::time::Instant::now();
+        let reset = tokio::spawn({
+            let manager = manager.clone();
+            let id = id.clone();
+            async move { manager.reset_fido2(&id).await }
+        });
+        tokio::task::yield_now().await;
+
+        // An operation issued during the reset waits for the device to settle
+        manager.with_device(&id, |device| Box::pin(async move { device.send_raw(&[0x04]).await })).await.unwrap();
+        assert!(start.elapsed() >= settle);
+        reset.await.unwrap().unwrap();
+
+        assert!(manager.reported_capabilities.read().await.get(&id).is_none());
+        assert!(manager.scan_cache.read().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_race_assertion_without_candidates() {
+        let manager = DeviceManager::new();
+        let params = GetAssertionParams {
+            rp_id: "example.com".to_string(),
+            client_data_hash: vec![0; 32],
+            allow_list: None,
+            extensions: None,
+            options: GetAssertionOptions::default(),
+            pin_uv_auth_param: None,
+            pin_uv_auth_protocol: None,
+            get_cred_blob: false,
+            large_blob_key: false,
+        };
+
+        let result = manager.race_assertion(params, &[DeviceId::from("missing")]).await;
+        assert!(matches!(result, Err(YKeyError::DeviceNotFound(_))));
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Protocol clients over devices owned by the manager
+//!
+//! [`ManagedDevice`] is a [`Device`] that forwards to a connected device in
+//! the manager's map. Each call locks the device and goes through its rate
+//! limit on its own, so a long-lived client does not keep other operations
+//! away from the device between commands.
+
+use crate::SharedDevice;
+use async_trait::async_trait;
+use std::time::Duration;
+use ykey_core::{traits::*, types::*, YKeyResult};
+use ykey_protocol::Fido2Client;
+
+/// FIDO2 client over a device owned by a [`DeviceManager`](crate::DeviceManager)
+pub type ManagedFido2Client = Fido2Client<ManagedDevice>;
+
+/// Handle to a connected device that stays owned by the manager
+///
+/// The handle keeps working after the manager disconnects the device, but
+/// every operation then fails with the device's own not-connected error.
+pub struct ManagedDevice {
+    device: SharedDevice,
+}
+
+impl ManagedDevice {
+    pub(crate) fn new(device: SharedDevice) -> Self {
+        Self { device }
+    }
+}
+
+#[async_trait]
+impl Device for ManagedDevice {
+    async fn info(&self) -> YKeyResult<DeviceInfo> {
+        self.device.device.lock().await.info().await
+    }
+
+    async fn connect(&mut self) -> YKeyResult<()> {
+        self.device.device.lock().await.connect().await
+    }
+
+    async fn connect_with(&mut self, options: &ConnectOptions) -> YKeyResult<()> {
+        self.device.device.lock().await.connect_with(options).await
+    }
+
+    async fn disconnect(&mut self) -> YKeyResult<()> {
+        self.device.device.lock().await.disconnect().await
+    }
+
+    /// A device busy with another operation counts as connected
+    fn is_connected(&self) -> bool {
+        self.device.device.try_lock().map_or(true, |device| device.is_connected())
+    }
+
+    async fn send_raw(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        self.device.acquire().await.send_raw(data).await
+    }
+
+    fn max_message_size(&self) -> usize {
+        self.device.max_message_size
+    }
+
+    fn packet_payload_sizes(&self) -> Option<(usize, usize)> {
+        self.device.packet_payload_sizes
+    }
+
+    async fn send_fragments(&mut self, fragments: &[&[u8]]) -> YKeyResult<Vec<u8>> {
+        self.device.acquire().await.send_fragments(fragments).await
+    }
+
+    fn operation_timeout(&self) -> Duration {
+        self.device.operation_timeout
+    }
+
+    /// Cancelling bypasses the rate limit and, when the transport has a
+    /// canceller, the device lock, so it reaches a busy device. Otherwise it
+    /// waits for the operation in flight.
+    async fn cancel(&mut self) -> YKeyResult<()> {
+        self.device.cancel().await
+    }
+
+    fn canceller(&self) -> Option<std::sync::Arc<dyn Canceller>> {
+        self.device.canceller.clone()
+    }
+
+    async fn ping(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        self.device.acquire().await.ping(data).await
+    }
+
+    async fn wink(&mut self) -> YKeyResult<()> {
+        self.device.acquire().await.wink().await
+    }
+
+    async fn reset_channel(&mut self) -> YKeyResult<()> {
+        self.device.acquire().await.reset_channel().await
+    }
+
+    async fn send_u2f(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>> {
+        self.device.acquire().await.send_u2f(apdu).await
+    }
+}
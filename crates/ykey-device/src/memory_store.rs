@@ -0,0 +1,251 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! In-memory credential storage
+//!
+//! Clones share the same credentials, so one store can be handed to several
+//! tasks. Every operation holds the lock for its whole read and write, which
+//! keeps partial updates atomic. Usage and cleanup times come from the
+//! store's [`Clock`], the system clock unless replaced with `with_clock`.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+use ykey_core::{traits::*, types::*, Clock, SystemClock, YKeyError, YKeyResult};
+
+#[derive(Debug)]
+struct Contents {
+    credentials: HashMap<CredentialId, Credential>,
+    last_cleanup: DateTime<Utc>,
+}
+
+/// Credential store that lives only in memory
+#[derive(Debug, Clone)]
+pub struct MemoryCredentialStore {
+    contents: Arc<RwLock<Contents>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for MemoryCredentialStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryCredentialStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+
+    /// Create an empty store taking its timestamps from `clock`
+    pub fn with_clock(clock: impl Clock + 'static) -> Self {
+        Self {
+            contents: Arc::new(RwLock::new(Contents {
+                credentials: HashMap::new(),
+                last_cleanup: clock.now(),
+            })),
+            clock: Arc::new(clock),
+        }
+    }
+
+    fn read(&self) -> YKeyResult<RwLockReadGuard<'_, Contents>> {
+        self.contents.read().map_err(|_| poisoned())
+    }
+
+    fn write(&self) -> YKeyResult<RwLockWriteGuard<'_, Contents>> {
+        self.contents.write().map_err(|_| poisoned())
+    }
+
+    /// Apply `change` to one credential under the write lock
+    fn update(&self, id: &CredentialId, change: impl FnOnce(&mut Credential)) -> YKeyResult<()> {
+        let mut contents = self.write()?;
+        let credential = contents
+            .credentials
+            .get_mut(id)
+            .ok_or_else(|| YKeyError::CredentialNotFound(hex_id(id)))?;
+        change(credential);
+        Ok(())
+    }
+
+    /// Credentials matching `filter`, oldest first
+    fn collect(&self, filter: impl Fn(&Credential) -> bool) -> YKeyResult<Vec<Credential>> {
+        let mut credentials: Vec<_> = self.read()?.credentials.values().filter(|c| filter(c)).cloned().collect();
+        credentials.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        Ok(credentials)
+    }
+}
+
+#[async_trait]
+impl CredentialStore for MemoryCredentialStore {
+    async fn store(&mut self, credential: &Credential) -> YKeyResult<()> {
+        self.write()?.credentials.insert(credential.id.clone(), credential.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: &CredentialId) -> YKeyResult<Option<Credential>> {
+        Ok(self.read()?.credentials.get(id).cloned())
+    }
+
+    async fn list(&self) -> YKeyResult<Vec<Credential>> {
+        self.collect(|_| true)
+    }
+
+    async fn list_by_rp(&self, rp_id: &str) -> YKeyResult<Vec<Credential>> {
+        self.collect(|credential| credential.rp_id == rp_id)
+    }
+
+    async fn delete(&mut self, id: &CredentialId) -> YKeyResult<()> {
+        match self.write()?.credentials.remove(id) {
+            Some(_) => Ok(()),
+            None => Err(YKeyError::CredentialNotFound(hex_id(id))),
+        }
+    }
+
+    async fn update_usage(&mut self, id: &CredentialId) -> YKeyResult<()> {
+        let now = self.clock.now();
+        self.update(id, |credential| {
+            credential.counter += 1;
+            credential.last_used = Some(now);
+        })
+    }
+
+    async fn increment_counter(&mut self, id: &CredentialId, new_counter: u32) -> YKeyResult<()> {
+        self.update(id, |credential| credential.counter = credential.counter.max(new_counter))
+    }
+
+    async fn update_last_used(&mut self, id: &CredentialId, time: DateTime<Utc>) -> YKeyResult<()> {
+        self.update(id, |credential| credential.last_used = Some(time))
+    }
+
+    async fn clear(&mut self) -> YKeyResult<()> {
+        let mut contents = self.write()?;
+        contents.credentials.clear();
+        contents.last_cleanup = self.clock.now();
+        Ok(())
+    }
+
+    /// Storage used is the size of the credential fields; there is no fixed capacity
+    async fn stats(&self) -> YKeyResult<StorageStats> {
+        let contents = self.read()?;
+        let storage_used = contents
+            .credentials
+            .values()
+            .map(|c| {
+                c.id.len() + c.rp_id.len() + c.user_id.len() + c.user_name.len() + c.user_display_name.len()
+                    + c.public_key.len()
+            })
+            .sum::<usize>();
+        Ok(StorageStats {
+            total_credentials: contents.credentials.len() as u64,
+            storage_used: storage_used as u64,
+            storage_available: 0,
+            last_cleanup: contents.last_cleanup,
+        })
+    }
+}
+
+fn hex_id(id: &CredentialId) -> String {
+    id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn poisoned() -> YKeyError {
+    YKeyError::communication("Credential store lock poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn credential(id: u8, rp_id: &str) -> Credential {
+        Credential {
+            id: vec![id; 16],
+            rp_id: rp_id.to_string(),
+            user_id: vec![id],
+            user_name: format!("user{}", id),
+            user_display_name: format!("User {}", id),
+            public_key: vec![0x04, id],
+            counter: 0,
+            created_at: DateTime::<Utc>::UNIX_EPOCH + Duration::seconds(id as i64),
+            last_used: None,
+            backup_state: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_list_and_partial_updates() {
+        let mut store = MemoryCredentialStore::new();
+        store.store(&credential(3, "github.com")).await.unwrap();
+        store.store(&credential(1, "github.com")).await.unwrap();
+        store.store(&credential(2, "example.com")).await.unwrap();
+
+        let github: Vec<_> = store.list_by_rp("github.com").await.unwrap().into_iter().map(|c| c.id[0]).collect();
+        assert_eq!(github, vec![1, 3]);
+
+        store.increment_counter(&vec![1; 16], 7).await.unwrap();
+        store.increment_counter(&vec![1; 16], 3).await.unwrap();
+        let used = DateTime::<Utc>::UNIX_EPOCH + Duration::days(1);
+        store.update_last_used(&vec![1; 16], used).await.unwrap();
+        let updated = store.get(&vec![1; 16]).await.unwrap().unwrap();
+        assert_eq!((updated.counter, updated.last_used), (7, Some(used)));
+
+        store.delete(&vec![2; 16]).await.unwrap();
+        assert!(matches!(store.delete(&vec![2; 16]).await, Err(YKeyError::CredentialNotFound(_))));
+        assert_eq!(store.stats().await.unwrap().total_credentials, 2);
+        store.clear().await.unwrap();
+        assert!(store.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_timestamps_come_from_clock() {
+        let clock = ykey_core::MockClock::new(DateTime::<Utc>::UNIX_EPOCH + Duration::days(1));
+        let mut store = MemoryCredentialStore::with_clock(clock.clone());
+        store.store(&credential(1, "github.com")).await.unwrap();
+
+        clock.advance(Duration::hours(2));
+        store.update_usage(&vec![1; 16]).await.unwrap();
+        let used = store.get(&vec![1; 16]).await.unwrap().unwrap().last_used;
+        assert_eq!(used, Some(DateTime::<Utc>::UNIX_EPOCH + Duration::days(1) + Duration::hours(2)));
+
+        clock.advance(Duration::hours(1));
+        store.clear().await.unwrap();
+        assert_eq!(
+            store.stats().await.unwrap().last_cleanup,
+            DateTime::<Utc>::UNIX_EPOCH + Duration::days(1) + Duration::hours(3)
+        );
+    }
+
+    /// Run `update` on a hundred tasks sharing clones of `store`
+    async fn concurrently<F, Fut>(store: &MemoryCredentialStore, update: F)
+    where
+        F: Fn(MemoryCredentialStore, u32) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let tasks: Vec<_> = (1..=100).map(|n| tokio::spawn(update(store.clone(), n))).collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_counter_updates() {
+        let mut store = MemoryCredentialStore::new();
+        store.store(&credential(1, "github.com")).await.unwrap();
+
+        concurrently(&store, |mut store, _| async move {
+            store.update_usage(&vec![1; 16]).await.unwrap();
+        })
+        .await;
+        assert_eq!(store.get(&vec![1; 16]).await.unwrap().unwrap().counter, 100);
+
+        concurrently(&store, |mut store, n| async move {
+            store.increment_counter(&vec![1; 16], 100 + n).await.unwrap();
+        })
+        .await;
+        assert_eq!(store.get(&vec![1; 16]).await.unwrap().unwrap().counter, 200);
+    }
+}
@@ -0,0 +1,30 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Everything needed to find a key and talk to it in one import
+//!
+//! ```
+//! use ykey_device::prelude::*;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> YKeyResult<()> {
+//! let manager = DeviceManager::new();
+//! for info in manager.scan_devices().await? {
+//!     manager.connect_device(&info.id).await?;
+//!     let authenticator = manager
+//!         .with_device(&info.id, |device| {
+//!             Box::pin(async move {
+//!                 let mut client = Fido2Client::new(device);
+//!                 client.get_info().await
+//!             })
+//!         })
+//!         .await?;
+//!     println!("{}: {:?}", info.id, authenticator.versions);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub use ykey_protocol::prelude::*;
+
+pub use crate::{DeviceFactory, DeviceManager, DuplicatePolicy, ManagedFido2Client, RateLimit};
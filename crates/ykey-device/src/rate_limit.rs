@@ -0,0 +1,113 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Per-device command rate limiting
+//!
+//! Some authenticators drop or stall commands when they arrive faster than
+//! they can process them. A token bucket smooths bursts: up to `burst`
+//! commands go out at once, after which they are spaced to the configured
+//! rate. Callers reserve their slot before sleeping, so concurrent operations
+//! queue up in order instead of all waking at the same moment.
+
+use std::{sync::Mutex, time::Duration};
+use tokio::time::Instant;
+
+/// Rate applied to the commands sent to one device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Sustained commands per second
+    pub commands_per_second: f64,
+    /// Commands that may be sent back to back before spacing kicks in
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Limit to `commands_per_second`, one command at a time
+    pub fn per_second(commands_per_second: f64) -> Self {
+        Self { commands_per_second, burst: 1 }
+    }
+
+    /// Allow `burst` commands back to back
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    fn capacity(&self) -> f64 {
+        self.burst.max(1) as f64
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    /// Tokens available; negative when callers are queued for future slots
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Token bucket shared by the operations on one device, unlimited when unset
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    bucket: Mutex<Option<Bucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: Option<RateLimit>) -> Self {
+        let limiter = Self::default();
+        limiter.set(limit);
+        limiter
+    }
+
+    /// Replace the limit, starting from a full bucket
+    pub(crate) fn set(&self, limit: Option<RateLimit>) {
+        let bucket = limit
+            .filter(|limit| limit.commands_per_second > 0.0)
+            .map(|limit| Bucket { limit, tokens: limit.capacity(), refilled_at: Instant::now() });
+        *self.bucket.lock().unwrap_or_else(|e| e.into_inner()) = bucket;
+    }
+
+    /// Wait until the next command may be sent
+    pub(crate) async fn acquire(&self) {
+        let wait = {
+            let mut guard = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(bucket) = guard.as_mut() else { return };
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * bucket.limit.commands_per_second).min(bucket.limit.capacity());
+            bucket.refilled_at = now;
+            bucket.tokens -= 1.0;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / bucket.limit.commands_per_second)
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_burst_then_spacing() {
+        let limiter = RateLimiter::new(Some(RateLimit::per_second(20.0).with_burst(3)));
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(40));
+
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(90));
+
+        limiter.set(None);
+        let unlimited = Instant::now();
+        for _ in 0..100 {
+            limiter.acquire().await;
+        }
+        assert!(unlimited.elapsed() < Duration::from_millis(40));
+    }
+}
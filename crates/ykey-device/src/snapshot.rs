@@ -0,0 +1,172 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Full device list snapshots
+//!
+//! Consumers that only care about the current device set can wait on a
+//! debounced change signal and re-read the snapshot, instead of applying
+//! individual device events.
+
+use crate::DeviceManager;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use ykey_core::{types::*, YKeyResult};
+
+/// Device together with its connection status
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeviceSnapshot {
+    /// Device as reported by discovery
+    pub info: DeviceInfo,
+    /// Whether the manager holds an open connection to it
+    pub connected: bool,
+}
+
+impl DeviceManager {
+    /// Current device list with connection status
+    pub async fn snapshot(&self) -> YKeyResult<Vec<DeviceSnapshot>> {
+        let devices = self.scan_devices().await?;
+        let connected = self.connected_device_ids().await;
+
+        Ok(devices
+            .into_iter()
+            .map(|info| DeviceSnapshot {
+                connected: connected.contains(&info.id),
+                info,
+            })
+            .collect())
+    }
+}
+
+/// Coalesce bursts of notifications into single change signals
+///
+/// A signal is sent once `quiet` passes without further input. If the
+/// receiver has not taken the previous signal yet, no new one is queued.
+pub fn debounce<T: Send + 'static>(mut input: mpsc::Receiver<T>, quiet: Duration) -> mpsc::Receiver<()> {
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        while input.recv().await.is_some() {
+            let closed = loop {
+                match tokio::time::timeout(quiet, input.recv()).await {
+                    Ok(Some(_)) => continue,
+                    Ok(None) => break true,
+                    Err(_) => break false,
+                }
+            };
+
+            if let Err(mpsc::error::TrySendError::Closed(_)) = tx.try_send(()) {
+                return;
+            }
+            if closed {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+    use ykey_core::traits::DeviceDiscovery;
+
+    /// Discovery whose device set and events are driven by the test
+    struct PluggableDiscovery {
+        devices: Arc<Mutex<Vec<DeviceInfo>>>,
+        events: Mutex<Option<mpsc::Receiver<DeviceEvent>>>,
+    }
+
+    #[async_trait]
+    impl DeviceDiscovery for PluggableDiscovery {
+        async fn scan(&self) -> YKeyResult<Vec<DeviceInfo>> {
+            Ok(self.devices.lock().unwrap().clone())
+        }
+
+        async fn watch(&self) -> YKeyResult<DeviceEventStream> {
+            Ok(self.events.lock().unwrap().take().expect("watched once"))
+        }
+
+        async fn stop_watch(&self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        async fn is_device_available(&self, device_id: &str) -> YKeyResult<bool> {
+            Ok(self.devices.lock().unwrap().iter().any(|d| d.id == device_id))
+        }
+    }
+
+    fn device(id: &str) -> DeviceInfo {
+        DeviceInfo::new(
+            id.to_string(),
+            "Test Key".to_string(),
+            "Test".to_string(),
+            "Test Key".to_string(),
+            0x1234,
+            0x5678,
+            DeviceType::Generic,
+            TransportType::Usb,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_plug_and_unplug_coalesce_into_net_snapshot() {
+        let devices = Arc::new(Mutex::new(vec![device("resident")]));
+        let (events, rx) = mpsc::channel(8);
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(PluggableDiscovery {
+            devices: devices.clone(),
+            events: Mutex::new(Some(rx)),
+        }));
+
+        let mut changes = debounce(manager.watch_devices().await.unwrap(), Duration::from_millis(100));
+
+        // Plug and unplug in quick succession
+        devices.lock().unwrap().push(device("transient"));
+        events.send(DeviceEvent::Connected(device("transient"))).await.unwrap();
+        devices.lock().unwrap().retain(|d| d.id != "transient");
+        events.send(DeviceEvent::Disconnected("transient".to_string())).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(2), changes.recv()).await.unwrap().unwrap();
+        let snapshot = manager.snapshot().await.unwrap();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].info.id, "resident");
+        assert!(!snapshot[0].connected);
+
+        // The burst produced exactly one signal
+        assert!(tokio::time::timeout(Duration::from_millis(300), changes.recv()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_reports_connection_status() {
+        let devices = Arc::new(Mutex::new(vec![device("a"), device("b")]));
+        let (_events, rx) = mpsc::channel(1);
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(Box::new(PluggableDiscovery {
+            devices,
+            events: Mutex::new(Some(rx)),
+        }));
+
+        manager.connect_device(&"b".into()).await.unwrap();
+        let connected: Vec<_> = manager
+            .snapshot()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.info.id, s.connected))
+            .collect();
+        assert_eq!(connected, vec![("a".into(), false), ("b".into(), true)]);
+    }
+
+    #[tokio::test]
+    async fn test_debounce_flushes_when_input_closes() {
+        let (tx, rx) = mpsc::channel(4);
+        let mut changes = debounce(rx, Duration::from_secs(60));
+        tx.send(()).await.unwrap();
+        drop(tx);
+
+        assert_eq!(tokio::time::timeout(Duration::from_secs(2), changes.recv()).await.unwrap(), Some(()));
+        assert_eq!(changes.recv().await, None);
+    }
+}
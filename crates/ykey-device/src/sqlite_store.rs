@@ -0,0 +1,413 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! SQLite-backed credential storage
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use ykey_core::{traits::*, types::*, Clock, SystemClock, YKeyError, YKeyResult};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS credentials (
+        id BLOB PRIMARY KEY,
+        rp_id TEXT NOT NULL,
+        user_id BLOB NOT NULL,
+        user_name TEXT NOT NULL,
+        user_display_name TEXT NOT NULL,
+        public_key BLOB NOT NULL,
+        counter INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        last_used TEXT,
+        backup_state INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS credentials_rp_id ON credentials (rp_id);
+    CREATE TABLE IF NOT EXISTS metadata (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+";
+
+/// How long a write waits for another connection to release the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const COLUMNS: &str =
+    "id, rp_id, user_id, user_name, user_display_name, public_key, counter, created_at, last_used, backup_state";
+
+/// Credential store persisted in a SQLite database
+pub struct SqliteCredentialStore {
+    connection: Mutex<Connection>,
+    path: Option<PathBuf>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SqliteCredentialStore {
+    /// Open or create a database file
+    pub fn open(path: impl AsRef<Path>) -> YKeyResult<Self> {
+        let connection = Connection::open(path.as_ref()).map_err(sql_error)?;
+        Self::init(connection, Some(path.as_ref().to_path_buf()))
+    }
+
+    /// Create a store that lives only in memory
+    pub fn open_in_memory() -> YKeyResult<Self> {
+        let connection = Connection::open_in_memory().map_err(sql_error)?;
+        Self::init(connection, None)
+    }
+
+    /// Take usage and cleanup times from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    fn init(connection: Connection, path: Option<PathBuf>) -> YKeyResult<Self> {
+        connection.busy_timeout(BUSY_TIMEOUT).map_err(sql_error)?;
+        connection.execute_batch(SCHEMA).map_err(sql_error)?;
+        add_backup_state_column(&connection).map_err(sql_error)?;
+        connection
+            .execute(
+                "INSERT OR IGNORE INTO metadata (key, value) VALUES ('last_cleanup', ?1)",
+                params![Utc::now().to_rfc3339()],
+            )
+            .map_err(sql_error)?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            path,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    fn with_connection<T>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> YKeyResult<T> {
+        let connection = self
+            .connection
+            .lock()
+            .map_err(|_| YKeyError::communication("Credential database lock poisoned"))?;
+        f(&connection).map_err(sql_error)
+    }
+
+    fn query(&self, filter: &str, args: &[&dyn rusqlite::ToSql]) -> YKeyResult<Vec<Credential>> {
+        self.with_connection(|connection| {
+            let mut statement = connection.prepare(&format!(
+                "SELECT {} FROM credentials {} ORDER BY created_at, id",
+                COLUMNS, filter
+            ))?;
+            let rows = statement.query_map(args, read_credential)?;
+            rows.collect()
+        })
+    }
+
+    /// Apply `assignments` to one credential in a single statement
+    ///
+    /// The credential ID is bound as `?1`.
+    fn update(&self, id: &CredentialId, assignments: &str, args: &[&dyn rusqlite::ToSql]) -> YKeyResult<()> {
+        let updated = self.with_connection(|connection| {
+            connection.execute(&format!("UPDATE credentials SET {} WHERE id = ?1", assignments), args)
+        })?;
+        if updated == 0 {
+            return Err(YKeyError::CredentialNotFound(hex_id(id)));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CredentialStore for SqliteCredentialStore {
+    async fn store(&mut self, credential: &Credential) -> YKeyResult<()> {
+        self.with_connection(|connection| {
+            connection.execute(
+                &format!("INSERT OR REPLACE INTO credentials ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)", COLUMNS),
+                params![
+                    credential.id,
+                    credential.rp_id,
+                    credential.user_id,
+                    credential.user_name,
+                    credential.user_display_name,
+                    credential.public_key,
+                    credential.counter,
+                    credential.created_at.to_rfc3339(),
+                    credential.last_used.map(|t| t.to_rfc3339()),
+                    credential.backup_state,
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    async fn get(&self, id: &CredentialId) -> YKeyResult<Option<Credential>> {
+        self.with_connection(|connection| {
+            connection
+                .query_row(
+                    &format!("SELECT {} FROM credentials WHERE id = ?1", COLUMNS),
+                    params![id],
+                    read_credential,
+                )
+                .optional()
+        })
+    }
+
+    async fn list(&self) -> YKeyResult<Vec<Credential>> {
+        self.query("", &[])
+    }
+
+    async fn list_by_rp(&self, rp_id: &str) -> YKeyResult<Vec<Credential>> {
+        self.query("WHERE rp_id = ?1", &[&rp_id])
+    }
+
+    async fn delete(&mut self, id: &CredentialId) -> YKeyResult<()> {
+        let deleted = self.with_connection(|connection| {
+            connection.execute("DELETE FROM credentials WHERE id = ?1", params![id])
+        })?;
+        if deleted == 0 {
+            return Err(YKeyError::CredentialNotFound(hex_id(id)));
+        }
+        Ok(())
+    }
+
+    async fn update_usage(&mut self, id: &CredentialId) -> YKeyResult<()> {
+        self.update(id, "counter = counter + 1, last_used = ?2", params![id, self.clock.now().to_rfc3339()])
+    }
+
+    async fn increment_counter(&mut self, id: &CredentialId, new_counter: u32) -> YKeyResult<()> {
+        self.update(id, "counter = MAX(counter, ?2)", params![id, new_counter])
+    }
+
+    async fn update_last_used(&mut self, id: &CredentialId, time: DateTime<Utc>) -> YKeyResult<()> {
+        self.update(id, "last_used = ?2", params![id, time.to_rfc3339()])
+    }
+
+    async fn clear(&mut self) -> YKeyResult<()> {
+        let now = self.clock.now();
+        self.with_connection(|connection| {
+            connection.execute("DELETE FROM credentials", [])?;
+            connection.execute(
+                "UPDATE metadata SET value = ?1 WHERE key = 'last_cleanup'",
+                params![now.to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    async fn stats(&self) -> YKeyResult<StorageStats> {
+        let (total_credentials, page_size, page_count, free_pages, last_cleanup) =
+            self.with_connection(|connection| {
+                let total: i64 = connection.query_row("SELECT COUNT(*) FROM credentials", [], |row| row.get(0))?;
+                let page_size: i64 = connection.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+                let page_count: i64 = connection.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+                let free_pages: i64 = connection.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+                let last_cleanup: String = connection.query_row(
+                    "SELECT value FROM metadata WHERE key = 'last_cleanup'",
+                    [],
+                    |row| row.get(0),
+                )?;
+                Ok((total, page_size, page_count, free_pages, last_cleanup))
+            })?;
+
+        // In-memory databases have no file, so fall back to the page count
+        let storage_used = match &self.path {
+            Some(path) => std::fs::metadata(path)?.len(),
+            None => (page_size * page_count) as u64,
+        };
+
+        Ok(StorageStats {
+            total_credentials: total_credentials as u64,
+            storage_used,
+            storage_available: (page_size * free_pages) as u64,
+            last_cleanup: parse_timestamp(&last_cleanup)?,
+        })
+    }
+}
+
+/// Databases created before backup states were tracked lack the column
+fn add_backup_state_column(connection: &Connection) -> rusqlite::Result<()> {
+    let has_column = connection
+        .prepare("SELECT 1 FROM pragma_table_info('credentials') WHERE name = 'backup_state'")?
+        .exists([])?;
+    if !has_column {
+        connection.execute("ALTER TABLE credentials ADD COLUMN backup_state INTEGER", [])?;
+    }
+    Ok(())
+}
+
+fn read_credential(row: &Row<'_>) -> rusqlite::Result<Credential> {
+    let timestamp = |index: usize, value: String| {
+        parse_timestamp(&value).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+        })
+    };
+
+    Ok(Credential {
+        id: row.get(0)?,
+        rp_id: row.get(1)?,
+        user_id: row.get(2)?,
+        user_name: row.get(3)?,
+        user_display_name: row.get(4)?,
+        public_key: row.get(5)?,
+        counter: row.get(6)?,
+        created_at: timestamp(7, row.get(7)?)?,
+        last_used: row.get::<_, Option<String>>(8)?.map(|value| timestamp(8, value)).transpose()?,
+        backup_state: row.get(9)?,
+    })
+}
+
+fn parse_timestamp(value: &str) -> YKeyResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| YKeyError::InvalidCredential(format!("Invalid timestamp {:?}: {}", value, e)))
+}
+
+fn hex_id(id: &CredentialId) -> String {
+    id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sql_error(error: rusqlite::Error) -> YKeyError {
+    YKeyError::Generic(anyhow::Error::new(error).context("Credential database error"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn credential(id: u8, rp_id: &str) -> Credential {
+        Credential {
+            id: vec![id; 16],
+            rp_id: rp_id.to_string(),
+            user_id: vec![id],
+            user_name: format!("user{}", id),
+            user_display_name: format!("User {}", id),
+            public_key: vec![0x04, id],
+            counter: 0,
+            created_at: DateTime::<Utc>::UNIX_EPOCH + Duration::seconds(id as i64),
+            last_used: None,
+            backup_state: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_get_and_list() {
+        let mut store = SqliteCredentialStore::open_in_memory().unwrap();
+        store.store(&credential(1, "github.com")).await.unwrap();
+        store.store(&credential(2, "example.com")).await.unwrap();
+        store.store(&credential(3, "github.com")).await.unwrap();
+
+        assert_eq!(store.get(&vec![1; 16]).await.unwrap(), Some(credential(1, "github.com")));
+        assert_eq!(store.get(&vec![9; 16]).await.unwrap(), None);
+        assert_eq!(store.list().await.unwrap().len(), 3);
+
+        let github: Vec<_> = store.list_by_rp("github.com").await.unwrap().into_iter().map(|c| c.id[0]).collect();
+        assert_eq!(github, vec![1, 3]);
+
+        // Storing an existing ID replaces the row
+        let mut renamed = credential(1, "github.com");
+        renamed.user_name = "renamed".to_string();
+        renamed.backup_state = Some(true);
+        store.store(&renamed).await.unwrap();
+        assert_eq!(store.list().await.unwrap().len(), 3);
+        assert_eq!(store.get(&vec![1; 16]).await.unwrap(), Some(renamed));
+    }
+
+    #[tokio::test]
+    async fn test_update_usage() {
+        let clock = ykey_core::MockClock::new(DateTime::<Utc>::UNIX_EPOCH + Duration::days(1));
+        let mut store = SqliteCredentialStore::open_in_memory().unwrap().with_clock(clock.clone());
+        store.store(&credential(1, "github.com")).await.unwrap();
+
+        store.update_usage(&vec![1; 16]).await.unwrap();
+        clock.advance(Duration::minutes(1));
+        store.update_usage(&vec![1; 16]).await.unwrap();
+
+        let updated = store.get(&vec![1; 16]).await.unwrap().unwrap();
+        assert_eq!(updated.counter, 2);
+        assert_eq!(updated.last_used, Some(DateTime::<Utc>::UNIX_EPOCH + Duration::days(1) + Duration::minutes(1)));
+        assert!(matches!(
+            store.update_usage(&vec![9; 16]).await,
+            Err(YKeyError::CredentialNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_partial_updates() {
+        let mut store = SqliteCredentialStore::open_in_memory().unwrap();
+        store.store(&credential(1, "github.com")).await.unwrap();
+
+        store.increment_counter(&vec![1; 16], 7).await.unwrap();
+        // A stale counter does not move it backwards
+        store.increment_counter(&vec![1; 16], 3).await.unwrap();
+        let used = DateTime::<Utc>::UNIX_EPOCH + Duration::days(1);
+        store.update_last_used(&vec![1; 16], used).await.unwrap();
+
+        let updated = store.get(&vec![1; 16]).await.unwrap().unwrap();
+        assert_eq!(updated.counter, 7);
+        assert_eq!(updated.last_used, Some(used));
+        assert_eq!(updated.user_name, "user1");
+        assert!(matches!(
+            store.increment_counter(&vec![9; 16], 1).await,
+            Err(YKeyError::CredentialNotFound(_))
+        ));
+    }
+
+    /// Run `update` on twenty tasks, each writing through its own connection to `path`
+    async fn concurrently<F, Fut>(path: &Path, update: F)
+    where
+        F: Fn(SqliteCredentialStore, u32) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let tasks: Vec<_> = (1..=20)
+            .map(|n| tokio::spawn(update(SqliteCredentialStore::open(path).unwrap(), n)))
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_counter_updates() {
+        let path = std::env::temp_dir().join(format!("ykey-credentials-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut store = SqliteCredentialStore::open(&path).unwrap();
+        store.store(&credential(1, "github.com")).await.unwrap();
+
+        concurrently(&path, |mut store, _| async move {
+            store.update_usage(&vec![1; 16]).await.unwrap();
+        })
+        .await;
+        assert_eq!(store.get(&vec![1; 16]).await.unwrap().unwrap().counter, 20);
+
+        concurrently(&path, |mut store, n| async move {
+            store.increment_counter(&vec![1; 16], 20 + n).await.unwrap();
+        })
+        .await;
+        assert_eq!(store.get(&vec![1; 16]).await.unwrap().unwrap().counter, 40);
+
+        drop(store);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_delete_clear_and_stats() {
+        let mut store = SqliteCredentialStore::open_in_memory().unwrap();
+        for id in 1..=3 {
+            store.store(&credential(id, "github.com")).await.unwrap();
+        }
+
+        let stats = store.stats().await.unwrap();
+        assert_eq!(stats.total_credentials, 3);
+        assert!(stats.storage_used > 0);
+
+        store.delete(&vec![2; 16]).await.unwrap();
+        assert!(matches!(store.delete(&vec![2; 16]).await, Err(YKeyError::CredentialNotFound(_))));
+        assert_eq!(store.stats().await.unwrap().total_credentials, 2);
+
+        store.clear().await.unwrap();
+        let cleared = store.stats().await.unwrap();
+        assert_eq!(cleared.total_credentials, 0);
+        assert!(cleared.last_cleanup >= stats.last_cleanup);
+        assert!(store.list().await.unwrap().is_empty());
+    }
+}
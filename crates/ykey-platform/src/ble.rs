@@ -0,0 +1,659 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! CTAP over Bluetooth Low Energy
+//!
+//! Requests are written to the FIDO control point and responses arrive as
+//! notifications on the status characteristic. Both directions use the same
+//! framing: a frame is split into fragments no longer than the control point
+//! length, the first carrying the command and total length and each of the
+//! rest a 7-bit sequence number. While a request is pending the authenticator
+//! sends KEEPALIVE frames. With the `ble` feature, peripherals advertising the
+//! FIDO service are found by [`BleDiscovery`] and driven through btleplug.
+
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::time::Instant;
+use ykey_core::{traits::*, types::*, YKeyError, YKeyResult};
+use ykey_protocol::ctaphid::{KeepaliveHandler, KeepaliveStatus, ERR_CHANNEL_BUSY};
+
+/// Echo the frame data back
+pub const CMD_PING: u8 = 0x81;
+/// Authenticator is still working on the request
+pub const CMD_KEEPALIVE: u8 = 0x82;
+/// CTAP message
+pub const CMD_MSG: u8 = 0x83;
+/// Abort the pending request
+pub const CMD_CANCEL: u8 = 0xBE;
+/// Framing error, with the error code as data
+pub const CMD_ERROR: u8 = 0xBF;
+
+/// Shortest control point length the specification allows
+pub const MIN_FRAGMENT_LENGTH: usize = 20;
+
+/// Longest control point length the specification allows
+pub const MAX_FRAGMENT_LENGTH: usize = 512;
+
+/// Header of the first fragment: command and big-endian length
+const INITIAL_HEADER_LENGTH: usize = 3;
+
+/// Sequence numbers of continuation fragments wrap after 0x7F
+const SEQUENCE_MASK: u8 = 0x7F;
+
+/// A complete frame, reassembled from its fragments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub command: u8,
+    pub data: Vec<u8>,
+}
+
+/// Split a frame into fragments of at most `fragment_length` bytes
+pub fn fragment(command: u8, data: &[u8], fragment_length: usize) -> YKeyResult<Vec<Vec<u8>>> {
+    if !(MIN_FRAGMENT_LENGTH..=MAX_FRAGMENT_LENGTH).contains(&fragment_length) {
+        return Err(YKeyError::InvalidParameters(format!(
+            "BLE control point length {} out of range",
+            fragment_length
+        )));
+    }
+    let length = u16::try_from(data.len())
+        .map_err(|_| YKeyError::InvalidParameters("BLE frame too long".to_string()))?;
+
+    let (first, mut rest) = data.split_at(data.len().min(fragment_length - INITIAL_HEADER_LENGTH));
+    let mut fragments = vec![[&[command][..], &length.to_be_bytes(), first].concat()];
+    let mut sequence = 0u8;
+    while !rest.is_empty() {
+        let (chunk, remaining) = rest.split_at(rest.len().min(fragment_length - 1));
+        fragments.push([&[sequence][..], chunk].concat());
+        sequence = sequence.wrapping_add(1) & SEQUENCE_MASK;
+        rest = remaining;
+    }
+    Ok(fragments)
+}
+
+/// Frame being reassembled
+#[derive(Debug)]
+struct PartialFrame {
+    command: u8,
+    length: usize,
+    data: Vec<u8>,
+    next_sequence: u8,
+}
+
+/// Reassembles frames from the fragments received on the status characteristic
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    partial: Option<PartialFrame>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a frame has been started but not completed
+    pub fn is_pending(&self) -> bool {
+        self.partial.is_some()
+    }
+
+    /// Drop any partially received frame
+    pub fn reset(&mut self) {
+        self.partial = None;
+    }
+
+    /// Add a fragment, returning the frame once its last fragment arrives
+    pub fn push(&mut self, fragment: &[u8]) -> YKeyResult<Option<Frame>> {
+        let Some((&first, payload)) = fragment.split_first() else {
+            return Err(YKeyError::communication("Empty BLE fragment"));
+        };
+
+        let mut partial = match self.partial.take() {
+            None if first & 0x80 == 0 => {
+                return Err(YKeyError::communication("BLE continuation fragment without a frame"));
+            }
+            None => {
+                if payload.len() < 2 {
+                    return Err(YKeyError::communication("Truncated BLE frame header"));
+                }
+                let length = u16::from_be_bytes([payload[0], payload[1]]) as usize;
+                PartialFrame {
+                    command: first,
+                    length,
+                    data: Vec::with_capacity(length),
+                    next_sequence: 0,
+                }
+                .append(&payload[2..])
+            }
+            Some(_) if first & 0x80 != 0 => {
+                return Err(YKeyError::communication("BLE frame started before the previous one completed"));
+            }
+            Some(partial) if first != partial.next_sequence => {
+                return Err(YKeyError::communication(format!(
+                    "BLE sequence mismatch: expected {}, got {}",
+                    partial.next_sequence, first
+                )));
+            }
+            Some(partial) => {
+                let mut partial = partial.append(payload);
+                partial.next_sequence = partial.next_sequence.wrapping_add(1) & SEQUENCE_MASK;
+                partial
+            }
+        };
+
+        if partial.data.len() < partial.length {
+            self.partial = Some(partial);
+            return Ok(None);
+        }
+        // Authenticators may pad the last fragment
+        partial.data.truncate(partial.length);
+        Ok(Some(Frame { command: partial.command, data: partial.data }))
+    }
+}
+
+impl PartialFrame {
+    fn append(mut self, payload: &[u8]) -> Self {
+        self.data.extend_from_slice(payload);
+        self
+    }
+}
+
+/// GATT connection to the FIDO service of a peripheral
+#[async_trait]
+pub trait GattLink: Send + Sync {
+    /// Write one fragment to the control point
+    async fn write_control_point(&mut self, fragment: &[u8]) -> YKeyResult<()>;
+
+    /// Wait for the next notification on the status characteristic
+    async fn next_status(&mut self) -> YKeyResult<Vec<u8>>;
+
+    /// Longest fragment the control point accepts
+    fn control_point_length(&self) -> usize;
+
+    /// Disconnect from the peripheral
+    async fn disconnect(&mut self) -> YKeyResult<()>;
+}
+
+/// CTAP transport over a BLE GATT link
+pub struct BleTransport<L: GattLink> {
+    link: L,
+    connected: bool,
+    reassembler: Reassembler,
+    timeout: Duration,
+    last_keepalive: Option<KeepaliveStatus>,
+    keepalive_handler: Option<KeepaliveHandler>,
+}
+
+impl<L: GattLink> BleTransport<L> {
+    /// Wrap a connected link
+    pub fn new(link: L) -> Self {
+        Self {
+            link,
+            connected: true,
+            reassembler: Reassembler::new(),
+            timeout: Duration::from_secs(10),
+            last_keepalive: None,
+            keepalive_handler: None,
+        }
+    }
+
+    /// Get underlying link reference
+    pub fn link(&self) -> &L {
+        &self.link
+    }
+
+    /// Get the time allowed between notifications
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Set the time allowed between notifications
+    ///
+    /// Unlike CTAPHID, every keepalive restarts the timeout: radio links drop
+    /// silently, so it is the silence that matters, while waiting for a touch
+    /// may take as long as the authenticator keeps reporting progress.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Set the callback told about keepalives, e.g. to prompt for a touch
+    pub fn set_keepalive_handler(&mut self, handler: Option<KeepaliveHandler>) {
+        self.keepalive_handler = handler;
+    }
+
+    /// Status of the last keepalive received for the pending request
+    pub fn last_keepalive(&self) -> Option<KeepaliveStatus> {
+        self.last_keepalive
+    }
+
+    /// Echo data through the authenticator
+    pub async fn ping(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        self.write_frame(CMD_PING, data).await?;
+        self.read_frame(CMD_PING).await
+    }
+
+    /// Abort the pending request
+    pub async fn cancel(&mut self) -> YKeyResult<()> {
+        self.write_frame(CMD_CANCEL, &[]).await
+    }
+
+    async fn write_frame(&mut self, command: u8, data: &[u8]) -> YKeyResult<()> {
+        if !self.connected {
+            return Err(YKeyError::communication("BLE link closed"));
+        }
+        for fragment in fragment(command, data, self.link.control_point_length())? {
+            self.link.write_control_point(&fragment).await?;
+        }
+        Ok(())
+    }
+
+    /// Read the response frame to `command`, skipping keepalives
+    async fn read_frame(&mut self, command: u8) -> YKeyResult<Vec<u8>> {
+        self.reassembler.reset();
+        self.last_keepalive = None;
+        loop {
+            let deadline = Instant::now() + self.timeout;
+            let fragment = tokio::time::timeout_at(deadline, self.link.next_status())
+                .await
+                .map_err(|_| YKeyError::timeout(self.timeout.as_secs()))??;
+            let Some(frame) = self.reassembler.push(&fragment)? else {
+                continue;
+            };
+
+            match frame.command {
+                CMD_KEEPALIVE => {
+                    let status = frame.data.first().copied().map(KeepaliveStatus::from_code);
+                    self.last_keepalive = status;
+                    if let (Some(handler), Some(status)) = (self.keepalive_handler.as_mut(), status) {
+                        handler(status);
+                    }
+                }
+                CMD_ERROR => {
+                    return match frame.data.first().copied() {
+                        Some(ERR_CHANNEL_BUSY) => Err(YKeyError::DeviceBusy(
+                            "Authenticator busy with another request".to_string(),
+                        )),
+                        code => Err(YKeyError::ctap_error(code.unwrap_or(0x7F))),
+                    };
+                }
+                response if response == command => return Ok(frame.data),
+                _ => return Err(YKeyError::UnexpectedResponse),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<L: GattLink> Transport for BleTransport<L> {
+    async fn send(&mut self, data: &[u8]) -> YKeyResult<()> {
+        self.write_frame(CMD_MSG, data).await
+    }
+
+    async fn receive(&mut self) -> YKeyResult<Vec<u8>> {
+        self.read_frame(CMD_MSG).await
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    async fn close(&mut self) -> YKeyResult<()> {
+        if self.connected {
+            self.connected = false;
+            self.link.disconnect().await?;
+        }
+        Ok(())
+    }
+
+    fn properties(&self) -> TransportProperties {
+        TransportProperties {
+            max_packet_size: self.link.control_point_length(),
+            supports_fragmentation: true,
+            connection_type: TransportType::Bluetooth,
+            latency_ms: None,
+        }
+    }
+}
+
+#[cfg(feature = "ble")]
+pub use btleplug_link::{BleDiscovery, BtleplugLink, FIDO_SERVICE_UUID};
+
+#[cfg(feature = "ble")]
+mod btleplug_link {
+    use super::*;
+    use btleplug::api::{Central, Characteristic, Manager as _, Peripheral as _, ScanFilter, WriteType};
+    use btleplug::platform::{Adapter, Manager, Peripheral};
+    use futures::StreamExt;
+    use tokio::sync::mpsc;
+    use uuid::{uuid, Uuid};
+
+    /// FIDO GATT service, assigned the 16-bit UUID 0xFFFD
+    pub const FIDO_SERVICE_UUID: Uuid = uuid!("0000fffd-0000-1000-8000-00805f9b34fb");
+
+    const CONTROL_POINT_UUID: Uuid = uuid!("f1d0fff1-deb4-6d4f-a4e3-93c93a5fcd0e");
+    const STATUS_UUID: Uuid = uuid!("f1d0fff2-deb4-6d4f-a4e3-93c93a5fcd0e");
+    const CONTROL_POINT_LENGTH_UUID: Uuid = uuid!("f1d0fff3-deb4-6d4f-a4e3-93c93a5fcd0e");
+    const SERVICE_REVISION_BITFIELD_UUID: Uuid = uuid!("f1d0fff4-deb4-6d4f-a4e3-93c93a5fcd0e");
+
+    /// Service revision bit selecting FIDO2
+    const REVISION_FIDO2: u8 = 0x20;
+
+    fn ble_error(error: btleplug::Error) -> YKeyError {
+        YKeyError::communication(format!("Bluetooth error: {}", error))
+    }
+
+    /// FIDO service of a connected btleplug peripheral
+    pub struct BtleplugLink {
+        peripheral: Peripheral,
+        control_point: Characteristic,
+        control_point_length: usize,
+        status: mpsc::Receiver<Vec<u8>>,
+    }
+
+    impl BtleplugLink {
+        /// Connect to the peripheral and subscribe to its status notifications
+        pub async fn connect(peripheral: Peripheral) -> YKeyResult<Self> {
+            peripheral.connect().await.map_err(ble_error)?;
+            peripheral.discover_services().await.map_err(ble_error)?;
+
+            let characteristics = peripheral.characteristics();
+            let find = |uuid: Uuid| {
+                characteristics
+                    .iter()
+                    .find(|c| c.service_uuid == FIDO_SERVICE_UUID && c.uuid == uuid)
+                    .cloned()
+            };
+            let missing = || YKeyError::communication("Peripheral does not expose the FIDO service");
+            let control_point = find(CONTROL_POINT_UUID).ok_or_else(missing)?;
+            let status = find(STATUS_UUID).ok_or_else(missing)?;
+            let length = find(CONTROL_POINT_LENGTH_UUID).ok_or_else(missing)?;
+
+            let length = peripheral.read(&length).await.map_err(ble_error)?;
+            let control_point_length = match length[..] {
+                [high, low, ..] => u16::from_be_bytes([high, low]) as usize,
+                _ => return Err(YKeyError::communication("Invalid FIDO control point length")),
+            };
+
+            // Authenticators that also speak U2F wait for the client to pick a revision
+            if let Some(revision) = find(SERVICE_REVISION_BITFIELD_UUID) {
+                let supported = peripheral.read(&revision).await.map_err(ble_error)?;
+                if supported.first().is_some_and(|bits| bits & REVISION_FIDO2 != 0) {
+                    peripheral
+                        .write(&revision, &[REVISION_FIDO2], WriteType::WithResponse)
+                        .await
+                        .map_err(ble_error)?;
+                }
+            }
+
+            peripheral.subscribe(&status).await.map_err(ble_error)?;
+            let mut notifications = peripheral.notifications().await.map_err(ble_error)?;
+            let (tx, rx) = mpsc::channel(32);
+            tokio::spawn(async move {
+                while let Some(notification) = notifications.next().await {
+                    if notification.uuid == STATUS_UUID && tx.send(notification.value).await.is_err() {
+                        break;
+                    }
+                }
+            });
+
+            Ok(Self {
+                peripheral,
+                control_point,
+                control_point_length,
+                status: rx,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl GattLink for BtleplugLink {
+        async fn write_control_point(&mut self, fragment: &[u8]) -> YKeyResult<()> {
+            self.peripheral
+                .write(&self.control_point, fragment, WriteType::WithResponse)
+                .await
+                .map_err(ble_error)
+        }
+
+        async fn next_status(&mut self) -> YKeyResult<Vec<u8>> {
+            self.status
+                .recv()
+                .await
+                .ok_or_else(|| YKeyError::communication("Bluetooth peripheral disconnected"))
+        }
+
+        fn control_point_length(&self) -> usize {
+            self.control_point_length
+        }
+
+        async fn disconnect(&mut self) -> YKeyResult<()> {
+            self.peripheral.disconnect().await.map_err(ble_error)
+        }
+    }
+
+    /// Scans for peripherals advertising the FIDO service
+    pub struct BleDiscovery {
+        adapter: Adapter,
+        scan_duration: Duration,
+    }
+
+    impl BleDiscovery {
+        /// Use the first Bluetooth adapter
+        pub async fn new() -> YKeyResult<Self> {
+            let manager = Manager::new().await.map_err(ble_error)?;
+            let adapter = manager
+                .adapters()
+                .await
+                .map_err(ble_error)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| YKeyError::communication("No Bluetooth adapter found"))?;
+            Ok(Self {
+                adapter,
+                scan_duration: Duration::from_secs(3),
+            })
+        }
+
+        /// Set how long each scan listens for advertisements
+        pub fn with_scan_duration(mut self, scan_duration: Duration) -> Self {
+            self.scan_duration = scan_duration;
+            self
+        }
+
+        /// Peripherals advertising the FIDO service, with their device info
+        async fn peripherals(&self) -> YKeyResult<Vec<(Peripheral, DeviceInfo)>> {
+            let filter = ScanFilter {
+                services: vec![FIDO_SERVICE_UUID],
+            };
+            self.adapter.start_scan(filter).await.map_err(ble_error)?;
+            tokio::time::sleep(self.scan_duration).await;
+            self.adapter.stop_scan().await.map_err(ble_error)?;
+
+            let mut found = Vec::new();
+            for peripheral in self.adapter.peripherals().await.map_err(ble_error)? {
+                // Not every platform applies the scan filter
+                let Some(properties) = peripheral.properties().await.map_err(ble_error)? else {
+                    continue;
+                };
+                if !properties.services.contains(&FIDO_SERVICE_UUID) {
+                    continue;
+                }
+                let name = properties
+                    .local_name
+                    .unwrap_or_else(|| "Bluetooth Security Key".to_string());
+                let mut info = DeviceInfo::new(
+                    DeviceId::new(format!("ble:{}", properties.address)),
+                    name.clone(),
+                    "Unknown".to_string(),
+                    name,
+                    0,
+                    0,
+                    DeviceType::Generic,
+                    TransportType::Bluetooth,
+                );
+                info.add_capability(Capability::Fido2);
+                found.push((peripheral, info));
+            }
+            Ok(found)
+        }
+
+        /// Open a transport to a peripheral returned by `scan`
+        pub async fn connect(&self, device_id: &DeviceId) -> YKeyResult<BleTransport<BtleplugLink>> {
+            let peripheral = self
+                .peripherals()
+                .await?
+                .into_iter()
+                .find(|(_, info)| info.id == *device_id)
+                .map(|(peripheral, _)| peripheral)
+                .ok_or_else(|| YKeyError::DeviceNotFound(device_id.to_string()))?;
+            Ok(BleTransport::new(BtleplugLink::connect(peripheral).await?))
+        }
+    }
+
+    #[async_trait]
+    impl DeviceDiscovery for BleDiscovery {
+        async fn scan(&self) -> YKeyResult<Vec<DeviceInfo>> {
+            Ok(self.peripherals().await?.into_iter().map(|(_, info)| info).collect())
+        }
+
+        async fn watch(&self) -> YKeyResult<DeviceEventStream> {
+            let (_tx, rx) = mpsc::channel(10);
+            Ok(rx)
+        }
+
+        async fn stop_watch(&self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        async fn is_device_available(&self, device_id: &str) -> YKeyResult<bool> {
+            Ok(self.peripherals().await?.iter().any(|(_, info)| info.id == device_id))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_fragment_round_trip() {
+        // 17 bytes fit the first fragment, then 19 per continuation, enough to wrap the sequence
+        let message: Vec<u8> = (0..17 + 19 * 130).map(|i| i as u8).collect();
+        let fragments = fragment(CMD_MSG, &message, MIN_FRAGMENT_LENGTH).unwrap();
+        assert_eq!(fragments.len(), 131);
+        assert!(fragments.iter().all(|f| f.len() == MIN_FRAGMENT_LENGTH));
+        assert_eq!(fragments[0][..3], [CMD_MSG, 0x09, 0xB7]);
+        assert_eq!(fragments[1][0], 0x00);
+        assert_eq!(fragments[128][0], 0x7F);
+        assert_eq!(fragments[129][0], 0x00);
+
+        let mut reassembler = Reassembler::new();
+        for fragment in &fragments[..130] {
+            assert_eq!(reassembler.push(fragment).unwrap(), None);
+        }
+        assert!(reassembler.is_pending());
+        let frame = reassembler.push(&fragments[130]).unwrap().unwrap();
+        assert_eq!(frame, Frame { command: CMD_MSG, data: message });
+        assert!(!reassembler.is_pending());
+
+        // Empty frames are a single header
+        assert_eq!(fragment(CMD_CANCEL, &[], 20).unwrap(), [vec![CMD_CANCEL, 0x00, 0x00]]);
+        assert!(fragment(CMD_MSG, &[0; 4], 19).is_err());
+        assert!(fragment(CMD_MSG, &vec![0; 65536], 512).is_err());
+    }
+
+    #[test]
+    fn test_reassembly_errors() {
+        let mut reassembler = Reassembler::new();
+        assert!(reassembler.push(&[0x00, 0x01]).is_err());
+        assert!(reassembler.push(&[CMD_MSG, 0x00]).is_err());
+
+        assert_eq!(reassembler.push(&[CMD_MSG, 0x00, 0x05, 0x01]).unwrap(), None);
+        let error = reassembler.push(&[0x01, 0x02]).unwrap_err();
+        assert!(error.to_string().contains("expected 0, got 1"));
+
+        reassembler.reset();
+        assert_eq!(reassembler.push(&[CMD_MSG, 0x00, 0x05, 0x01]).unwrap(), None);
+        assert!(reassembler.push(&[CMD_KEEPALIVE, 0x00, 0x01, 0x02]).is_err());
+
+        // Padding past the announced length is dropped
+        let frame = reassembler.push(&[CMD_KEEPALIVE, 0x00, 0x01, 0x02, 0x00, 0x00]).unwrap().unwrap();
+        assert_eq!(frame.data, [0x02]);
+    }
+
+    /// Link replaying scripted notifications
+    struct FakeLink {
+        control_point_length: usize,
+        notifications: VecDeque<Vec<u8>>,
+        written: Vec<Vec<u8>>,
+    }
+
+    #[async_trait]
+    impl GattLink for FakeLink {
+        async fn write_control_point(&mut self, fragment: &[u8]) -> YKeyResult<()> {
+            self.written.push(fragment.to_vec());
+            Ok(())
+        }
+
+        async fn next_status(&mut self) -> YKeyResult<Vec<u8>> {
+            match self.notifications.pop_front() {
+                Some(notification) => Ok(notification),
+                None => std::future::pending().await,
+            }
+        }
+
+        fn control_point_length(&self) -> usize {
+            self.control_point_length
+        }
+
+        async fn disconnect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+    }
+
+    fn transport(notifications: Vec<Vec<u8>>) -> BleTransport<FakeLink> {
+        BleTransport::new(FakeLink {
+            control_point_length: 20,
+            notifications: notifications.into(),
+            written: Vec::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_message_with_keepalives() {
+        let response: Vec<u8> = (0..30).collect();
+        let mut notifications = vec![
+            vec![CMD_KEEPALIVE, 0x00, 0x01, 0x01],
+            vec![CMD_KEEPALIVE, 0x00, 0x01, 0x02],
+        ];
+        notifications.extend(fragment(CMD_MSG, &response, 20).unwrap());
+        let mut ble = transport(notifications);
+
+        let statuses = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = statuses.clone();
+        ble.set_keepalive_handler(Some(Box::new(move |status| seen.lock().unwrap().push(status))));
+
+        ble.send(&[0x04; 25]).await.unwrap();
+        assert_eq!(ble.receive().await.unwrap(), response);
+        assert_eq!(*statuses.lock().unwrap(), [KeepaliveStatus::Processing, KeepaliveStatus::UpNeeded]);
+        assert_eq!(ble.last_keepalive(), Some(KeepaliveStatus::UpNeeded));
+
+        let written = &ble.link().written;
+        assert_eq!(written.len(), 2);
+        assert_eq!(written[0][..3], [CMD_MSG, 0x00, 25]);
+        assert_eq!(written[1], [&[0x00][..], &[0x04; 8]].concat());
+        assert_eq!(ble.properties().connection_type, TransportType::Bluetooth);
+    }
+
+    #[tokio::test]
+    async fn test_error_frame_and_timeout() {
+        let mut ble = transport(vec![vec![CMD_ERROR, 0x00, 0x01, ERR_CHANNEL_BUSY]]);
+        ble.send(&[0x04]).await.unwrap();
+        assert!(matches!(ble.receive().await, Err(YKeyError::DeviceBusy(_))));
+
+        ble.set_timeout(Duration::from_millis(50));
+        assert!(matches!(ble.receive().await, Err(YKeyError::Timeout { .. })));
+
+        ble.close().await.unwrap();
+        assert!(!ble.is_connected());
+        assert!(ble.send(&[0x04]).await.is_err());
+    }
+}
@@ -11,17 +11,99 @@ use async_trait::async_trait;
 use std::collections::HashMap;
 use tokio::sync::mpsc;
 
-// Platform-specific modules will be implemented in future versions
-// For now, we use mock implementations
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[cfg(target_os = "linux")]
+pub use linux::LinuxHidDiscovery;
+
+#[cfg(any(target_os = "windows", test))]
+mod windows;
+
+#[cfg(target_os = "windows")]
+pub use self::windows::WindowsHidDiscovery;
+
+#[cfg(any(target_os = "macos", test))]
+mod macos;
+
+#[cfg(target_os = "macos")]
+pub use macos::MacOsHidDiscovery;
+
+#[cfg(any(feature = "nfc", test))]
+pub mod nfc;
+
+#[cfg(feature = "nfc")]
+pub use nfc::{NfcDiscovery, NfcTransport};
+
+#[cfg(any(feature = "ble", test))]
+pub mod ble;
+
+#[cfg(feature = "ble")]
+pub use ble::{BleDiscovery, BleTransport};
+
+#[cfg(feature = "system-profiler")]
+mod system_profiler;
+
+#[cfg(feature = "system-profiler")]
+pub use system_profiler::{parse_usb_devices, SystemProfilerDiscovery};
 
 /// Create platform-specific device discovery
 /// 
 /// Returns the most appropriate device discovery implementation for the current platform.
-/// For now, this returns a mock implementation while platform-specific modules are being developed.
+/// Platforms without a native backend yet fall back to the mock implementation.
 pub fn create_platform_discovery() -> Box<dyn DeviceDiscovery> {
-    // TODO: Implement platform-specific discovery
-    // For now, return mock discovery for all platforms
-    Box::new(MockDiscovery::new())
+    #[cfg(target_os = "linux")]
+    {
+        Box::new(LinuxHidDiscovery::new())
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        Box::new(WindowsHidDiscovery::new())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Box::new(MacOsHidDiscovery::new())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    {
+        Box::new(MockDiscovery::new())
+    }
+}
+
+/// Poll `enumerate` and report devices that appeared or went away
+///
+/// Used by backends without a native hotplug notification. Failed scans keep
+/// the previous view so a transient error doesn't look like an unplug.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn spawn_poll_watch<F>(enumerate: F) -> (tokio::task::JoinHandle<()>, DeviceEventStream)
+where
+    F: Fn() -> YKeyResult<Vec<DeviceInfo>> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(10);
+    let task = tokio::spawn(async move {
+        let mut known: Vec<DeviceInfo> = Vec::new();
+        loop {
+            if let Ok(current) = enumerate() {
+                let removed = known.iter().filter(|d| !current.iter().any(|c| c.id == d.id));
+                let added = current.iter().filter(|c| !known.iter().any(|d| d.id == c.id));
+                let events: Vec<_> = removed
+                    .map(|d| DeviceEvent::Disconnected(d.id.clone()))
+                    .chain(added.cloned().map(DeviceEvent::Connected))
+                    .collect();
+                for event in events {
+                    if tx.send(event).await.is_err() {
+                        return;
+                    }
+                }
+                known = current;
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    });
+    (task, rx)
 }
 
 /// Mock discovery implementation for unsupported platforms or testing
@@ -137,6 +219,17 @@ impl FidoDeviceIds {
             .map(|(_, _, device_type)| *device_type)
     }
     
+    /// Manufacturer name for a device type
+    pub fn vendor_name(device_type: DeviceType) -> &'static str {
+        match device_type {
+            DeviceType::YubiKey => "Yubico",
+            DeviceType::CanoKey => "CanoKeys",
+            DeviceType::Nitrokey => "Nitrokey",
+            DeviceType::SoloKey => "SoloKeys",
+            DeviceType::Generic => "Unknown",
+        }
+    }
+    
     /// Get all known vendor IDs
     pub fn known_vendor_ids() -> Vec<u16> {
         let mut vendor_ids: Vec<u16> = Self::KNOWN_DEVICES.iter().map(|(vid, _, _)| *vid).collect();
@@ -0,0 +1,354 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Linux device discovery over udev and hidraw
+//!
+//! Enumerates `/dev/hidraw*` nodes, keeps those whose HID report descriptor
+//! declares the FIDO usage page, and describes them from the udev
+//! properties of their parent HID device. Hotplug events come from a udev
+//! monitor on the `hidraw` subsystem.
+
+use crate::FidoDeviceIds;
+use async_trait::async_trait;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::OpenOptions,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tokio::{io::unix::AsyncFd, sync::mpsc, task::JoinHandle};
+use ykey_core::{traits::*, types::*, YKeyError, YKeyResult};
+
+/// FIDO Alliance HID usage page
+const FIDO_USAGE_PAGE: u16 = 0xF1D0;
+
+/// hidraw node as reported by udev
+#[derive(Debug, Clone, Default)]
+struct HidrawEntry {
+    /// Device node, e.g. `/dev/hidraw0`
+    devnode: PathBuf,
+    /// Properties of the parent `hid` device (`HID_ID`, `HID_NAME`, `HID_UNIQ`)
+    properties: HashMap<String, String>,
+    /// Raw HID report descriptor
+    report_descriptor: Vec<u8>,
+}
+
+/// Change to the set of hidraw nodes
+#[derive(Debug)]
+enum HidrawEvent {
+    Added(HidrawEntry),
+    Removed(PathBuf),
+}
+
+/// Device discovery backed by udev and hidraw
+#[derive(Default)]
+pub struct LinuxHidDiscovery {
+    watch_task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LinuxHidDiscovery {
+    /// Create a new Linux discovery
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl DeviceDiscovery for LinuxHidDiscovery {
+    async fn scan(&self) -> YKeyResult<Vec<DeviceInfo>> {
+        let devices = fido_devices(enumerate_hidraw()?);
+        for device in &devices {
+            check_access(Path::new(device.id.as_str()))?;
+        }
+        Ok(devices)
+    }
+
+    async fn watch(&self) -> YKeyResult<DeviceEventStream> {
+        let socket = udev::MonitorBuilder::new()?.match_subsystem("hidraw")?.listen()?;
+        let monitor = AsyncFd::new(socket)?;
+
+        let (changes_tx, changes_rx) = mpsc::channel(10);
+        let (tx, rx) = mpsc::channel(10);
+        // Aborting the monitor closes `changes_rx`, which ends the forwarder
+        // and with it the returned stream
+        let task = tokio::spawn(monitor_hidraw(monitor, changes_tx));
+        tokio::spawn(forward_events(changes_rx, tx));
+        if let Some(previous) = self.watch_task.lock().unwrap().replace(task) {
+            previous.abort();
+        }
+        Ok(rx)
+    }
+
+    async fn stop_watch(&self) -> YKeyResult<()> {
+        if let Some(task) = self.watch_task.lock().unwrap().take() {
+            task.abort();
+        }
+        Ok(())
+    }
+
+    async fn is_device_available(&self, device_id: &str) -> YKeyResult<bool> {
+        Ok(fido_devices(enumerate_hidraw()?).iter().any(|d| d.id == device_id))
+    }
+}
+
+/// List hidraw nodes together with their parent HID device properties
+fn enumerate_hidraw() -> YKeyResult<Vec<HidrawEntry>> {
+    let mut enumerator = udev::Enumerator::new()?;
+    enumerator.match_subsystem("hidraw")?;
+    Ok(enumerator.scan_devices()?.filter_map(|device| hidraw_entry(&device)).collect())
+}
+
+fn hidraw_entry(device: &udev::Device) -> Option<HidrawEntry> {
+    let devnode = device.devnode()?;
+    let parent = device.parent_with_subsystem("hid").ok()??;
+
+    let properties = parent
+        .properties()
+        .map(|p| (p.name().to_string_lossy().into_owned(), p.value().to_string_lossy().into_owned()))
+        .collect();
+    let report_descriptor = std::fs::read(parent.syspath().join("report_descriptor")).ok()?;
+
+    Some(HidrawEntry {
+        devnode: devnode.to_path_buf(),
+        properties,
+        report_descriptor,
+    })
+}
+
+/// Report the current hidraw nodes, then every node added or removed
+async fn monitor_hidraw(mut monitor: AsyncFd<udev::MonitorSocket>, changes: mpsc::Sender<HidrawEvent>) {
+    // The monitor is already listening, so nothing is lost between the
+    // enumeration and the first event
+    let current = enumerate_hidraw().unwrap_or_default();
+    for entry in current {
+        if changes.send(HidrawEvent::Added(entry)).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        let Ok(mut guard) = monitor.readable_mut().await else {
+            return;
+        };
+        let events: Vec<HidrawEvent> = guard
+            .get_inner()
+            .iter()
+            .filter_map(|event| match event.event_type() {
+                udev::EventType::Add => hidraw_entry(&event).map(HidrawEvent::Added),
+                udev::EventType::Remove => event.devnode().map(|node| HidrawEvent::Removed(node.to_path_buf())),
+                _ => None,
+            })
+            .collect();
+        guard.clear_ready();
+
+        for event in events {
+            if changes.send(event).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Turn hidraw changes into events for the FIDO devices among them
+async fn forward_events(mut changes: mpsc::Receiver<HidrawEvent>, events: mpsc::Sender<DeviceEvent>) {
+    let mut known = HashSet::new();
+    while let Some(change) = changes.recv().await {
+        let event = match change {
+            HidrawEvent::Added(entry) => {
+                let Some(info) = fido_devices(vec![entry]).pop() else {
+                    continue;
+                };
+                known.insert(info.id.clone());
+                DeviceEvent::Connected(info)
+            }
+            HidrawEvent::Removed(devnode) => {
+                let id = devnode.to_string_lossy().into_owned();
+                if !known.remove(id.as_str()) {
+                    continue;
+                }
+                DeviceEvent::Disconnected(id)
+            }
+        };
+        if events.send(event).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Keep known FIDO devices that expose the FIDO usage page
+fn fido_devices(entries: Vec<HidrawEntry>) -> Vec<DeviceInfo> {
+    entries
+        .into_iter()
+        .filter(|entry| usage_pages(&entry.report_descriptor).contains(&FIDO_USAGE_PAGE))
+        .filter_map(|entry| device_info(&entry))
+        .collect()
+}
+
+fn device_info(entry: &HidrawEntry) -> Option<DeviceInfo> {
+    let (vendor_id, product_id) = parse_hid_id(entry.properties.get("HID_ID")?)?;
+    let device_type = FidoDeviceIds::is_known_fido_device(vendor_id, product_id)?;
+
+    let name = entry
+        .properties
+        .get("HID_NAME")
+        .cloned()
+        .unwrap_or_else(|| format!("FIDO device {:04x}:{:04x}", vendor_id, product_id));
+    let manufacturer = FidoDeviceIds::vendor_name(device_type);
+    let product = name.strip_prefix(manufacturer).map(str::trim).unwrap_or(&name).to_string();
+
+    let mut info = DeviceInfo::new(
+        entry.devnode.to_string_lossy().into_owned(),
+        name,
+        manufacturer.to_string(),
+        product,
+        vendor_id,
+        product_id,
+        device_type,
+        TransportType::Usb,
+    );
+    info.serial_number = entry.properties.get("HID_UNIQ").filter(|s| !s.is_empty()).cloned();
+    info.add_capability(Capability::Fido2);
+    Some(info)
+}
+
+/// Parse a udev `HID_ID` of the form `bus:vendor:product` in hex
+fn parse_hid_id(hid_id: &str) -> Option<(u16, u16)> {
+    let mut parts = hid_id.split(':');
+    let _bus = parts.next()?;
+    let vendor = u32::from_str_radix(parts.next()?, 16).ok()?;
+    let product = u32::from_str_radix(parts.next()?, 16).ok()?;
+    Some((u16::try_from(vendor).ok()?, u16::try_from(product).ok()?))
+}
+
+/// Usage pages declared by the global items of a HID report descriptor
+fn usage_pages(descriptor: &[u8]) -> Vec<u16> {
+    let mut pages = Vec::new();
+    let mut rest = descriptor;
+    while let [prefix, tail @ ..] = rest {
+        // Long items carry their size in the following byte
+        if *prefix == 0xFE {
+            let size = tail.first().map_or(0, |s| *s as usize);
+            rest = tail.get(size + 2..).unwrap_or_default();
+            continue;
+        }
+
+        let size = match prefix & 0x03 {
+            3 => 4,
+            n => n as usize,
+        };
+        let Some(data) = tail.get(..size) else {
+            break;
+        };
+        // Usage Page: global item with tag 0
+        if prefix & 0xFC == 0x04 {
+            let mut value = [0u8; 4];
+            value[..size].copy_from_slice(data);
+            pages.push(u32::from_le_bytes(value) as u16);
+        }
+        rest = &tail[size..];
+    }
+    pages
+}
+
+/// Fail with `PermissionDenied` when the node cannot be opened for I/O
+fn check_access(devnode: &Path) -> YKeyResult<()> {
+    match OpenOptions::new().read(true).write(true).open(devnode) {
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            Err(YKeyError::permission_denied(devnode.display().to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FIDO report descriptor header: Usage Page (0xF1D0), Usage (0x01)
+    const FIDO_DESCRIPTOR: &[u8] = &[0x06, 0xD0, 0xF1, 0x09, 0x01, 0xA1, 0x01, 0xC0];
+    /// Keyboard report descriptor header: Usage Page (Generic Desktop), Usage (Keyboard)
+    const KEYBOARD_DESCRIPTOR: &[u8] = &[0x05, 0x01, 0x09, 0x06, 0xA1, 0x01, 0xC0];
+
+    fn entry(devnode: &str, hid_id: &str, name: &str, uniq: &str, descriptor: &[u8]) -> HidrawEntry {
+        HidrawEntry {
+            devnode: PathBuf::from(devnode),
+            properties: [("HID_ID", hid_id), ("HID_NAME", name), ("HID_UNIQ", uniq)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            report_descriptor: descriptor.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_parse_synthetic_enumeration() {
+        let entries = vec![
+            // YubiKey exposes a keyboard interface alongside the FIDO one
+            entry("/dev/hidraw0", "0003:00001050:00000407", "Yubico YubiKey OTP+FIDO+CCID", "", KEYBOARD_DESCRIPTOR),
+            entry("/dev/hidraw1", "0003:00001050:00000407", "Yubico YubiKey OTP+FIDO+CCID", "", FIDO_DESCRIPTOR),
+            entry("/dev/hidraw2", "0003:000020A0:000042D4", "CanoKeys CanoKey", "SN123", FIDO_DESCRIPTOR),
+            // FIDO usage page, but not a device we know
+            entry("/dev/hidraw3", "0003:0000FFFF:0000FFFF", "Unknown Token", "", FIDO_DESCRIPTOR),
+            // Malformed HID_ID
+            entry("/dev/hidraw4", "garbage", "Broken", "", FIDO_DESCRIPTOR),
+        ];
+
+        let devices = fido_devices(entries);
+        assert_eq!(devices.len(), 2);
+
+        assert_eq!(devices[0].id, "/dev/hidraw1");
+        assert_eq!(devices[0].device_type, DeviceType::YubiKey);
+        assert_eq!(devices[0].manufacturer, "Yubico");
+        assert_eq!(devices[0].product_name, "YubiKey OTP+FIDO+CCID");
+        assert_eq!((devices[0].vendor_id, devices[0].product_id), (0x1050, 0x0407));
+        assert_eq!(devices[0].serial_number, None);
+        assert!(devices[0].has_capability(&Capability::Fido2));
+
+        assert_eq!(devices[1].id, "/dev/hidraw2");
+        assert_eq!(devices[1].device_type, DeviceType::CanoKey);
+        assert_eq!(devices[1].serial_number.as_deref(), Some("SN123"));
+    }
+
+    #[tokio::test]
+    async fn test_forward_simulated_hotplug() {
+        let (changes, changes_rx) = mpsc::channel(10);
+        let (tx, mut events) = mpsc::channel(10);
+        tokio::spawn(forward_events(changes_rx, tx));
+
+        let yubikey = || entry("/dev/hidraw1", "0003:00001050:00000407", "Yubico YubiKey", "", FIDO_DESCRIPTOR);
+        changes.send(HidrawEvent::Added(yubikey())).await.unwrap();
+        // Keyboard interface comes and goes without events
+        let keyboard = entry("/dev/hidraw0", "0003:00001050:00000407", "Yubico YubiKey", "", KEYBOARD_DESCRIPTOR);
+        changes.send(HidrawEvent::Added(keyboard)).await.unwrap();
+        changes.send(HidrawEvent::Removed(PathBuf::from("/dev/hidraw0"))).await.unwrap();
+        changes.send(HidrawEvent::Removed(PathBuf::from("/dev/hidraw1"))).await.unwrap();
+        drop(changes);
+
+        match events.recv().await {
+            Some(DeviceEvent::Connected(info)) => {
+                assert_eq!(info.id, "/dev/hidraw1");
+                assert_eq!(info.device_type, DeviceType::YubiKey);
+            }
+            other => panic!("expected Connected, got {:?}", other),
+        }
+        assert!(matches!(events.recv().await, Some(DeviceEvent::Disconnected(id)) if id == "/dev/hidraw1"));
+        // Closing the source ends the stream
+        assert!(events.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_usage_pages() {
+        assert_eq!(usage_pages(FIDO_DESCRIPTOR), vec![FIDO_USAGE_PAGE]);
+        assert_eq!(usage_pages(KEYBOARD_DESCRIPTOR), vec![0x01]);
+        // Truncated item stops parsing without panicking
+        assert_eq!(usage_pages(&[0x05, 0x01, 0x06, 0xD0]), vec![0x01]);
+    }
+
+    #[test]
+    fn test_parse_hid_id() {
+        assert_eq!(parse_hid_id("0003:00001050:00000407"), Some((0x1050, 0x0407)));
+        assert_eq!(parse_hid_id("0003:00001050"), None);
+        assert_eq!(parse_hid_id("0003:00011050:00000407"), None);
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! macOS device discovery over IOKit
+//!
+//! Asks an `IOHIDManager` for devices whose primary usage page is the FIDO
+//! page and describes them from their registry properties.
+
+use crate::FidoDeviceIds;
+use ykey_core::types::*;
+
+#[cfg(target_os = "macos")]
+use async_trait::async_trait;
+#[cfg(target_os = "macos")]
+use core_foundation::{
+    base::{kCFAllocatorDefault, CFGetTypeID, CFRelease, CFTypeRef, TCFType},
+    dictionary::CFDictionary,
+    number::{CFNumber, CFNumberGetTypeID, CFNumberRef},
+    set::{CFSetGetCount, CFSetGetValues},
+    string::{CFString, CFStringGetTypeID, CFStringRef},
+};
+#[cfg(target_os = "macos")]
+use io_kit_sys::{
+    hid::{
+        base::IOHIDDeviceRef,
+        device::{IOHIDDeviceClose, IOHIDDeviceGetProperty, IOHIDDeviceOpen},
+        keys::*,
+        manager::{
+            kIOHIDManagerOptionNone, IOHIDManagerCopyDevices, IOHIDManagerCreate, IOHIDManagerSetDeviceMatching,
+        },
+    },
+    ret::{kIOReturnNotPermitted, kIOReturnNotPrivileged},
+};
+#[cfg(target_os = "macos")]
+use std::{ffi::CStr, os::raw::c_char, sync::Mutex};
+#[cfg(target_os = "macos")]
+use tokio::task::JoinHandle;
+#[cfg(target_os = "macos")]
+use ykey_core::{traits::*, YKeyError, YKeyResult};
+
+/// FIDO Alliance HID usage page
+#[cfg(target_os = "macos")]
+const FIDO_USAGE_PAGE: i32 = 0xF1D0;
+
+/// Registry properties of a HID device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct HidProperties {
+    vendor_id: u16,
+    product_id: u16,
+    /// USB location, stable while the device stays in the same port
+    location_id: u32,
+    manufacturer: Option<String>,
+    product: Option<String>,
+    serial: Option<String>,
+    /// Whether the device could be opened by this process
+    accessible: bool,
+}
+
+/// Device discovery backed by IOHIDManager
+#[cfg(target_os = "macos")]
+#[derive(Default)]
+pub struct MacOsHidDiscovery {
+    watch_task: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[cfg(target_os = "macos")]
+impl MacOsHidDiscovery {
+    /// Create a new macOS discovery
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[async_trait]
+impl DeviceDiscovery for MacOsHidDiscovery {
+    async fn scan(&self) -> YKeyResult<Vec<DeviceInfo>> {
+        let devices = copy_fido_devices()?;
+        if let Some(denied) = devices.iter().find(|d| !d.accessible) {
+            return Err(YKeyError::permission_denied(format!(
+                "HID device {:04x}:{:04x} at location {:#010x}",
+                denied.vendor_id, denied.product_id, denied.location_id
+            )));
+        }
+        Ok(devices.iter().filter_map(device_info).collect())
+    }
+
+    async fn watch(&self) -> YKeyResult<DeviceEventStream> {
+        let (task, rx) =
+            crate::spawn_poll_watch(|| copy_fido_devices().map(|d| d.iter().filter_map(device_info).collect()));
+        if let Some(previous) = self.watch_task.lock().unwrap().replace(task) {
+            previous.abort();
+        }
+        Ok(rx)
+    }
+
+    async fn stop_watch(&self) -> YKeyResult<()> {
+        if let Some(task) = self.watch_task.lock().unwrap().take() {
+            task.abort();
+        }
+        Ok(())
+    }
+
+    async fn is_device_available(&self, device_id: &str) -> YKeyResult<bool> {
+        Ok(copy_fido_devices()?.iter().filter_map(device_info).any(|d| d.id == device_id))
+    }
+}
+
+fn device_info(properties: &HidProperties) -> Option<DeviceInfo> {
+    let device_type = FidoDeviceIds::is_known_fido_device(properties.vendor_id, properties.product_id)?;
+
+    let manufacturer = properties
+        .manufacturer
+        .clone()
+        .unwrap_or_else(|| FidoDeviceIds::vendor_name(device_type).to_string());
+    let product = properties
+        .product
+        .clone()
+        .unwrap_or_else(|| format!("FIDO device {:04x}:{:04x}", properties.vendor_id, properties.product_id));
+
+    let mut info = DeviceInfo::new(
+        format!("macos-hid-{:08x}", properties.location_id),
+        product.clone(),
+        manufacturer,
+        product,
+        properties.vendor_id,
+        properties.product_id,
+        device_type,
+        TransportType::Usb,
+    );
+    info.serial_number = properties.serial.clone();
+    info.add_capability(Capability::Fido2);
+    Some(info)
+}
+
+/// Enumerate devices on the FIDO usage page
+#[cfg(target_os = "macos")]
+fn copy_fido_devices() -> YKeyResult<Vec<HidProperties>> {
+    let matching = CFDictionary::from_CFType_pairs(&[(
+        key(kIOHIDPrimaryUsagePageKey).as_CFType(),
+        CFNumber::from(FIDO_USAGE_PAGE).as_CFType(),
+    )]);
+
+    unsafe {
+        let manager = IOHIDManagerCreate(kCFAllocatorDefault, kIOHIDManagerOptionNone);
+        if manager.is_null() {
+            return Err(YKeyError::communication("Failed to create IOHIDManager"));
+        }
+        IOHIDManagerSetDeviceMatching(manager, matching.as_concrete_TypeRef());
+
+        let set = IOHIDManagerCopyDevices(manager);
+        let mut devices = Vec::new();
+        if !set.is_null() {
+            let count = CFSetGetCount(set) as usize;
+            let mut refs: Vec<*const std::ffi::c_void> = vec![std::ptr::null(); count];
+            CFSetGetValues(set, refs.as_mut_ptr());
+            devices.extend(refs.into_iter().map(|device| read_properties(device as IOHIDDeviceRef)));
+            CFRelease(set as CFTypeRef);
+        }
+        CFRelease(manager as CFTypeRef);
+        Ok(devices)
+    }
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn read_properties(device: IOHIDDeviceRef) -> HidProperties {
+    let number = |name| {
+        let value = IOHIDDeviceGetProperty(device, key(name).as_concrete_TypeRef());
+        (!value.is_null() && CFGetTypeID(value) == CFNumberGetTypeID())
+            .then(|| CFNumber::wrap_under_get_rule(value as CFNumberRef).to_i64())
+            .flatten()
+    };
+    let string = |name| {
+        let value = IOHIDDeviceGetProperty(device, key(name).as_concrete_TypeRef());
+        (!value.is_null() && CFGetTypeID(value) == CFStringGetTypeID())
+            .then(|| CFString::wrap_under_get_rule(value as CFStringRef).to_string())
+            .filter(|s| !s.trim().is_empty())
+    };
+
+    // Opening fails when the user has not granted Input Monitoring access
+    let status = IOHIDDeviceOpen(device, kIOHIDOptionsTypeNone);
+    let accessible = status != kIOReturnNotPermitted && status != kIOReturnNotPrivileged;
+    if status == 0 {
+        IOHIDDeviceClose(device, kIOHIDOptionsTypeNone);
+    }
+
+    HidProperties {
+        vendor_id: number(kIOHIDVendorIDKey).unwrap_or_default() as u16,
+        product_id: number(kIOHIDProductIDKey).unwrap_or_default() as u16,
+        location_id: number(kIOHIDLocationIDKey).unwrap_or_default() as u32,
+        manufacturer: string(kIOHIDManufacturerKey),
+        product: string(kIOHIDProductKey),
+        serial: string(kIOHIDSerialNumberKey),
+        accessible,
+    }
+}
+
+/// Wrap an IOKit property key in a CFString
+#[cfg(target_os = "macos")]
+fn key(name: *const c_char) -> CFString {
+    let name = unsafe { CStr::from_ptr(name) };
+    CFString::new(&name.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_info_from_properties() {
+        let yubikey = HidProperties {
+            vendor_id: 0x1050,
+            product_id: 0x0407,
+            location_id: 0x1420_0000,
+            manufacturer: Some("Yubico".to_string()),
+            product: Some("YubiKey OTP+FIDO+CCID".to_string()),
+            serial: None,
+            accessible: true,
+        };
+        let info = device_info(&yubikey).unwrap();
+        assert_eq!(info.id, "macos-hid-14200000");
+        assert_eq!(info.device_type, DeviceType::YubiKey);
+        assert_eq!(info.manufacturer, "Yubico");
+        assert_eq!(info.product_name, "YubiKey OTP+FIDO+CCID");
+
+        let bare = HidProperties {
+            vendor_id: 0x20A0,
+            product_id: 0x42D4,
+            ..Default::default()
+        };
+        let info = device_info(&bare).unwrap();
+        assert_eq!(info.manufacturer, "CanoKeys");
+        assert_eq!(info.product_name, "FIDO device 20a0:42d4");
+
+        let unknown = HidProperties {
+            vendor_id: 0xFFFF,
+            product_id: 0xFFFF,
+            ..Default::default()
+        };
+        assert!(device_info(&unknown).is_none());
+    }
+}
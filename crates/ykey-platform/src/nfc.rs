@@ -0,0 +1,416 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! CTAP over NFC through contactless readers
+//!
+//! The FIDO applet is selected once, after which every CTAP message travels in
+//! an NFCCTAP_MSG APDU. Messages too long for one short APDU are split with
+//! ISO 7816 command chaining unless the reader accepts extended APDUs, and
+//! long responses are read back with GET RESPONSE. While the authenticator is
+//! busy or waiting for a touch it answers with status updates, and the
+//! response is polled with NFCCTAP_GETRESPONSE. With the `nfc` feature,
+//! PC/SC readers are listed by [`NfcDiscovery`] and driven by [`PcscReader`].
+
+use async_trait::async_trait;
+use ykey_core::{traits::*, types::*, YKeyError, YKeyResult};
+use ykey_protocol::apdu::{CommandApdu, ResponseApdu, INS_GET_RESPONSE};
+
+/// FIDO applet AID
+const FIDO_AID: [u8; 8] = [0xA0, 0x00, 0x00, 0x06, 0x47, 0x2F, 0x00, 0x01];
+
+const INS_SELECT: u8 = 0xA4;
+
+/// Class and instruction of the NFCCTAP_MSG command
+const CLA_CTAP: u8 = 0x80;
+const INS_NFCCTAP_MSG: u8 = 0x10;
+
+/// Instruction polling for the response to an NFCCTAP_MSG
+const INS_NFCCTAP_GETRESPONSE: u8 = 0x11;
+
+/// NFCCTAP_MSG P1 telling the authenticator we poll with NFCCTAP_GETRESPONSE
+const P1_GETRESPONSE_SUPPORTED: u8 = 0x80;
+
+/// Status word of a status update sent instead of the response
+const SW_STATUS_UPDATE: u16 = 0x9100;
+
+/// CLA bit marking every APDU of a chained command but the last
+const CLA_CHAINING: u8 = 0x10;
+
+/// SW1 announcing more response data
+const SW1_MORE_DATA: u8 = 0x61;
+
+/// Most data bytes in a short APDU
+const SHORT_DATA_LENGTH: usize = 255;
+
+/// Longest short APDU: header, Lc, data and Le
+pub const SHORT_APDU_LENGTH: usize = 4 + 1 + SHORT_DATA_LENGTH + 1;
+
+/// A contactless reader exchanging APDUs with the card in its field
+pub trait CardReader: Send + Sync {
+    /// Send one APDU and return the card's response, status word included
+    fn transmit(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>>;
+
+    /// Longest APDU the reader accepts
+    fn max_apdu_length(&self) -> usize;
+}
+
+/// CTAP transport over an NFC reader
+pub struct NfcTransport<R: CardReader> {
+    reader: R,
+    /// Version reported by the applet once selected
+    version: Option<String>,
+    /// Response to the last message, until it is received
+    pending: Option<Vec<u8>>,
+}
+
+impl<R: CardReader> NfcTransport<R> {
+    /// Wrap a reader; the applet is selected on the first message
+    pub fn new(reader: R) -> Self {
+        Self { reader, version: None, pending: None }
+    }
+
+    /// Get underlying reader reference
+    pub fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    /// Select the FIDO applet, returning the version it reports
+    ///
+    /// Authenticators answer `FIDO_2_0`, or `U2F_V2` if they only speak CTAP1.
+    pub fn select(&mut self) -> YKeyResult<&str> {
+        let response = self.exchange(0x00, INS_SELECT, 0x04, 0x00, &FIDO_AID)?;
+        Ok(self.version.insert(String::from_utf8_lossy(&response).into_owned()))
+    }
+
+    /// Version reported by the applet, if it has been selected
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Send a command, chaining it if needed, and read the whole response
+    fn exchange(&mut self, cla: u8, ins: u8, p1: u8, p2: u8, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        let extended = self.reader.max_apdu_length() > SHORT_APDU_LENGTH;
+        let (chained, last) = if extended || data.len() <= SHORT_DATA_LENGTH {
+            (&[][..], data)
+        } else {
+            // Every chunk but the last is full, so the last one is never empty
+            data.split_at((data.len() - 1) / SHORT_DATA_LENGTH * SHORT_DATA_LENGTH)
+        };
+
+        for chunk in chained.chunks(SHORT_DATA_LENGTH) {
+            let response = self.transmit(&CommandApdu::new(cla | CLA_CHAINING, ins, p1, p2).with_data(chunk))?;
+            if !response.is_success() {
+                return Err(status_error(&response));
+            }
+        }
+
+        let max_le = if extended { 65536 } else { 256 };
+        let mut response = self.transmit(&CommandApdu::new(cla, ins, p1, p2).with_data(last).with_le(max_le))?;
+        let mut output = Vec::new();
+        loop {
+            if response.sw1 == SW1_MORE_DATA {
+                output.append(&mut response.data);
+                let le = if response.sw2 == 0 { 256 } else { response.sw2 as usize };
+                response = self.transmit(&CommandApdu::new(0x00, INS_GET_RESPONSE, 0x00, 0x00).with_le(le))?;
+            } else if response.status() == SW_STATUS_UPDATE {
+                // The data only says whether the authenticator is busy or waiting for a touch
+                let poll = CommandApdu::new(CLA_CTAP, INS_NFCCTAP_GETRESPONSE, 0x00, 0x00).with_le(max_le);
+                response = self.transmit(&poll)?;
+            } else {
+                break;
+            }
+        }
+
+        if !response.is_success() {
+            return Err(status_error(&response));
+        }
+        output.append(&mut response.data);
+        Ok(output)
+    }
+
+    fn transmit(&mut self, command: &CommandApdu) -> YKeyResult<ResponseApdu> {
+        ResponseApdu::parse(self.reader.transmit(&command.encode()?)?)
+    }
+}
+
+fn status_error(response: &ResponseApdu) -> YKeyError {
+    YKeyError::communication(format!("NFC command failed with status {:04X}", response.status()))
+}
+
+#[async_trait]
+impl<R: CardReader> Transport for NfcTransport<R> {
+    async fn send(&mut self, data: &[u8]) -> YKeyResult<()> {
+        if self.version.is_none() {
+            self.select()?;
+        }
+        let response = self.exchange(CLA_CTAP, INS_NFCCTAP_MSG, P1_GETRESPONSE_SUPPORTED, 0x00, data)?;
+        self.pending = Some(response);
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> YKeyResult<Vec<u8>> {
+        self.pending
+            .take()
+            .ok_or_else(|| YKeyError::communication("No NFC response pending"))
+    }
+
+    fn is_connected(&self) -> bool {
+        self.version.is_some()
+    }
+
+    async fn close(&mut self) -> YKeyResult<()> {
+        self.version = None;
+        self.pending = None;
+        Ok(())
+    }
+
+    fn properties(&self) -> TransportProperties {
+        TransportProperties {
+            max_packet_size: self.reader.max_apdu_length(),
+            supports_fragmentation: true,
+            connection_type: TransportType::Nfc,
+            latency_ms: None,
+        }
+    }
+}
+
+#[cfg(feature = "nfc")]
+pub use pcsc_reader::{NfcDiscovery, PcscReader};
+
+#[cfg(feature = "nfc")]
+mod pcsc_reader {
+    use super::*;
+    use std::ffi::{CStr, CString};
+    use tokio::sync::mpsc;
+
+    fn pcsc_error(error: pcsc::Error) -> YKeyError {
+        YKeyError::communication(format!("PC/SC error: {}", error))
+    }
+
+    /// Card in the field of a PC/SC reader
+    pub struct PcscReader {
+        card: pcsc::Card,
+        max_apdu_length: usize,
+    }
+
+    impl PcscReader {
+        /// Connect to the card in the field of the named reader
+        pub fn connect(context: &pcsc::Context, reader: &CStr) -> YKeyResult<Self> {
+            let card = context
+                .connect(reader, pcsc::ShareMode::Shared, pcsc::Protocols::ANY)
+                .map_err(pcsc_error)?;
+
+            // Readers that do not report a limit are assumed to take short APDUs only
+            let max_apdu_length = card
+                .get_attribute_owned(pcsc::Attribute::Maxinput)
+                .ok()
+                .and_then(|value| Some(u32::from_le_bytes(value.get(..4)?.try_into().ok()?) as usize))
+                .filter(|&length| length > 0)
+                .unwrap_or(SHORT_APDU_LENGTH);
+            Ok(Self { card, max_apdu_length })
+        }
+    }
+
+    impl CardReader for PcscReader {
+        fn transmit(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>> {
+            let mut buffer = vec![0u8; pcsc::MAX_BUFFER_SIZE_EXTENDED];
+            let response = self.card.transmit(apdu, &mut buffer).map_err(pcsc_error)?;
+            Ok(response.to_vec())
+        }
+
+        fn max_apdu_length(&self) -> usize {
+            self.max_apdu_length
+        }
+    }
+
+    /// Lists PC/SC readers as NFC devices
+    pub struct NfcDiscovery {
+        context: pcsc::Context,
+    }
+
+    impl NfcDiscovery {
+        /// Connect to the PC/SC service
+        pub fn new() -> YKeyResult<Self> {
+            let context = pcsc::Context::establish(pcsc::Scope::User).map_err(pcsc_error)?;
+            Ok(Self { context })
+        }
+
+        fn readers(&self) -> YKeyResult<Vec<CString>> {
+            match self.context.list_readers_owned() {
+                Ok(readers) => Ok(readers),
+                Err(pcsc::Error::NoReadersAvailable) => Ok(Vec::new()),
+                Err(e) => Err(pcsc_error(e)),
+            }
+        }
+
+        /// Open a transport to the card on a reader returned by `scan`
+        pub fn connect(&self, device_id: &DeviceId) -> YKeyResult<NfcTransport<PcscReader>> {
+            let reader = self
+                .readers()?
+                .into_iter()
+                .find(|reader| reader_id(reader) == *device_id)
+                .ok_or_else(|| YKeyError::DeviceNotFound(device_id.to_string()))?;
+            Ok(NfcTransport::new(PcscReader::connect(&self.context, &reader)?))
+        }
+    }
+
+    /// Device ID for a reader, with whitespace replaced so it stays parseable
+    fn reader_id(reader: &CStr) -> DeviceId {
+        let name = reader.to_string_lossy().replace(char::is_whitespace, "_");
+        DeviceId::new(format!("nfc:{}", name))
+    }
+
+    fn reader_info(reader: &CStr) -> DeviceInfo {
+        let name = reader.to_string_lossy().into_owned();
+        let mut info = DeviceInfo::new(
+            reader_id(reader),
+            name.clone(),
+            "Unknown".to_string(),
+            name,
+            0,
+            0,
+            DeviceType::Generic,
+            TransportType::Nfc,
+        );
+        info.add_capability(Capability::Fido2);
+        info
+    }
+
+    #[async_trait]
+    impl DeviceDiscovery for NfcDiscovery {
+        async fn scan(&self) -> YKeyResult<Vec<DeviceInfo>> {
+            Ok(self.readers()?.iter().map(|reader| reader_info(reader)).collect())
+        }
+
+        async fn watch(&self) -> YKeyResult<DeviceEventStream> {
+            let (_tx, rx) = mpsc::channel(10);
+            Ok(rx)
+        }
+
+        async fn stop_watch(&self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        async fn is_device_available(&self, device_id: &str) -> YKeyResult<bool> {
+            Ok(self.readers()?.iter().any(|reader| reader_id(reader) == device_id))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Reader answering APDUs from a fixed script
+    struct FakeReader {
+        max_apdu_length: usize,
+        responses: VecDeque<Vec<u8>>,
+        apdus: Vec<Vec<u8>>,
+    }
+
+    impl CardReader for FakeReader {
+        fn transmit(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>> {
+            self.apdus.push(apdu.to_vec());
+            self.responses
+                .pop_front()
+                .ok_or_else(|| YKeyError::communication("No response scripted"))
+        }
+
+        fn max_apdu_length(&self) -> usize {
+            self.max_apdu_length
+        }
+    }
+
+    fn transport(max_apdu_length: usize, responses: Vec<Vec<u8>>) -> NfcTransport<FakeReader> {
+        NfcTransport::new(FakeReader {
+            max_apdu_length,
+            responses: responses.into(),
+            apdus: Vec::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_chained_message_over_short_apdus() {
+        let message = [vec![0x02], vec![0xA5; 599]].concat();
+        let mut nfc = transport(
+            SHORT_APDU_LENGTH,
+            vec![
+                b"FIDO_2_0\x90\x00".to_vec(),
+                vec![0x90, 0x00],
+                vec![0x90, 0x00],
+                [vec![0x00; 4], vec![0x61, 0x03]].concat(),
+                vec![0x01, 0x02, 0x03, 0x90, 0x00],
+            ],
+        );
+
+        nfc.send(&message).await.unwrap();
+        assert_eq!(nfc.version(), Some("FIDO_2_0"));
+        assert_eq!(nfc.receive().await.unwrap(), [0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03]);
+        assert!(nfc.receive().await.is_err());
+
+        let apdus = &nfc.reader().apdus;
+        assert_eq!(apdus[0], [&[0x00, INS_SELECT, 0x04, 0x00, 0x08][..], &FIDO_AID, &[0x00]].concat());
+        // 600 bytes go out as 255 + 255 + 90, only the last without the chaining bit
+        assert_eq!(apdus[1][..5], [0x90, INS_NFCCTAP_MSG, 0x80, 0x00, 0xFF]);
+        assert_eq!(apdus[2][..5], [0x90, INS_NFCCTAP_MSG, 0x80, 0x00, 0xFF]);
+        assert_eq!(apdus[3][..5], [0x80, INS_NFCCTAP_MSG, 0x80, 0x00, 90]);
+        assert_eq!(apdus[3].len(), 5 + 90 + 1);
+        let sent: Vec<u8> = [&apdus[1][5..], &apdus[2][5..], &apdus[3][5..95]].concat();
+        assert_eq!(sent, message);
+        assert_eq!(apdus[4], [0x00, INS_GET_RESPONSE, 0x00, 0x00, 0x03]);
+    }
+
+    #[tokio::test]
+    async fn test_extended_reader_sends_one_apdu() {
+        let mut nfc = transport(
+            3072,
+            vec![b"FIDO_2_0\x90\x00".to_vec(), vec![0x00, 0xA0, 0x90, 0x00]],
+        );
+
+        nfc.send(&[0x04; 600]).await.unwrap();
+        assert_eq!(nfc.receive().await.unwrap(), [0x00, 0xA0]);
+
+        let apdus = &nfc.reader().apdus;
+        assert_eq!(apdus.len(), 2);
+        assert_eq!(apdus[1][..7], [0x80, INS_NFCCTAP_MSG, 0x80, 0x00, 0x00, 0x02, 0x58]);
+        assert_eq!(apdus[1][607..], [0x00, 0x00]);
+
+        let properties = nfc.properties();
+        assert_eq!(properties.connection_type, TransportType::Nfc);
+        assert_eq!(properties.max_packet_size, 3072);
+    }
+
+    #[tokio::test]
+    async fn test_status_updates_are_polled() {
+        let mut nfc = transport(
+            SHORT_APDU_LENGTH,
+            vec![
+                b"FIDO_2_0\x90\x00".to_vec(),
+                vec![0x01, 0x91, 0x00], // Processing
+                vec![0x02, 0x91, 0x00], // Waiting for a touch
+                [vec![0x00, 0xA1], vec![0x61, 0x02]].concat(),
+                vec![0x01, 0x02, 0x90, 0x00],
+            ],
+        );
+
+        nfc.send(&[0x02, 0xA0]).await.unwrap();
+        assert_eq!(nfc.receive().await.unwrap(), [0x00, 0xA1, 0x01, 0x02]);
+
+        let apdus = &nfc.reader().apdus;
+        assert_eq!(apdus.len(), 5);
+        assert_eq!(apdus[1][..4], [0x80, INS_NFCCTAP_MSG, 0x80, 0x00]);
+        assert_eq!(apdus[2], [0x80, INS_NFCCTAP_GETRESPONSE, 0x00, 0x00, 0x00]);
+        assert_eq!(apdus[3], [0x80, INS_NFCCTAP_GETRESPONSE, 0x00, 0x00, 0x00]);
+        assert_eq!(apdus[4], [0x00, INS_GET_RESPONSE, 0x00, 0x00, 0x02]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_select() {
+        let mut nfc = transport(SHORT_APDU_LENGTH, vec![vec![0x6A, 0x82]]);
+
+        let error = nfc.send(&[0x04]).await.unwrap_err();
+        assert!(error.to_string().contains("6A82"));
+        assert!(!nfc.is_connected());
+    }
+}
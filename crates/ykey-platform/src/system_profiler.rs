@@ -0,0 +1,205 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! USB discovery through `system_profiler SPUSBDataType -json`
+//!
+//! Fallback for macOS setups where IOKit is unavailable. It is slow and
+//! cannot see HID usage pages, so devices are matched on vendor and product
+//! IDs alone.
+
+use crate::FidoDeviceIds;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::process::Command;
+use tokio::sync::mpsc;
+use ykey_core::{traits::*, types::*, YKeyError, YKeyResult};
+
+/// Device discovery that shells out to `system_profiler`
+#[derive(Debug, Default)]
+pub struct SystemProfilerDiscovery;
+
+impl SystemProfilerDiscovery {
+    /// Create a new system_profiler discovery
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn scan_usb_devices(&self) -> YKeyResult<Vec<DeviceInfo>> {
+        let output = Command::new("system_profiler")
+            .args(["SPUSBDataType", "-json"])
+            .output()
+            .map_err(|e| YKeyError::communication(format!("Failed to run system_profiler: {}", e)))?;
+
+        let json: Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| YKeyError::communication(format!("Failed to parse system_profiler output: {}", e)))?;
+        Ok(parse_usb_devices(&json))
+    }
+}
+
+#[async_trait]
+impl DeviceDiscovery for SystemProfilerDiscovery {
+    async fn scan(&self) -> YKeyResult<Vec<DeviceInfo>> {
+        self.scan_usb_devices()
+    }
+
+    async fn watch(&self) -> YKeyResult<DeviceEventStream> {
+        let (_tx, rx) = mpsc::channel(10);
+        Ok(rx)
+    }
+
+    async fn stop_watch(&self) -> YKeyResult<()> {
+        Ok(())
+    }
+
+    async fn is_device_available(&self, device_id: &str) -> YKeyResult<bool> {
+        Ok(self.scan_usb_devices()?.iter().any(|d| d.id == device_id))
+    }
+}
+
+/// Collect known FIDO devices from `SPUSBDataType` JSON
+pub fn parse_usb_devices(json: &Value) -> Vec<DeviceInfo> {
+    let mut devices = Vec::new();
+    if let Some(buses) = json.get("SPUSBDataType").and_then(Value::as_array) {
+        for bus in buses {
+            collect(bus, &mut devices);
+        }
+    }
+    devices
+}
+
+/// Walk a USB tree entry and its `_items` children
+fn collect(item: &Value, devices: &mut Vec<DeviceInfo>) {
+    if let Some(info) = device_info(item) {
+        devices.push(info);
+    }
+    for child in item.get("_items").and_then(Value::as_array).into_iter().flatten() {
+        collect(child, devices);
+    }
+}
+
+fn device_info(item: &Value) -> Option<DeviceInfo> {
+    let vendor_id = parse_id(item.get("vendor_id")?.as_str()?)?;
+    let product_id = parse_id(item.get("product_id")?.as_str()?)?;
+    let device_type = FidoDeviceIds::is_known_fido_device(vendor_id, product_id)?;
+
+    let field = |name: &str| item.get(name).and_then(Value::as_str).map(str::to_string);
+    let name = field("_name").unwrap_or_else(|| "Unknown Device".to_string());
+    let manufacturer = field("manufacturer").unwrap_or_else(|| FidoDeviceIds::vendor_name(device_type).to_string());
+
+    let mut info = DeviceInfo::new(
+        DeviceId::usb(device_type, vendor_id, product_id),
+        name.clone(),
+        manufacturer,
+        name,
+        vendor_id,
+        product_id,
+        device_type,
+        TransportType::Usb,
+    );
+    info.serial_number = field("serial_num");
+    info.add_capability(Capability::Fido2);
+    if matches!(device_type, DeviceType::YubiKey) {
+        info.add_capability(Capability::Fido1);
+        info.add_capability(Capability::Oath);
+        info.add_capability(Capability::Piv);
+        info.add_capability(Capability::Otp);
+    }
+    Some(info)
+}
+
+/// Parse IDs like `0x1050  (Yubico.com)` or `0x0407`
+fn parse_id(value: &str) -> Option<u16> {
+    let hex = value.split_whitespace().next()?.trim_start_matches("0x");
+    u16::from_str_radix(hex, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trimmed `system_profiler SPUSBDataType -json` output with a YubiKey
+    /// behind a hub next to unrelated devices
+    const SP_USB_DATA: &str = r#"{
+      "SPUSBDataType" : [
+        {
+          "_items" : [
+            {
+              "_items" : [
+                {
+                  "_name" : "YubiKey OTP+FIDO+CCID",
+                  "bcd_device" : "5.43",
+                  "bus_power" : "500",
+                  "bus_power_used" : "30",
+                  "device_speed" : "full_speed",
+                  "extra_current_used" : "0",
+                  "location_id" : "0x14110000 / 4",
+                  "manufacturer" : "Yubico",
+                  "product_id" : "0x0407",
+                  "vendor_id" : "0x1050  (Yubico.com)"
+                },
+                {
+                  "_name" : "USB Receiver",
+                  "location_id" : "0x14120000 / 5",
+                  "manufacturer" : "Logitech",
+                  "product_id" : "0xc52b",
+                  "serial_num" : "ABC123",
+                  "vendor_id" : "0x046d  (Logitech Inc.)"
+                }
+              ],
+              "_name" : "USB2.0 Hub",
+              "location_id" : "0x14100000 / 3",
+              "product_id" : "0x0610",
+              "vendor_id" : "0x05e3  (Genesys Logic, Inc.)"
+            },
+            {
+              "_name" : "CanoKey",
+              "location_id" : "0x14200000 / 6",
+              "product_id" : "0x42d4",
+              "serial_num" : "A1B2C3D4",
+              "vendor_id" : "0x20a0"
+            }
+          ],
+          "_name" : "USB31Bus",
+          "host_controller" : "AppleUSBXHCITR"
+        },
+        {
+          "_name" : "USB30Bus",
+          "host_controller" : "AppleUSBXHCIPPT"
+        }
+      ]
+    }"#;
+
+    #[test]
+    fn test_parse_captured_sp_usb_data() {
+        let json: Value = serde_json::from_str(SP_USB_DATA).unwrap();
+        let devices = parse_usb_devices(&json);
+        assert_eq!(devices.len(), 2);
+
+        let yubikey = &devices[0];
+        assert_eq!(yubikey.id, "yubikey-1050-0407");
+        assert_eq!(yubikey.name, "YubiKey OTP+FIDO+CCID");
+        assert_eq!(yubikey.manufacturer, "Yubico");
+        assert_eq!(yubikey.device_type, DeviceType::YubiKey);
+        assert!(yubikey.has_capability(&Capability::Oath));
+        assert_eq!(yubikey.serial_number, None);
+
+        let canokey = &devices[1];
+        assert_eq!(canokey.id, "canokey-20a0-42d4");
+        assert_eq!(canokey.manufacturer, "CanoKeys");
+        assert_eq!(canokey.serial_number.as_deref(), Some("A1B2C3D4"));
+        assert!(!canokey.has_capability(&Capability::Oath));
+    }
+
+    #[test]
+    fn test_parse_id() {
+        assert_eq!(parse_id("0x1050  (Yubico.com)"), Some(0x1050));
+        assert_eq!(parse_id("0x0407"), Some(0x0407));
+        assert_eq!(parse_id("apple_vendor_id"), None);
+        assert_eq!(parse_id(""), None);
+    }
+
+    #[test]
+    fn test_missing_root_yields_nothing() {
+        assert!(parse_usb_devices(&serde_json::json!({ "SPHardwareDataType": [] })).is_empty());
+    }
+}
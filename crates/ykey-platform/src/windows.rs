@@ -0,0 +1,373 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Windows device discovery over SetupAPI and HID.dll
+//!
+//! Enumerates HID device interfaces, reads their attributes and top-level
+//! usage page, and keeps known devices on the FIDO usage page. Since
+//! Windows 10 1903, FIDO interfaces can only be opened for I/O by elevated
+//! processes; scans report that as `PermissionDenied`.
+
+use crate::FidoDeviceIds;
+use ykey_core::types::*;
+
+#[cfg(target_os = "windows")]
+use ::windows::{
+    core::PCWSTR,
+    Win32::{
+        Devices::{
+            DeviceAndDriverInstallation::{
+                SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInterfaces, SetupDiGetClassDevsW,
+                SetupDiGetDeviceInterfaceDetailW, DIGCF_DEVICEINTERFACE, DIGCF_PRESENT, SP_DEVICE_INTERFACE_DATA,
+                SP_DEVICE_INTERFACE_DETAIL_DATA_W,
+            },
+            HumanInterfaceDevice::{
+                HidD_FreePreparsedData, HidD_GetAttributes, HidD_GetHidGuid, HidD_GetPreparsedData,
+                HidD_GetProductString, HidD_GetSerialNumberString, HidP_GetCaps, HIDD_ATTRIBUTES, HIDP_CAPS,
+                HIDP_STATUS_SUCCESS, PHIDP_PREPARSED_DATA,
+            },
+        },
+        Foundation::{CloseHandle, ERROR_ACCESS_DENIED, GENERIC_READ, GENERIC_WRITE, HANDLE, WIN32_ERROR},
+        Storage::FileSystem::{
+            CreateFileW, FILE_FLAGS_AND_ATTRIBUTES, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+        },
+    },
+};
+#[cfg(target_os = "windows")]
+use async_trait::async_trait;
+#[cfg(target_os = "windows")]
+use std::sync::Mutex;
+#[cfg(target_os = "windows")]
+use tokio::task::JoinHandle;
+#[cfg(target_os = "windows")]
+use ykey_core::{traits::*, YKeyError, YKeyResult};
+
+/// FIDO Alliance HID usage page
+const FIDO_USAGE_PAGE: u16 = 0xF1D0;
+
+/// HID interface as read through HID.dll
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct HidInterface {
+    /// Device interface path, e.g. `\\?\hid#vid_1050&pid_0407&mi_01#...`
+    path: String,
+    /// Vendor ID from `HidD_GetAttributes`
+    vendor_id: u16,
+    /// Product ID from `HidD_GetAttributes`
+    product_id: u16,
+    /// Top-level collection usage page from `HidP_GetCaps`
+    usage_page: u16,
+    /// Product string, if the device reports one
+    product: Option<String>,
+    /// Serial number string, if the device reports one
+    serial: Option<String>,
+}
+
+/// Device discovery backed by SetupAPI and HID.dll
+#[cfg(target_os = "windows")]
+#[derive(Default)]
+pub struct WindowsHidDiscovery {
+    watch_task: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[cfg(target_os = "windows")]
+impl WindowsHidDiscovery {
+    /// Create a new Windows discovery
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(target_os = "windows")]
+#[async_trait]
+impl DeviceDiscovery for WindowsHidDiscovery {
+    async fn scan(&self) -> YKeyResult<Vec<DeviceInfo>> {
+        let devices = fido_devices(enumerate_interfaces()?);
+        for device in &devices {
+            check_access(&device.id)?;
+        }
+        Ok(devices)
+    }
+
+    async fn watch(&self) -> YKeyResult<DeviceEventStream> {
+        let (task, rx) = crate::spawn_poll_watch(|| enumerate_interfaces().map(fido_devices));
+        if let Some(previous) = self.watch_task.lock().unwrap().replace(task) {
+            previous.abort();
+        }
+        Ok(rx)
+    }
+
+    async fn stop_watch(&self) -> YKeyResult<()> {
+        if let Some(task) = self.watch_task.lock().unwrap().take() {
+            task.abort();
+        }
+        Ok(())
+    }
+
+    async fn is_device_available(&self, device_id: &str) -> YKeyResult<bool> {
+        Ok(fido_devices(enumerate_interfaces()?).iter().any(|d| d.id == device_id))
+    }
+}
+
+/// Keep known FIDO devices on the FIDO usage page
+fn fido_devices(interfaces: Vec<HidInterface>) -> Vec<DeviceInfo> {
+    interfaces
+        .into_iter()
+        .filter(|interface| interface.usage_page == FIDO_USAGE_PAGE)
+        .filter_map(|interface| device_info(&interface))
+        .collect()
+}
+
+fn device_info(interface: &HidInterface) -> Option<DeviceInfo> {
+    // Some drivers report zeroed attributes; the interface path still carries the IDs
+    let (vendor_id, product_id) = match (interface.vendor_id, interface.product_id) {
+        (0, 0) => parse_vid_pid(&interface.path)?,
+        ids => ids,
+    };
+    let device_type = FidoDeviceIds::is_known_fido_device(vendor_id, product_id)?;
+
+    let manufacturer = FidoDeviceIds::vendor_name(device_type);
+    let product = interface
+        .product
+        .clone()
+        .unwrap_or_else(|| format!("FIDO device {:04x}:{:04x}", vendor_id, product_id));
+
+    let mut info = DeviceInfo::new(
+        interface.path.clone(),
+        format!("{} {}", manufacturer, product),
+        manufacturer.to_string(),
+        product,
+        vendor_id,
+        product_id,
+        device_type,
+        TransportType::Usb,
+    );
+    info.serial_number = interface.serial.clone();
+    info.add_capability(Capability::Fido2);
+    Some(info)
+}
+
+/// Extract vendor and product IDs from a HID interface path
+fn parse_vid_pid(path: &str) -> Option<(u16, u16)> {
+    let path = path.to_ascii_lowercase();
+    let field = |prefix: &str| {
+        let start = path.find(prefix)? + prefix.len();
+        u16::from_str_radix(path.get(start..start + 4)?, 16).ok()
+    };
+    Some((field("vid_")?, field("pid_")?))
+}
+
+/// Decode a NUL-terminated UTF-16 buffer filled in by HID.dll
+fn wide_to_string(buffer: &[u16]) -> Option<String> {
+    let length = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    let value = String::from_utf16_lossy(&buffer[..length]);
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// List present HID interfaces with their attributes
+#[cfg(target_os = "windows")]
+fn enumerate_interfaces() -> YKeyResult<Vec<HidInterface>> {
+    let mut interfaces = Vec::new();
+    unsafe {
+        let guid = HidD_GetHidGuid();
+        let flags = DIGCF_PRESENT | DIGCF_DEVICEINTERFACE;
+        let set = SetupDiGetClassDevsW(Some(&guid as *const _), PCWSTR::null(), None, flags).map_err(windows_error)?;
+
+        let mut index = 0;
+        loop {
+            let mut data = SP_DEVICE_INTERFACE_DATA {
+                cbSize: std::mem::size_of::<SP_DEVICE_INTERFACE_DATA>() as u32,
+                ..Default::default()
+            };
+            if SetupDiEnumDeviceInterfaces(set, None, &guid, index, &mut data).is_err() {
+                break;
+            }
+            index += 1;
+
+            if let Some(path) = interface_path(set, &data) {
+                // Interfaces that vanish or refuse even a query handle are skipped
+                if let Ok(interface) = read_interface(&path) {
+                    interfaces.push(interface);
+                }
+            }
+        }
+
+        let _ = SetupDiDestroyDeviceInfoList(set);
+    }
+    Ok(interfaces)
+}
+
+/// Resolve the device path of an enumerated interface as UTF-16
+#[cfg(target_os = "windows")]
+unsafe fn interface_path(
+    set: ::windows::Win32::Devices::DeviceAndDriverInstallation::HDEVINFO,
+    data: &SP_DEVICE_INTERFACE_DATA,
+) -> Option<Vec<u16>> {
+    let mut required = 0u32;
+    let _ = SetupDiGetDeviceInterfaceDetailW(set, data, None, 0, Some(&mut required as *mut u32), None);
+    if required == 0 {
+        return None;
+    }
+
+    // u32 storage keeps the detail struct aligned
+    let mut buffer = vec![0u32; (required as usize).div_ceil(4)];
+    let detail = buffer.as_mut_ptr() as *mut SP_DEVICE_INTERFACE_DETAIL_DATA_W;
+    (*detail).cbSize = std::mem::size_of::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>() as u32;
+    SetupDiGetDeviceInterfaceDetailW(set, data, Some(detail), required, None, None).ok()?;
+
+    let offset = std::mem::offset_of!(SP_DEVICE_INTERFACE_DETAIL_DATA_W, DevicePath);
+    let chars = (required as usize - offset) / 2;
+    let path = std::slice::from_raw_parts((*detail).DevicePath.as_ptr(), chars);
+    let length = path.iter().position(|&c| c == 0).unwrap_or(chars);
+    let mut path = path[..length].to_vec();
+    path.push(0);
+    Some(path)
+}
+
+/// Read attributes, usage page and strings through a query-only handle
+#[cfg(target_os = "windows")]
+unsafe fn read_interface(path: &[u16]) -> YKeyResult<HidInterface> {
+    let handle = open(path, 0).map_err(windows_error)?;
+
+    let mut attributes = HIDD_ATTRIBUTES {
+        Size: std::mem::size_of::<HIDD_ATTRIBUTES>() as u32,
+        ..Default::default()
+    };
+    let have_attributes = HidD_GetAttributes(handle, &mut attributes).as_bool();
+
+    let mut usage_page = 0;
+    let mut preparsed = PHIDP_PREPARSED_DATA::default();
+    if HidD_GetPreparsedData(handle, &mut preparsed).as_bool() {
+        let mut caps = HIDP_CAPS::default();
+        if HidP_GetCaps(preparsed, &mut caps) == HIDP_STATUS_SUCCESS {
+            usage_page = caps.UsagePage;
+        }
+        let _ = HidD_FreePreparsedData(preparsed);
+    }
+
+    let mut buffer = [0u16; 128];
+    let size = std::mem::size_of_val(&buffer) as u32;
+    let product = HidD_GetProductString(handle, buffer.as_mut_ptr().cast(), size)
+        .as_bool()
+        .then(|| wide_to_string(&buffer))
+        .flatten();
+    buffer.fill(0);
+    let serial = HidD_GetSerialNumberString(handle, buffer.as_mut_ptr().cast(), size)
+        .as_bool()
+        .then(|| wide_to_string(&buffer))
+        .flatten();
+
+    let _ = CloseHandle(handle);
+
+    let path = String::from_utf16_lossy(&path[..path.len() - 1]);
+    if !have_attributes {
+        return Err(YKeyError::communication(format!("Failed to read HID attributes of {}", path)));
+    }
+    Ok(HidInterface {
+        path,
+        vendor_id: attributes.VendorID,
+        product_id: attributes.ProductID,
+        usage_page,
+        product,
+        serial,
+    })
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn open(path: &[u16], access: u32) -> ::windows::core::Result<HANDLE> {
+    CreateFileW(
+        PCWSTR(path.as_ptr()),
+        access,
+        FILE_SHARE_READ | FILE_SHARE_WRITE,
+        None,
+        OPEN_EXISTING,
+        FILE_FLAGS_AND_ATTRIBUTES(0),
+        None,
+    )
+}
+
+/// Fail with `PermissionDenied` when the interface cannot be opened for I/O
+#[cfg(target_os = "windows")]
+fn check_access(path: &str) -> YKeyResult<()> {
+    let wide: Vec<u16> = path.encode_utf16().chain(std::iter::once(0)).collect();
+    match unsafe { open(&wide, GENERIC_READ.0 | GENERIC_WRITE.0) } {
+        Ok(handle) => {
+            let _ = unsafe { CloseHandle(handle) };
+            Ok(())
+        }
+        Err(e) if WIN32_ERROR::from_error(&e) == Some(ERROR_ACCESS_DENIED) => Err(YKeyError::permission_denied(
+            format!("{} (FIDO devices require an elevated process on Windows)", path),
+        )),
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn windows_error(error: ::windows::core::Error) -> YKeyError {
+    YKeyError::communication(format!("Windows HID error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interface(path: &str, vendor_id: u16, product_id: u16, usage_page: u16) -> HidInterface {
+        HidInterface {
+            path: path.to_string(),
+            vendor_id,
+            product_id,
+            usage_page,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_vid_pid() {
+        assert_eq!(
+            parse_vid_pid(r"\\?\hid#vid_1050&pid_0407&mi_01#7&2b2e8a1&0&0000#{4d1e55b2-f16f-11cf-88cb-001111000030}"),
+            Some((0x1050, 0x0407))
+        );
+        assert_eq!(parse_vid_pid(r"\\?\HID#VID_20A0&PID_42D4#6&1"), Some((0x20A0, 0x42D4)));
+        assert_eq!(parse_vid_pid(r"\\?\hid#vid_1050#6&1"), None);
+        assert_eq!(parse_vid_pid(r"\\?\hid#vid_zz50&pid_0407"), None);
+    }
+
+    #[test]
+    fn test_wide_to_string() {
+        let mut buffer = [0u16; 16];
+        for (slot, c) in buffer.iter_mut().zip("YubiKey".encode_utf16()) {
+            *slot = c;
+        }
+        assert_eq!(wide_to_string(&buffer).as_deref(), Some("YubiKey"));
+        assert_eq!(wide_to_string(&[0u16; 4]), None);
+        assert_eq!(wide_to_string(&[0x20, 0x20, 0]), None);
+    }
+
+    #[test]
+    fn test_attributes_filtered_by_usage_page_and_known_ids() {
+        let mut yubikey = interface(r"\\?\hid#vid_1050&pid_0407&mi_01#a", 0x1050, 0x0407, FIDO_USAGE_PAGE);
+        yubikey.product = Some("YubiKey OTP+FIDO+CCID".to_string());
+        yubikey.serial = Some("12345678".to_string());
+
+        let devices = fido_devices(vec![
+            // Keyboard interface of the same key
+            interface(r"\\?\hid#vid_1050&pid_0407&mi_00#a", 0x1050, 0x0407, 0x01),
+            yubikey,
+            // Attributes unavailable, IDs recovered from the path
+            interface(r"\\?\hid#vid_20a0&pid_42d4#b", 0, 0, FIDO_USAGE_PAGE),
+            // Unknown authenticator
+            interface(r"\\?\hid#vid_ffff&pid_ffff#c", 0xFFFF, 0xFFFF, FIDO_USAGE_PAGE),
+        ]);
+
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].device_type, DeviceType::YubiKey);
+        assert_eq!(devices[0].name, "Yubico YubiKey OTP+FIDO+CCID");
+        assert_eq!(devices[0].serial_number.as_deref(), Some("12345678"));
+        assert!(devices[0].has_capability(&Capability::Fido2));
+
+        assert_eq!(devices[1].device_type, DeviceType::CanoKey);
+        assert_eq!((devices[1].vendor_id, devices[1].product_id), (0x20A0, 0x42D4));
+        assert_eq!(devices[1].product_name, "FIDO device 20a0:42d4");
+        assert_eq!(devices[1].serial_number, None);
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Protocol checks against a real authenticator
+//!
+//! Plug in a FIDO2 key and run
+//! `cargo test -p ykey-platform --features hardware-tests -- --ignored --nocapture`.
+//! The first USB HID authenticator found is used; without one every test
+//! prints a note and passes. Nothing destructive is sent: there is no reset,
+//! and the PIN is only used when `YKEY_TEST_PIN` is set, to create a
+//! resident credential and delete it again afterwards. Expect to touch the
+//! key once per credential and assertion.
+
+#![cfg(feature = "hardware-tests")]
+
+use std::{sync::Mutex, time::Duration};
+use ykey_core::{traits::*, types::*, YKeyError, YKeyResult};
+use ykey_platform::FidoDeviceIds;
+use ykey_protocol::{
+    client_data::ClientData,
+    cose::AttestationObjectExt,
+    ctaphid::{HidDevice, HidReportIo, HID_REPORT_SIZE},
+    Fido2Client,
+};
+
+/// FIDO usage page and CTAPHID usage from the HID descriptor
+const FIDO_USAGE_PAGE: u16 = 0xF1D0;
+const CTAPHID_USAGE: u16 = 0x01;
+
+const RP_ID: &str = "ykey.test";
+
+/// hidapi handle exchanging CTAPHID reports
+struct HidapiReports(Mutex<hidapi::HidDevice>);
+
+impl HidReportIo for HidapiReports {
+    fn write_report(&mut self, report: &[u8]) -> YKeyResult<()> {
+        // hidapi expects the report ID first, which is zero for FIDO keys
+        let mut buffer = vec![0x00];
+        buffer.extend_from_slice(report);
+        self.0.get_mut().map_err(|_| poisoned())?.write(&buffer).map_err(hid_error)?;
+        Ok(())
+    }
+
+    fn read_report(&mut self, timeout: Duration) -> YKeyResult<Vec<u8>> {
+        let mut buffer = vec![0u8; HID_REPORT_SIZE];
+        let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+        match self.0.get_mut().map_err(|_| poisoned())?.read_timeout(&mut buffer, millis).map_err(hid_error)? {
+            0 => Err(YKeyError::timeout(timeout.as_secs())),
+            read => {
+                buffer.truncate(read);
+                Ok(buffer)
+            }
+        }
+    }
+}
+
+fn hid_error(error: hidapi::HidError) -> YKeyError {
+    YKeyError::communication(format!("hidapi error: {}", error))
+}
+
+fn poisoned() -> YKeyError {
+    YKeyError::communication("HID handle lock poisoned")
+}
+
+/// Connect to the first FIDO authenticator, or `None` if there is none
+async fn first_authenticator() -> Option<Fido2Client<HidDevice<HidapiReports>>> {
+    let api = match hidapi::HidApi::new() {
+        Ok(api) => api,
+        Err(e) => {
+            eprintln!("skipping: cannot open HID subsystem: {}", e);
+            return None;
+        }
+    };
+    let Some(found) = api
+        .device_list()
+        .find(|d| d.usage_page() == FIDO_USAGE_PAGE && d.usage() == CTAPHID_USAGE)
+    else {
+        eprintln!("skipping: no FIDO authenticator connected");
+        return None;
+    };
+
+    let device_type = FidoDeviceIds::is_known_fido_device(found.vendor_id(), found.product_id())
+        .unwrap_or(DeviceType::Generic);
+    let product = found.product_string().unwrap_or("Unknown").to_string();
+    let info = DeviceInfo::new(
+        found.path().to_string_lossy().into_owned(),
+        product.clone(),
+        found.manufacturer_string().unwrap_or("Unknown").to_string(),
+        product,
+        found.vendor_id(),
+        found.product_id(),
+        device_type,
+        TransportType::Usb,
+    );
+    eprintln!("using {} ({:04x}:{:04x})", info.name, info.vendor_id, info.product_id);
+
+    let handle = found.open_device(&api).expect("open HID device");
+    let mut device = HidDevice::new(info, HidapiReports(Mutex::new(handle)));
+    device.connect().await.expect("CTAPHID_INIT");
+    Some(Fido2Client::new(device))
+}
+
+#[tokio::test]
+#[ignore = "needs a connected authenticator"]
+async fn hardware_get_info() {
+    let Some(mut client) = first_authenticator().await else { return };
+
+    let info = client.get_info().await.unwrap();
+    eprintln!("versions: {:?}", info.versions);
+    eprintln!("aaguid: {}", info.aaguid);
+    eprintln!("options: {:?}", info.options);
+    eprintln!("supported commands: {:?}", info.supported_commands());
+    assert!(info.versions.iter().any(|v| v.starts_with("FIDO_2")));
+}
+
+#[tokio::test]
+#[ignore = "needs a connected authenticator and a touch"]
+async fn hardware_credential_round_trip() {
+    let Some(mut client) = first_authenticator().await else { return };
+    let pin = std::env::var("YKEY_TEST_PIN").ok();
+
+    let user = User {
+        id: b"ykey-hardware-test".to_vec(),
+        name: "hardware-test".to_string(),
+        display_name: "YKey hardware test".to_string(),
+        icon: None,
+    };
+    let creation = ClientData::create(b"hardware-test-create".to_vec(), format!("https://{}", RP_ID));
+    let params = MakeCredentialParams::builder()
+        .rp_id(RP_ID)
+        .user(user)
+        .resident_key(pin.is_some())
+        .client_data_hash(creation.hash())
+        .build()
+        .unwrap();
+    eprintln!("touch the key to create a credential");
+    let attestation = client.make_credential(params).await.unwrap();
+    let credential = attestation.parse_auth_data().unwrap();
+    eprintln!("attestation format {}, credential {} bytes", attestation.fmt, credential.credential_id.len());
+
+    let request = ClientData::get(b"hardware-test-get".to_vec(), format!("https://{}", RP_ID));
+    let params = GetAssertionParams::builder()
+        .rp_id(RP_ID)
+        .allow(credential.credential_id.clone())
+        .client_data_hash(request.hash())
+        .build()
+        .unwrap();
+    eprintln!("touch the key again to sign an assertion");
+    let assertion = client.get_assertion(params).await;
+
+    // Remove the resident credential even if the assertion failed
+    if let Some(pin) = &pin {
+        client.unlock_credential_management(pin).await.unwrap();
+        client.delete_credential(&credential.credential_id).await.unwrap();
+        eprintln!("deleted the resident credential");
+    }
+
+    let assertion = assertion.unwrap();
+    client.verify_assertion(&assertion, &request.hash(), &credential.public_key).unwrap();
+    eprintln!("assertion signature verified");
+}
@@ -0,0 +1,240 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! ISO 7816-4 APDU framing
+//!
+//! Shared by the smartcard applets (OATH, PIV, OpenPGP): building command
+//! APDUs in short or extended form, splitting off the status word and
+//! following the 61xx/6Cxx status words until the whole response is read.
+
+use ykey_core::{traits::Device, YKeyError, YKeyResult};
+
+/// GET RESPONSE instruction, used to fetch data announced by 61xx
+pub const INS_GET_RESPONSE: u8 = 0xC0;
+
+/// Status word for a successful command
+pub const SW_SUCCESS: u16 = 0x9000;
+
+/// SW1 announcing more response data, with SW2 bytes available
+const SW1_MORE_DATA: u8 = 0x61;
+
+/// SW1 rejecting Le, with SW2 giving the exact length to ask for
+const SW1_WRONG_LENGTH: u8 = 0x6C;
+
+/// Largest Lc and Le that fit the short encoding
+const SHORT_MAX_DATA: usize = 255;
+const SHORT_MAX_LE: usize = 256;
+
+/// Largest Lc and Le that fit the extended encoding
+const EXTENDED_MAX_DATA: usize = 65535;
+const EXTENDED_MAX_LE: usize = 65536;
+
+/// A command APDU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandApdu {
+    pub cla: u8,
+    pub ins: u8,
+    pub p1: u8,
+    pub p2: u8,
+    /// Command data, sent with Lc when not empty
+    pub data: Vec<u8>,
+    /// Expected response length; 256 or 65536 request the maximum
+    pub le: Option<usize>,
+}
+
+impl CommandApdu {
+    /// Command with no data and no Le
+    pub fn new(cla: u8, ins: u8, p1: u8, p2: u8) -> Self {
+        Self { cla, ins, p1, p2, data: Vec::new(), le: None }
+    }
+
+    /// Attach command data
+    pub fn with_data(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    /// Attach an expected response length
+    pub fn with_le(mut self, le: usize) -> Self {
+        self.le = Some(le);
+        self
+    }
+
+    /// Encode in short form when the lengths allow it, extended form otherwise
+    pub fn encode(&self) -> YKeyResult<Vec<u8>> {
+        if self.data.len() <= SHORT_MAX_DATA && self.le.is_none_or(|le| le <= SHORT_MAX_LE) {
+            Ok(self.encode_short())
+        } else {
+            self.encode_extended()
+        }
+    }
+
+    fn encode_short(&self) -> Vec<u8> {
+        let mut apdu = vec![self.cla, self.ins, self.p1, self.p2];
+        if !self.data.is_empty() {
+            apdu.push(self.data.len() as u8);
+            apdu.extend_from_slice(&self.data);
+        }
+        if let Some(le) = self.le {
+            // 256 wraps to 0x00
+            apdu.push(le as u8);
+        }
+        apdu
+    }
+
+    /// Encode in extended form regardless of the lengths
+    pub fn encode_extended(&self) -> YKeyResult<Vec<u8>> {
+        if self.data.len() > EXTENDED_MAX_DATA {
+            return Err(YKeyError::InvalidParameters("APDU data too long".to_string()));
+        }
+        if self.le.is_some_and(|le| le > EXTENDED_MAX_LE) {
+            return Err(YKeyError::InvalidParameters("APDU Le too large".to_string()));
+        }
+
+        let mut apdu = vec![self.cla, self.ins, self.p1, self.p2];
+        if !self.data.is_empty() {
+            apdu.push(0x00);
+            apdu.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+            apdu.extend_from_slice(&self.data);
+        }
+        if let Some(le) = self.le {
+            // Without Lc the extended marker byte introduces Le instead
+            if self.data.is_empty() {
+                apdu.push(0x00);
+            }
+            // 65536 wraps to 0x0000
+            apdu.extend_from_slice(&(le as u16).to_be_bytes());
+        }
+        Ok(apdu)
+    }
+}
+
+/// A response APDU split into data and status word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseApdu {
+    pub data: Vec<u8>,
+    pub sw1: u8,
+    pub sw2: u8,
+}
+
+impl ResponseApdu {
+    /// Split the trailing status word off a raw response
+    pub fn parse(mut response: Vec<u8>) -> YKeyResult<Self> {
+        if response.len() < 2 {
+            return Err(YKeyError::communication("APDU response missing status word"));
+        }
+        let status = response.split_off(response.len() - 2);
+        Ok(Self { data: response, sw1: status[0], sw2: status[1] })
+    }
+
+    /// The two-byte status word
+    pub fn status(&self) -> u16 {
+        u16::from_be_bytes([self.sw1, self.sw2])
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status() == SW_SUCCESS
+    }
+}
+
+/// Le requested by a 61xx or 6Cxx status, where 00 stands for 256
+fn le_from_sw2(sw2: u8) -> usize {
+    if sw2 == 0 { SHORT_MAX_LE } else { sw2 as usize }
+}
+
+/// Send a command and collect the complete response
+///
+/// 61xx is answered with GET RESPONSE until the card stops announcing more
+/// data, and 6Cxx repeats the command once with the Le the card asked for.
+/// Data from every chunk is concatenated; the status word is the final one,
+/// which the caller interprets.
+pub async fn transceive<D: Device + ?Sized>(device: &mut D, command: &CommandApdu) -> YKeyResult<ResponseApdu> {
+    let mut request = command.clone();
+    let mut corrected = false;
+    let mut data = Vec::new();
+    loop {
+        let response = ResponseApdu::parse(device.send_raw(&request.encode()?).await?)?;
+        match response.sw1 {
+            SW1_WRONG_LENGTH if !corrected => {
+                corrected = true;
+                request.le = Some(le_from_sw2(response.sw2));
+            }
+            SW1_MORE_DATA => {
+                data.extend(response.data);
+                corrected = false;
+                request = CommandApdu::new(command.cla, INS_GET_RESPONSE, 0x00, 0x00)
+                    .with_le(le_from_sw2(response.sw2));
+            }
+            _ => {
+                data.extend(response.data);
+                return Ok(ResponseApdu { data, ..response });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ReplayDevice;
+
+    #[test]
+    fn test_command_encoding() {
+        let select = CommandApdu::new(0x00, 0xA4, 0x04, 0x00).with_data([0xA0, 0x00, 0x00, 0x03, 0x08]);
+        assert_eq!(select.encode().unwrap(), [0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08]);
+        assert_eq!(CommandApdu::new(0x00, 0xC0, 0, 0).with_le(256).encode().unwrap(), [0x00, 0xC0, 0, 0, 0x00]);
+
+        // Too much data for Lc switches to the extended form
+        let long = CommandApdu::new(0x00, 0xDB, 0x3F, 0xFF).with_data(vec![0xAB; 300]).with_le(65536);
+        let encoded = long.encode().unwrap();
+        assert_eq!(encoded[..7], [0x00, 0xDB, 0x3F, 0xFF, 0x00, 0x01, 0x2C]);
+        assert_eq!(encoded.len(), 7 + 300 + 2);
+        assert_eq!(encoded[307..], [0x00, 0x00]);
+
+        // Le alone in extended form carries its own marker byte
+        let read = CommandApdu::new(0x00, 0xB0, 0, 0).with_le(1000);
+        assert_eq!(read.encode().unwrap(), [0x00, 0xB0, 0, 0, 0x00, 0x03, 0xE8]);
+        assert!(CommandApdu::new(0, 0, 0, 0).with_data(vec![0; 65536]).encode().is_err());
+    }
+
+    #[test]
+    fn test_status_word_parsing() {
+        let response = ResponseApdu::parse(vec![0x01, 0x02, 0x90, 0x00]).unwrap();
+        assert_eq!(response.data, [0x01, 0x02]);
+        assert_eq!(response.status(), SW_SUCCESS);
+        assert!(response.is_success());
+
+        let error = ResponseApdu::parse(vec![0x6A, 0x82]).unwrap();
+        assert!(error.data.is_empty());
+        assert_eq!((error.sw1, error.sw2), (0x6A, 0x82));
+        assert_eq!(error.status(), 0x6A82);
+        assert!(!error.is_success());
+
+        assert!(ResponseApdu::parse(vec![0x90]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transceive_chains_responses() {
+        let mut card = ReplayDevice::new(vec![
+            vec![0x6C, 0x04],
+            vec![0x01, 0x02, 0x61, 0x00],
+            [vec![0x03; 256], vec![0x61, 0x01]].concat(),
+            vec![0x04, 0x90, 0x00],
+        ]);
+
+        let command = CommandApdu::new(0x00, 0xCA, 0x00, 0x6E).with_le(256);
+        let response = transceive(&mut card, &command).await.unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.data, [vec![0x01, 0x02], vec![0x03; 256], vec![0x04]].concat());
+
+        assert_eq!(
+            card.requests,
+            [
+                vec![0x00, 0xCA, 0x00, 0x6E, 0x00],
+                vec![0x00, 0xCA, 0x00, 0x6E, 0x04],
+                vec![0x00, INS_GET_RESPONSE, 0x00, 0x00, 0x00],
+                vec![0x00, INS_GET_RESPONSE, 0x00, 0x00, 0x01],
+            ]
+        );
+    }
+}
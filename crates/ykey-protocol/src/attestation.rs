@@ -0,0 +1,376 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Attestation statement verification
+//!
+//! Verifies the signature of a `packed` attestation statement and checks that
+//! its certificate chain ends at a root in a [`TrustStore`]. A valid chain to
+//! a root the store does not know is reported as [`TrustLevel::Unknown`]
+//! rather than rejected, so callers decide how much assurance they need.
+//!
+//! The store starts empty; vendor attestation roots are added by the caller
+//! from the vendors' published certificates.
+
+use crate::cose::{self, AuthenticatorData};
+use ring::signature::{self, UnparsedPublicKey, VerificationAlgorithm};
+use std::time::SystemTime;
+use x509_cert::{
+    der::{asn1::ObjectIdentifier, Decode, Encode},
+    spki::SubjectPublicKeyInfoOwned,
+    Certificate,
+};
+use ykey_core::{types::AttestationObject, YKeyError, YKeyResult};
+
+const ECDSA_WITH_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const ECDSA_WITH_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+const SHA256_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+const SHA384_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.12");
+const SHA512_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.13");
+const ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+const CURVE_P256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+const CURVE_P384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.34");
+
+/// COSE algorithm: ECDSA w/ SHA-384
+const ALG_ES384: i64 = -35;
+
+/// How far an attestation could be traced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustLevel {
+    /// The certificate chain ends at a root in the trust store
+    Trusted,
+    /// The chain is valid but its root is not in the trust store
+    Unknown,
+    /// Signed by the credential key itself, no certificate
+    SelfAttested,
+    /// The `none` format, nothing to verify
+    None,
+}
+
+/// Outcome of verifying an attestation statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestationResult {
+    /// How far the attestation could be traced
+    pub trust_level: TrustLevel,
+    /// Name of the matching root, for `TrustLevel::Trusted`
+    pub root: Option<String>,
+}
+
+struct TrustRoot {
+    name: String,
+    certificate: Certificate,
+}
+
+/// Attestation root certificates to verify chains against
+#[derive(Default)]
+pub struct TrustStore {
+    roots: Vec<TrustRoot>,
+}
+
+impl TrustStore {
+    /// Create an empty trust store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a DER encoded root certificate under a display name
+    pub fn add_root(&mut self, name: impl Into<String>, der: &[u8]) -> YKeyResult<()> {
+        let certificate = parse_certificate(der)?;
+        self.roots.push(TrustRoot { name: name.into(), certificate });
+        Ok(())
+    }
+
+    /// Names of the roots in the store
+    pub fn root_names(&self) -> impl Iterator<Item = &str> {
+        self.roots.iter().map(|root| root.name.as_str())
+    }
+
+    /// Find the root that issued `certificate`, or is `certificate`
+    fn find_issuer(&self, certificate: &Certificate) -> Option<&TrustRoot> {
+        let issuer = &certificate.tbs_certificate.issuer;
+        self.roots.iter().find(|root| {
+            root.certificate == *certificate
+                || (root.certificate.tbs_certificate.subject == *issuer
+                    && verify_issued_by(certificate, &root.certificate).is_ok())
+        })
+    }
+}
+
+/// Verify an attestation statement over `client_data_hash`
+///
+/// Supports the `packed` format, with a certificate chain or self attestation,
+/// and `none`. A bad signature or broken chain fails with
+/// `AuthenticationFailed`.
+pub fn verify_attestation(
+    attestation: &AttestationObject,
+    client_data_hash: &[u8],
+    trust_store: &TrustStore,
+) -> YKeyResult<AttestationResult> {
+    match attestation.fmt.as_str() {
+        "none" => Ok(AttestationResult { trust_level: TrustLevel::None, root: None }),
+        "packed" => verify_packed(attestation, client_data_hash, trust_store),
+        other => Err(YKeyError::unsupported(
+            "Attestation verification",
+            format!("attestation format {:?} is not supported", other),
+        )),
+    }
+}
+
+fn verify_packed(
+    attestation: &AttestationObject,
+    client_data_hash: &[u8],
+    trust_store: &TrustStore,
+) -> YKeyResult<AttestationResult> {
+    let statement = &attestation.att_stmt;
+    let alg = statement
+        .get("alg")
+        .and_then(serde_json::Value::as_i64)
+        .ok_or_else(|| YKeyError::InvalidCredential("Packed attestation missing alg".to_string()))?;
+    let sig = statement
+        .get("sig")
+        .and_then(json_bytes)
+        .ok_or_else(|| YKeyError::InvalidCredential("Packed attestation missing sig".to_string()))?;
+    let signed_data = [attestation.auth_data.as_slice(), client_data_hash].concat();
+
+    let Some(x5c) = statement.get("x5c") else {
+        // Self attestation, signed with the new credential's own key
+        let credential = AuthenticatorData::parse(&attestation.auth_data)?
+            .attested_credential
+            .ok_or_else(|| YKeyError::InvalidCredential("Self attestation without credential data".to_string()))?;
+        if credential.public_key.alg() != alg {
+            return Err(YKeyError::auth_failed("Self attestation alg does not match the credential key"));
+        }
+        cose::verify_signature(&credential.public_key, &signed_data, &sig)?;
+        return Ok(AttestationResult { trust_level: TrustLevel::SelfAttested, root: None });
+    };
+
+    let chain = x5c
+        .as_array()
+        .filter(|certificates| !certificates.is_empty())
+        .ok_or_else(|| YKeyError::InvalidCredential("Packed attestation x5c is empty".to_string()))?
+        .iter()
+        .map(|certificate| {
+            json_bytes(certificate)
+                .ok_or_else(|| YKeyError::InvalidCredential("x5c entry is not a byte string".to_string()))
+                .and_then(|der| parse_certificate(&der))
+        })
+        .collect::<YKeyResult<Vec<_>>>()?;
+
+    let leaf_key = &chain[0].tbs_certificate.subject_public_key_info;
+    let algorithm = cose_algorithm(alg, leaf_key)?;
+    verify_with(leaf_key, algorithm, &signed_data, &sig)
+        .map_err(|_| YKeyError::auth_failed("Attestation signature does not match the certificate"))?;
+
+    let now = SystemTime::now();
+    for certificate in &chain {
+        check_validity(certificate, now)?;
+    }
+    for pair in chain.windows(2) {
+        verify_issued_by(&pair[0], &pair[1])?;
+    }
+
+    let last = chain.last().expect("chain is not empty");
+    Ok(match trust_store.find_issuer(last) {
+        Some(root) => {
+            check_validity(&root.certificate, now)?;
+            AttestationResult { trust_level: TrustLevel::Trusted, root: Some(root.name.clone()) }
+        }
+        None => AttestationResult { trust_level: TrustLevel::Unknown, root: None },
+    })
+}
+
+fn parse_certificate(der: &[u8]) -> YKeyResult<Certificate> {
+    Certificate::from_der(der).map_err(|e| YKeyError::InvalidCredential(format!("Invalid certificate: {}", e)))
+}
+
+/// Decode a byte string as converted by `cbor::to_json`
+fn json_bytes(value: &serde_json::Value) -> Option<Vec<u8>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|byte| byte.as_u64().and_then(|b| u8::try_from(b).ok()))
+        .collect()
+}
+
+fn check_validity(certificate: &Certificate, now: SystemTime) -> YKeyResult<()> {
+    let validity = &certificate.tbs_certificate.validity;
+    if now < validity.not_before.to_system_time() || now > validity.not_after.to_system_time() {
+        return Err(YKeyError::auth_failed(format!(
+            "Certificate {} is not valid at this time",
+            certificate.tbs_certificate.subject
+        )));
+    }
+    Ok(())
+}
+
+/// Check that `issuer` signed `certificate`
+fn verify_issued_by(certificate: &Certificate, issuer: &Certificate) -> YKeyResult<()> {
+    let issuer_key = &issuer.tbs_certificate.subject_public_key_info;
+    let algorithm = certificate_algorithm(&certificate.signature_algorithm.oid, issuer_key)?;
+    let tbs = certificate
+        .tbs_certificate
+        .to_der()
+        .map_err(|e| YKeyError::InvalidCredential(format!("Invalid certificate: {}", e)))?;
+    let signature = certificate
+        .signature
+        .as_bytes()
+        .ok_or_else(|| YKeyError::InvalidCredential("Certificate signature has unused bits".to_string()))?;
+
+    verify_with(issuer_key, algorithm, &tbs, signature).map_err(|_| {
+        YKeyError::auth_failed(format!(
+            "Certificate {} was not signed by {}",
+            certificate.tbs_certificate.subject, issuer.tbs_certificate.subject
+        ))
+    })
+}
+
+fn verify_with(
+    key: &SubjectPublicKeyInfoOwned,
+    algorithm: &'static dyn VerificationAlgorithm,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), ring::error::Unspecified> {
+    UnparsedPublicKey::new(algorithm, key.subject_public_key.raw_bytes()).verify(message, signature)
+}
+
+/// Named curve of an EC public key
+fn curve(key: &SubjectPublicKeyInfoOwned) -> Option<ObjectIdentifier> {
+    key.algorithm.parameters.as_ref()?.decode_as().ok()
+}
+
+/// Verification algorithm for an X.509 signature algorithm and issuer key
+fn certificate_algorithm(
+    oid: &ObjectIdentifier,
+    key: &SubjectPublicKeyInfoOwned,
+) -> YKeyResult<&'static dyn VerificationAlgorithm> {
+    let algorithm: &'static dyn VerificationAlgorithm = match (*oid, curve(key)) {
+        (ECDSA_WITH_SHA256, Some(CURVE_P256)) => &signature::ECDSA_P256_SHA256_ASN1,
+        (ECDSA_WITH_SHA256, Some(CURVE_P384)) => &signature::ECDSA_P384_SHA256_ASN1,
+        (ECDSA_WITH_SHA384, Some(CURVE_P256)) => &signature::ECDSA_P256_SHA384_ASN1,
+        (ECDSA_WITH_SHA384, Some(CURVE_P384)) => &signature::ECDSA_P384_SHA384_ASN1,
+        (SHA256_WITH_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA256,
+        (SHA384_WITH_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA384,
+        (SHA512_WITH_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA512,
+        (ED25519, _) => &signature::ED25519,
+        (oid, _) => {
+            return Err(YKeyError::unsupported(
+                "Certificate verification",
+                format!("signature algorithm {} is not supported", oid),
+            ))
+        }
+    };
+    Ok(algorithm)
+}
+
+/// Verification algorithm for a COSE algorithm and attestation certificate key
+fn cose_algorithm(alg: i64, key: &SubjectPublicKeyInfoOwned) -> YKeyResult<&'static dyn VerificationAlgorithm> {
+    let algorithm: &'static dyn VerificationAlgorithm = match (alg, curve(key)) {
+        (cose::ALG_ES256, Some(CURVE_P256)) => &signature::ECDSA_P256_SHA256_ASN1,
+        (ALG_ES384, Some(CURVE_P384)) => &signature::ECDSA_P384_SHA384_ASN1,
+        (cose::ALG_RS256, _) => &signature::RSA_PKCS1_2048_8192_SHA256,
+        (cose::ALG_EDDSA, _) => &signature::ED25519,
+        (alg, _) => {
+            return Err(YKeyError::unsupported(
+                "Attestation verification",
+                format!("COSE algorithm {} with this certificate key is not supported", alg),
+            ))
+        }
+    };
+    Ok(algorithm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::{signature::Signer, DerSignature, SigningKey};
+    use std::{collections::HashMap, str::FromStr, time::Duration};
+    use x509_cert::{
+        builder::{Builder, CertificateBuilder, Profile},
+        name::Name,
+        serial_number::SerialNumber,
+        time::Validity,
+    };
+
+    fn certificate(subject: &str, key: &SigningKey, profile: Profile, signer: &SigningKey) -> Vec<u8> {
+        let spki = SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).unwrap();
+        CertificateBuilder::new(
+            profile,
+            SerialNumber::from(1u32),
+            Validity::from_now(Duration::from_secs(3600)).unwrap(),
+            Name::from_str(subject).unwrap(),
+            spki,
+            signer,
+        )
+        .unwrap()
+        .build::<DerSignature>()
+        .unwrap()
+        .to_der()
+        .unwrap()
+    }
+
+    /// A root certificate and a packed attestation whose x5c chains to it
+    fn packed_attestation() -> (Vec<u8>, AttestationObject) {
+        let root_key = SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let leaf_key = SigningKey::from_slice(&[0x22; 32]).unwrap();
+        let root = certificate("CN=Test Attestation Root", &root_key, Profile::Root, &root_key);
+        let leaf = certificate(
+            "CN=Test Authenticator",
+            &leaf_key,
+            Profile::Leaf {
+                issuer: Name::from_str("CN=Test Attestation Root").unwrap(),
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            },
+            &root_key,
+        );
+
+        let auth_data = vec![0xAB; 37];
+        let signature: DerSignature = leaf_key.sign(&[auth_data.as_slice(), &[0xCD; 32]].concat());
+        let bytes = |data: &[u8]| serde_json::json!(data);
+        let attestation = AttestationObject {
+            fmt: "packed".to_string(),
+            att_stmt: HashMap::from([
+                ("alg".to_string(), serde_json::json!(cose::ALG_ES256)),
+                ("sig".to_string(), bytes(signature.as_bytes())),
+                ("x5c".to_string(), serde_json::Value::Array(vec![bytes(&leaf)])),
+            ]),
+            auth_data,
+            cred_blob_stored: None,
+            large_blob_key: None,
+        };
+        (root, attestation)
+    }
+
+    #[test]
+    fn test_chain_to_trusted_root() {
+        let (root, attestation) = packed_attestation();
+        let mut store = TrustStore::new();
+        store.add_root("Test Vendor", &root).unwrap();
+
+        let result = verify_attestation(&attestation, &[0xCD; 32], &store).unwrap();
+        assert_eq!(result.trust_level, TrustLevel::Trusted);
+        assert_eq!(result.root.as_deref(), Some("Test Vendor"));
+
+        // The root may also be included at the end of x5c
+        let mut with_root = attestation.clone();
+        with_root.att_stmt.get_mut("x5c").unwrap().as_array_mut().unwrap().push(serde_json::json!(root));
+        assert_eq!(verify_attestation(&with_root, &[0xCD; 32], &store).unwrap().trust_level, TrustLevel::Trusted);
+    }
+
+    #[test]
+    fn test_unknown_root_is_not_an_error() {
+        let (_, attestation) = packed_attestation();
+        let result = verify_attestation(&attestation, &[0xCD; 32], &TrustStore::new()).unwrap();
+        assert_eq!(result, AttestationResult { trust_level: TrustLevel::Unknown, root: None });
+    }
+
+    #[test]
+    fn test_bad_signature_rejected() {
+        let (root, attestation) = packed_attestation();
+        let mut store = TrustStore::new();
+        store.add_root("Test Vendor", &root).unwrap();
+
+        let result = verify_attestation(&attestation, &[0x00; 32], &store);
+        assert!(matches!(result, Err(YKeyError::AuthenticationFailed(_))));
+        assert!(store.add_root("Garbage", &[0x30, 0x03, 0x02, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,607 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Fingerprint enrollment
+//!
+//! Wraps the CTAP 2.1 authenticatorBioEnrollment command. An enrollment
+//! collects samples until the authenticator reports none remaining; an
+//! abandoned enrollment must be cancelled explicitly or the authenticator
+//! stays in enroll mode.
+
+use crate::{cbor, pin::Permissions, Fido2Client};
+use std::future::Future;
+use std::time::Duration;
+use ykey_core::{traits::Device, YKeyError, YKeyResult};
+
+/// authenticatorBioEnrollment command byte
+pub const BIO_ENROLLMENT_COMMAND: u8 = 0x09;
+
+/// Fingerprint modality
+const MODALITY_FINGERPRINT: i64 = 0x01;
+
+/// Returned by enumerateEnrollments when nothing is enrolled
+const CTAP2_ERR_INVALID_OPTION: u8 = 0x2C;
+
+/// authenticatorBioEnrollment subcommands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BioEnrollCommand {
+    EnrollBegin { timeout: Option<Duration> },
+    EnrollCaptureNextSample { template_id: Vec<u8>, timeout: Option<Duration> },
+    CancelCurrentEnrollment,
+    EnumerateEnrollments,
+    SetFriendlyName { template_id: Vec<u8>, name: String },
+    RemoveEnrollment { template_id: Vec<u8> },
+    GetFingerprintSensorInfo,
+}
+
+impl BioEnrollCommand {
+    /// Subcommand number as sent on the wire
+    pub fn subcommand(&self) -> u8 {
+        match self {
+            BioEnrollCommand::EnrollBegin { .. } => 0x01,
+            BioEnrollCommand::EnrollCaptureNextSample { .. } => 0x02,
+            BioEnrollCommand::CancelCurrentEnrollment => 0x03,
+            BioEnrollCommand::EnumerateEnrollments => 0x04,
+            BioEnrollCommand::SetFriendlyName { .. } => 0x05,
+            BioEnrollCommand::RemoveEnrollment { .. } => 0x06,
+            BioEnrollCommand::GetFingerprintSensorInfo => 0x07,
+        }
+    }
+
+    /// Whether the subcommand carries a pinUvAuthParam
+    pub fn requires_auth(&self) -> bool {
+        !matches!(
+            self,
+            BioEnrollCommand::CancelCurrentEnrollment | BioEnrollCommand::GetFingerprintSensorInfo
+        )
+    }
+
+    /// Encode the subCommandParams map, if the subcommand takes one
+    pub fn params(&self) -> Option<cbor::Value> {
+        let timeout_ms = |timeout: Duration| cbor::int(timeout.as_millis() as i64);
+        match self {
+            BioEnrollCommand::EnrollBegin { timeout } => {
+                timeout.map(|t| cbor::int_map(vec![(0x03, Some(timeout_ms(t)))]))
+            }
+            BioEnrollCommand::EnrollCaptureNextSample { template_id, timeout } => Some(cbor::int_map(vec![
+                (0x01, Some(cbor::bytes(template_id))),
+                (0x03, timeout.map(timeout_ms)),
+            ])),
+            BioEnrollCommand::SetFriendlyName { template_id, name } => Some(cbor::int_map(vec![
+                (0x01, Some(cbor::bytes(template_id))),
+                (0x02, Some(cbor::text(name))),
+            ])),
+            BioEnrollCommand::RemoveEnrollment { template_id } => {
+                Some(cbor::int_map(vec![(0x01, Some(cbor::bytes(template_id)))]))
+            }
+            BioEnrollCommand::CancelCurrentEnrollment
+            | BioEnrollCommand::EnumerateEnrollments
+            | BioEnrollCommand::GetFingerprintSensorInfo => None,
+        }
+    }
+}
+
+/// Progress reported after each captured sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnrollSample {
+    /// lastEnrollSampleStatus, 0x00 for a good sample
+    pub status: u8,
+    /// Samples still needed to complete the enrollment
+    pub remaining_samples: u32,
+}
+
+impl EnrollSample {
+    /// lastEnrollSampleStatus of a good sample
+    pub const STATUS_GOOD: u8 = 0x00;
+
+    /// Whether the sample was accepted
+    pub fn is_good(&self) -> bool {
+        self.status == Self::STATUS_GOOD
+    }
+
+    /// Guidance for the user on what went wrong with the sample
+    pub fn feedback(&self) -> &'static str {
+        match self.status {
+            0x00 => "Good sample",
+            0x01 => "Finger too high",
+            0x02 => "Finger too low",
+            0x03 => "Finger too far left",
+            0x04 => "Finger too far right",
+            0x05 => "Finger moved too fast",
+            0x06 => "Finger moved too slow",
+            0x07 => "Poor quality sample",
+            0x08 => "Finger too skewed",
+            0x09 => "Touch too short",
+            0x0A => "Sample could not be merged",
+            0x0B => "Fingerprint already enrolled",
+            0x0D => "No finger detected",
+            0x0E => "Lift and touch again",
+            _ => "Sample not accepted",
+        }
+    }
+}
+
+/// Capabilities of the fingerprint sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FingerprintSensorInfo {
+    /// fingerprintKind: 1 for touch sensors, 2 for swipe sensors
+    pub fingerprint_kind: u8,
+    /// Good samples needed to complete an enrollment
+    pub max_capture_samples_required_for_enroll: u32,
+    /// Longest friendly name the authenticator stores, in bytes
+    pub max_template_friendly_name: Option<u32>,
+}
+
+/// An enrolled fingerprint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateInfo {
+    pub template_id: Vec<u8>,
+    pub friendly_name: Option<String>,
+}
+
+impl<D: Device> Fido2Client<D> {
+    /// Send a bio enrollment subcommand, authenticated with the stored token
+    pub async fn bio_enrollment(&mut self, command: BioEnrollCommand) -> YKeyResult<Option<cbor::Value>> {
+        let params = command.params();
+        let auth = if command.requires_auth() {
+            let token = self.permitted_pin_token(Permissions::BIO_ENROLLMENT)?;
+            let mut message = vec![MODALITY_FINGERPRINT as u8, command.subcommand()];
+            if let Some(params) = &params {
+                message.extend(cbor::encode(params)?);
+            }
+            Some((token.protocol(), token.authenticate(&message)))
+        } else {
+            None
+        };
+
+        self.send_cbor(
+            BIO_ENROLLMENT_COMMAND,
+            Some(cbor::int_map(vec![
+                (0x01, Some(cbor::int(MODALITY_FINGERPRINT))),
+                (0x02, Some(cbor::int(command.subcommand() as i64))),
+                (0x03, params),
+                (0x04, auth.as_ref().map(|(protocol, _)| cbor::int(protocol.version() as i64))),
+                (0x05, auth.as_ref().map(|(_, param)| cbor::bytes(param))),
+            ])),
+        )
+        .await
+    }
+
+    /// Read the fingerprint sensor's capabilities, which needs no token
+    pub async fn fingerprint_sensor_info(&mut self) -> YKeyResult<FingerprintSensorInfo> {
+        let response = self
+            .bio_enrollment(BioEnrollCommand::GetFingerprintSensorInfo)
+            .await?
+            .ok_or_else(|| YKeyError::communication("Missing getFingerprintSensorInfo response"))?;
+        let map = cbor::as_map(&response)?;
+        let uint = |key: i64| cbor::get_int(map, key).map(cbor::to_u64).transpose();
+        Ok(FingerprintSensorInfo {
+            fingerprint_kind: uint(0x02)?.ok_or_else(|| YKeyError::communication("Missing fingerprintKind"))? as u8,
+            max_capture_samples_required_for_enroll: uint(0x03)?
+                .ok_or_else(|| YKeyError::communication("Missing maxCaptureSamplesRequiredForEnroll"))?
+                as u32,
+            max_template_friendly_name: uint(0x08)?.map(|n| n as u32),
+        })
+    }
+}
+
+/// Fingerprint enrollment client
+pub struct BioEnrollClient<D: Device> {
+    client: Fido2Client<D>,
+    sample_timeout: Option<Duration>,
+}
+
+impl<D: Device> BioEnrollClient<D> {
+    /// Unlock bio enrollment with the device PIN
+    pub async fn new(mut client: Fido2Client<D>, pin: &str) -> YKeyResult<Self> {
+        client.get_pin_uv_auth_token_with_permissions(pin, Permissions::BIO_ENROLLMENT, None).await?;
+        Ok(Self { client, sample_timeout: None })
+    }
+
+    /// Get the underlying FIDO2 client
+    pub fn client(&self) -> &Fido2Client<D> {
+        &self.client
+    }
+
+    /// Release the underlying FIDO2 client
+    pub fn into_client(self) -> Fido2Client<D> {
+        self.client
+    }
+
+    /// Set how long the authenticator waits for each sample
+    pub fn set_sample_timeout(&mut self, timeout: Option<Duration>) {
+        self.sample_timeout = timeout;
+    }
+
+    /// Read the fingerprint sensor's capabilities
+    pub async fn sensor_info(&mut self) -> YKeyResult<FingerprintSensorInfo> {
+        self.client.fingerprint_sensor_info().await
+    }
+
+    /// Start enrolling a fingerprint, capturing the first sample
+    ///
+    /// Returns the new template ID with the first sample's progress. Call
+    /// `capture_next_sample` until no samples remain.
+    pub async fn begin_enrollment(&mut self) -> YKeyResult<(Vec<u8>, EnrollSample)> {
+        let response = self
+            .client
+            .bio_enrollment(BioEnrollCommand::EnrollBegin { timeout: self.sample_timeout })
+            .await?
+            .ok_or_else(|| YKeyError::communication("Missing enrollBegin response"))?;
+        Ok((parse_template_id(&response)?, parse_sample(&response)?))
+    }
+
+    /// Capture the next sample of an enrollment in progress
+    pub async fn capture_next_sample(&mut self, template_id: &[u8]) -> YKeyResult<EnrollSample> {
+        let capture = BioEnrollCommand::EnrollCaptureNextSample {
+            template_id: template_id.to_vec(),
+            timeout: self.sample_timeout,
+        };
+        let response = self
+            .client
+            .bio_enrollment(capture)
+            .await?
+            .ok_or_else(|| YKeyError::communication("Missing enrollCaptureNextSample response"))?;
+        parse_sample(&response)
+    }
+
+    /// List the enrolled fingerprints
+    pub async fn enumerate_enrollments(&mut self) -> YKeyResult<Vec<TemplateInfo>> {
+        let response = match self.client.bio_enrollment(BioEnrollCommand::EnumerateEnrollments).await {
+            Ok(response) => response,
+            // Authenticators answer with an error rather than an empty list
+            Err(YKeyError::CtapError { code: CTAP2_ERR_INVALID_OPTION, .. }) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let Some(response) = response else {
+            return Ok(Vec::new());
+        };
+
+        let infos = cbor::get_int(cbor::as_map(&response)?, 0x07)
+            .ok_or_else(|| YKeyError::communication("Missing templateInfos"))?;
+        cbor::to_array(infos)?
+            .iter()
+            .map(|info| {
+                let map = cbor::as_map(info)?;
+                Ok(TemplateInfo {
+                    template_id: cbor::get_int(map, 0x01)
+                        .ok_or_else(|| YKeyError::communication("Missing templateId"))
+                        .and_then(cbor::to_bytes)?,
+                    friendly_name: cbor::get_int(map, 0x02).map(cbor::to_text).transpose()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Give an enrolled fingerprint a friendly name
+    pub async fn set_friendly_name(&mut self, template_id: &[u8], name: &str) -> YKeyResult<()> {
+        let command = BioEnrollCommand::SetFriendlyName {
+            template_id: template_id.to_vec(),
+            name: name.to_string(),
+        };
+        self.client.bio_enrollment(command).await?;
+        Ok(())
+    }
+
+    /// Delete an enrolled fingerprint
+    pub async fn remove_enrollment(&mut self, template_id: &[u8]) -> YKeyResult<()> {
+        let command = BioEnrollCommand::RemoveEnrollment { template_id: template_id.to_vec() };
+        self.client.bio_enrollment(command).await?;
+        Ok(())
+    }
+
+    /// Enroll a new fingerprint and return its template ID
+    ///
+    /// `on_sample` is called after every captured sample. When `cancelled`
+    /// resolves first, the pending capture is aborted and the enrollment
+    /// cancelled on the authenticator before `UserCancelled` is returned. A
+    /// oneshot receiver works as the token: dropping its sender cancels too.
+    pub async fn enroll<C>(&mut self, cancelled: C, mut on_sample: impl FnMut(EnrollSample)) -> YKeyResult<Vec<u8>>
+    where
+        C: Future<Output = ()>,
+    {
+        tokio::pin!(cancelled);
+
+        let begin = BioEnrollCommand::EnrollBegin { timeout: self.sample_timeout };
+        let Some(response) = self.run_cancellable(begin, &mut cancelled).await? else {
+            return Err(YKeyError::communication("Missing enrollBegin response"));
+        };
+        let template_id = parse_template_id(&response)?;
+
+        let mut sample = parse_sample(&response)?;
+        on_sample(sample);
+        while sample.remaining_samples > 0 {
+            let capture = BioEnrollCommand::EnrollCaptureNextSample {
+                template_id: template_id.clone(),
+                timeout: self.sample_timeout,
+            };
+            let response = self
+                .run_cancellable(capture, &mut cancelled)
+                .await?
+                .ok_or_else(|| YKeyError::communication("Missing enrollCaptureNextSample response"))?;
+            sample = parse_sample(&response)?;
+            on_sample(sample);
+        }
+        Ok(template_id)
+    }
+
+    /// Cancel the enrollment in progress
+    pub async fn cancel_enrollment(&mut self) -> YKeyResult<()> {
+        self.client.bio_enrollment(BioEnrollCommand::CancelCurrentEnrollment).await?;
+        Ok(())
+    }
+
+    /// Run a subcommand unless `cancelled` resolves first
+    async fn run_cancellable<C>(
+        &mut self,
+        command: BioEnrollCommand,
+        cancelled: &mut std::pin::Pin<&mut C>,
+    ) -> YKeyResult<Option<cbor::Value>>
+    where
+        C: Future<Output = ()>,
+    {
+        tokio::select! {
+            result = self.client.bio_enrollment(command) => return result,
+            _ = cancelled.as_mut() => {}
+        }
+
+        // The capture still pending on the transport is abandoned first
+        let _ = self.client.device_mut().cancel().await;
+        self.cancel_enrollment().await?;
+        Err(YKeyError::UserCancelled)
+    }
+}
+
+/// templateId of an enrollBegin response
+fn parse_template_id(response: &cbor::Value) -> YKeyResult<Vec<u8>> {
+    cbor::get_int(cbor::as_map(response)?, 0x04)
+        .ok_or_else(|| YKeyError::communication("Missing templateId"))
+        .and_then(cbor::to_bytes)
+}
+
+fn parse_sample(response: &cbor::Value) -> YKeyResult<EnrollSample> {
+    let map = cbor::as_map(response)?;
+    let field = |key: i64, name: &str| {
+        cbor::get_int(map, key)
+            .ok_or_else(|| YKeyError::communication(format!("Missing {}", name)))
+            .and_then(cbor::to_u64)
+    };
+    Ok(EnrollSample {
+        status: field(0x05, "lastEnrollSampleStatus")? as u8,
+        remaining_samples: field(0x06, "remainingSamples")? as u32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use tokio::sync::oneshot;
+    use ykey_core::types::*;
+
+    /// Replays canned responses; runs out into a capture that never completes
+    struct ScriptedDevice {
+        responses: VecDeque<Vec<u8>>,
+        requests: Vec<Vec<u8>>,
+        cancels: usize,
+    }
+
+    #[async_trait::async_trait]
+    impl Device for ScriptedDevice {
+        async fn info(&self) -> YKeyResult<DeviceInfo> {
+            Ok(DeviceInfo::new(
+                "bio".to_string(),
+                "Bio".to_string(),
+                "Yubico".to_string(),
+                "YubiKey Bio".to_string(),
+                0x1050,
+                0x0402,
+                DeviceType::YubiKey,
+                TransportType::Usb,
+            ))
+        }
+
+        async fn connect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        async fn disconnect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        fn is_connected(&self) -> bool {
+            true
+        }
+
+        async fn send_raw(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+            self.requests.push(data.to_vec());
+            match self.responses.pop_front() {
+                Some(response) => Ok(response),
+                None => std::future::pending().await,
+            }
+        }
+
+        async fn cancel(&mut self) -> YKeyResult<()> {
+            self.cancels += 1;
+            // Once cancelled, the authenticator answers the next request
+            self.responses.push_back(vec![0x00]);
+            Ok(())
+        }
+    }
+
+    fn sample_response(template_id: Option<&[u8]>, remaining: i64) -> Vec<u8> {
+        let mut data = vec![0x00];
+        data.extend(
+            cbor::encode(&cbor::int_map(vec![
+                (0x04, template_id.map(cbor::bytes)),
+                (0x05, Some(cbor::int(0))),
+                (0x06, Some(cbor::int(remaining))),
+            ]))
+            .unwrap(),
+        );
+        data
+    }
+
+    /// Client holding a protocol two token, replaying the given responses
+    fn enroll_client(responses: Vec<Vec<u8>>) -> BioEnrollClient<ScriptedDevice> {
+        let mut client = Fido2Client::new(ScriptedDevice {
+            responses: responses.into(),
+            requests: Vec::new(),
+            cancels: 0,
+        });
+        client.pin_token = Some(vec![0x11; 32]);
+        client.pin_protocol_version = Some(2);
+        client.pin_permissions = Permissions::BIO_ENROLLMENT;
+        BioEnrollClient { client, sample_timeout: None }
+    }
+
+    fn subcommand(request: &[u8]) -> cbor::Value {
+        assert_eq!(request[0], BIO_ENROLLMENT_COMMAND);
+        let map = cbor::decode(&request[1..], false).unwrap();
+        cbor::get_int(cbor::as_map(&map).unwrap(), 0x02).unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_enroll_until_no_samples_remain() {
+        let mut client = enroll_client(vec![
+            sample_response(Some(&[0x07, 0x01]), 2),
+            sample_response(None, 1),
+            sample_response(None, 0),
+        ]);
+
+        let mut progress = Vec::new();
+        let template_id = client
+            .enroll(std::future::pending(), |sample| progress.push(sample.remaining_samples))
+            .await
+            .unwrap();
+
+        assert_eq!(template_id, vec![0x07, 0x01]);
+        assert_eq!(progress, vec![2, 1, 0]);
+        let subcommands: Vec<_> = client.client().device().requests.iter().map(|r| subcommand(r)).collect();
+        assert_eq!(subcommands, vec![cbor::int(0x01), cbor::int(0x02), cbor::int(0x02)]);
+    }
+
+    fn response(entries: Vec<(i64, Option<cbor::Value>)>) -> Vec<u8> {
+        let mut data = vec![0x00];
+        data.extend(cbor::encode(&cbor::int_map(entries)).unwrap());
+        data
+    }
+
+    fn sub_params(request: &[u8]) -> Option<cbor::Value> {
+        let map = cbor::decode(&request[1..], false).unwrap();
+        cbor::get_int(cbor::as_map(&map).unwrap(), 0x03).cloned()
+    }
+
+    #[tokio::test]
+    async fn test_step_by_step_enrollment_reports_sample_status() {
+        let mut client = enroll_client(vec![
+            response(vec![(0x02, Some(cbor::int(1))), (0x03, Some(cbor::int(3))), (0x08, Some(cbor::int(15)))]),
+            response(vec![
+                (0x04, Some(cbor::bytes(&[0x0A]))),
+                (0x05, Some(cbor::int(0x00))),
+                (0x06, Some(cbor::int(2))),
+            ]),
+            response(vec![(0x05, Some(cbor::int(0x05))), (0x06, Some(cbor::int(2)))]),
+            response(vec![(0x05, Some(cbor::int(0x00))), (0x06, Some(cbor::int(1)))]),
+            response(vec![(0x05, Some(cbor::int(0x00))), (0x06, Some(cbor::int(0)))]),
+        ]);
+
+        let info = client.sensor_info().await.unwrap();
+        assert_eq!(info.fingerprint_kind, 1);
+        assert_eq!(info.max_capture_samples_required_for_enroll, 3);
+        assert_eq!(info.max_template_friendly_name, Some(15));
+
+        let (template_id, first) = client.begin_enrollment().await.unwrap();
+        assert_eq!(template_id, [0x0A]);
+        assert_eq!(first, EnrollSample { status: 0x00, remaining_samples: 2 });
+
+        // A rejected sample leaves the remaining count unchanged
+        let rejected = client.capture_next_sample(&template_id).await.unwrap();
+        assert!(!rejected.is_good());
+        assert_eq!(rejected.feedback(), "Finger moved too fast");
+        assert_eq!(rejected.remaining_samples, 2);
+        assert_eq!(client.capture_next_sample(&template_id).await.unwrap().remaining_samples, 1);
+        assert_eq!(client.capture_next_sample(&template_id).await.unwrap().remaining_samples, 0);
+
+        let requests = &client.client().device().requests;
+        let subcommands: Vec<_> = requests.iter().map(|r| subcommand(r)).collect();
+        assert_eq!(subcommands, [0x07, 0x01, 0x02, 0x02, 0x02].map(cbor::int).to_vec());
+        // Sensor info is read without a pinUvAuthParam
+        let sensor_request = cbor::decode(&requests[0][1..], false).unwrap();
+        assert_eq!(cbor::get_int(cbor::as_map(&sensor_request).unwrap(), 0x05), None);
+        assert_eq!(
+            sub_params(&requests[2]),
+            Some(cbor::int_map(vec![(0x01, Some(cbor::bytes(&[0x0A])))]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manage_enrollments() {
+        let template_info = |id: u8, name: Option<&str>| {
+            cbor::int_map(vec![(0x01, Some(cbor::bytes(&[id]))), (0x02, name.map(cbor::text))])
+        };
+        let mut client = enroll_client(vec![
+            response(vec![(
+                0x07,
+                Some(cbor::Value::Array(vec![template_info(0x0A, Some("Right thumb")), template_info(0x0B, None)])),
+            )]),
+            vec![0x00],
+            vec![0x00],
+            vec![CTAP2_ERR_INVALID_OPTION],
+        ]);
+
+        let enrollments = client.enumerate_enrollments().await.unwrap();
+        assert_eq!(
+            enrollments,
+            [
+                TemplateInfo { template_id: vec![0x0A], friendly_name: Some("Right thumb".to_string()) },
+                TemplateInfo { template_id: vec![0x0B], friendly_name: None },
+            ]
+        );
+
+        client.set_friendly_name(&[0x0B], "Left index").await.unwrap();
+        client.remove_enrollment(&[0x0A]).await.unwrap();
+        assert!(client.enumerate_enrollments().await.unwrap().is_empty());
+
+        let requests = &client.client().device().requests;
+        assert_eq!(subcommand(&requests[1]), cbor::int(0x05));
+        assert_eq!(
+            sub_params(&requests[1]),
+            Some(cbor::int_map(vec![
+                (0x01, Some(cbor::bytes(&[0x0B]))),
+                (0x02, Some(cbor::text("Left index"))),
+            ]))
+        );
+        assert_eq!(subcommand(&requests[2]), cbor::int(0x06));
+    }
+
+    #[tokio::test]
+    async fn test_cancelling_enrollment_sends_cancel_subcommand() {
+        let mut client = enroll_client(vec![sample_response(Some(&[0x07, 0x01]), 3)]);
+        let (cancel, cancelled) = oneshot::channel::<()>();
+
+        let enrollment = client.enroll(
+            async move {
+                let _ = cancelled.await;
+            },
+            |_| {},
+        );
+        let (result, _) = tokio::join!(enrollment, async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            // Dropping the sender cancels like sending would
+            drop(cancel);
+        });
+
+        assert!(matches!(result, Err(YKeyError::UserCancelled)));
+        let device = client.client().device();
+        assert_eq!(device.cancels, 1);
+        let requests = &device.requests;
+        assert_eq!(requests.len(), 3);
+        assert_eq!(subcommand(&requests[1]), cbor::int(0x02));
+        assert_eq!(subcommand(&requests[2]), cbor::int(0x03));
+
+        // cancelCurrentEnrollment carries no pinUvAuthParam
+        let cancel_request = cbor::decode(&requests[2][1..], false).unwrap();
+        assert_eq!(cbor::get_int(cbor::as_map(&cancel_request).unwrap(), 0x05), None);
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! CBOR helpers for CTAP2 message encoding and decoding
+//!
+//! CTAP2 messages are CBOR maps keyed by small integers. These helpers keep
+//! the command and response code free of repetitive `ciborium::Value` matching.
+
+pub use ciborium::Value;
+use ykey_core::{YKeyResult, YKeyError};
+
+/// Encode a CBOR value to bytes
+pub fn encode(value: &Value) -> YKeyResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    ciborium::ser::into_writer(value, &mut bytes)
+        .map_err(|e| YKeyError::InvalidParameters(format!("CBOR encoding failed: {}", e)))?;
+    Ok(bytes)
+}
+
+/// Bounds on the structure of decoded CBOR
+///
+/// Checked before decoding so a hostile authenticator cannot exhaust the stack
+/// with deep nesting or make the decoder allocate for a huge declared length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Deepest nesting of arrays, maps and tags
+    pub max_depth: usize,
+    /// Most entries in a single array or map
+    pub max_collection_len: u64,
+}
+
+impl Default for DecodeLimits {
+    /// Far beyond any legitimate CTAP response, which nests a handful of levels
+    fn default() -> Self {
+        Self { max_depth: 32, max_collection_len: 4096 }
+    }
+}
+
+/// Decode a response body consisting of a single CBOR data item
+///
+/// Trailing bytes after the item are rejected unless `lenient` is set.
+pub fn decode(body: &[u8], lenient: bool) -> YKeyResult<Value> {
+    decode_with_limits(body, lenient, DecodeLimits::default())
+}
+
+/// Decode a single CBOR data item, enforcing the given structural limits
+pub fn decode_with_limits(body: &[u8], lenient: bool, limits: DecodeLimits) -> YKeyResult<Value> {
+    check_limits(body, limits)?;
+
+    let mut reader = body;
+    let value = ciborium::de::from_reader::<Value, _>(&mut reader)
+        .map_err(|e| YKeyError::communication(format!("Invalid CBOR response: {}", e)))?;
+
+    if !reader.is_empty() && !lenient {
+        return Err(YKeyError::communication(format!(
+            "Unexpected {} trailing bytes after CBOR response",
+            reader.len()
+        )));
+    }
+
+    Ok(value)
+}
+
+/// Collection still being read by [`check_limits`]
+struct OpenCollection {
+    /// Items left, or `None` until the break of an indefinite-length item
+    remaining: Option<u64>,
+    /// Items read so far
+    seen: u64,
+    /// Most items allowed, counting keys and values separately
+    max_items: u64,
+}
+
+/// Walk the first data item without recursing, enforcing `limits`
+///
+/// Malformed input ends the walk early and is left for the decoder to report.
+fn check_limits(body: &[u8], limits: DecodeLimits) -> YKeyResult<()> {
+    let too_large = || YKeyError::InvalidParameters("CBOR too large".to_string());
+    let mut open: Vec<OpenCollection> = Vec::new();
+    let mut rest = body;
+
+    while let Some((&initial, tail)) = rest.split_first() {
+        let (major, info) = (initial >> 5, initial & 0x1F);
+        let Some((argument, tail)) = read_argument(info, tail) else {
+            return Ok(());
+        };
+        rest = tail;
+
+        let max_items = if major == 5 { limits.max_collection_len * 2 } else { limits.max_collection_len };
+        let children = match (major, info) {
+            // Indefinite-length strings, arrays and maps run until a break
+            (2..=5, 31) => Some(None),
+            (2 | 3, _) => {
+                let Some(tail) = usize::try_from(argument).ok().and_then(|len| rest.get(len..)) else {
+                    return Ok(());
+                };
+                rest = tail;
+                None
+            }
+            (4 | 5, _) if argument > limits.max_collection_len => return Err(too_large()),
+            (4, _) => Some(Some(argument)),
+            (5, _) => Some(Some(argument * 2)),
+            (6, _) => Some(Some(1)),
+            (7, 31) => match open.pop() {
+                Some(OpenCollection { remaining: None, .. }) => None,
+                _ => return Ok(()),
+            },
+            _ => None,
+        };
+
+        match children {
+            Some(remaining) if remaining != Some(0) => {
+                open.push(OpenCollection { remaining, seen: 0, max_items });
+                if open.len() > limits.max_depth {
+                    return Err(YKeyError::InvalidParameters("CBOR too deep".to_string()));
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        // An item is complete; close every collection it completes in turn
+        loop {
+            let Some(parent) = open.last_mut() else {
+                return Ok(());
+            };
+            parent.seen += 1;
+            match &mut parent.remaining {
+                Some(remaining) => {
+                    *remaining -= 1;
+                    if *remaining > 0 {
+                        break;
+                    }
+                    open.pop();
+                }
+                None => {
+                    if parent.seen > parent.max_items {
+                        return Err(too_large());
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read the argument following an initial byte, or `None` if malformed
+fn read_argument(info: u8, data: &[u8]) -> Option<(u64, &[u8])> {
+    let width = match info {
+        0..=23 => return Some((info as u64, data)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        31 => return Some((0, data)),
+        _ => return None,
+    };
+    let (bytes, rest) = (data.get(..width)?, &data[width..]);
+    let mut argument = [0u8; 8];
+    argument[8 - width..].copy_from_slice(bytes);
+    Some((u64::from_be_bytes(argument), rest))
+}
+
+/// Build an integer CBOR value
+pub fn int(value: i64) -> Value {
+    Value::Integer(value.into())
+}
+
+/// Build a text CBOR value
+pub fn text(value: &str) -> Value {
+    Value::Text(value.to_string())
+}
+
+/// Build a byte string CBOR value
+pub fn bytes(value: &[u8]) -> Value {
+    Value::Bytes(value.to_vec())
+}
+
+/// Build a CBOR map keyed by integers, skipping absent entries
+pub fn int_map(entries: Vec<(i64, Option<Value>)>) -> Value {
+    Value::Map(
+        entries
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|v| (int(key), v)))
+            .collect(),
+    )
+}
+
+/// Convert a JSON value (as used for extensions) into CBOR
+pub fn from_json(value: &serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Bool(*b),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => int(i),
+            (None, Some(u)) => Value::Integer(u.into()),
+            _ => Value::Float(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => text(s),
+        serde_json::Value::Array(items) => Value::Array(items.iter().map(from_json).collect()),
+        serde_json::Value::Object(map) => Value::Map(
+            map.iter().map(|(k, v)| (text(k), from_json(v))).collect(),
+        ),
+    }
+}
+
+/// Convert a CBOR value into JSON, for loosely typed fields
+pub fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Bool(b) => serde_json::Value::Bool(*b),
+        Value::Integer(i) => match i64::try_from(*i) {
+            Ok(i) => i.into(),
+            Err(_) => u64::try_from(*i).map(Into::into).unwrap_or(serde_json::Value::Null),
+        },
+        Value::Float(f) => serde_json::Number::from_f64(*f)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Value::Text(t) => serde_json::Value::String(t.clone()),
+        Value::Bytes(b) => serde_json::Value::Array(b.iter().map(|&byte| byte.into()).collect()),
+        Value::Array(items) => serde_json::Value::Array(items.iter().map(to_json).collect()),
+        Value::Map(entries) => serde_json::Value::Object(
+            entries
+                .iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        Value::Text(t) => t.clone(),
+                        other => to_json(other).to_string(),
+                    };
+                    (key, to_json(v))
+                })
+                .collect(),
+        ),
+        Value::Tag(_, inner) => to_json(inner),
+        _ => serde_json::Value::Null,
+    }
+}
+
+/// View a value as a map
+pub fn as_map(value: &Value) -> YKeyResult<&[(Value, Value)]> {
+    match value {
+        Value::Map(entries) => Ok(entries),
+        _ => Err(YKeyError::communication("Expected CBOR map")),
+    }
+}
+
+/// Look up an integer key in a CBOR map
+pub fn get_int(map: &[(Value, Value)], key: i64) -> Option<&Value> {
+    map.iter()
+        .find(|(k, _)| matches!(k, Value::Integer(i) if i128::from(*i) == key as i128))
+        .map(|(_, v)| v)
+}
+
+/// Look up a text key in a CBOR map
+pub fn get_text<'a>(map: &'a [(Value, Value)], key: &str) -> Option<&'a Value> {
+    map.iter()
+        .find(|(k, _)| matches!(k, Value::Text(t) if t == key))
+        .map(|(_, v)| v)
+}
+
+/// Extract a byte string
+pub fn to_bytes(value: &Value) -> YKeyResult<Vec<u8>> {
+    match value {
+        Value::Bytes(b) => Ok(b.clone()),
+        _ => Err(YKeyError::communication("Expected CBOR byte string")),
+    }
+}
+
+/// Extract a text string
+pub fn to_text(value: &Value) -> YKeyResult<String> {
+    match value {
+        Value::Text(t) => Ok(t.clone()),
+        _ => Err(YKeyError::communication("Expected CBOR text string")),
+    }
+}
+
+/// Extract an unsigned integer
+pub fn to_u64(value: &Value) -> YKeyResult<u64> {
+    match value {
+        Value::Integer(i) => u64::try_from(*i)
+            .map_err(|_| YKeyError::communication("Expected unsigned CBOR integer")),
+        _ => Err(YKeyError::communication("Expected CBOR integer")),
+    }
+}
+
+/// Extract a signed integer
+pub fn to_i64(value: &Value) -> YKeyResult<i64> {
+    match value {
+        Value::Integer(i) => i64::try_from(*i)
+            .map_err(|_| YKeyError::communication("CBOR integer out of range")),
+        _ => Err(YKeyError::communication("Expected CBOR integer")),
+    }
+}
+
+/// Extract a boolean
+pub fn to_bool(value: &Value) -> YKeyResult<bool> {
+    match value {
+        Value::Bool(b) => Ok(*b),
+        _ => Err(YKeyError::communication("Expected CBOR boolean")),
+    }
+}
+
+/// Extract an array
+pub fn to_array(value: &Value) -> YKeyResult<&[Value]> {
+    match value {
+        Value::Array(items) => Ok(items),
+        _ => Err(YKeyError::communication("Expected CBOR array")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int_map_round_trip() {
+        let value = int_map(vec![
+            (1, Some(text("FIDO_2_0"))),
+            (2, None),
+            (3, Some(bytes(&[1, 2, 3]))),
+        ]);
+
+        let encoded = encode(&value).unwrap();
+        let decoded = decode(&encoded, false).unwrap();
+        let map = as_map(&decoded).unwrap();
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(to_text(get_int(map, 1).unwrap()).unwrap(), "FIDO_2_0");
+        assert!(get_int(map, 2).is_none());
+        assert_eq!(to_bytes(get_int(map, 3).unwrap()).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_decode_trailing_bytes() {
+        let mut encoded = encode(&int(5)).unwrap();
+        encoded.push(0x00);
+
+        assert!(decode(&encoded, false).is_err());
+        assert_eq!(to_u64(&decode(&encoded, true).unwrap()).unwrap(), 5);
+    }
+
+    fn is_limit_error(result: YKeyResult<Value>, message: &str) -> bool {
+        matches!(result, Err(YKeyError::InvalidParameters(m)) if m == message)
+    }
+
+    #[test]
+    fn test_decode_rejects_pathological_nesting() {
+        // A million nested single-element arrays, definite and indefinite
+        let mut nested = vec![0x81; 1_000_000];
+        nested.push(0x00);
+        assert!(is_limit_error(decode(&nested, false), "CBOR too deep"));
+        let mut nested = vec![0x9F; 1_000_000];
+        nested.push(0xFF);
+        assert!(is_limit_error(decode(&nested, false), "CBOR too deep"));
+
+        // Tags nest as well
+        let tags = [vec![0xC6; 100], vec![0x00]].concat();
+        assert!(is_limit_error(decode(&tags, false), "CBOR too deep"));
+
+        // Ordinary nesting within the limit still decodes
+        let shallow = [vec![0x81; 10], vec![0x00]].concat();
+        assert!(decode(&shallow, false).is_ok());
+        let limits = DecodeLimits { max_depth: 5, ..DecodeLimits::default() };
+        assert!(is_limit_error(decode_with_limits(&shallow, false, limits), "CBOR too deep"));
+    }
+
+    #[test]
+    fn test_decode_rejects_huge_collections() {
+        // A map claiming four billion entries
+        assert!(is_limit_error(decode(&[0xBA, 0xFF, 0xFF, 0xFF, 0xFF], false), "CBOR too large"));
+
+        let limits = DecodeLimits { max_collection_len: 2, ..DecodeLimits::default() };
+        let array = encode(&Value::Array(vec![int(1), int(2), int(3)])).unwrap();
+        assert!(is_limit_error(decode_with_limits(&array, false, limits), "CBOR too large"));
+        let indefinite = [0x9F, 0x01, 0x02, 0x03, 0xFF];
+        assert!(is_limit_error(decode_with_limits(&indefinite, false, limits), "CBOR too large"));
+        assert_eq!(to_array(&decode(&indefinite, false).unwrap()).unwrap().len(), 3);
+
+        // Truncated input is still reported by the decoder
+        assert!(matches!(decode(&[0x82, 0x01], false), Err(YKeyError::CommunicationError(_))));
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! WebAuthn client data
+//!
+//! Builds the CollectedClientData JSON a platform signs over and hashes it
+//! into the clientDataHash passed to the authenticator.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use ykey_core::{YKeyError, YKeyResult};
+
+/// Hash algorithm used to compute the client data hash
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum HashAlg {
+    /// SHA-256, as WebAuthn and CTAP2 require
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlg {
+    /// Digest length in bytes
+    pub fn output_len(self) -> usize {
+        match self {
+            HashAlg::Sha256 => 32,
+            HashAlg::Sha384 => 48,
+            HashAlg::Sha512 => 64,
+        }
+    }
+
+    /// Hash the given data
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlg::Sha256 => Sha256::digest(data).to_vec(),
+            HashAlg::Sha384 => Sha384::digest(data).to_vec(),
+            HashAlg::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+/// Ceremony the client data belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientDataType {
+    /// Registration (`webauthn.create`)
+    Create,
+    /// Authentication (`webauthn.get`)
+    Get,
+}
+
+impl ClientDataType {
+    fn as_str(self) -> &'static str {
+        match self {
+            ClientDataType::Create => "webauthn.create",
+            ClientDataType::Get => "webauthn.get",
+        }
+    }
+}
+
+/// Collected client data for a WebAuthn ceremony
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientData {
+    /// Ceremony type
+    pub kind: ClientDataType,
+    /// Relying party challenge
+    pub challenge: Vec<u8>,
+    /// Origin of the caller
+    pub origin: String,
+    /// Whether the caller is in a cross-origin iframe
+    pub cross_origin: bool,
+    /// Algorithm used by `hash`
+    pub hash_alg: HashAlg,
+}
+
+impl ClientData {
+    /// Client data for a registration ceremony
+    pub fn create(challenge: Vec<u8>, origin: impl Into<String>) -> Self {
+        Self::new(ClientDataType::Create, challenge, origin)
+    }
+
+    /// Client data for an authentication ceremony
+    pub fn get(challenge: Vec<u8>, origin: impl Into<String>) -> Self {
+        Self::new(ClientDataType::Get, challenge, origin)
+    }
+
+    pub(crate) fn new(kind: ClientDataType, challenge: Vec<u8>, origin: impl Into<String>) -> Self {
+        Self {
+            kind,
+            challenge,
+            origin: origin.into(),
+            cross_origin: false,
+            hash_alg: HashAlg::default(),
+        }
+    }
+
+    /// Use a different hash algorithm
+    pub fn with_hash_alg(mut self, hash_alg: HashAlg) -> Self {
+        self.hash_alg = hash_alg;
+        self
+    }
+
+    /// Serialize in the WebAuthn limited-verification member order
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"type\":{},\"challenge\":{},\"origin\":{},\"crossOrigin\":{}}}",
+            serde_json::Value::from(self.kind.as_str()),
+            serde_json::Value::from(URL_SAFE_NO_PAD.encode(&self.challenge)),
+            serde_json::Value::from(self.origin.as_str()),
+            self.cross_origin,
+        )
+    }
+
+    /// Hash of the serialized client data
+    pub fn hash(&self) -> Vec<u8> {
+        self.hash_alg.digest(self.to_json().as_bytes())
+    }
+}
+
+/// Origins the client is willing to build client data for
+///
+/// Guards embedded browsers against signing for a page that navigated
+/// somewhere unexpected. An origin is allowed when it equals one of the
+/// exact origins, or when it is an `https` origin whose host is one of the
+/// registrable domains or a subdomain of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OriginAllowList {
+    origins: Vec<String>,
+    domains: Vec<String>,
+}
+
+impl OriginAllowList {
+    /// Create an allow-list that allows nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow an exact origin, e.g. `https://example.com`
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origins.push(origin.into().trim_end_matches('/').to_ascii_lowercase());
+        self
+    }
+
+    /// Allow `https` origins on a registrable domain and its subdomains
+    pub fn allow_domain(mut self, domain: impl Into<String>) -> Self {
+        self.domains.push(domain.into().trim_matches('.').to_ascii_lowercase());
+        self
+    }
+
+    /// Whether client data may be built for the origin
+    pub fn is_allowed(&self, origin: &str) -> bool {
+        let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+        if self.origins.contains(&origin) {
+            return true;
+        }
+
+        let Some(host) = origin.strip_prefix("https://").and_then(|rest| rest.split(':').next()) else {
+            return false;
+        };
+        self.domains.iter().any(|domain| {
+            host == domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+
+    /// Fail with `OperationNotAllowed` unless the origin is allowed
+    pub fn check(&self, origin: &str) -> YKeyResult<()> {
+        if self.is_allowed(origin) {
+            Ok(())
+        } else {
+            Err(YKeyError::OperationNotAllowed(format!("origin {} is not on the allow-list", origin)))
+        }
+    }
+
+    /// Client data for a ceremony, if the origin is allowed
+    pub fn client_data(
+        &self,
+        kind: ClientDataType,
+        challenge: Vec<u8>,
+        origin: impl Into<String>,
+    ) -> YKeyResult<ClientData> {
+        let origin = origin.into();
+        self.check(&origin)?;
+        Ok(ClientData::new(kind, challenge, origin))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ykey_core::types::*;
+
+    fn params_with_hash(hash: Vec<u8>) -> ykey_core::YKeyResult<MakeCredentialParams> {
+        MakeCredentialParams::new(
+            hash,
+            RelyingParty { id: "example.com".to_string(), name: None, icon: None },
+            User {
+                id: vec![1],
+                name: "alice".to_string(),
+                display_name: "Alice".to_string(),
+                icon: None,
+            },
+            vec![PublicKeyCredentialParameter { cred_type: "public-key".to_string(), alg: -7 }],
+        )
+    }
+
+    #[test]
+    fn test_client_data_json() {
+        let data = ClientData::create(vec![0xFB, 0xFF], "https://example.com");
+        assert_eq!(
+            data.to_json(),
+            r#"{"type":"webauthn.create","challenge":"-_8","origin":"https://example.com","crossOrigin":false}"#
+        );
+    }
+
+    /// clientDataJSON of the WebAuthn Level 3 test vector "ES256 Credential
+    /// with No Attestation", up to the `extraData` member the spec appends to
+    /// show that clients may add fields
+    const SPEC_CLIENT_DATA: &str = r#"{"type":"webauthn.create","challenge":"AMMPt4UxxGTStncdq417YDwBFi8vpIa-pw8oOuVW4TA","origin":"https://example.org","crossOrigin":false"#;
+
+    #[test]
+    fn test_client_data_matches_webauthn_test_vector() {
+        let challenge = hex::decode("00c30fb78531c464d2b6771dab8d7b603c01162f2fa486bea70f283ae556e130").unwrap();
+        let mut data = ClientData::create(challenge, "https://example.org");
+        let expected = format!("{}}}", SPEC_CLIENT_DATA);
+        assert_eq!(data.to_json(), expected);
+        assert_eq!(data.hash(), Sha256::digest(expected.as_bytes()).to_vec());
+
+        data.cross_origin = true;
+        assert!(data.to_json().ends_with(r#""crossOrigin":true}"#));
+        assert_ne!(data.hash(), Sha256::digest(expected.as_bytes()).to_vec());
+    }
+
+    #[test]
+    fn test_default_hash_is_sha256() {
+        let data = ClientData::get(vec![1, 2, 3], "https://example.com");
+        let hash = data.hash();
+
+        assert_eq!(data.hash_alg, HashAlg::Sha256);
+        assert_eq!(hash.len(), 32);
+        assert_eq!(hash, Sha256::digest(data.to_json().as_bytes()).to_vec());
+        assert!(params_with_hash(hash).is_ok());
+    }
+
+    #[test]
+    fn test_mismatched_hash_length_rejected() {
+        let data = ClientData::create(vec![1, 2, 3], "https://example.com").with_hash_alg(HashAlg::Sha512);
+        let hash = data.hash();
+
+        assert_eq!(hash.len(), HashAlg::Sha512.output_len());
+        assert!(matches!(params_with_hash(hash), Err(YKeyError::InvalidParameters(_))));
+    }
+
+    #[test]
+    fn test_origin_allow_list() {
+        let allow_list = OriginAllowList::new()
+            .allow_origin("https://login.example.com")
+            .allow_domain("example.org");
+
+        let data = allow_list
+            .client_data(ClientDataType::Get, vec![1, 2, 3], "https://login.example.com")
+            .unwrap();
+        assert_eq!(data.origin, "https://login.example.com");
+
+        assert!(allow_list.is_allowed("https://example.org"));
+        assert!(allow_list.is_allowed("https://accounts.example.org:8443"));
+        assert!(!allow_list.is_allowed("http://example.org"));
+        assert!(!allow_list.is_allowed("https://evilexample.org"));
+        assert!(!allow_list.is_allowed("https://example.com"));
+
+        let result = allow_list.client_data(ClientDataType::Create, vec![1, 2, 3], "https://example.org.evil.com");
+        assert!(matches!(result, Err(YKeyError::OperationNotAllowed(_))));
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Authenticator configuration
+//!
+//! Wraps the CTAP 2.1 authenticatorConfig command. Every subcommand is
+//! authenticated with a protocol two pinUvAuthToken carrying the
+//! authenticator configuration permission, over 32 bytes of 0xFF, the
+//! command byte, the subcommand and its encoded parameters.
+
+use crate::{cbor, pin::{Permissions, PinUvAuthProtocol}, Fido2Client};
+use ykey_core::{traits::Device, YKeyError, YKeyResult};
+
+/// authenticatorConfig command byte
+pub const AUTHENTICATOR_CONFIG_COMMAND: u8 = 0x0D;
+
+/// authenticatorConfig subcommands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigCommand {
+    EnableEnterpriseAttestation,
+    ToggleAlwaysUv,
+    SetMinPinLength {
+        /// New minimum PIN length, if raising it
+        new_min_pin_length: Option<u64>,
+        /// Relying parties allowed to read the minimum PIN length
+        rp_ids: Vec<String>,
+        /// Require the PIN to be changed before its next use
+        force_change_pin: bool,
+    },
+}
+
+impl ConfigCommand {
+    /// Subcommand number as sent on the wire
+    pub fn subcommand(&self) -> u8 {
+        match self {
+            ConfigCommand::EnableEnterpriseAttestation => 0x01,
+            ConfigCommand::ToggleAlwaysUv => 0x02,
+            ConfigCommand::SetMinPinLength { .. } => 0x03,
+        }
+    }
+
+    /// Encode the subCommandParams map, if the subcommand takes one
+    pub fn params(&self) -> Option<cbor::Value> {
+        match self {
+            ConfigCommand::SetMinPinLength { new_min_pin_length, rp_ids, force_change_pin } => {
+                Some(cbor::int_map(vec![
+                    (0x01, new_min_pin_length.map(|length| cbor::int(length as i64))),
+                    (0x02, (!rp_ids.is_empty()).then(|| {
+                        cbor::Value::Array(rp_ids.iter().map(|id| cbor::text(id)).collect())
+                    })),
+                    (0x03, force_change_pin.then(|| cbor::Value::Bool(true))),
+                ]))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<D: Device> Fido2Client<D> {
+    /// Acquire a pinUvAuthToken with the authenticator configuration permission
+    pub async fn unlock_authenticator_config(&mut self, pin: &str) -> YKeyResult<()> {
+        self.get_pin_uv_auth_token_with_permissions(pin, Permissions::AUTHENTICATOR_CONFIG, None).await?;
+        Ok(())
+    }
+
+    /// Raise the minimum PIN length and choose which RPs may read it
+    ///
+    /// The length can only grow, and no more RP IDs are accepted than the
+    /// authenticator advertises in maxRPIDsForSetMinPINLength. With
+    /// `force_change` the PIN must be changed before it is used again.
+    pub async fn set_min_pin_length(
+        &mut self,
+        length: Option<u64>,
+        rp_ids: &[&str],
+        force_change: bool,
+    ) -> YKeyResult<()> {
+        let info = self.authenticator_info().await?;
+        if let (Some(length), Some(current)) = (length, info.min_pin_length) {
+            if length < current {
+                return Err(YKeyError::InvalidParameters(format!(
+                    "Minimum PIN length cannot be lowered from {} to {}",
+                    current, length
+                )));
+            }
+        }
+        let max_rp_ids = info.max_rp_ids_for_set_min_pin_length.unwrap_or(0);
+        if rp_ids.len() as u64 > max_rp_ids {
+            return Err(YKeyError::InvalidParameters(format!(
+                "Authenticator accepts at most {} RP IDs for setMinPINLength, got {}",
+                max_rp_ids,
+                rp_ids.len()
+            )));
+        }
+
+        self.authenticator_config(ConfigCommand::SetMinPinLength {
+            new_min_pin_length: length,
+            rp_ids: rp_ids.iter().map(|id| id.to_string()).collect(),
+            force_change_pin: force_change,
+        })
+        .await
+    }
+
+    /// Turn the alwaysUv option on or off, whichever it is not
+    pub async fn toggle_always_uv(&mut self) -> YKeyResult<()> {
+        self.authenticator_config(ConfigCommand::ToggleAlwaysUv).await
+    }
+
+    /// Allow enterprise attestation to be requested on makeCredential
+    pub async fn enable_enterprise_attestation(&mut self) -> YKeyResult<()> {
+        self.authenticator_config(ConfigCommand::EnableEnterpriseAttestation).await
+    }
+
+    /// Send a configuration subcommand, authenticated with the stored token
+    pub async fn authenticator_config(&mut self, command: ConfigCommand) -> YKeyResult<()> {
+        let token = self.permitted_pin_token(Permissions::AUTHENTICATOR_CONFIG)?;
+        if token.protocol() != PinUvAuthProtocol::Two {
+            return Err(YKeyError::UnsupportedProtocolVersion(
+                "authenticatorConfig requires PIN/UV auth protocol 2".to_string(),
+            ));
+        }
+
+        let params = command.params();
+        let mut message = vec![0xFF; 32];
+        message.push(AUTHENTICATOR_CONFIG_COMMAND);
+        message.push(command.subcommand());
+        if let Some(params) = &params {
+            message.extend(cbor::encode(params)?);
+        }
+
+        self.send_cbor(
+            AUTHENTICATOR_CONFIG_COMMAND,
+            Some(cbor::int_map(vec![
+                (0x01, Some(cbor::int(command.subcommand() as i64))),
+                (0x02, params),
+                (0x03, Some(cbor::int(token.protocol().version() as i64))),
+                (0x04, Some(cbor::bytes(&token.authenticate(&message)))),
+            ])),
+        )
+        .await?;
+        // A new alwaysUv or minimum PIN length makes the cached GetInfo stale
+        self.invalidate_info_cache();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pin::PinUvAuthToken, test_support::ReplayDevice};
+    use ykey_core::types::*;
+
+    /// Client holding a token under `version`, with a cached GetInfo
+    fn config_client(version: u8, responses: Vec<Vec<u8>>) -> Fido2Client<ReplayDevice> {
+        let mut client = Fido2Client::new(ReplayDevice::new(responses));
+        client.pin_token = Some(vec![0x11; 32]);
+        client.pin_protocol_version = Some(version);
+        client.pin_permissions = Permissions::AUTHENTICATOR_CONFIG;
+        client.authenticator_info = Some(AuthenticatorInfo {
+            min_pin_length: Some(6),
+            max_rp_ids_for_set_min_pin_length: Some(2),
+            ..Default::default()
+        });
+        client
+    }
+
+    /// Decode a request, checking its pinUvAuthParam over the subcommand bytes
+    fn verified_request(request: &[u8]) -> cbor::Value {
+        assert_eq!(request[0], AUTHENTICATOR_CONFIG_COMMAND);
+        let value = cbor::decode(&request[1..], false).unwrap();
+        let map = cbor::as_map(&value).unwrap();
+        let subcommand = cbor::to_u64(cbor::get_int(map, 0x01).unwrap()).unwrap() as u8;
+        let mut message = [vec![0xFF; 32], vec![AUTHENTICATOR_CONFIG_COMMAND, subcommand]].concat();
+        if let Some(params) = cbor::get_int(map, 0x02) {
+            message.extend(cbor::encode(params).unwrap());
+        }
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::Two, vec![0x11; 32]);
+        assert_eq!(cbor::get_int(map, 0x03), Some(&cbor::int(2)));
+        assert_eq!(cbor::get_int(map, 0x04), Some(&cbor::bytes(&token.authenticate(&message))));
+        value
+    }
+
+    #[tokio::test]
+    async fn test_config_requests_are_authenticated() {
+        let mut client = config_client(2, vec![vec![0x00], vec![0x00], vec![0x00]]);
+
+        client.set_min_pin_length(Some(8), &["example.com"], true).await.unwrap();
+        client.authenticator_info = Some(AuthenticatorInfo::default());
+        client.toggle_always_uv().await.unwrap();
+        client.enable_enterprise_attestation().await.unwrap();
+
+        let requests = &client.device().requests;
+        let set = verified_request(&requests[0]);
+        let set = cbor::as_map(&set).unwrap();
+        assert_eq!(cbor::get_int(set, 0x01), Some(&cbor::int(0x03)));
+        assert_eq!(
+            cbor::get_int(set, 0x02),
+            Some(&cbor::int_map(vec![
+                (0x01, Some(cbor::int(8))),
+                (0x02, Some(cbor::Value::Array(vec![cbor::text("example.com")]))),
+                (0x03, Some(cbor::Value::Bool(true))),
+            ]))
+        );
+
+        let toggle = verified_request(&requests[1]);
+        let toggle = cbor::as_map(&toggle).unwrap();
+        assert_eq!(cbor::get_int(toggle, 0x01), Some(&cbor::int(0x02)));
+        assert_eq!(cbor::get_int(toggle, 0x02), None);
+        let enterprise = verified_request(&requests[2]);
+        assert_eq!(cbor::get_int(cbor::as_map(&enterprise).unwrap(), 0x01), Some(&cbor::int(0x01)));
+    }
+
+    #[tokio::test]
+    async fn test_set_min_pin_length_validation() {
+        let mut client = config_client(2, Vec::new());
+        let too_many = client.set_min_pin_length(Some(8), &["a.com", "b.com", "c.com"], false).await;
+        assert!(matches!(too_many, Err(YKeyError::InvalidParameters(_))));
+        let lowered = client.set_min_pin_length(Some(4), &[], false).await;
+        assert!(matches!(lowered, Err(YKeyError::InvalidParameters(_))));
+        assert!(client.device().requests.is_empty());
+
+        let mut client = config_client(1, Vec::new());
+        assert!(matches!(client.toggle_always_uv().await, Err(YKeyError::UnsupportedProtocolVersion(_))));
+    }
+}
@@ -0,0 +1,482 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Authenticator data and COSE public keys
+//!
+//! Parses the authenticator data returned by MakeCredential and
+//! GetAssertion, including the attested credential data carrying the new
+//! credential's COSE_Key, and verifies signatures made with such keys.
+
+use crate::cbor;
+use ykey_core::{
+    types::{Aaguid, AssertionObject, AttestationObject, Credential},
+    YKeyError, YKeyResult,
+};
+
+/// COSE algorithm: ECDSA w/ SHA-256
+pub const ALG_ES256: i64 = -7;
+/// COSE algorithm: EdDSA
+pub const ALG_EDDSA: i64 = -8;
+/// COSE algorithm: RSASSA-PKCS1-v1_5 w/ SHA-256
+pub const ALG_RS256: i64 = -257;
+
+/// COSE curve: NIST P-256
+pub const CURVE_P256: i64 = 1;
+/// COSE curve: Ed25519
+pub const CURVE_ED25519: i64 = 6;
+
+const KTY_OKP: i64 = 1;
+const KTY_EC2: i64 = 2;
+const KTY_RSA: i64 = 3;
+
+/// Credential public key decoded from a COSE_Key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CosePublicKey {
+    /// Elliptic curve key with x and y coordinates, e.g. ES256 on P-256
+    Ec2 { alg: i64, curve: i64, x: Vec<u8>, y: Vec<u8> },
+    /// RSA key, e.g. RS256
+    Rsa { alg: i64, n: Vec<u8>, e: Vec<u8> },
+    /// Octet key pair, e.g. EdDSA on Ed25519
+    Okp { alg: i64, curve: i64, x: Vec<u8> },
+}
+
+impl CosePublicKey {
+    /// Decode a COSE_Key
+    pub fn from_cbor(value: &cbor::Value) -> YKeyResult<Self> {
+        let map = cbor::as_map(value)?;
+        let int = |key| {
+            cbor::get_int(map, key)
+                .ok_or_else(|| YKeyError::InvalidCredential(format!("COSE key missing parameter {}", key)))
+                .and_then(cbor::to_i64)
+        };
+        let bytes = |key| {
+            cbor::get_int(map, key)
+                .ok_or_else(|| YKeyError::InvalidCredential(format!("COSE key missing parameter {}", key)))
+                .and_then(cbor::to_bytes)
+        };
+
+        let alg = int(3)?;
+        let key = match int(1)? {
+            KTY_EC2 => {
+                let (curve, x, y) = (int(-1)?, bytes(-2)?, bytes(-3)?);
+                if curve == CURVE_P256 && (x.len() != 32 || y.len() != 32) {
+                    return Err(YKeyError::InvalidCredential("Invalid P-256 coordinate length".to_string()));
+                }
+                CosePublicKey::Ec2 { alg, curve, x, y }
+            }
+            KTY_OKP => {
+                let (curve, x) = (int(-1)?, bytes(-2)?);
+                if curve == CURVE_ED25519 && x.len() != 32 {
+                    return Err(YKeyError::InvalidCredential("Invalid Ed25519 key length".to_string()));
+                }
+                CosePublicKey::Okp { alg, curve, x }
+            }
+            KTY_RSA => CosePublicKey::Rsa { alg, n: bytes(-1)?, e: bytes(-2)? },
+            kty => return Err(YKeyError::InvalidCredential(format!("Unsupported COSE key type {}", kty))),
+        };
+        Ok(key)
+    }
+
+    /// Decode an encoded COSE_Key, such as `Credential::public_key`
+    pub fn from_bytes(data: &[u8]) -> YKeyResult<Self> {
+        let mut rest = data;
+        let (value, _) = read_cbor_item(&mut rest)?;
+        Self::from_cbor(&value)
+    }
+
+    /// COSE algorithm identifier
+    pub fn alg(&self) -> i64 {
+        match self {
+            CosePublicKey::Ec2 { alg, .. } | CosePublicKey::Rsa { alg, .. } | CosePublicKey::Okp { alg, .. } => *alg,
+        }
+    }
+}
+
+/// Verify a signature over `message` with a credential public key
+///
+/// Supports ES256, whose signatures are DER encoded, and EdDSA on Ed25519.
+/// A signature that does not match fails with `AuthenticationFailed`.
+pub fn verify_signature(public_key: &CosePublicKey, message: &[u8], signature: &[u8]) -> YKeyResult<()> {
+    let verified = match public_key {
+        CosePublicKey::Ec2 { alg: ALG_ES256, curve: CURVE_P256, x, y } => {
+            use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+
+            let point = p256::EncodedPoint::from_affine_coordinates(x[..].into(), y[..].into(), false);
+            let key = VerifyingKey::from_encoded_point(&point)
+                .map_err(|_| YKeyError::InvalidCredential("Invalid P-256 public key".to_string()))?;
+            Signature::from_der(signature).is_ok_and(|signature| key.verify(message, &signature).is_ok())
+        }
+        CosePublicKey::Okp { alg: ALG_EDDSA, curve: CURVE_ED25519, x } => {
+            use ed25519_dalek::{Signature, VerifyingKey};
+
+            let bytes: &[u8; 32] = x[..].try_into().expect("length checked when decoded");
+            let key = VerifyingKey::from_bytes(bytes)
+                .map_err(|_| YKeyError::InvalidCredential("Invalid Ed25519 public key".to_string()))?;
+            Signature::from_slice(signature).is_ok_and(|signature| key.verify_strict(message, &signature).is_ok())
+        }
+        other => {
+            return Err(YKeyError::unsupported(
+                "Signature verification",
+                format!("COSE algorithm {} is not supported", other.alg()),
+            ))
+        }
+    };
+
+    if verified {
+        Ok(())
+    } else {
+        Err(YKeyError::auth_failed("Signature does not match the credential public key"))
+    }
+}
+
+/// Attested credential data of a newly created credential
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestedCredentialData {
+    /// Authenticator model identifier
+    pub aaguid: Aaguid,
+    /// Credential ID
+    pub credential_id: Vec<u8>,
+    /// Encoded COSE_Key, as stored in `Credential::public_key`
+    pub cose_key: Vec<u8>,
+    /// Decoded public key
+    pub public_key: CosePublicKey,
+}
+
+/// Authenticator data as defined by WebAuthn
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthenticatorData {
+    /// SHA-256 of the relying party ID
+    pub rp_id_hash: [u8; 32],
+    /// Flag bits, see the `FLAG_*` constants
+    pub flags: u8,
+    /// Signature counter
+    pub sign_count: u32,
+    /// Present when `FLAG_ATTESTED_CREDENTIAL_DATA` is set
+    pub attested_credential: Option<AttestedCredentialData>,
+    /// Extension outputs, present when `FLAG_EXTENSION_DATA` is set
+    pub extensions: Option<cbor::Value>,
+}
+
+impl AuthenticatorData {
+    /// User present
+    pub const FLAG_USER_PRESENT: u8 = 0x01;
+    /// User verified
+    pub const FLAG_USER_VERIFIED: u8 = 0x04;
+    /// Credential can be backed up
+    pub const FLAG_BACKUP_ELIGIBLE: u8 = 0x08;
+    /// Credential is currently backed up
+    pub const FLAG_BACKUP_STATE: u8 = 0x10;
+    /// Attested credential data included
+    pub const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+    /// Extension data included
+    pub const FLAG_EXTENSION_DATA: u8 = 0x80;
+
+    /// Parse authenticator data
+    pub fn parse(data: &[u8]) -> YKeyResult<Self> {
+        if data.len() < 37 {
+            return Err(YKeyError::InvalidCredential(format!(
+                "Authenticator data of {} bytes is too short",
+                data.len()
+            )));
+        }
+        let rp_id_hash = data[..32].try_into().expect("length checked");
+        let flags = data[32];
+        let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+        let mut rest = &data[37..];
+
+        let attested_credential = if flags & Self::FLAG_ATTESTED_CREDENTIAL_DATA != 0 {
+            if rest.len() < 18 {
+                return Err(YKeyError::InvalidCredential("Truncated attested credential data".to_string()));
+            }
+            let aaguid = Aaguid::from_slice(&rest[..16])?;
+            let id_len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+            let credential_id = rest
+                .get(18..18 + id_len)
+                .ok_or_else(|| YKeyError::InvalidCredential("Truncated credential ID".to_string()))?
+                .to_vec();
+            rest = &rest[18 + id_len..];
+
+            let (value, cose_key) = read_cbor_item(&mut rest)?;
+            Some(AttestedCredentialData {
+                aaguid,
+                credential_id,
+                cose_key,
+                public_key: CosePublicKey::from_cbor(&value)?,
+            })
+        } else {
+            None
+        };
+
+        let extensions = if flags & Self::FLAG_EXTENSION_DATA != 0 {
+            Some(read_cbor_item(&mut rest)?.0)
+        } else {
+            None
+        };
+        if !rest.is_empty() {
+            return Err(YKeyError::InvalidCredential(format!(
+                "Unexpected {} trailing bytes in authenticator data",
+                rest.len()
+            )));
+        }
+
+        Ok(Self {
+            rp_id_hash,
+            flags,
+            sign_count,
+            attested_credential,
+            extensions,
+        })
+    }
+
+    /// Whether the user was present
+    pub fn user_present(&self) -> bool {
+        self.flags & Self::FLAG_USER_PRESENT != 0
+    }
+
+    /// Whether the user was verified
+    pub fn user_verified(&self) -> bool {
+        self.flags & Self::FLAG_USER_VERIFIED != 0
+    }
+
+    /// Whether the credential may be backed up, e.g. a synced passkey
+    pub fn backup_eligible(&self) -> bool {
+        self.flags & Self::FLAG_BACKUP_ELIGIBLE != 0
+    }
+
+    /// Whether the credential is currently backed up
+    pub fn backed_up(&self) -> bool {
+        self.flags & Self::FLAG_BACKUP_STATE != 0
+    }
+}
+
+/// Transition of a credential's backup state between authentications
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupStateChange {
+    /// A device-bound credential is now backed up
+    BackedUp,
+    /// A backed-up credential is device-bound again
+    NoLongerBackedUp,
+}
+
+/// Compare the stored backup state with the BS flag of a new assertion
+///
+/// Returns `None` when the state is unchanged, was never recorded, or the
+/// assertion's authenticator data cannot be parsed.
+pub fn detect_backup_state_change(stored: &Credential, assertion: &AssertionObject) -> Option<BackupStateChange> {
+    let previous = stored.backup_state?;
+    let current = AuthenticatorData::parse(&assertion.auth_data).ok()?.backed_up();
+    match (previous, current) {
+        (false, true) => Some(BackupStateChange::BackedUp),
+        (true, false) => Some(BackupStateChange::NoLongerBackedUp),
+        _ => None,
+    }
+}
+
+/// Decode one CBOR item from the front of `data`, returning it with its encoding
+fn read_cbor_item(data: &mut &[u8]) -> YKeyResult<(cbor::Value, Vec<u8>)> {
+    let start = *data;
+    let value = ciborium::de::from_reader::<cbor::Value, _>(&mut *data)
+        .map_err(|e| YKeyError::InvalidCredential(format!("Invalid CBOR in authenticator data: {}", e)))?;
+    let consumed = start.len() - data.len();
+    Ok((value, start[..consumed].to_vec()))
+}
+
+/// Access to the credential in an attestation object
+pub trait AttestationObjectExt {
+    /// Parse the authenticator data and return the attested credential
+    fn parse_auth_data(&self) -> YKeyResult<AttestedCredentialData>;
+}
+
+impl AttestationObjectExt for AttestationObject {
+    fn parse_auth_data(&self) -> YKeyResult<AttestedCredentialData> {
+        AuthenticatorData::parse(&self.auth_data)?
+            .attested_credential
+            .ok_or_else(|| YKeyError::InvalidCredential("Attestation carries no credential data".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// MakeCredential authData for webauthn.io from a YubiKey 5 AAGUID, ES256
+    const ES256_AUTH_DATA: &str = "74a6ea9213c99c2f74b22492b320cf40262a94c1a950a0397f29250b60841ef0\
+        450000002aee882879721c491397753dfcce97072a0020101112131415161718191a1b1c1d1e1f20212223\
+        2425262728292a2b2c2d2e2fa5010203262001215820a5fd5ce1b1c458c530a54fa61b31bf6b04be8b97af\
+        de54dd8cbbbba5f4ad1b08225820e0d8d4d2c4c3e0f5a5f26a5d12d69f6ba4a8a7e8c7b0f1a0f0c1d2e3f4\
+        a5b6c7";
+
+    /// MakeCredential authData with an EdDSA key and a credProtect extension output
+    const EDDSA_AUTH_DATA: &str = "74a6ea9213c99c2f74b22492b320cf40262a94c1a950a0397f29250b60841ef0\
+        c500000000ee882879721c491397753dfcce97072a00109a3c1b2e4d5f60718293a4b5c6d7e8f9a4010103\
+        272006215820d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511aa16b637265\
+        6450726f7465637402";
+
+    fn attestation(auth_data: &str) -> AttestationObject {
+        AttestationObject {
+            fmt: "none".to_string(),
+            att_stmt: HashMap::new(),
+            auth_data: hex::decode(auth_data).unwrap(),
+            cred_blob_stored: None,
+            large_blob_key: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_es256_auth_data() {
+        let data = AuthenticatorData::parse(&hex::decode(ES256_AUTH_DATA).unwrap()).unwrap();
+        assert!(data.user_present());
+        assert!(data.user_verified());
+        assert_eq!(data.sign_count, 42);
+        assert!(data.extensions.is_none());
+
+        let credential = attestation(ES256_AUTH_DATA).parse_auth_data().unwrap();
+        assert_eq!(credential.aaguid.as_bytes()[..4], [0xEE, 0x88, 0x28, 0x79]);
+        assert_eq!(credential.credential_id, (0x10..0x30).collect::<Vec<u8>>());
+        assert_eq!(credential.cose_key.len(), 77);
+        match credential.public_key {
+            CosePublicKey::Ec2 { alg, curve, x, y } => {
+                assert_eq!((alg, curve), (ALG_ES256, CURVE_P256));
+                assert_eq!(x[0], 0xA5);
+                assert_eq!(y[31], 0xC7);
+            }
+            other => panic!("expected EC2 key, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_eddsa_auth_data_with_extensions() {
+        let data = AuthenticatorData::parse(&hex::decode(EDDSA_AUTH_DATA).unwrap()).unwrap();
+        let extensions = cbor::as_map(data.extensions.as_ref().unwrap()).unwrap();
+        assert_eq!(cbor::get_text(extensions, "credProtect"), Some(&cbor::int(2)));
+
+        let credential = data.attested_credential.unwrap();
+        assert_eq!(credential.credential_id.len(), 16);
+        assert_eq!(credential.public_key.alg(), ALG_EDDSA);
+        assert!(matches!(credential.public_key, CosePublicKey::Okp { curve: CURVE_ED25519, .. }));
+    }
+
+    #[test]
+    fn test_verify_es256_signature() {
+        use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+
+        let signing_key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+        let point = signing_key.verifying_key().to_encoded_point(false);
+        let public_key = CosePublicKey::Ec2 {
+            alg: ALG_ES256,
+            curve: CURVE_P256,
+            x: point.x().unwrap().to_vec(),
+            y: point.y().unwrap().to_vec(),
+        };
+        let signature: Signature = signing_key.sign(b"signed data");
+        let der = signature.to_der().as_bytes().to_vec();
+
+        assert!(verify_signature(&public_key, b"signed data", &der).is_ok());
+        assert!(matches!(
+            verify_signature(&public_key, b"signed datA", &der),
+            Err(YKeyError::AuthenticationFailed(_))
+        ));
+        assert!(matches!(
+            verify_signature(&public_key, b"signed data", &der[..der.len() - 1]),
+            Err(YKeyError::AuthenticationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_eddsa_signature() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let signing_key = SigningKey::from_bytes(&[0x07; 32]);
+        let public_key = CosePublicKey::Okp {
+            alg: ALG_EDDSA,
+            curve: CURVE_ED25519,
+            x: signing_key.verifying_key().to_bytes().to_vec(),
+        };
+        let mut signature = signing_key.sign(b"signed data").to_bytes().to_vec();
+        assert!(verify_signature(&public_key, b"signed data", &signature).is_ok());
+
+        signature[10] ^= 0x01;
+        assert!(matches!(
+            verify_signature(&public_key, b"signed data", &signature),
+            Err(YKeyError::AuthenticationFailed(_))
+        ));
+
+        let rsa = CosePublicKey::Rsa { alg: ALG_RS256, n: vec![0x01], e: vec![0x03] };
+        assert!(matches!(
+            verify_signature(&rsa, b"signed data", &signature),
+            Err(YKeyError::UnsupportedOperation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_malformed_auth_data() {
+        let auth_data = hex::decode(ES256_AUTH_DATA).unwrap();
+        assert!(AuthenticatorData::parse(&auth_data[..36]).is_err());
+        assert!(AuthenticatorData::parse(&auth_data[..120]).is_err());
+
+        // Assertion authData carries no credential
+        let mut assertion = attestation(ES256_AUTH_DATA);
+        assertion.auth_data.truncate(37);
+        assertion.auth_data[32] = AuthenticatorData::FLAG_USER_PRESENT;
+        assert!(AuthenticatorData::parse(&assertion.auth_data).is_ok());
+        assert!(matches!(assertion.parse_auth_data(), Err(YKeyError::InvalidCredential(_))));
+    }
+
+    /// Assertion whose authData has the given flags and no credential
+    fn assertion(flags: u8) -> AssertionObject {
+        let mut auth_data = hex::decode(ES256_AUTH_DATA).unwrap();
+        auth_data.truncate(37);
+        auth_data[32] = flags;
+        AssertionObject {
+            credential_id: Some(vec![0x10; 32]),
+            auth_data,
+            signature: Vec::new(),
+            user: None,
+            number_of_credentials: None,
+            cred_blob: None,
+            large_blob_key: None,
+        }
+    }
+
+    fn stored(backup_state: Option<bool>) -> Credential {
+        Credential {
+            id: vec![0x10; 32],
+            rp_id: "webauthn.io".to_string(),
+            user_id: vec![0x01],
+            user_name: "alice".to_string(),
+            user_display_name: "Alice".to_string(),
+            public_key: Vec::new(),
+            counter: 0,
+            created_at: chrono::DateTime::<chrono::Utc>::UNIX_EPOCH,
+            last_used: None,
+            backup_state,
+        }
+    }
+
+    #[test]
+    fn test_device_bound_credential_synced() {
+        let synced = assertion(
+            AuthenticatorData::FLAG_USER_PRESENT | AuthenticatorData::FLAG_BACKUP_ELIGIBLE
+                | AuthenticatorData::FLAG_BACKUP_STATE,
+        );
+        let data = AuthenticatorData::parse(&synced.auth_data).unwrap();
+        assert!(data.backup_eligible() && data.backed_up());
+        assert_eq!(detect_backup_state_change(&stored(Some(false)), &synced), Some(BackupStateChange::BackedUp));
+
+        let bound = assertion(AuthenticatorData::FLAG_USER_PRESENT | AuthenticatorData::FLAG_BACKUP_ELIGIBLE);
+        assert_eq!(
+            detect_backup_state_change(&stored(Some(true)), &bound),
+            Some(BackupStateChange::NoLongerBackedUp)
+        );
+    }
+
+    #[test]
+    fn test_unchanged_backup_state() {
+        let bound = assertion(AuthenticatorData::FLAG_USER_PRESENT);
+        assert_eq!(detect_backup_state_change(&stored(Some(false)), &bound), None);
+        // Nothing to compare against before the state has been recorded
+        let synced = assertion(AuthenticatorData::FLAG_USER_PRESENT | AuthenticatorData::FLAG_BACKUP_STATE);
+        assert_eq!(detect_backup_state_change(&stored(None), &synced), None);
+    }
+}
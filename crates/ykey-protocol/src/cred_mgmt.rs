@@ -0,0 +1,508 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Resident credential management
+//!
+//! Wraps the CTAP 2.1 authenticatorCredentialManagement command, which
+//! requires a pinUvAuthToken with the credential management permission.
+
+use crate::{
+    cbor,
+    pin::Permissions,
+    Fido2Client,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ykey_core::{traits::Device, types::*, YKeyError, YKeyResult};
+
+/// authenticatorCredentialManagement command byte
+pub const CREDENTIAL_MANAGEMENT_COMMAND: u8 = 0x0A;
+
+/// Returned when there is nothing to enumerate
+const CTAP2_ERR_NO_CREDENTIALS: u8 = 0x2E;
+
+/// authenticatorCredentialManagement subcommands
+#[derive(Debug, Clone)]
+pub enum CredentialManagementCommand {
+    GetCredsMetadata,
+    EnumerateRPsBegin,
+    EnumerateRPsGetNextRP,
+    EnumerateCredentialsBegin { rp_id_hash: Vec<u8> },
+    EnumerateCredentialsGetNextCredential,
+    DeleteCredential { credential_id: Vec<u8> },
+    UpdateUserInformation { credential_id: Vec<u8>, user: User },
+}
+
+impl CredentialManagementCommand {
+    /// Subcommand number as sent on the wire
+    pub fn subcommand(&self) -> u8 {
+        match self {
+            CredentialManagementCommand::GetCredsMetadata => 0x01,
+            CredentialManagementCommand::EnumerateRPsBegin => 0x02,
+            CredentialManagementCommand::EnumerateRPsGetNextRP => 0x03,
+            CredentialManagementCommand::EnumerateCredentialsBegin { .. } => 0x04,
+            CredentialManagementCommand::EnumerateCredentialsGetNextCredential => 0x05,
+            CredentialManagementCommand::DeleteCredential { .. } => 0x06,
+            CredentialManagementCommand::UpdateUserInformation { .. } => 0x07,
+        }
+    }
+
+    /// Whether the subcommand carries a pinUvAuthParam
+    ///
+    /// Enumeration continuations rely on the state set up by the begin call.
+    pub fn requires_auth(&self) -> bool {
+        !matches!(
+            self,
+            CredentialManagementCommand::EnumerateRPsGetNextRP
+                | CredentialManagementCommand::EnumerateCredentialsGetNextCredential
+        )
+    }
+
+    /// Encode the subCommandParams map, if the subcommand takes one
+    pub fn params(&self) -> Option<cbor::Value> {
+        match self {
+            CredentialManagementCommand::EnumerateCredentialsBegin { rp_id_hash } => {
+                Some(cbor::int_map(vec![(0x01, Some(cbor::bytes(rp_id_hash)))]))
+            }
+            CredentialManagementCommand::DeleteCredential { credential_id } => {
+                Some(cbor::int_map(vec![(0x02, Some(credential_descriptor(credential_id)))]))
+            }
+            CredentialManagementCommand::UpdateUserInformation { credential_id, user } => {
+                Some(cbor::int_map(vec![
+                    (0x02, Some(credential_descriptor(credential_id))),
+                    (0x03, Some(cbor::Value::Map(vec![
+                        (cbor::text("id"), cbor::bytes(&user.id)),
+                        (cbor::text("name"), cbor::text(&user.name)),
+                        (cbor::text("displayName"), cbor::text(&user.display_name)),
+                    ]))),
+                ]))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Resident credential usage reported by getCredsMetadata
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CredsMetadata {
+    /// Number of resident credentials stored
+    pub existing_resident_credentials_count: u32,
+    /// Estimated number of additional resident credentials that fit
+    pub max_possible_remaining_resident_credentials_count: u32,
+}
+
+/// Relying party with resident credentials on the device
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RpInfo {
+    /// Relying party identifier
+    pub id: String,
+    /// Relying party name, if stored
+    pub name: Option<String>,
+    /// SHA-256 of the relying party identifier
+    pub id_hash: Vec<u8>,
+}
+
+impl<D: Device> Fido2Client<D> {
+    /// Acquire a pinUvAuthToken with the credential management permission
+    pub async fn unlock_credential_management(&mut self, pin: &str) -> YKeyResult<()> {
+        self.get_pin_uv_auth_token_with_permissions(pin, Permissions::CREDENTIAL_MANAGEMENT, None).await?;
+        Ok(())
+    }
+
+    /// Read how many resident credentials are stored and how many more fit
+    pub async fn get_creds_metadata(&mut self) -> YKeyResult<CredsMetadata> {
+        let response = self
+            .credential_management(CredentialManagementCommand::GetCredsMetadata)
+            .await?
+            .ok_or_else(|| YKeyError::communication("Missing credential metadata"))?;
+        let map = cbor::as_map(&response)?;
+        let count = |key: i64, name: &str| -> YKeyResult<u32> {
+            cbor::get_int(map, key)
+                .ok_or_else(|| YKeyError::communication(format!("Missing {}", name)))
+                .and_then(cbor::to_u64)
+                .map(|v| v as u32)
+        };
+
+        Ok(CredsMetadata {
+            existing_resident_credentials_count: count(0x01, "existingResidentCredentialsCount")?,
+            max_possible_remaining_resident_credentials_count: count(
+                0x02,
+                "maxPossibleRemainingResidentCredentialsCount",
+            )?,
+        })
+    }
+
+    /// List the relying parties with resident credentials
+    pub async fn enumerate_rps(&mut self) -> YKeyResult<Vec<RpInfo>> {
+        let first = match self.credential_management(CredentialManagementCommand::EnumerateRPsBegin).await {
+            Ok(response) => response.ok_or_else(|| YKeyError::communication("Missing RP response"))?,
+            Err(YKeyError::CtapError { code: CTAP2_ERR_NO_CREDENTIALS, .. }) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let total = total_count(&first, 0x05, "totalRPs")?;
+
+        let mut rps = vec![parse_rp(&first)?];
+        for _ in 1..total {
+            let next = self
+                .credential_management(CredentialManagementCommand::EnumerateRPsGetNextRP)
+                .await?
+                .ok_or_else(|| YKeyError::communication("Missing RP response"))?;
+            rps.push(parse_rp(&next)?);
+        }
+        Ok(rps)
+    }
+
+    /// List the resident credentials stored for a relying party
+    ///
+    /// Authenticators don't report creation or usage times, so `created_at`
+    /// is the Unix epoch and `counter` is zero.
+    pub async fn enumerate_credentials(&mut self, rp_id: &str) -> YKeyResult<Vec<Credential>> {
+        let command = CredentialManagementCommand::EnumerateCredentialsBegin {
+            rp_id_hash: Sha256::digest(rp_id.as_bytes()).to_vec(),
+        };
+        let first = match self.credential_management(command).await {
+            Ok(response) => response.ok_or_else(|| YKeyError::communication("Missing credential response"))?,
+            Err(YKeyError::CtapError { code: CTAP2_ERR_NO_CREDENTIALS, .. }) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let total = total_count(&first, 0x09, "totalCredentials")?;
+
+        let mut credentials = vec![parse_credential(rp_id, &first)?];
+        for _ in 1..total {
+            let next = self
+                .credential_management(CredentialManagementCommand::EnumerateCredentialsGetNextCredential)
+                .await?
+                .ok_or_else(|| YKeyError::communication("Missing credential response"))?;
+            credentials.push(parse_credential(rp_id, &next)?);
+        }
+        Ok(credentials)
+    }
+
+    /// Delete a resident credential
+    pub async fn delete_credential(&mut self, credential_id: &[u8]) -> YKeyResult<()> {
+        self.credential_management(CredentialManagementCommand::DeleteCredential {
+            credential_id: credential_id.to_vec(),
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Replace the user information stored with a resident credential
+    pub async fn update_user_information(&mut self, credential_id: &[u8], user: User) -> YKeyResult<()> {
+        self.credential_management(CredentialManagementCommand::UpdateUserInformation {
+            credential_id: credential_id.to_vec(),
+            user,
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Send a credential management subcommand, authenticated with the stored token
+    pub async fn credential_management(
+        &mut self,
+        command: CredentialManagementCommand,
+    ) -> YKeyResult<Option<cbor::Value>> {
+        let params = command.params();
+        let auth = if command.requires_auth() {
+            let token = self.permitted_pin_token(Permissions::CREDENTIAL_MANAGEMENT)?;
+            let mut message = vec![command.subcommand()];
+            if let Some(params) = &params {
+                message.extend(cbor::encode(params)?);
+            }
+            Some((token.protocol(), token.authenticate(&message)))
+        } else {
+            None
+        };
+
+        self.send_cbor(
+            CREDENTIAL_MANAGEMENT_COMMAND,
+            Some(cbor::int_map(vec![
+                (0x01, Some(cbor::int(command.subcommand() as i64))),
+                (0x02, params),
+                (0x03, auth.as_ref().map(|(protocol, _)| cbor::int(protocol.version() as i64))),
+                (0x04, auth.as_ref().map(|(_, param)| cbor::bytes(param))),
+            ])),
+        )
+        .await
+    }
+}
+
+/// Credential management client
+pub struct CredMgmtClient<D: Device> {
+    client: Fido2Client<D>,
+}
+
+impl<D: Device> CredMgmtClient<D> {
+    /// Unlock credential management with the device PIN
+    pub async fn new(mut client: Fido2Client<D>, pin: &str) -> YKeyResult<Self> {
+        client.unlock_credential_management(pin).await?;
+        Ok(Self { client })
+    }
+
+    /// Get the underlying FIDO2 client
+    pub fn client(&self) -> &Fido2Client<D> {
+        &self.client
+    }
+
+    /// Release the underlying FIDO2 client
+    pub fn into_client(self) -> Fido2Client<D> {
+        self.client
+    }
+
+    /// Count resident credentials per relying party
+    ///
+    /// Only the first credential of each RP is fetched, since
+    /// enumerateCredentialsBegin already reports the total.
+    pub async fn rp_credential_counts(&mut self) -> YKeyResult<Vec<(RpInfo, u32)>> {
+        let mut counts = Vec::new();
+        for rp in self.client.enumerate_rps().await? {
+            let command = CredentialManagementCommand::EnumerateCredentialsBegin {
+                rp_id_hash: rp.id_hash.clone(),
+            };
+            let count = match self.client.credential_management(command).await {
+                Ok(response) => {
+                    let response = response.ok_or_else(|| YKeyError::communication("Missing credential response"))?;
+                    total_count(&response, 0x09, "totalCredentials")? as u32
+                }
+                Err(YKeyError::CtapError { code: CTAP2_ERR_NO_CREDENTIALS, .. }) => 0,
+                Err(e) => return Err(e),
+            };
+            counts.push((rp, count));
+        }
+        Ok(counts)
+    }
+}
+
+fn credential_descriptor(credential_id: &[u8]) -> cbor::Value {
+    cbor::Value::Map(vec![
+        (cbor::text("id"), cbor::bytes(credential_id)),
+        (cbor::text("type"), cbor::text("public-key")),
+    ])
+}
+
+/// Read the total reported alongside the first enumerated item
+fn total_count(response: &cbor::Value, key: i64, name: &str) -> YKeyResult<u64> {
+    cbor::get_int(cbor::as_map(response)?, key)
+        .ok_or_else(|| YKeyError::communication(format!("Missing {}", name)))
+        .and_then(cbor::to_u64)
+}
+
+/// Parse the RP fields of an enumerateRPs response
+fn parse_rp(response: &cbor::Value) -> YKeyResult<RpInfo> {
+    let map = cbor::as_map(response)?;
+    let rp = cbor::get_int(map, 0x03)
+        .ok_or_else(|| YKeyError::communication("Missing rp"))
+        .and_then(cbor::as_map)?;
+
+    Ok(RpInfo {
+        id: cbor::get_text(rp, "id")
+            .ok_or_else(|| YKeyError::communication("RP missing id"))
+            .and_then(cbor::to_text)?,
+        name: cbor::get_text(rp, "name").map(cbor::to_text).transpose()?,
+        id_hash: cbor::get_int(map, 0x04)
+            .ok_or_else(|| YKeyError::communication("Missing rpIDHash"))
+            .and_then(cbor::to_bytes)?,
+    })
+}
+
+/// Parse the credential fields of an enumerateCredentials response
+fn parse_credential(rp_id: &str, response: &cbor::Value) -> YKeyResult<Credential> {
+    let map = cbor::as_map(response)?;
+    let user = cbor::get_int(map, 0x06)
+        .ok_or_else(|| YKeyError::communication("Missing user"))
+        .and_then(cbor::as_map)?;
+    let descriptor = cbor::get_int(map, 0x07)
+        .ok_or_else(|| YKeyError::communication("Missing credentialID"))
+        .and_then(cbor::as_map)?;
+    let text_field = |key: &str| -> YKeyResult<String> {
+        cbor::get_text(user, key).map(cbor::to_text).transpose().map(Option::unwrap_or_default)
+    };
+
+    Ok(Credential {
+        id: cbor::get_text(descriptor, "id")
+            .ok_or_else(|| YKeyError::communication("Credential descriptor missing id"))
+            .and_then(cbor::to_bytes)?,
+        rp_id: rp_id.to_string(),
+        user_id: cbor::get_text(user, "id")
+            .ok_or_else(|| YKeyError::communication("User missing id"))
+            .and_then(cbor::to_bytes)?,
+        user_name: text_field("name")?,
+        user_display_name: text_field("displayName")?,
+        public_key: cbor::get_int(map, 0x08).map(cbor::encode).transpose()?.unwrap_or_default(),
+        counter: 0,
+        created_at: DateTime::<Utc>::UNIX_EPOCH,
+        last_used: None,
+        backup_state: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pin::{PinUvAuthProtocol, PinUvAuthToken},
+        soft::SoftAuthenticator,
+        test_support::ReplayDevice,
+    };
+    use sha2::{Digest, Sha256};
+
+    async fn cred_mgmt(device: SoftAuthenticator) -> CredMgmtClient<SoftAuthenticator> {
+        CredMgmtClient::new(Fido2Client::new(device), "1234").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rp_credential_counts() {
+        let mut device = SoftAuthenticator::new("1234");
+        for rp_id in ["github.com", "example.com", "github.com", "login.test", "github.com"] {
+            device.add_credential(rp_id);
+        }
+        let mut client = cred_mgmt(device).await;
+
+        let counts = client.rp_credential_counts().await.unwrap();
+        let summary: Vec<_> = counts.iter().map(|(rp, count)| (rp.id.as_str(), *count)).collect();
+        assert_eq!(summary, vec![("github.com", 3), ("example.com", 1), ("login.test", 1)]);
+
+        // Counts agree with what is actually stored for each RP
+        for (rp, count) in &counts {
+            assert_eq!(rp.id_hash, Sha256::digest(rp.id.as_bytes()).to_vec());
+            assert_eq!(client.client().device().credentials_for(&rp.id).len() as u32, *count);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rp_credential_counts_empty() {
+        let mut client = cred_mgmt(SoftAuthenticator::new("1234")).await;
+        assert!(client.rp_credential_counts().await.unwrap().is_empty());
+    }
+
+    fn ok_response(entries: Vec<(i64, Option<cbor::Value>)>) -> Vec<u8> {
+        let mut data = vec![0x00];
+        data.extend(cbor::encode(&cbor::int_map(entries)).unwrap());
+        data
+    }
+
+    fn credential_response(id: u8, user: &str, total: Option<i64>) -> Vec<u8> {
+        ok_response(vec![
+            (0x06, Some(cbor::Value::Map(vec![
+                (cbor::text("id"), cbor::bytes(&[id; 8])),
+                (cbor::text("name"), cbor::text(user)),
+                (cbor::text("displayName"), cbor::text(&user.to_uppercase())),
+            ]))),
+            (0x07, Some(credential_descriptor(&[id; 16]))),
+            (0x08, Some(cbor::int_map(vec![(1, Some(cbor::int(2))), (3, Some(cbor::int(-7)))]))),
+            (0x09, total.map(cbor::int)),
+            (0x0A, Some(cbor::int(1))),
+        ])
+    }
+
+    /// Client holding a protocol two token, replaying the given responses
+    fn replay_client(responses: Vec<Vec<u8>>) -> Fido2Client<ReplayDevice> {
+        let mut client = Fido2Client::new(ReplayDevice::new(responses));
+        client.pin_token = Some(vec![0x11; 32]);
+        client.pin_protocol_version = Some(2);
+        client.pin_permissions = Permissions::CREDENTIAL_MANAGEMENT;
+        client
+    }
+
+    fn request_map(request: &[u8]) -> cbor::Value {
+        assert_eq!(request[0], CREDENTIAL_MANAGEMENT_COMMAND);
+        cbor::decode(&request[1..], false).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replayed_enumeration() {
+        let mut client = replay_client(vec![
+            ok_response(vec![(0x01, Some(cbor::int(3))), (0x02, Some(cbor::int(22)))]),
+            ok_response(vec![
+                (0x03, Some(cbor::Value::Map(vec![
+                    (cbor::text("id"), cbor::text("github.com")),
+                    (cbor::text("name"), cbor::text("GitHub")),
+                ]))),
+                (0x04, Some(cbor::bytes(&Sha256::digest(b"github.com")))),
+                (0x05, Some(cbor::int(2))),
+            ]),
+            ok_response(vec![
+                (0x03, Some(cbor::Value::Map(vec![(cbor::text("id"), cbor::text("example.com"))]))),
+                (0x04, Some(cbor::bytes(&Sha256::digest(b"example.com")))),
+            ]),
+            credential_response(0xA1, "alice", Some(2)),
+            credential_response(0xA2, "bob", None),
+        ]);
+
+        let metadata = client.get_creds_metadata().await.unwrap();
+        assert_eq!(metadata.existing_resident_credentials_count, 3);
+        assert_eq!(metadata.max_possible_remaining_resident_credentials_count, 22);
+
+        let rps = client.enumerate_rps().await.unwrap();
+        assert_eq!(rps.len(), 2);
+        assert_eq!(rps[0].name.as_deref(), Some("GitHub"));
+        assert_eq!(rps[1].id, "example.com");
+        assert_eq!(rps[1].name, None);
+
+        let credentials = client.enumerate_credentials("github.com").await.unwrap();
+        assert_eq!(credentials.len(), 2);
+        assert_eq!(credentials[0].id, vec![0xA1; 16]);
+        assert_eq!(credentials[0].rp_id, "github.com");
+        assert_eq!(credentials[0].user_id, vec![0xA1; 8]);
+        assert_eq!(credentials[1].user_name, "bob");
+        assert_eq!(credentials[1].user_display_name, "BOB");
+        assert!(!credentials[1].public_key.is_empty());
+
+        // Begin calls are authenticated, continuations are not
+        let requests = &client.device().requests;
+        let begin = request_map(&requests[3]);
+        let begin = cbor::as_map(&begin).unwrap();
+        let params = cbor::encode(cbor::get_int(begin, 0x02).unwrap()).unwrap();
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::Two, vec![0x11; 32]);
+        assert_eq!(
+            cbor::get_int(begin, 0x04),
+            Some(&cbor::bytes(&token.authenticate(&[&[0x04][..], &params].concat())))
+        );
+        let next = request_map(&requests[4]);
+        assert_eq!(cbor::get_int(cbor::as_map(&next).unwrap(), 0x04), None);
+    }
+
+    #[tokio::test]
+    async fn test_delete_and_update_requests() {
+        let mut client = replay_client(vec![vec![0x00], vec![0x00], vec![0x2E]]);
+
+        client.delete_credential(&[0xA1; 16]).await.unwrap();
+        let user = User {
+            id: vec![0xA1; 8],
+            name: "alice@example.com".to_string(),
+            display_name: "Alice".to_string(),
+            icon: None,
+        };
+        client.update_user_information(&[0xA1; 16], user).await.unwrap();
+        assert!(client.enumerate_credentials("missing.example").await.unwrap().is_empty());
+
+        let requests = &client.device().requests;
+        let delete = request_map(&requests[0]);
+        let delete = cbor::as_map(&delete).unwrap();
+        assert_eq!(cbor::get_int(delete, 0x01), Some(&cbor::int(0x06)));
+        assert_eq!(
+            cbor::get_int(delete, 0x02),
+            Some(&cbor::int_map(vec![(0x02, Some(credential_descriptor(&[0xA1; 16])))]))
+        );
+
+        let update = request_map(&requests[1]);
+        let update = cbor::as_map(&update).unwrap();
+        let params = cbor::as_map(cbor::get_int(update, 0x02).unwrap()).unwrap();
+        assert_eq!(cbor::get_int(update, 0x01), Some(&cbor::int(0x07)));
+        let stored_user = cbor::as_map(cbor::get_int(params, 0x03).unwrap()).unwrap();
+        assert_eq!(cbor::get_text(stored_user, "name"), Some(&cbor::text("alice@example.com")));
+    }
+
+    #[tokio::test]
+    async fn test_requires_pin_token() {
+        let mut client = Fido2Client::new(SoftAuthenticator::new("1234"));
+        assert!(matches!(client.enumerate_rps().await, Err(YKeyError::PinRequired)));
+    }
+
+    #[tokio::test]
+    async fn test_requires_pin() {
+        let result = CredMgmtClient::new(Fido2Client::new(SoftAuthenticator::new("1234")), "0000").await;
+        assert!(matches!(result, Err(YKeyError::InvalidPin { retries_remaining: Some(7), .. })));
+    }
+}
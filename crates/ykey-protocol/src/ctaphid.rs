@@ -0,0 +1,1246 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! CTAPHID framing for USB HID authenticators
+//!
+//! Messages are split into one initialization packet followed by
+//! continuation packets, all addressed to a channel ID allocated by the
+//! authenticator in response to CTAPHID_INIT.
+
+use ykey_core::{traits::*, types::*, YKeyResult, YKeyError};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+/// Default HID report size for FIDO authenticators
+pub const HID_REPORT_SIZE: usize = 64;
+
+/// Broadcast channel used before a channel has been allocated
+pub const BROADCAST_CID: u32 = 0xFFFF_FFFF;
+
+/// CTAPHID_PING command
+pub const CTAPHID_PING: u8 = 0x81;
+/// CTAPHID_MSG command (CTAP1/U2F APDUs)
+pub const CTAPHID_MSG: u8 = 0x83;
+/// CTAPHID_INIT command
+pub const CTAPHID_INIT: u8 = 0x86;
+/// CTAPHID_WINK command
+pub const CTAPHID_WINK: u8 = 0x88;
+/// CTAPHID_CBOR command (CTAP2 messages)
+pub const CTAPHID_CBOR: u8 = 0x90;
+/// CTAPHID_CANCEL command
+pub const CTAPHID_CANCEL: u8 = 0x91;
+/// CTAPHID_KEEPALIVE response
+pub const CTAPHID_KEEPALIVE: u8 = 0xBB;
+/// CTAPHID_ERROR response
+pub const CTAPHID_ERROR: u8 = 0xBF;
+
+/// Raw HID report I/O used by [`HidDevice`]
+///
+/// Reports are exchanged without the leading report ID byte.
+pub trait HidReportIo: Send + Sync {
+    /// Write a single output report
+    fn write_report(&mut self, report: &[u8]) -> YKeyResult<()>;
+
+    /// Read a single input report, waiting at most `timeout`
+    fn read_report(&mut self, timeout: Duration) -> YKeyResult<Vec<u8>>;
+
+    /// Size of the device's reports, e.g. from [`report_size_from_descriptor`]
+    fn report_size(&self) -> usize {
+        HID_REPORT_SIZE
+    }
+
+    /// Release the OS handle to the device
+    ///
+    /// Called when the owning [`HidDevice`] is dropped, whether or not it was
+    /// disconnected first, so it must not block or assume anything is pending.
+    fn release(&mut self) {}
+}
+
+/// Byte length of the first input report declared by a HID report descriptor
+pub fn report_size_from_descriptor(descriptor: &[u8]) -> Option<usize> {
+    let mut report_size = 0usize;
+    let mut report_count = 0usize;
+    let mut rest = descriptor;
+    while let [prefix, tail @ ..] = rest {
+        // Long items carry their size in the following byte
+        if *prefix == 0xFE {
+            let size = tail.first().map_or(0, |s| *s as usize);
+            rest = tail.get(size + 2..).unwrap_or_default();
+            continue;
+        }
+
+        let size = match prefix & 0x03 {
+            3 => 4,
+            n => n as usize,
+        };
+        let data = tail.get(..size)?;
+        let mut value = [0u8; 4];
+        value[..size].copy_from_slice(data);
+        let value = u32::from_le_bytes(value) as usize;
+
+        match prefix & 0xFC {
+            0x74 => report_size = value,
+            0x94 => report_count = value,
+            // Input main item
+            0x80 => return Some(report_size * report_count / 8).filter(|len| *len > 0),
+            _ => {}
+        }
+        rest = &tail[size..];
+    }
+    None
+}
+
+/// Observable state of a CTAPHID channel
+///
+/// Intended for diagnostics: it is cheap to obtain and never touches the device.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelState {
+    /// Allocated channel ID, if INIT has completed
+    pub cid: Option<u32>,
+    /// CTAPHID protocol version reported by INIT
+    pub protocol_version: u8,
+    /// Device version (major, minor, build) reported by INIT
+    pub device_version: (u8, u8, u8),
+    /// Capability flags reported by INIT
+    pub capabilities: u8,
+    /// Sequence number of the last continuation packet received
+    pub last_sequence: Option<u8>,
+    /// Bytes still expected for the message currently being received
+    pub pending_bytes: usize,
+    /// Status of the last keepalive received for the pending transaction
+    pub last_keepalive: Option<KeepaliveStatus>,
+}
+
+impl ChannelState {
+    /// Authenticator implements CTAPHID_WINK
+    pub const CAPABILITY_WINK: u8 = 0x01;
+    /// Authenticator implements CTAPHID_CBOR
+    pub const CAPABILITY_CBOR: u8 = 0x04;
+    /// Authenticator does not implement CTAPHID_MSG
+    pub const CAPABILITY_NMSG: u8 = 0x08;
+
+    /// Check whether a capability flag is set
+    pub fn has_capability(&self, flag: u8) -> bool {
+        self.capabilities & flag != 0
+    }
+}
+
+/// Split a message into CTAPHID packets of the default report size
+pub fn encode_packets(cid: u32, command: u8, payload: &[u8]) -> YKeyResult<Vec<Vec<u8>>> {
+    encode_packets_sized(HID_REPORT_SIZE, cid, command, payload)
+}
+
+/// Split a message into CTAPHID packets of `report_size` bytes
+pub fn encode_packets_sized(report_size: usize, cid: u32, command: u8, payload: &[u8]) -> YKeyResult<Vec<Vec<u8>>> {
+    let (init_capacity, cont_capacity) = packet_capacities(report_size)?;
+    let max_len = init_capacity + MAX_CONTINUATION_PACKETS * cont_capacity;
+    if payload.len() > max_len {
+        return Err(YKeyError::InvalidParameters(format!(
+            "CTAPHID payload of {} bytes exceeds maximum of {}",
+            payload.len(),
+            max_len
+        )));
+    }
+
+    let fragments = crate::split_message(payload, init_capacity, cont_capacity)?;
+    frame_packets(report_size, cid, command, &fragments)
+}
+
+/// Frame message fragments as CTAPHID packets of `report_size` bytes
+///
+/// The first fragment goes into the initialization packet and every later
+/// one into a continuation packet, so each must fit its packet, as laid out
+/// by [`split_message`](crate::split_message).
+pub fn frame_packets(report_size: usize, cid: u32, command: u8, fragments: &[&[u8]]) -> YKeyResult<Vec<Vec<u8>>> {
+    let (init_capacity, cont_capacity) = packet_capacities(report_size)?;
+    let Some((first, rest)) = fragments.split_first() else {
+        return Err(YKeyError::InvalidParameters("CTAPHID message without packets".to_string()));
+    };
+    if rest.len() > MAX_CONTINUATION_PACKETS {
+        return Err(YKeyError::InvalidParameters(format!(
+            "CTAPHID message of {} packets exceeds maximum of {}",
+            fragments.len(),
+            MAX_CONTINUATION_PACKETS + 1
+        )));
+    }
+    if first.len() > init_capacity || rest.iter().any(|fragment| fragment.len() > cont_capacity) {
+        return Err(YKeyError::InvalidParameters(format!(
+            "message fragment does not fit a {} byte CTAPHID packet",
+            report_size
+        )));
+    }
+    let length = u16::try_from(fragments.iter().map(|fragment| fragment.len()).sum::<usize>())
+        .map_err(|_| YKeyError::InvalidParameters("CTAPHID payload too long".to_string()))?;
+
+    let mut packets = Vec::with_capacity(fragments.len());
+    let mut packet = Vec::with_capacity(report_size);
+    packet.extend_from_slice(&cid.to_be_bytes());
+    packet.push(command);
+    packet.extend_from_slice(&length.to_be_bytes());
+    packet.extend_from_slice(first);
+    packet.resize(report_size, 0);
+    packets.push(packet);
+
+    for (sequence, chunk) in (0u8..).zip(rest) {
+        let mut packet = Vec::with_capacity(report_size);
+        packet.extend_from_slice(&cid.to_be_bytes());
+        packet.push(sequence);
+        packet.extend_from_slice(chunk);
+        packet.resize(report_size, 0);
+        packets.push(packet);
+    }
+
+    Ok(packets)
+}
+
+/// Continuation packets a message may take, bounded by the sequence numbers
+const MAX_CONTINUATION_PACKETS: usize = 128;
+
+/// Payload bytes of the initialization and continuation packets
+fn packet_capacities(report_size: usize) -> YKeyResult<(usize, usize)> {
+    if report_size <= 7 {
+        return Err(YKeyError::InvalidParameters(format!(
+            "HID report size of {} bytes cannot hold a CTAPHID packet",
+            report_size
+        )));
+    }
+    Ok((report_size - 7, report_size - 5))
+}
+
+/// CTAPHID_ERROR code: channel busy with another transaction
+pub const ERR_CHANNEL_BUSY: u8 = 0x06;
+
+/// Keepalive status: authenticator is still processing
+pub const KEEPALIVE_PROCESSING: u8 = 0x01;
+/// Keepalive status: authenticator is waiting for user presence
+pub const KEEPALIVE_UP_NEEDED: u8 = 0x02;
+
+/// Status carried by a CTAPHID_KEEPALIVE packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeepaliveStatus {
+    /// Still processing the request
+    Processing,
+    /// Waiting for the user to touch the key
+    UpNeeded,
+    /// A status this client does not know
+    Other(u8),
+}
+
+impl KeepaliveStatus {
+    /// Decode a keepalive status byte
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            KEEPALIVE_PROCESSING => KeepaliveStatus::Processing,
+            KEEPALIVE_UP_NEEDED => KeepaliveStatus::UpNeeded,
+            other => KeepaliveStatus::Other(other),
+        }
+    }
+
+    /// Status byte as sent on the wire
+    pub fn code(&self) -> u8 {
+        match self {
+            KeepaliveStatus::Processing => KEEPALIVE_PROCESSING,
+            KeepaliveStatus::UpNeeded => KEEPALIVE_UP_NEEDED,
+            KeepaliveStatus::Other(code) => *code,
+        }
+    }
+}
+
+/// Callback told about every keepalive received while waiting for a response
+pub type KeepaliveHandler = Box<dyn FnMut(KeepaliveStatus) + Send + Sync>;
+
+/// Build a keepalive handler that drives a busy indicator and a touch prompt
+///
+/// Keepalives repeat every 100ms or so; each callback only runs when the
+/// status changes, so `on_processing` can start a spinner and `on_up_needed`
+/// can ask for a touch without flickering. Unknown statuses are ignored.
+pub fn keepalive_prompts(
+    mut on_processing: impl FnMut() + Send + Sync + 'static,
+    mut on_up_needed: impl FnMut() + Send + Sync + 'static,
+) -> KeepaliveHandler {
+    let mut last = None;
+    Box::new(move |status| {
+        if last == Some(status) {
+            return;
+        }
+        match status {
+            KeepaliveStatus::Processing => on_processing(),
+            KeepaliveStatus::UpNeeded => on_up_needed(),
+            KeepaliveStatus::Other(_) => return,
+        }
+        last = Some(status);
+    })
+}
+
+/// Requests CTAPHID_CANCEL for the transaction pending on a channel
+///
+/// The channel is busy while a transaction waits for the user, so the cancel
+/// is only recorded here. The waiting channel writes the CANCEL packet before
+/// its next read, and the authenticator then ends the transaction with
+/// CTAP2_ERR_KEEPALIVE_CANCEL.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    /// Ask the channel to cancel its pending transaction
+    ///
+    /// Has no effect on transactions started afterwards.
+    pub fn cancel(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Canceller for CancelHandle {
+    fn cancel(&self) {
+        CancelHandle::cancel(self);
+    }
+}
+
+/// A CTAPHID channel on top of raw HID reports
+///
+/// Runs the INIT handshake to allocate a channel ID and then carries every
+/// CBOR and MSG transaction on that channel. Dropping the channel releases
+/// the underlying report I/O.
+pub struct CtapHidChannel<R: HidReportIo> {
+    io: R,
+    state: ChannelState,
+    timeout: Duration,
+    report_size: usize,
+    keepalive_handler: Option<KeepaliveHandler>,
+    cancel: CancelHandle,
+}
+
+impl<R: HidReportIo> CtapHidChannel<R> {
+    /// Create an uninitialized channel over the given report I/O
+    pub fn new(io: R) -> Self {
+        Self {
+            report_size: io.report_size(),
+            io,
+            state: ChannelState::default(),
+            timeout: Duration::from_secs(30),
+            keepalive_handler: None,
+            cancel: CancelHandle::default(),
+        }
+    }
+
+    /// Get a handle that cancels the pending transaction from another task
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Get the size of the reports written to the device
+    pub fn report_size(&self) -> usize {
+        self.report_size
+    }
+
+    /// Override the report size advertised by the report I/O
+    pub fn set_report_size(&mut self, report_size: usize) {
+        self.report_size = report_size;
+    }
+
+    /// Get the allocated channel ID, if INIT has completed
+    pub fn cid(&self) -> Option<u32> {
+        self.state.cid
+    }
+
+    /// Get the current channel state
+    pub fn state(&self) -> &ChannelState {
+        &self.state
+    }
+
+    /// Get the time allowed for a response, keepalives included
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Set the time allowed for a response, keepalives included
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Set the callback told about keepalives, e.g. to prompt for a touch
+    pub fn set_keepalive_handler(&mut self, handler: Option<KeepaliveHandler>) {
+        self.keepalive_handler = handler;
+    }
+
+    /// Forget the allocated channel
+    pub fn close(&mut self) {
+        self.state = ChannelState::default();
+    }
+
+    /// Run the CTAPHID_INIT handshake on the broadcast channel
+    /// 
+    /// Responses carrying a different nonce belong to another client sharing
+    /// the broadcast channel and are skipped.
+    pub fn init(&mut self) -> YKeyResult<u32> {
+        self.state = ChannelState::default();
+        let nonce: [u8; 8] = rand::random();
+        self.write_message(BROADCAST_CID, CTAPHID_INIT, &nonce)?;
+
+        let response = loop {
+            let response = self.read_message(BROADCAST_CID, CTAPHID_INIT)?;
+            if response.len() < 17 {
+                return Err(YKeyError::communication("Invalid CTAPHID_INIT response"));
+            }
+            if response[..8] == nonce {
+                break response;
+            }
+        };
+
+        let cid = u32::from_be_bytes([response[8], response[9], response[10], response[11]]);
+        if cid == BROADCAST_CID || cid == 0 {
+            return Err(YKeyError::communication("Authenticator allocated a reserved channel ID"));
+        }
+
+        self.state.cid = Some(cid);
+        self.state.protocol_version = response[12];
+        self.state.device_version = (response[13], response[14], response[15]);
+        self.state.capabilities = response[16];
+        Ok(cid)
+    }
+
+    /// Send a CTAP2 CBOR message and return the response payload
+    pub fn send_cbor(&mut self, payload: &[u8]) -> YKeyResult<Vec<u8>> {
+        let cid = self.require_cid()?;
+        self.transact(cid, CTAPHID_CBOR, payload)
+    }
+
+    /// Send a CTAP2 CBOR message already split into packet payloads
+    pub fn send_cbor_fragments(&mut self, fragments: &[&[u8]]) -> YKeyResult<Vec<u8>> {
+        let cid = self.require_cid()?;
+        let packets = frame_packets(self.report_size, cid, CTAPHID_CBOR, fragments)?;
+        self.transact_packets(cid, CTAPHID_CBOR, packets)
+    }
+
+    /// Message bytes of the initialization and continuation packets
+    pub fn packet_payload_sizes(&self) -> YKeyResult<(usize, usize)> {
+        packet_capacities(self.report_size)
+    }
+
+    /// Send a CTAP1/U2F APDU and return the raw response
+    pub fn send_msg(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>> {
+        if self.state.has_capability(ChannelState::CAPABILITY_NMSG) {
+            return Err(YKeyError::unsupported(
+                "CTAPHID_MSG",
+                "authenticator only speaks CTAP2",
+            ));
+        }
+        let cid = self.require_cid()?;
+        self.transact(cid, CTAPHID_MSG, apdu)
+    }
+
+    /// Echo data through the authenticator
+    pub fn ping(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        let cid = self.require_cid()?;
+        self.transact(cid, CTAPHID_PING, data)
+    }
+
+    /// Ask the authenticator to identify itself visually
+    pub fn wink(&mut self) -> YKeyResult<()> {
+        if !self.state.has_capability(ChannelState::CAPABILITY_WINK) {
+            return Err(YKeyError::unsupported("wink", "authenticator does not implement CTAPHID_WINK"));
+        }
+        let cid = self.require_cid()?;
+        self.transact(cid, CTAPHID_WINK, &[]).map(|_| ())
+    }
+
+    /// Abort the pending transaction on this channel
+    ///
+    /// Writes CTAPHID_CANCEL without waiting for anything: the cancelled
+    /// transaction receives the authenticator's answer.
+    pub fn cancel(&mut self) -> YKeyResult<()> {
+        let cid = self.require_cid()?;
+        self.write_message(cid, CTAPHID_CANCEL, &[])
+    }
+
+    fn require_cid(&self) -> YKeyResult<u32> {
+        self.state.cid
+            .ok_or_else(|| YKeyError::communication("CTAPHID channel not initialized"))
+    }
+
+    /// Send a message and wait for the matching response
+    fn transact(&mut self, cid: u32, command: u8, payload: &[u8]) -> YKeyResult<Vec<u8>> {
+        let packets = encode_packets_sized(self.report_size, cid, command, payload)?;
+        self.transact_packets(cid, command, packets)
+    }
+
+    /// Send a framed message and wait for the matching response
+    fn transact_packets(&mut self, cid: u32, command: u8, packets: Vec<Vec<u8>>) -> YKeyResult<Vec<u8>> {
+        // A cancel requested while idle must not abort this transaction
+        self.cancel.requested.store(false, Ordering::SeqCst);
+        for packet in packets {
+            self.io.write_report(&packet)?;
+        }
+        self.read_message(cid, command)
+    }
+
+    fn write_message(&mut self, cid: u32, command: u8, payload: &[u8]) -> YKeyResult<()> {
+        for packet in encode_packets_sized(self.report_size, cid, command, payload)? {
+            self.io.write_report(&packet)?;
+        }
+        Ok(())
+    }
+
+    /// Read the next complete message on a channel, skipping keepalives
+    ///
+    /// Keepalives are reported to the keepalive handler and do not extend the
+    /// channel timeout, which bounds the whole wait.
+    fn read_message(&mut self, cid: u32, command: u8) -> YKeyResult<Vec<u8>> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if cid != BROADCAST_CID && self.cancel.requested.swap(false, Ordering::SeqCst) {
+                self.write_message(cid, CTAPHID_CANCEL, &[])?;
+            }
+            let packet = self.read_report_until(deadline)?;
+            if packet.len() < 7 || u32::from_be_bytes([packet[0], packet[1], packet[2], packet[3]]) != cid {
+                continue; // Not for our channel
+            }
+
+            let response_command = packet[4];
+            if response_command == CTAPHID_KEEPALIVE {
+                let status = packet.get(7).copied().map(KeepaliveStatus::from_code);
+                self.state.last_keepalive = status;
+                if let (Some(handler), Some(status)) = (self.keepalive_handler.as_mut(), status) {
+                    handler(status);
+                }
+                continue;
+            }
+
+            let length = u16::from_be_bytes([packet[5], packet[6]]) as usize;
+            let mut data = packet[7..].to_vec();
+            data.truncate(length);
+            self.state.last_sequence = None;
+            self.state.pending_bytes = length - data.len();
+
+            while self.state.pending_bytes > 0 {
+                let packet = self.read_report_until(deadline)?;
+                if packet.len() < 5 || u32::from_be_bytes([packet[0], packet[1], packet[2], packet[3]]) != cid {
+                    continue;
+                }
+
+                let sequence = packet[4];
+                let expected = self.state.last_sequence.map_or(0, |s| s.wrapping_add(1));
+                if sequence != expected {
+                    return Err(YKeyError::communication(format!(
+                        "CTAPHID sequence mismatch: expected {}, got {}",
+                        expected, sequence
+                    )));
+                }
+
+                let take = self.state.pending_bytes.min(packet.len() - 5);
+                data.extend_from_slice(&packet[5..5 + take]);
+                self.state.last_sequence = Some(sequence);
+                self.state.pending_bytes -= take;
+            }
+            self.state.last_keepalive = None;
+
+            if response_command == CTAPHID_ERROR {
+                return match data.first().copied() {
+                    Some(ERR_CHANNEL_BUSY) => Err(YKeyError::DeviceBusy(
+                        "CTAPHID channel busy with another transaction".to_string(),
+                    )),
+                    code => Err(YKeyError::ctap_error(code.unwrap_or(0x7F))),
+                };
+            }
+            if response_command != command {
+                return Err(YKeyError::UnexpectedResponse);
+            }
+
+            return Ok(data);
+        }
+    }
+
+    /// Read one report, failing once `deadline` has passed
+    fn read_report_until(&mut self, deadline: Instant) -> YKeyResult<Vec<u8>> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(YKeyError::timeout(self.timeout.as_secs()));
+        }
+        self.io.read_report(remaining)
+    }
+}
+
+impl<R: HidReportIo> Drop for CtapHidChannel<R> {
+    fn drop(&mut self) {
+        self.io.release();
+    }
+}
+
+/// Cancels the pending transaction unless disarmed first
+struct CancelOnDrop(Option<CancelHandle>);
+
+impl CancelOnDrop {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(cancel) = self.0.take() {
+            cancel.cancel();
+        }
+    }
+}
+
+/// USB HID authenticator speaking CTAPHID
+///
+/// `send_raw` sends its payload as a CTAPHID_CBOR message on the allocated
+/// channel and returns the response payload. Report I/O runs on the blocking
+/// thread pool, so a transaction can be timed out or dropped: the dropped
+/// transaction is cancelled and finishes in the background. Dropping the
+/// device releases the report I/O once no transaction is left, even if
+/// `disconnect` was never awaited.
+pub struct HidDevice<R: HidReportIo> {
+    info: DeviceInfo,
+    channel: Arc<Mutex<CtapHidChannel<R>>>,
+    cancel: CancelHandle,
+    connected: bool,
+}
+
+impl<R: HidReportIo> HidDevice<R> {
+    /// Create a new HID device over the given report I/O
+    pub fn new(info: DeviceInfo, io: R) -> Self {
+        let channel = CtapHidChannel::new(io);
+        Self {
+            info,
+            cancel: channel.cancel_handle(),
+            channel: Arc::new(Mutex::new(channel)),
+            connected: false,
+        }
+    }
+
+    /// Get the current CTAPHID channel state
+    ///
+    /// Waits for the transaction in flight, if any.
+    pub fn channel_state(&self) -> ChannelState {
+        self.lock_channel().state().clone()
+    }
+
+    /// Lock the underlying CTAPHID channel
+    ///
+    /// Waits for the transaction in flight, if any.
+    pub fn lock_channel(&self) -> MutexGuard<'_, CtapHidChannel<R>> {
+        // A panicking transaction leaves the channel state usable
+        self.channel.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Set the callback told about keepalives, e.g. to prompt for a touch
+    pub fn set_keepalive_handler(&mut self, handler: Option<KeepaliveHandler>) {
+        self.lock_channel().set_keepalive_handler(handler);
+    }
+
+    /// Get a handle that cancels the pending transaction from another task
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+}
+
+impl<R: HidReportIo + 'static> HidDevice<R> {
+    /// Collect diagnostics for this device
+    pub async fn diagnostics(&self) -> YKeyResult<crate::diagnostics::DiagnosticsReport> {
+        let mut report = crate::diagnostics::DiagnosticsReport::new(self.info().await?);
+        report.channel = Some(self.with_channel(|channel| Ok(channel.state().clone())).await?);
+        Ok(report)
+    }
+
+    /// Run a channel operation on the blocking thread pool
+    ///
+    /// Dropping the returned future cancels the pending transaction.
+    async fn with_channel<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&mut CtapHidChannel<R>) -> YKeyResult<T> + Send + 'static,
+    ) -> YKeyResult<T> {
+        let channel = self.channel.clone();
+        let guard = CancelOnDrop(Some(self.cancel.clone()));
+        let result = tokio::task::spawn_blocking(move || {
+            operation(&mut channel.lock().unwrap_or_else(PoisonError::into_inner))
+        })
+        .await
+        .map_err(|e| YKeyError::communication(format!("HID transaction failed: {}", e)))?;
+        guard.disarm();
+        result
+    }
+}
+
+impl<R: HidReportIo> Drop for HidDevice<R> {
+    fn drop(&mut self) {
+        // A transaction still running in the background holds the channel
+        self.connected = false;
+        self.cancel.cancel();
+    }
+}
+
+#[async_trait]
+impl<R: HidReportIo + 'static> Device for HidDevice<R> {
+    async fn info(&self) -> YKeyResult<DeviceInfo> {
+        Ok(self.info.clone())
+    }
+
+    async fn connect(&mut self) -> YKeyResult<()> {
+        self.with_channel(|channel| channel.init()).await?;
+        self.connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> YKeyResult<()> {
+        self.connected = false;
+        self.with_channel(|channel| {
+            channel.close();
+            Ok(())
+        })
+        .await
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    async fn send_raw(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        if !self.connected {
+            return Err(YKeyError::communication("Device not connected"));
+        }
+        let data = data.to_vec();
+        self.with_channel(move |channel| channel.send_cbor(&data)).await
+    }
+
+    fn packet_payload_sizes(&self) -> Option<(usize, usize)> {
+        self.lock_channel().packet_payload_sizes().ok()
+    }
+
+    async fn send_fragments(&mut self, fragments: &[&[u8]]) -> YKeyResult<Vec<u8>> {
+        if !self.connected {
+            return Err(YKeyError::communication("Device not connected"));
+        }
+        let fragments: Vec<Vec<u8>> = fragments.iter().map(|fragment| fragment.to_vec()).collect();
+        self.with_channel(move |channel| {
+            let fragments: Vec<&[u8]> = fragments.iter().map(Vec::as_slice).collect();
+            channel.send_cbor_fragments(&fragments)
+        })
+        .await
+    }
+
+    fn operation_timeout(&self) -> Duration {
+        self.lock_channel().timeout()
+    }
+
+    /// Cancel the pending transaction without waiting for the channel
+    async fn cancel(&mut self) -> YKeyResult<()> {
+        if self.connected {
+            self.cancel.cancel();
+        }
+        Ok(())
+    }
+
+    fn canceller(&self) -> Option<Arc<dyn Canceller>> {
+        Some(Arc::new(self.cancel.clone()))
+    }
+
+    async fn ping(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        let data = data.to_vec();
+        self.with_channel(move |channel| channel.ping(&data)).await
+    }
+
+    async fn wink(&mut self) -> YKeyResult<()> {
+        self.with_channel(|channel| channel.wink()).await
+    }
+
+    /// Allocate a fresh channel, discarding the current one
+    async fn reset_channel(&mut self) -> YKeyResult<()> {
+        self.with_channel(|channel| channel.init().map(|_| ())).await
+    }
+
+    async fn send_u2f(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>> {
+        if !self.connected {
+            return Err(YKeyError::communication("Device not connected"));
+        }
+        let apdu = apdu.to_vec();
+        self.with_channel(move |channel| channel.send_msg(&apdu)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Fake HID authenticator answering INIT and echoing CBOR requests
+    #[derive(Default)]
+    struct FakeHid {
+        next_cid: u32,
+        incoming: Option<(u32, u8, usize, Vec<u8>)>,
+        pending: VecDeque<Vec<u8>>,
+        /// Commands received, with the channel they were addressed to
+        received: Vec<(u32, u8)>,
+        /// Answer INIT with a nonce that doesn't match the request
+        corrupt_nonce: bool,
+        /// Answer INIT for another client before answering ours
+        foreign_init: bool,
+        /// Keepalive statuses sent before each CBOR response
+        keepalives: Vec<u8>,
+        /// Keep sending keepalives instead of ever answering
+        endless_keepalives: bool,
+        /// CTAPHID_ERROR code sent instead of CBOR responses
+        error: Option<u8>,
+        /// Size of the reports exchanged
+        report_size: usize,
+        /// Times the handle was released, shared so it outlives the device
+        releases: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl FakeHid {
+        fn new(first_cid: u32) -> Self {
+            Self::with_report_size(first_cid, HID_REPORT_SIZE)
+        }
+
+        fn with_report_size(first_cid: u32, report_size: usize) -> Self {
+            Self {
+                next_cid: first_cid,
+                report_size,
+                ..Default::default()
+            }
+        }
+
+        fn handle(&mut self, cid: u32, command: u8, payload: Vec<u8>) -> YKeyResult<()> {
+            self.received.push((cid, command));
+            let encode = |cid, command, payload: &[u8]| encode_packets_sized(self.report_size, cid, command, payload);
+            match command {
+                CTAPHID_INIT => {
+                    if self.foreign_init {
+                        let mut foreign = vec![0xEE; 8];
+                        foreign.extend_from_slice(&0xDEAD_BEEFu32.to_be_bytes());
+                        foreign.extend_from_slice(&[2, 1, 0, 0, 0]);
+                        self.pending.extend(encode(cid, CTAPHID_INIT, &foreign)?);
+                    }
+
+                    let mut response = payload[..8].to_vec();
+                    if self.corrupt_nonce {
+                        response[0] ^= 0xFF;
+                    }
+                    response.extend_from_slice(&self.next_cid.to_be_bytes());
+                    response.extend_from_slice(&[2, 5, 4, 3, ChannelState::CAPABILITY_CBOR | ChannelState::CAPABILITY_WINK]);
+                    self.next_cid += 1;
+                    self.pending.extend(encode(cid, CTAPHID_INIT, &response)?);
+                }
+                CTAPHID_PING => self.pending.extend(encode(cid, command, &payload)?),
+                // Ends an endless wait for the user with CTAP2_ERR_KEEPALIVE_CANCEL
+                CTAPHID_CANCEL if self.endless_keepalives => {
+                    self.endless_keepalives = false;
+                    self.pending.extend(encode(cid, CTAPHID_CBOR, &[0x2D])?);
+                }
+                CTAPHID_WINK => self.pending.extend(encode(cid, command, &[])?),
+                CTAPHID_CBOR | CTAPHID_MSG => {
+                    for status in &self.keepalives {
+                        self.pending.extend(encode(cid, CTAPHID_KEEPALIVE, &[*status])?);
+                    }
+                    if self.endless_keepalives {
+                        return Ok(());
+                    }
+                    match self.error {
+                        Some(code) => self.pending.extend(encode(cid, CTAPHID_ERROR, &[code])?),
+                        None => self.pending.extend(encode(cid, command, &payload)?),
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+    }
+
+    impl HidReportIo for FakeHid {
+        fn write_report(&mut self, report: &[u8]) -> YKeyResult<()> {
+            if report.len() != self.report_size {
+                return Err(YKeyError::communication(format!("unexpected report length {}", report.len())));
+            }
+            let cid = u32::from_be_bytes([report[0], report[1], report[2], report[3]]);
+            let (cid, command, length, mut data) = match self.incoming.take() {
+                Some((cid, command, length, mut data)) => {
+                    data.extend_from_slice(&report[5..]);
+                    (cid, command, length, data)
+                }
+                None => {
+                    let length = u16::from_be_bytes([report[5], report[6]]) as usize;
+                    (cid, report[4], length, report[7..].to_vec())
+                }
+            };
+
+            if data.len() >= length {
+                data.truncate(length);
+                self.handle(cid, command, data)
+            } else {
+                self.incoming = Some((cid, command, length, data));
+                Ok(())
+            }
+        }
+
+        fn read_report(&mut self, _timeout: Duration) -> YKeyResult<Vec<u8>> {
+            if self.pending.is_empty() && self.endless_keepalives {
+                std::thread::sleep(Duration::from_millis(5));
+                let cid = self.next_cid - 1;
+                let packets = encode_packets_sized(self.report_size, cid, CTAPHID_KEEPALIVE, &[KEEPALIVE_UP_NEEDED])?;
+                return Ok(packets[0].clone());
+            }
+            self.pending.pop_front()
+                .ok_or_else(|| YKeyError::timeout(0))
+        }
+
+        fn report_size(&self) -> usize {
+            self.report_size
+        }
+
+        fn release(&mut self) {
+            self.releases.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    fn test_info() -> DeviceInfo {
+        DeviceInfo::new(
+            "hid-test".to_string(),
+            "HID Test".to_string(),
+            "Test".to_string(),
+            "Test".to_string(),
+            0x1050,
+            0x0407,
+            DeviceType::YubiKey,
+            TransportType::Usb,
+        )
+    }
+
+    #[test]
+    fn test_encode_packets() {
+        let payload = vec![0xAA; 100];
+        let packets = encode_packets(0x01020304, CTAPHID_CBOR, &payload).unwrap();
+
+        assert_eq!(packets.len(), 2);
+        assert_eq!(&packets[0][..7], &[0x01, 0x02, 0x03, 0x04, CTAPHID_CBOR, 0x00, 100]);
+        assert_eq!(&packets[1][..5], &[0x01, 0x02, 0x03, 0x04, 0x00]);
+        assert!(packets.iter().all(|p| p.len() == HID_REPORT_SIZE));
+    }
+
+    #[test]
+    fn test_encode_packets_sized() {
+        let payload = vec![0xAA; 100];
+        let packets = encode_packets_sized(32, 0x01020304, CTAPHID_CBOR, &payload).unwrap();
+
+        // 25 bytes in the init packet, then 27 per continuation packet
+        assert_eq!(packets.len(), 4);
+        assert!(packets.iter().all(|p| p.len() == 32));
+        assert_eq!(&packets[0][7..], &payload[..25]);
+        assert_eq!(&packets[1][5..], &payload[25..52]);
+        assert_eq!(packets[3][4], 2);
+
+        assert!(encode_packets_sized(7, 0x01020304, CTAPHID_CBOR, &payload).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fragments_framed_like_whole_messages() {
+        let payload = vec![0x5A; 300];
+        let mut device = HidDevice::new(test_info(), FakeHid::with_report_size(0x1000, 32));
+        device.connect().await.unwrap();
+
+        let (first, continuation) = device.packet_payload_sizes().unwrap();
+        assert_eq!((first, continuation), (25, 27));
+        let fragments = crate::split_message(&payload, first, continuation).unwrap();
+        assert_eq!(
+            frame_packets(32, 0x1000, CTAPHID_CBOR, &fragments).unwrap(),
+            encode_packets_sized(32, 0x1000, CTAPHID_CBOR, &payload).unwrap()
+        );
+        assert_eq!(device.send_fragments(&fragments).await.unwrap(), payload);
+
+        // Fragments sized for larger reports do not fit
+        let oversized = crate::split_message(&payload, 57, 59).unwrap();
+        assert!(matches!(device.send_fragments(&oversized).await, Err(YKeyError::InvalidParameters(_))));
+    }
+
+    #[test]
+    fn test_report_size_from_descriptor() {
+        // FIDO descriptor declaring 32-byte input and output reports
+        let descriptor = [
+            0x06, 0xD0, 0xF1, 0x09, 0x01, 0xA1, 0x01, // Usage Page (FIDO), Usage, Collection
+            0x09, 0x20, 0x15, 0x00, 0x26, 0xFF, 0x00, // Usage (Input), Logical Min/Max
+            0x75, 0x08, 0x95, 0x20, 0x81, 0x02, // Report Size (8), Report Count (32), Input
+            0x09, 0x21, 0x15, 0x00, 0x26, 0xFF, 0x00, // Usage (Output), Logical Min/Max
+            0x75, 0x08, 0x95, 0x20, 0x91, 0x02, // Report Size (8), Report Count (32), Output
+            0xC0,
+        ];
+        assert_eq!(report_size_from_descriptor(&descriptor), Some(32));
+        assert_eq!(report_size_from_descriptor(&descriptor[..7]), None);
+    }
+
+    #[test]
+    fn test_channel_uses_device_report_size() {
+        let mut channel = CtapHidChannel::new(FakeHid::with_report_size(0x1000, 32));
+        assert_eq!(channel.report_size(), 32);
+
+        channel.init().unwrap();
+        assert_eq!(channel.state().capabilities & ChannelState::CAPABILITY_CBOR, ChannelState::CAPABILITY_CBOR);
+        assert_eq!(channel.ping(&[0x5A; 100]).unwrap(), vec![0x5A; 100]);
+
+        // Reports of the wrong size are rejected by the device
+        channel.set_report_size(HID_REPORT_SIZE);
+        assert!(channel.ping(&[0x5A; 10]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_channel_state_after_init() {
+        let mut device = HidDevice::new(test_info(), FakeHid::new(0x1000));
+        assert_eq!(device.channel_state(), ChannelState::default());
+
+        device.connect().await.unwrap();
+        let state = device.channel_state();
+        assert_eq!(state.cid, Some(0x1000));
+        assert_eq!(state.protocol_version, 2);
+        assert_eq!(state.device_version, (5, 4, 3));
+        assert!(state.has_capability(ChannelState::CAPABILITY_CBOR));
+        assert!(!state.has_capability(ChannelState::CAPABILITY_NMSG));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_device_releases_handle() {
+        use std::sync::atomic::Ordering;
+
+        let hid = FakeHid::new(0x1000);
+        let releases = hid.releases.clone();
+        let mut device = HidDevice::new(test_info(), hid);
+        device.connect().await.unwrap();
+
+        // Dropped while connected, without awaiting disconnect
+        drop(device);
+        assert_eq!(releases.load(Ordering::SeqCst), 1);
+
+        let hid = FakeHid::new(0x1000);
+        let releases = hid.releases.clone();
+        let mut device = HidDevice::new(test_info(), hid);
+        device.connect().await.unwrap();
+        device.disconnect().await.unwrap();
+        assert_eq!(releases.load(Ordering::SeqCst), 0);
+        drop(device);
+        assert_eq!(releases.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_channel_state_updates_after_reset() {
+        let mut device = HidDevice::new(test_info(), FakeHid::new(0x1000));
+        device.connect().await.unwrap();
+
+        let payload = vec![0x55; 100];
+        let response = device.send_raw(&payload).await.unwrap();
+        assert_eq!(response, payload);
+        assert_eq!(device.channel_state().last_sequence, Some(0));
+        assert_eq!(device.channel_state().pending_bytes, 0);
+
+        device.reset_channel().await.unwrap();
+        let state = device.channel_state();
+        assert_eq!(state.cid, Some(0x1001));
+        assert_eq!(state.last_sequence, None);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_include_channel_state() {
+        let mut device = HidDevice::new(test_info(), FakeHid::new(0x2000));
+        device.connect().await.unwrap();
+
+        let report = device.diagnostics().await.unwrap();
+        assert_eq!(report.device.id, "hid-test");
+        assert_eq!(report.channel.unwrap().cid, Some(0x2000));
+    }
+
+    #[tokio::test]
+    async fn test_init_rejects_nonce_mismatch() {
+        let mut hid = FakeHid::new(0x1000);
+        hid.corrupt_nonce = true;
+        let mut device = HidDevice::new(test_info(), hid);
+
+        assert!(device.connect().await.is_err());
+        assert_eq!(device.channel_state().cid, None);
+        assert!(!device.is_connected());
+    }
+
+    #[test]
+    fn test_init_skips_other_clients_responses() {
+        let mut hid = FakeHid::new(0x1000);
+        hid.foreign_init = true;
+        let mut channel = CtapHidChannel::new(hid);
+
+        assert_eq!(channel.init().unwrap(), 0x1000);
+        assert_eq!(channel.cid(), Some(0x1000));
+    }
+
+    #[test]
+    fn test_cid_reused_across_messages() {
+        let mut channel = CtapHidChannel::new(FakeHid::new(0x3000));
+        assert!(channel.send_cbor(&[0x04]).is_err());
+
+        channel.init().unwrap();
+        assert_eq!(channel.send_cbor(&[0x04]).unwrap(), vec![0x04]);
+        assert_eq!(channel.send_msg(&[0x00, 0x03, 0x00, 0x00]).unwrap(), vec![0x00, 0x03, 0x00, 0x00]);
+        assert_eq!(channel.send_cbor(&[0x04]).unwrap(), vec![0x04]);
+
+        assert_eq!(
+            channel.io.received,
+            vec![
+                (BROADCAST_CID, CTAPHID_INIT),
+                (0x3000, CTAPHID_CBOR),
+                (0x3000, CTAPHID_MSG),
+                (0x3000, CTAPHID_CBOR),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keepalives_are_skipped() {
+        let mut hid = FakeHid::new(0x1000);
+        hid.keepalives = vec![KEEPALIVE_UP_NEEDED; 3];
+        let mut channel = CtapHidChannel::new(hid);
+        channel.init().unwrap();
+
+        assert_eq!(channel.send_cbor(&[0x02, 0xA0]).unwrap(), vec![0x02, 0xA0]);
+        assert_eq!(channel.state().last_keepalive, None);
+    }
+
+    #[tokio::test]
+    async fn test_keepalives_reach_handler_before_response() {
+        let mut hid = FakeHid::new(0x1000);
+        hid.keepalives = vec![KEEPALIVE_PROCESSING, KEEPALIVE_UP_NEEDED, KEEPALIVE_UP_NEEDED, 0x7E];
+        let mut device = HidDevice::new(test_info(), hid);
+        device.connect().await.unwrap();
+
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = seen.clone();
+        device.set_keepalive_handler(Some(Box::new(move |status| recorder.lock().unwrap().push(status))));
+
+        assert_eq!(device.send_raw(&[0x01, 0xA0]).await.unwrap(), vec![0x01, 0xA0]);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                KeepaliveStatus::Processing,
+                KeepaliveStatus::UpNeeded,
+                KeepaliveStatus::UpNeeded,
+                KeepaliveStatus::Other(0x7E),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_prompts_route_by_status() {
+        let mut hid = FakeHid::new(0x1000);
+        hid.keepalives = vec![
+            KEEPALIVE_PROCESSING,
+            KEEPALIVE_PROCESSING,
+            KEEPALIVE_UP_NEEDED,
+            KEEPALIVE_UP_NEEDED,
+            0x7E,
+            KEEPALIVE_PROCESSING,
+        ];
+        let mut device = HidDevice::new(test_info(), hid);
+        device.connect().await.unwrap();
+
+        let prompts = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (spinner, touch) = (prompts.clone(), prompts.clone());
+        device.set_keepalive_handler(Some(keepalive_prompts(
+            move || spinner.lock().unwrap().push("spinner"),
+            move || touch.lock().unwrap().push("touch your key"),
+        )));
+
+        device.send_raw(&[0x01, 0xA0]).await.unwrap();
+        assert_eq!(*prompts.lock().unwrap(), vec!["spinner", "touch your key", "spinner"]);
+        assert_eq!(KeepaliveStatus::from_code(0x7E).code(), 0x7E);
+        assert_eq!(KeepaliveStatus::UpNeeded.code(), KEEPALIVE_UP_NEEDED);
+    }
+
+    #[test]
+    fn test_keepalives_do_not_extend_timeout() {
+        let mut channel = CtapHidChannel::new(FakeHid::new(0x1000));
+        channel.init().unwrap();
+        channel.io.endless_keepalives = true;
+        channel.set_timeout(Duration::from_millis(30));
+
+        assert!(matches!(channel.send_cbor(&[0x01]), Err(YKeyError::Timeout { .. })));
+        assert_eq!(channel.state().last_keepalive, Some(KeepaliveStatus::UpNeeded));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_cancel_pending_assertion() {
+        let mut device = HidDevice::new(test_info(), FakeHid::new(0x1000));
+        device.connect().await.unwrap();
+        device.lock_channel().io.endless_keepalives = true;
+        let cancel = device.cancel_handle();
+
+        let mut client = crate::Fido2Client::new(device);
+        let params = GetAssertionParams::builder()
+            .rp_id("example.com")
+            .client_data_hash(vec![0xCD; 32])
+            .build()
+            .unwrap();
+        let pending = tokio::spawn(async move {
+            let result = client.get_assertion(params).await;
+            (result, client)
+        });
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(!pending.is_finished());
+        cancel.cancel();
+        let (result, client) = tokio::time::timeout(Duration::from_secs(5), pending).await.unwrap().unwrap();
+        assert!(matches!(result, Err(YKeyError::UserCancelled)));
+
+        let channel = client.device().lock_channel();
+        assert_eq!(channel.io.received.last(), Some(&(0x1000, CTAPHID_CANCEL)));
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_transaction_is_cancelled() {
+        let mut device = HidDevice::new(test_info(), FakeHid::new(0x1000));
+        device.connect().await.unwrap();
+        device.lock_channel().io.endless_keepalives = true;
+
+        // The single-threaded runtime stays free to fire the timeout
+        let result = tokio::time::timeout(Duration::from_millis(30), device.send_raw(&[0x02, 0xA0])).await;
+        assert!(result.is_err());
+
+        // The abandoned transaction was cancelled and the channel is free again
+        assert_eq!(device.send_raw(&[0x04]).await.unwrap(), vec![0x04]);
+        let channel = device.lock_channel();
+        assert_eq!(
+            channel.io.received[1..],
+            [(0x1000, CTAPHID_CBOR), (0x1000, CTAPHID_CANCEL), (0x1000, CTAPHID_CBOR)]
+        );
+    }
+
+    #[test]
+    fn test_error_responses_surface() {
+        let mut hid = FakeHid::new(0x1000);
+        hid.error = Some(ERR_CHANNEL_BUSY);
+        let mut channel = CtapHidChannel::new(hid);
+        channel.init().unwrap();
+        assert!(matches!(channel.send_cbor(&[0x04]), Err(YKeyError::DeviceBusy(_))));
+
+        channel.io.error = Some(0x01);
+        assert!(matches!(channel.send_cbor(&[0x04]), Err(YKeyError::CtapError { code: 0x01, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_ping_and_wink() {
+        let mut device = HidDevice::new(test_info(), FakeHid::new(0x1000));
+        device.connect().await.unwrap();
+
+        assert_eq!(device.ping(&[0xAB; 80]).await.unwrap(), vec![0xAB; 80]);
+        device.wink().await.unwrap();
+
+        // Without the capability flags the commands are reported as unsupported
+        device.lock_channel().state.capabilities = ChannelState::CAPABILITY_CBOR | ChannelState::CAPABILITY_NMSG;
+        match device.wink().await {
+            Err(YKeyError::UnsupportedOperation { operation, .. }) => assert_eq!(operation, "wink"),
+            other => panic!("expected UnsupportedOperation, got {:?}", other),
+        }
+        assert!(device.lock_channel().send_msg(&[0x00, 0x03, 0x00, 0x00]).unwrap_err().is_unsupported());
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Diagnostic reports for troubleshooting device communication
+
+use ykey_core::{types::DeviceInfo, YKeyError, YKeyResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::ctaphid::ChannelState;
+
+/// Snapshot of a device's state, suitable for attaching to bug reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    /// Device metadata
+    pub device: DeviceInfo,
+    /// CTAPHID channel state, for HID devices
+    pub channel: Option<ChannelState>,
+    /// When the report was generated
+    pub generated_at: DateTime<Utc>,
+}
+
+impl DiagnosticsReport {
+    /// Create a new report for a device
+    pub fn new(device: DeviceInfo) -> Self {
+        Self {
+            device,
+            channel: None,
+            generated_at: Utc::now(),
+        }
+    }
+}
+
+/// A response field that does not match the CTAP specification
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CborFieldIssue {
+    /// Response the field belongs to, e.g. `GetInfo`
+    pub response: String,
+    /// Field name as the specification spells it, e.g. `versions`
+    pub field: String,
+    /// What is wrong with the field
+    pub problem: String,
+}
+
+impl fmt::Display for CborFieldIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.response, self.field, self.problem)
+    }
+}
+
+/// Field problems found while decoding one response
+///
+/// Strict decoding fails on the first problem, with the error the field
+/// produced. Diagnostic decoding records every problem, substitutes a
+/// default for the field and carries on.
+pub(crate) struct FieldIssues {
+    response: &'static str,
+    diagnostic: bool,
+    issues: Vec<CborFieldIssue>,
+}
+
+impl FieldIssues {
+    pub(crate) fn new(response: &'static str, diagnostic: bool) -> Self {
+        Self { response, diagnostic, issues: Vec::new() }
+    }
+
+    /// Keep an optional field, recording a value that failed to decode
+    pub(crate) fn optional<T>(&mut self, field: &str, value: YKeyResult<Option<T>>) -> YKeyResult<Option<T>> {
+        match value {
+            Ok(value) => Ok(value),
+            Err(error) => self.record_error(field, error).map(|_| None),
+        }
+    }
+
+    /// Keep a required field, recording it as missing when absent
+    pub(crate) fn required<T: Default>(&mut self, field: &str, value: YKeyResult<Option<T>>) -> YKeyResult<T> {
+        match value {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => {
+                let missing = YKeyError::communication(format!("{} missing {}", self.response, field));
+                self.record(field, missing, "missing required field".to_string()).map(|_| T::default())
+            }
+            Err(error) => self.record_error(field, error).map(|_| T::default()),
+        }
+    }
+
+    pub(crate) fn into_issues(self) -> Vec<CborFieldIssue> {
+        self.issues
+    }
+
+    fn record_error(&mut self, field: &str, error: YKeyError) -> YKeyResult<()> {
+        let problem = match &error {
+            YKeyError::CommunicationError(message) => message.clone(),
+            error => error.to_string(),
+        };
+        self.record(field, error, problem)
+    }
+
+    /// Fail with `error` when strict, otherwise note `problem` and continue
+    fn record(&mut self, field: &str, error: YKeyError, problem: String) -> YKeyResult<()> {
+        if !self.diagnostic {
+            return Err(error);
+        }
+        self.issues.push(CborFieldIssue { response: self.response.to_string(), field: field.to_string(), problem });
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Symmetric keys derived through the hmac-secret extension
+//!
+//! The authenticator keeps a random secret per credential and returns
+//! HMAC-SHA-256(secret, salt) for a salt chosen by the platform. The salt
+//! travels encrypted under the PIN/UV shared secret and so does the output,
+//! so neither is visible on the wire. Fixing the salt per application turns
+//! this into a stable key that only exists while the security key is present.
+
+use crate::{
+    cbor,
+    cose::AuthenticatorData,
+    pin::{Permissions, PinUvAuthProtocol},
+    CtapCommand, Fido2Client,
+};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use ykey_core::{traits::Device, types::PublicKeyCredentialDescriptor, YKeyError, YKeyResult};
+
+/// Extension name in requests and authenticator data
+pub const HMAC_SECRET_EXTENSION: &str = "hmac-secret";
+
+impl<D: Device> Fido2Client<D> {
+    /// Derive a 32-byte key from a credential's hmac-secret and an app salt
+    ///
+    /// The same credential and `app_salt` always yield the same key, so it
+    /// can encrypt data that is decrypted later by deriving it again.
+    /// `app_salt` may be any length; it is hashed into the 32-byte salt the
+    /// extension takes.
+    ///
+    /// User verification is performed whenever the authenticator supports
+    /// it, with `pin` as the fallback for built-in UV. Authenticators keep
+    /// separate secrets for verified and unverified requests, so a key
+    /// derived before a PIN was set differs from one derived after.
+    pub async fn derive_encryption_key(
+        &mut self,
+        rp_id: &str,
+        credential_id: &[u8],
+        app_salt: &[u8],
+        pin: Option<&str>,
+    ) -> YKeyResult<[u8; 32]> {
+        let info = self.authenticator_info().await?;
+        if !info.extensions.as_ref().is_some_and(|e| e.iter().any(|e| e == HMAC_SECRET_EXTENSION)) {
+            return Err(YKeyError::unsupported(
+                HMAC_SECRET_EXTENSION,
+                "authenticator does not advertise the extension",
+            ));
+        }
+        let token = if info.option_enabled("clientPin") || info.option_enabled("uv") {
+            Some(self.obtain_uv(pin, Permissions::GET_ASSERTION, Some(rp_id)).await?)
+        } else {
+            None
+        };
+
+        let protocol = token.as_ref().map_or(PinUvAuthProtocol::Two, |token| token.protocol());
+        let (platform_key, shared) = self.key_agreement(protocol).await?;
+        let salt = Sha256::digest(app_salt);
+        let salt_enc = shared.encrypt_with_rng(self.rng.as_mut(), &salt)?;
+        let extension = cbor::int_map(vec![
+            (0x01, Some(platform_key)),
+            (0x02, Some(cbor::bytes(&salt_enc))),
+            (0x03, Some(cbor::bytes(&shared.authenticate(&salt_enc)))),
+            (0x04, Some(cbor::int(protocol.version() as i64))),
+        ]);
+
+        let mut client_data_hash = [0u8; 32];
+        self.rng.fill_bytes(&mut client_data_hash);
+        let descriptor = PublicKeyCredentialDescriptor {
+            cred_type: "public-key".to_string(),
+            id: credential_id.to_vec(),
+            transports: None,
+        };
+        let response = self
+            .send_cbor(
+                0x02,
+                Some(cbor::int_map(vec![
+                    (0x01, Some(cbor::text(rp_id))),
+                    (0x02, Some(cbor::bytes(&client_data_hash))),
+                    (0x03, Some(CtapCommand::descriptor_list(&[descriptor]))),
+                    (0x04, Some(cbor::Value::Map(vec![(cbor::text(HMAC_SECRET_EXTENSION), extension)]))),
+                    (0x06, token.as_ref().map(|token| cbor::bytes(&token.authenticate(&client_data_hash)))),
+                    (0x07, token.as_ref().map(|token| cbor::int(token.protocol().version() as i64))),
+                ])),
+            )
+            .await?
+            .ok_or_else(|| YKeyError::communication("Missing assertion response"))?;
+
+        let auth_data = cbor::get_int(cbor::as_map(&response)?, 0x02)
+            .ok_or_else(|| YKeyError::communication("Assertion missing authData"))
+            .and_then(cbor::to_bytes)?;
+        let output_enc = AuthenticatorData::parse(&auth_data)?
+            .extensions
+            .as_ref()
+            .and_then(|extensions| cbor::get_text(cbor::as_map(extensions).ok()?, HMAC_SECRET_EXTENSION).cloned())
+            .ok_or_else(|| YKeyError::InvalidCredential("Credential returned no hmac-secret output".to_string()))
+            .and_then(|output| cbor::to_bytes(&output))?;
+
+        shared.decrypt(&output_enc)?.try_into().map_err(|output: Vec<u8>| {
+            YKeyError::communication(format!("hmac-secret output of {} bytes, expected 32", output.len()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soft::SoftAuthenticator;
+
+    #[tokio::test]
+    async fn test_derived_key_is_deterministic() {
+        let mut authenticator = SoftAuthenticator::new("1234");
+        let credential = authenticator.add_credential("example.com");
+        let other = authenticator.add_credential("example.com");
+        let mut client = Fido2Client::new(authenticator);
+
+        let key = client.derive_encryption_key("example.com", &credential, b"files", Some("1234")).await.unwrap();
+        let again = client.derive_encryption_key("example.com", &credential, b"files", Some("1234")).await.unwrap();
+        assert_eq!(key, again);
+
+        let other_salt = client.derive_encryption_key("example.com", &credential, b"notes", Some("1234")).await.unwrap();
+        let other_credential = client.derive_encryption_key("example.com", &other, b"files", Some("1234")).await.unwrap();
+        assert_ne!(key, other_salt);
+        assert_ne!(key, other_credential);
+
+        let missing_pin = client.derive_encryption_key("example.com", &credential, b"files", None).await;
+        assert!(matches!(missing_pin, Err(YKeyError::PinRequired)));
+    }
+
+    #[tokio::test]
+    async fn test_derived_key_without_user_verification() {
+        let mut authenticator = SoftAuthenticator::blank();
+        let credential = authenticator.add_credential("example.com");
+        let unverified = authenticator.hmac_secret_output(&credential, false, b"files");
+        let mut client = Fido2Client::new(authenticator);
+
+        let key = client.derive_encryption_key("example.com", &credential, b"files", None).await.unwrap();
+        assert_eq!(key, unverified);
+        assert_eq!(key, client.derive_encryption_key("example.com", &credential, b"files", None).await.unwrap());
+    }
+}
@@ -0,0 +1,497 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! caBLE v2 hybrid transport scaffolding
+//!
+//! Covers the client side of a QR-initiated hybrid transaction up to the point
+//! where CTAP messages start flowing: the `FIDO:/` QR code shown to the phone,
+//! the keys derived from its secret, decrypting the phone's BLE advert and the
+//! Noise KNpsk0 handshake. Scanning for adverts and connecting to the tunnel
+//! service are left to a [`Transport`] implementation; [`handshake`] only needs
+//! one that carries whole messages.
+
+use crate::{cbor, pin::hmac_sha256, rng::RngSource};
+use aes::{cipher::BlockDecrypt, Aes256};
+use aes_gcm::{aead::{Aead, KeyInit, Payload}, Aes256Gcm, Nonce};
+use hkdf::Hkdf;
+use p256::{elliptic_curve::sec1::ToEncodedPoint, PublicKey, SecretKey};
+use sha2::{Digest, Sha256};
+use ykey_core::{traits::Transport, YKeyError, YKeyResult};
+
+/// Scheme prefix of the QR code URL
+pub const QR_PREFIX: &str = "FIDO:/";
+
+/// Length of the secret carried in the QR code
+pub const QR_SECRET_LENGTH: usize = 16;
+
+/// Length of a BLE advert: one AES block plus a truncated HMAC
+pub const ADVERT_LENGTH: usize = 20;
+
+/// Number of tunnel server domains this client knows how to reach
+const KNOWN_DOMAINS: u64 = 2;
+
+/// Noise protocol name; shorter than the hash, so zero-padded rather than hashed
+const NOISE_PROTOCOL: &[u8] = b"Noise_KNpsk0_P256_AESGCM_SHA256";
+
+/// Prologue mixed into QR-initiated handshakes
+const QR_PROLOGUE: &[u8] = &[0];
+
+/// Length of an uncompressed P-256 point
+const POINT_LENGTH: usize = 65;
+
+/// Length of an AES-GCM tag
+const TAG_LENGTH: usize = 16;
+
+/// Decimal digits needed for a trailing chunk of 0 to 6 bytes
+const PARTIAL_CHUNK_DIGITS: [usize; 7] = [0, 3, 5, 8, 10, 13, 15];
+
+/// Operation the phone is asked to perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestType {
+    GetAssertion,
+    MakeCredential,
+}
+
+impl RequestType {
+    fn as_str(self) -> &'static str {
+        match self {
+            RequestType::GetAssertion => "ga",
+            RequestType::MakeCredential => "mc",
+        }
+    }
+}
+
+/// Contents of the QR code scanned by the phone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrPayload {
+    /// Compressed P-256 public key identifying this client
+    pub identity_key: [u8; 33],
+    /// Secret the advert, tunnel and handshake keys are derived from
+    pub secret: [u8; QR_SECRET_LENGTH],
+    /// Seconds since the Unix epoch when the code was generated
+    pub timestamp: u64,
+    /// Whether this client can store linking information
+    pub supports_linking: bool,
+    pub request_type: RequestType,
+}
+
+impl QrPayload {
+    /// Generate a payload with a fresh secret for the given identity key
+    pub fn new(
+        identity: &SecretKey,
+        request_type: RequestType,
+        timestamp: u64,
+        rng: &mut dyn RngSource,
+    ) -> Self {
+        let mut secret = [0u8; QR_SECRET_LENGTH];
+        rng.fill_bytes(&mut secret);
+
+        let mut identity_key = [0u8; 33];
+        identity_key.copy_from_slice(identity.public_key().to_encoded_point(true).as_bytes());
+        Self {
+            identity_key,
+            secret,
+            timestamp,
+            supports_linking: false,
+            request_type,
+        }
+    }
+
+    /// CBOR encoding of the payload
+    pub fn to_cbor(&self) -> YKeyResult<Vec<u8>> {
+        cbor::encode(&cbor::int_map(vec![
+            (0, Some(cbor::bytes(&self.identity_key))),
+            (1, Some(cbor::bytes(&self.secret))),
+            (2, Some(cbor::int(KNOWN_DOMAINS as i64))),
+            (3, Some(cbor::int(self.timestamp as i64))),
+            (4, Some(cbor::Value::Bool(self.supports_linking))),
+            (5, Some(cbor::text(self.request_type.as_str()))),
+        ]))
+    }
+
+    /// URL to render as the QR code
+    pub fn to_url(&self) -> YKeyResult<String> {
+        Ok(format!("{}{}", QR_PREFIX, encode_digits(&self.to_cbor()?)))
+    }
+}
+
+/// Encode bytes as decimal digits, which QR codes store more compactly
+///
+/// Every 7 bytes become a little-endian integer written as 17 digits; a
+/// shorter trailing chunk uses just enough digits for its largest value.
+pub fn encode_digits(data: &[u8]) -> String {
+    let mut digits = String::new();
+    for chunk in data.chunks(7) {
+        let mut value = [0u8; 8];
+        value[..chunk.len()].copy_from_slice(chunk);
+        let width = if chunk.len() == 7 { 17 } else { PARTIAL_CHUNK_DIGITS[chunk.len()] };
+        digits.push_str(&format!("{:0width$}", u64::from_le_bytes(value), width = width));
+    }
+    digits
+}
+
+/// Purpose labels for keys derived from the QR secret
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum KeyPurpose {
+    EidKey = 1,
+    TunnelId = 2,
+    Psk = 3,
+    PairedSecret = 4,
+    IdentityKeySeed = 5,
+    PerContactIdSecret = 6,
+}
+
+/// HKDF-SHA-256 over `secret`, salted with `nonce` and labelled with `purpose`
+pub fn derive<const N: usize>(secret: &[u8], nonce: &[u8], purpose: KeyPurpose) -> [u8; N] {
+    let salt = (!nonce.is_empty()).then_some(nonce);
+    let mut output = [0u8; N];
+    // Every caller asks for at most 64 bytes, far below the HKDF limit
+    Hkdf::<Sha256>::new(salt, secret)
+        .expand(&(purpose as u32).to_le_bytes(), &mut output)
+        .expect("valid HKDF length");
+    output
+}
+
+/// Keys derived from the QR secret before the phone is found
+pub struct QrKeys {
+    /// AES key followed by HMAC key for decrypting adverts
+    pub eid_key: [u8; 64],
+    /// Identifies the tunnel to the tunnel service
+    pub tunnel_id: [u8; 16],
+}
+
+impl QrKeys {
+    pub fn derive(secret: &[u8; QR_SECRET_LENGTH]) -> Self {
+        Self {
+            eid_key: derive(secret, &[], KeyPurpose::EidKey),
+            tunnel_id: derive(secret, &[], KeyPurpose::TunnelId),
+        }
+    }
+
+    /// Decrypt a BLE advert, or `None` when it is not from our phone
+    pub fn decrypt_advert(&self, advert: &[u8]) -> Option<Advert> {
+        if advert.len() != ADVERT_LENGTH {
+            return None;
+        }
+        let (ciphertext, tag) = advert.split_at(16);
+        if hmac_sha256(&self.eid_key[32..], ciphertext)[..4] != *tag {
+            return None;
+        }
+
+        let mut block = aes::Block::clone_from_slice(ciphertext);
+        Aes256::new_from_slice(&self.eid_key[..32])
+            .expect("32-byte AES key")
+            .decrypt_block(&mut block);
+        Advert::parse(block.into())
+    }
+}
+
+/// Decrypted contents of the phone's BLE advert
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Advert {
+    /// The decrypted block, which salts the PSK derivation
+    pub plaintext: [u8; 16],
+    pub nonce: [u8; 10],
+    /// Routes the tunnel connection to the phone
+    pub routing_id: [u8; 3],
+    /// Which tunnel server domain the phone connected to
+    pub domain: u16,
+}
+
+impl Advert {
+    fn parse(plaintext: [u8; 16]) -> Option<Self> {
+        // The first byte is reserved and must be zero
+        if plaintext[0] != 0 {
+            return None;
+        }
+        let mut nonce = [0u8; 10];
+        nonce.copy_from_slice(&plaintext[1..11]);
+        let mut routing_id = [0u8; 3];
+        routing_id.copy_from_slice(&plaintext[11..14]);
+        Some(Self {
+            plaintext,
+            nonce,
+            routing_id,
+            domain: u16::from_le_bytes([plaintext[14], plaintext[15]]),
+        })
+    }
+
+    /// Handshake pre-shared key for this advert
+    pub fn psk(&self, secret: &[u8; QR_SECRET_LENGTH]) -> [u8; 32] {
+        derive(secret, &self.plaintext, KeyPurpose::Psk)
+    }
+}
+
+/// Keys protecting CTAP messages once the handshake completes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrafficKeys {
+    /// Encrypts messages to the phone
+    pub write_key: [u8; 32],
+    /// Decrypts messages from the phone
+    pub read_key: [u8; 32],
+}
+
+/// Noise symmetric state
+struct SymmetricState {
+    chaining_key: [u8; 32],
+    hash: [u8; 32],
+    key: Option<[u8; 32]>,
+    counter: u64,
+}
+
+impl SymmetricState {
+    fn new() -> Self {
+        let mut hash = [0u8; 32];
+        hash[..NOISE_PROTOCOL.len()].copy_from_slice(NOISE_PROTOCOL);
+        Self { chaining_key: hash, hash, key: None, counter: 0 }
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        self.hash = Sha256::new().chain_update(self.hash).chain_update(data).finalize().into();
+    }
+
+    fn mix_key(&mut self, input: &[u8]) {
+        let [chaining_key, key, _] = noise_hkdf(&self.chaining_key, input);
+        self.chaining_key = chaining_key;
+        self.key = Some(key);
+        self.counter = 0;
+    }
+
+    fn mix_key_and_hash(&mut self, input: &[u8]) {
+        let [chaining_key, hash, key] = noise_hkdf(&self.chaining_key, input);
+        self.chaining_key = chaining_key;
+        self.mix_hash(&hash);
+        self.key = Some(key);
+        self.counter = 0;
+    }
+
+    fn cipher(&mut self) -> (Aes256Gcm, [u8; 12]) {
+        let key = self.key.expect("handshake keys are mixed before encryption");
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&self.counter.to_be_bytes());
+        self.counter += 1;
+        (Aes256Gcm::new(&key.into()), nonce)
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let (cipher, nonce) = self.cipher();
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &self.hash })
+            .expect("AES-GCM encryption cannot fail");
+        self.mix_hash(&ciphertext);
+        ciphertext
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> YKeyResult<Vec<u8>> {
+        let (cipher, nonce) = self.cipher();
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: ciphertext, aad: &self.hash })
+            .map_err(|_| YKeyError::auth_failed("Hybrid handshake response failed to decrypt"))?;
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    fn split(&self) -> ([u8; 32], [u8; 32]) {
+        let [first, second, _] = noise_hkdf(&self.chaining_key, &[]);
+        (first, second)
+    }
+}
+
+/// The Noise HKDF, producing three outputs
+fn noise_hkdf(chaining_key: &[u8; 32], input: &[u8]) -> [[u8; 32]; 3] {
+    let temp = hmac_sha256(chaining_key, input);
+    let first = hmac_sha256(&temp, &[1]);
+    let second = hmac_sha256(&temp, &[&first[..], &[2]].concat());
+    let third = hmac_sha256(&temp, &[&second[..], &[3]].concat());
+    [first, second, third]
+}
+
+fn ecdh(secret: &SecretKey, peer: &PublicKey) -> [u8; 32] {
+    p256::ecdh::diffie_hellman(secret.to_nonzero_scalar(), peer.as_affine())
+        .raw_secret_bytes()
+        .as_slice()
+        .try_into()
+        .expect("P-256 shared secrets are 32 bytes")
+}
+
+/// Client side of the KNpsk0 handshake with the phone
+pub struct HandshakeInitiator {
+    state: SymmetricState,
+    identity: SecretKey,
+    ephemeral: SecretKey,
+}
+
+impl HandshakeInitiator {
+    /// Start a handshake with a fresh ephemeral key
+    pub fn new(psk: &[u8; 32], identity: &SecretKey, mut rng: &mut dyn RngSource) -> Self {
+        Self::with_ephemeral(psk, identity, SecretKey::random(&mut rng))
+    }
+
+    /// Start a handshake with the given ephemeral key
+    pub fn with_ephemeral(psk: &[u8; 32], identity: &SecretKey, ephemeral: SecretKey) -> Self {
+        let mut state = SymmetricState::new();
+        state.mix_hash(QR_PROLOGUE);
+        state.mix_hash(identity.public_key().to_encoded_point(false).as_bytes());
+        state.mix_key_and_hash(psk);
+        Self { state, identity: identity.clone(), ephemeral }
+    }
+
+    /// First handshake message: our ephemeral key and an empty payload
+    pub fn initial_message(&mut self) -> Vec<u8> {
+        let ephemeral = self.ephemeral.public_key().to_encoded_point(false);
+        self.state.mix_hash(ephemeral.as_bytes());
+        self.state.mix_key(ephemeral.as_bytes());
+
+        let mut message = ephemeral.as_bytes().to_vec();
+        message.extend(self.state.encrypt_and_hash(&[]));
+        message
+    }
+
+    /// Complete the handshake from the phone's response
+    pub fn process_response(mut self, response: &[u8]) -> YKeyResult<TrafficKeys> {
+        if response.len() < POINT_LENGTH + TAG_LENGTH {
+            return Err(YKeyError::communication("Hybrid handshake response is truncated"));
+        }
+        let (peer_point, ciphertext) = response.split_at(POINT_LENGTH);
+        let peer = PublicKey::from_sec1_bytes(peer_point)
+            .map_err(|_| YKeyError::communication("Hybrid handshake response has an invalid key"))?;
+
+        self.state.mix_hash(peer_point);
+        self.state.mix_key(peer_point);
+        self.state.mix_key(&ecdh(&self.ephemeral, &peer));
+        self.state.mix_key(&ecdh(&self.identity, &peer));
+        self.state.decrypt_and_hash(ciphertext)?;
+
+        let (write_key, read_key) = self.state.split();
+        Ok(TrafficKeys { write_key, read_key })
+    }
+}
+
+/// Run the handshake over a connected tunnel
+pub async fn handshake(
+    transport: &mut dyn Transport,
+    mut initiator: HandshakeInitiator,
+) -> YKeyResult<TrafficKeys> {
+    transport.send(&initiator.initial_message()).await?;
+    let response = transport.receive().await?;
+    initiator.process_response(&response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use ykey_core::{traits::TransportProperties, types::TransportType};
+
+    const SECRET: [u8; 16] = [0x5A; 16];
+
+    fn key(byte: u8) -> SecretKey {
+        SecretKey::from_slice(&[byte; 32]).unwrap()
+    }
+
+    /// Tunnel replaying a recorded response from the phone
+    struct ReplayTunnel {
+        sent: Vec<Vec<u8>>,
+        response: Vec<u8>,
+    }
+
+    #[async_trait]
+    impl Transport for ReplayTunnel {
+        async fn send(&mut self, data: &[u8]) -> YKeyResult<()> {
+            self.sent.push(data.to_vec());
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> YKeyResult<Vec<u8>> {
+            Ok(self.response.clone())
+        }
+
+        fn is_connected(&self) -> bool {
+            true
+        }
+
+        async fn close(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        fn properties(&self) -> TransportProperties {
+            TransportProperties {
+                max_packet_size: usize::MAX,
+                supports_fragmentation: false,
+                connection_type: TransportType::Hybrid,
+                latency_ms: None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_qr_payload_encoding() {
+        assert_eq!(encode_digits(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]), "0197694344888371300657672");
+
+        let payload = QrPayload {
+            identity_key: key(0x11).public_key().to_encoded_point(true).as_bytes().try_into().unwrap(),
+            secret: SECRET,
+            timestamp: 1_700_000_000,
+            supports_linking: false,
+            request_type: RequestType::GetAssertion,
+        };
+        assert_eq!(
+            hex::encode(payload.to_cbor().unwrap()),
+            "a6005821020217e617f0b6443928278f96999e69a23a4f2c152bdf6d6cdf66e5b80282d4ed01505a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a0202031a6553f10004f405626761"
+        );
+        assert_eq!(
+            payload.to_url().unwrap(),
+            "FIDO:/064761326369506941132196635817367045715076129656615309261485932746823659474011834762825432091839819220254320920133863300066229406284860200265340480783106107096654083076"
+        );
+    }
+
+    #[test]
+    fn test_derived_keys() {
+        let keys = QrKeys::derive(&SECRET);
+        assert_eq!(
+            hex::encode(keys.eid_key),
+            "1bb70b3d7128be712a6a3d4cdbabee4dfac3c8e7f53e2dfe354ea5ef12d22ec70b808ec74631ca4cf14d43800a556521175449c638a0a3afc0b67c86b1791a31"
+        );
+        assert_eq!(hex::encode(keys.tunnel_id), "ae35f2ae2ab59866068c642bb1f95d8c");
+
+        let mut advert = hex::decode("081b75d1f85d6c4765dac09234222b5ce5133d49").unwrap();
+        let decrypted = keys.decrypt_advert(&advert).unwrap();
+        assert_eq!(decrypted.nonce, [0x10; 10]);
+        assert_eq!(decrypted.routing_id, [1, 2, 3]);
+        assert_eq!(decrypted.domain, 1);
+        assert_eq!(
+            hex::encode(decrypted.psk(&SECRET)),
+            "9a8a6648cdbca4546750d2bc002cd455f3d306a8af12cf6efd208dfff97570fa"
+        );
+
+        // A tampered advert fails its HMAC and is ignored
+        advert[0] ^= 1;
+        assert!(keys.decrypt_advert(&advert).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_traffic_keys() {
+        let initiator = HandshakeInitiator::with_ephemeral(&[0x44; 32], &key(0x11), key(0x22));
+        let mut tunnel = ReplayTunnel {
+            sent: Vec::new(),
+            response: hex::decode(
+                "0451a7580833898ea1b183cbd7350a4099078c6ef1c1e18e970cd7683035f25e7d0110522712b0b5a7cff081685486984a94e6831edac46e7360fa9d834a7a81a1313f39a423b421c1ccb5fcdd251dc770",
+            )
+            .unwrap(),
+        };
+
+        let keys = handshake(&mut tunnel, initiator).await.unwrap();
+        assert_eq!(
+            hex::encode(&tunnel.sent[0]),
+            "04d65a93977caa3d1b081852ff57a79e465f1660577304baead505dd3a48589cf350185e895372df6221ea3a137557e473fddb6755f05bd507c3c533fce9c9128575f92895073fe29f8b306ab5f7013a0c"
+        );
+        assert_eq!(hex::encode(keys.write_key), "edf85a83ac5cb0e6f46e08f9d832610cd7637277e048743fb3f62424c7944bbf");
+        assert_eq!(hex::encode(keys.read_key), "0f3454d6cb9fa11fa6645808f84a99ade7e51acd7de9ae8d59782866b9119df1");
+
+        // A response under a different PSK does not authenticate
+        let mut initiator = HandshakeInitiator::with_ephemeral(&[0x45; 32], &key(0x11), key(0x22));
+        initiator.initial_message();
+        assert!(initiator.process_response(&tunnel.response).is_err());
+    }
+}
@@ -0,0 +1,544 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Large blob storage and per-credential encrypted secrets
+//!
+//! CTAP 2.1 authenticators expose a single large blob array shared by all
+//! credentials. Each entry is encrypted with the largeBlobKey of the credential
+//! it belongs to, so a credential can only read back its own data.
+
+use crate::{cbor, pin::{PinUvAuthToken, Permissions}, rng::RngSource, CtapCommand, Fido2Client};
+use aes_gcm::{aead::{Aead, Payload}, Aes256Gcm, KeyInit, Nonce};
+use rand::{rngs::OsRng, RngCore};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use ykey_core::{traits::Device, types::*, YKeyError, YKeyResult};
+
+/// authenticatorLargeBlobs command byte
+pub const LARGE_BLOBS_COMMAND: u8 = 0x0C;
+
+/// Extension requesting the credential's largeBlobKey
+pub const LARGE_BLOB_KEY_EXTENSION: &str = "largeBlobKey";
+
+/// maxMsgSize assumed when the authenticator does not report one
+const DEFAULT_MAX_MSG_SIZE: usize = 1024;
+
+/// Room left in each message for the command's other parameters
+const MESSAGE_OVERHEAD: usize = 64;
+
+/// Length of the truncated SHA-256 checksum trailing the array
+const CHECKSUM_LENGTH: usize = 16;
+
+/// Serialize large blob entries, appending the trailing checksum
+pub fn serialize_array(entries: &[cbor::Value]) -> YKeyResult<Vec<u8>> {
+    let mut data = cbor::encode(&cbor::Value::Array(entries.to_vec()))?;
+    let checksum = Sha256::digest(&data);
+    data.extend_from_slice(&checksum[..CHECKSUM_LENGTH]);
+    Ok(data)
+}
+
+/// Parse a serialized large blob array, verifying its checksum
+pub fn parse_array(data: &[u8]) -> YKeyResult<Vec<cbor::Value>> {
+    if data.len() < CHECKSUM_LENGTH + 1 {
+        return Err(YKeyError::InvalidCredential("Large blob array is truncated".to_string()));
+    }
+
+    let (body, checksum) = data.split_at(data.len() - CHECKSUM_LENGTH);
+    if Sha256::digest(body)[..CHECKSUM_LENGTH] != *checksum {
+        return Err(YKeyError::InvalidCredential("Large blob array checksum mismatch".to_string()));
+    }
+
+    let array = cbor::decode(body, false)?;
+    Ok(cbor::to_array(&array)?.to_vec())
+}
+
+/// Encrypt data into a large blob entry for the given largeBlobKey
+pub fn encrypt_entry(large_blob_key: &[u8], plaintext: &[u8]) -> YKeyResult<cbor::Value> {
+    encrypt_entry_with_rng(&mut OsRng, large_blob_key, plaintext)
+}
+
+/// Encrypt data into a large blob entry, drawing the nonce from `rng`
+pub fn encrypt_entry_with_rng(
+    rng: &mut dyn RngSource,
+    large_blob_key: &[u8],
+    plaintext: &[u8],
+) -> YKeyResult<cbor::Value> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(plaintext)?;
+    let compressed = encoder.finish()?;
+
+    let mut nonce = [0u8; 12];
+    rng.fill_bytes(&mut nonce);
+    let ciphertext = entry_cipher(large_blob_key)?
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload { msg: &compressed, aad: &entry_aad(plaintext.len() as u64) },
+        )
+        .map_err(|_| YKeyError::InvalidParameters("Failed to encrypt large blob".to_string()))?;
+
+    Ok(cbor::int_map(vec![
+        (0x01, Some(cbor::bytes(&ciphertext))),
+        (0x02, Some(cbor::bytes(&nonce))),
+        (0x03, Some(cbor::int(plaintext.len() as i64))),
+    ]))
+}
+
+/// Decrypt a large blob entry, returning `None` if it belongs to another key
+pub fn decrypt_entry(large_blob_key: &[u8], entry: &cbor::Value) -> Option<Vec<u8>> {
+    let map = cbor::as_map(entry).ok()?;
+    let ciphertext = cbor::to_bytes(cbor::get_int(map, 0x01)?).ok()?;
+    let nonce = cbor::to_bytes(cbor::get_int(map, 0x02)?).ok()?;
+    let orig_size = cbor::to_u64(cbor::get_int(map, 0x03)?).ok()?;
+    if nonce.len() != 12 {
+        return None;
+    }
+
+    let compressed = entry_cipher(large_blob_key)
+        .ok()?
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload { msg: &ciphertext, aad: &entry_aad(orig_size) },
+        )
+        .ok()?;
+
+    let mut plaintext = Vec::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .take(orig_size)
+        .read_to_end(&mut plaintext)
+        .ok()?;
+    (plaintext.len() as u64 == orig_size).then_some(plaintext)
+}
+
+fn entry_cipher(large_blob_key: &[u8]) -> YKeyResult<Aes256Gcm> {
+    Aes256Gcm::new_from_slice(large_blob_key)
+        .map_err(|_| YKeyError::InvalidCredential("largeBlobKey must be 32 bytes".to_string()))
+}
+
+fn entry_aad(orig_size: u64) -> Vec<u8> {
+    let mut aad = b"blob".to_vec();
+    aad.extend_from_slice(&orig_size.to_le_bytes());
+    aad
+}
+
+/// Message authenticated by the pinUvAuthParam of a large blob write
+fn set_auth_message(offset: u32, fragment: &[u8]) -> Vec<u8> {
+    let mut message = vec![0xFF; 32];
+    message.extend_from_slice(&[LARGE_BLOBS_COMMAND, 0x00]);
+    message.extend_from_slice(&offset.to_le_bytes());
+    message.extend_from_slice(&Sha256::digest(fragment));
+    message
+}
+
+impl<D: Device> Fido2Client<D> {
+    /// Read and verify the large blob array
+    ///
+    /// The array is read in fragments sized to the authenticator's maxMsgSize
+    /// and rejected with `InvalidCredential` if its checksum does not match.
+    pub async fn read_large_blob_array(&mut self) -> YKeyResult<Vec<cbor::Value>> {
+        parse_array(&self.read_serialized_large_blob_array().await?)
+    }
+
+    /// Replace the large blob array, authorizing the write with the PIN
+    ///
+    /// The checksum is appended and the serialized array written in fragments
+    /// that fit the authenticator's maxMsgSize.
+    pub async fn write_large_blob_array(&mut self, entries: &[cbor::Value], pin: &str) -> YKeyResult<()> {
+        let data = serialize_array(entries)?;
+        self.check_large_blob_array_size(&data).await?;
+
+        let token = self.get_pin_uv_auth_token_with_permissions(pin, Permissions::LARGE_BLOB_WRITE, None).await?;
+        self.write_serialized_large_blob_array(&data, &token).await
+    }
+
+    /// Encrypt a secret under a credential's largeBlobKey and store it on the device
+    ///
+    /// Replaces any secret previously stored for the same credential. Requires
+    /// user presence for the assertion and the PIN to authorize the write.
+    pub async fn store_secret_for_credential(
+        &mut self,
+        rp_id: &str,
+        credential_id: &[u8],
+        plaintext: &[u8],
+        pin: &str,
+    ) -> YKeyResult<()> {
+        let large_blob_key = self.large_blob_key(rp_id, credential_id).await?;
+        let token = self.get_pin_uv_auth_token_with_permissions(pin, Permissions::LARGE_BLOB_WRITE, None).await?;
+
+        let mut entries = parse_array(&self.read_serialized_large_blob_array().await?)?;
+        entries.retain(|entry| decrypt_entry(&large_blob_key, entry).is_none());
+        entries.push(encrypt_entry_with_rng(self.rng.as_mut(), &large_blob_key, plaintext)?);
+
+        let data = serialize_array(&entries)?;
+        self.write_serialized_large_blob_array(&data, &token).await
+    }
+
+    /// Read back the secret stored for a credential, if any
+    pub async fn read_secret_for_credential(
+        &mut self,
+        rp_id: &str,
+        credential_id: &[u8],
+    ) -> YKeyResult<Option<Vec<u8>>> {
+        let large_blob_key = self.large_blob_key(rp_id, credential_id).await?;
+        let entries = parse_array(&self.read_serialized_large_blob_array().await?)?;
+
+        Ok(entries.iter().find_map(|entry| decrypt_entry(&large_blob_key, entry)))
+    }
+
+    /// Get an assertion with the largeBlobKey extension to learn the credential's key
+    async fn large_blob_key(&mut self, rp_id: &str, credential_id: &[u8]) -> YKeyResult<Vec<u8>> {
+        let mut client_data_hash = vec![0u8; 32];
+        self.rng.fill_bytes(&mut client_data_hash);
+        let params = GetAssertionParams {
+            rp_id: rp_id.to_string(),
+            client_data_hash,
+            allow_list: Some(vec![PublicKeyCredentialDescriptor {
+                cred_type: "public-key".to_string(),
+                id: credential_id.to_vec(),
+                transports: None,
+            }]),
+            extensions: None,
+            options: GetAssertionOptions::default(),
+            pin_uv_auth_param: None,
+            pin_uv_auth_protocol: None,
+            get_cred_blob: false,
+            large_blob_key: true,
+        };
+
+        let response = self
+            .send_cbor(0x02, Some(CtapCommand::get_assertion_map(&params)))
+            .await?
+            .ok_or_else(|| YKeyError::communication("Missing assertion response"))?;
+
+        cbor::get_int(cbor::as_map(&response)?, 0x07)
+            .ok_or_else(|| YKeyError::InvalidCredential("Credential has no largeBlobKey".to_string()))
+            .and_then(cbor::to_bytes)
+    }
+
+    /// Longest fragment that fits the authenticator's maxMsgSize
+    async fn large_blob_fragment_length(&mut self) -> YKeyResult<usize> {
+        let max_msg_size = self
+            .authenticator_info()
+            .await?
+            .max_msg_size
+            .map_or(DEFAULT_MAX_MSG_SIZE, |size| size as usize);
+        max_msg_size
+            .checked_sub(MESSAGE_OVERHEAD)
+            .filter(|&length| length > 0)
+            .ok_or_else(|| YKeyError::communication(format!("maxMsgSize {} too small for large blobs", max_msg_size)))
+    }
+
+    /// Reject a serialized array larger than the authenticator can hold
+    async fn check_large_blob_array_size(&mut self, data: &[u8]) -> YKeyResult<()> {
+        if let Some(max) = self.authenticator_info().await?.max_serialized_large_blob_array {
+            if data.len() as u64 > max {
+                return Err(YKeyError::InvalidParameters(format!(
+                    "Large blob array of {} bytes exceeds the authenticator's {} bytes",
+                    data.len(),
+                    max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read up to `length` bytes of the serialized array from `offset`
+    async fn read_large_blob_fragment(&mut self, offset: usize, length: usize) -> YKeyResult<Vec<u8>> {
+        let response = self
+            .send_cbor(
+                LARGE_BLOBS_COMMAND,
+                Some(cbor::int_map(vec![
+                    (0x01, Some(cbor::int(length as i64))),
+                    (0x03, Some(cbor::int(offset as i64))),
+                ])),
+            )
+            .await?
+            .ok_or_else(|| YKeyError::communication("Missing large blob response"))?;
+        cbor::get_int(cbor::as_map(&response)?, 0x01)
+            .ok_or_else(|| YKeyError::communication("Large blob response missing config"))
+            .and_then(cbor::to_bytes)
+    }
+
+    /// Read the whole serialized large blob array in fragments
+    async fn read_serialized_large_blob_array(&mut self) -> YKeyResult<Vec<u8>> {
+        let fragment_length = self.large_blob_fragment_length().await?;
+        let mut data = Vec::new();
+
+        loop {
+            let fragment = self.read_large_blob_fragment(data.len(), fragment_length).await?;
+            let done = fragment.len() < fragment_length;
+            data.extend(fragment);
+            if done {
+                return Ok(data);
+            }
+        }
+    }
+
+    /// Replace the serialized large blob array, writing it in fragments
+    async fn write_serialized_large_blob_array(
+        &mut self,
+        data: &[u8],
+        token: &PinUvAuthToken,
+    ) -> YKeyResult<()> {
+        let fragment_length = self.large_blob_fragment_length().await?;
+        let total = u32::try_from(data.len())
+            .map_err(|_| YKeyError::InvalidParameters("Large blob array too large".to_string()))?;
+
+        for (index, fragment) in data.chunks(fragment_length).enumerate() {
+            let offset = (index * fragment_length) as u32;
+            let auth_param = token.authenticate(&set_auth_message(offset, fragment));
+
+            self.send_cbor(
+                LARGE_BLOBS_COMMAND,
+                Some(cbor::int_map(vec![
+                    (0x02, Some(cbor::bytes(fragment))),
+                    (0x03, Some(cbor::int(offset as i64))),
+                    (0x04, (offset == 0).then(|| cbor::int(total as i64))),
+                    (0x05, Some(cbor::bytes(&auth_param))),
+                    (0x06, Some(cbor::int(token.protocol().version() as i64))),
+                ])),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Large blob client holding a token with the large blob write permission
+pub struct LargeBlobClient<D: Device> {
+    client: Fido2Client<D>,
+    token: PinUvAuthToken,
+}
+
+impl<D: Device> LargeBlobClient<D> {
+    /// Authorize large blob writes with the device PIN
+    pub async fn new(mut client: Fido2Client<D>, pin: &str) -> YKeyResult<Self> {
+        let token = client
+            .get_pin_uv_auth_token_with_permissions(pin, Permissions::LARGE_BLOB_WRITE, None)
+            .await?;
+        Ok(Self { client, token })
+    }
+
+    /// Get the underlying FIDO2 client
+    pub fn client(&self) -> &Fido2Client<D> {
+        &self.client
+    }
+
+    /// Release the underlying FIDO2 client
+    pub fn into_client(self) -> Fido2Client<D> {
+        self.client
+    }
+
+    /// Replace the one entry that decrypts under `large_blob_key`
+    ///
+    /// Every other entry is written back unchanged. Just before writing, the
+    /// checksum of the array read is compared with the one on the device, and
+    /// `DeviceBusy` is returned instead if another client changed it since.
+    /// This is best-effort detection, not an atomic compare-and-swap: CTAP has
+    /// no conditional write, so a change landing between that check and the
+    /// write is still lost.
+    pub async fn update_entry(&mut self, large_blob_key: &[u8], new_entry: cbor::Value) -> YKeyResult<()> {
+        let original = self.client.read_serialized_large_blob_array().await?;
+        let mut entries = parse_array(&original)?;
+        let entry = entries
+            .iter_mut()
+            .find(|entry| decrypt_entry(large_blob_key, entry).is_some())
+            .ok_or_else(|| YKeyError::CredentialNotFound("No large blob entry for this key".to_string()))?;
+        *entry = new_entry;
+
+        let data = serialize_array(&entries)?;
+        self.client.check_large_blob_array_size(&data).await?;
+        self.ensure_unchanged(&original).await?;
+        self.client.write_serialized_large_blob_array(&data, &self.token).await
+    }
+
+    /// Check the device still holds the array `original` was read from
+    ///
+    /// Reading one byte past the checksum catches an array that has grown.
+    async fn ensure_unchanged(&mut self, original: &[u8]) -> YKeyResult<()> {
+        let offset = original.len() - CHECKSUM_LENGTH;
+        let current = self.client.read_large_blob_fragment(offset, CHECKSUM_LENGTH + 1).await?;
+        if current != original[offset..] {
+            return Err(YKeyError::DeviceBusy("Large blob array changed while updating".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soft::SoftAuthenticator;
+
+    const RP_ID: &str = "example.com";
+    const PIN: &str = "123456";
+
+    #[test]
+    fn test_entry_round_trip() {
+        let key = [0x11; 32];
+        let entry = encrypt_entry(&key, b"hello large blob").unwrap();
+
+        assert_eq!(decrypt_entry(&key, &entry).unwrap(), b"hello large blob");
+        assert!(decrypt_entry(&[0x22; 32], &entry).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_seeded_rng_reproduces_entry_nonce() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        async fn stored_nonce(seed: u64) -> Vec<u8> {
+            let mut authenticator = SoftAuthenticator::new(PIN);
+            let credential = authenticator.add_credential(RP_ID);
+            let mut client = Fido2Client::with_rng(authenticator, StdRng::seed_from_u64(seed));
+            client.store_secret_for_credential(RP_ID, &credential, b"secret", PIN).await.unwrap();
+
+            let entries = parse_array(client.device().large_blob()).unwrap();
+            let map = cbor::as_map(&entries[0]).unwrap();
+            cbor::to_bytes(cbor::get_int(map, 0x02).unwrap()).unwrap().to_vec()
+        }
+
+        assert_eq!(stored_nonce(3).await, stored_nonce(3).await);
+        assert_ne!(stored_nonce(3).await, stored_nonce(4).await);
+    }
+
+    #[test]
+    fn test_array_checksum() {
+        let data = serialize_array(&[]).unwrap();
+        assert!(parse_array(&data).unwrap().is_empty());
+
+        let mut corrupted = data.clone();
+        *corrupted.last_mut().unwrap() ^= 0x01;
+        assert!(matches!(parse_array(&corrupted), Err(YKeyError::InvalidCredential(_))));
+    }
+
+    #[tokio::test]
+    async fn test_store_and_read_secret_end_to_end() {
+        let mut authenticator = SoftAuthenticator::new(PIN);
+        let first = authenticator.add_credential(RP_ID);
+        let second = authenticator.add_credential(RP_ID);
+        let mut client = Fido2Client::new(authenticator);
+
+        assert_eq!(client.read_secret_for_credential(RP_ID, &first).await.unwrap(), None);
+
+        client.store_secret_for_credential(RP_ID, &first, b"first secret", PIN).await.unwrap();
+        client.store_secret_for_credential(RP_ID, &second, b"second secret", PIN).await.unwrap();
+        assert!(client.has_pin_token());
+
+        // Overwriting one credential's secret leaves the other alone
+        client.store_secret_for_credential(RP_ID, &first, b"updated", PIN).await.unwrap();
+        assert_eq!(
+            client.read_secret_for_credential(RP_ID, &first).await.unwrap().unwrap(),
+            b"updated"
+        );
+        assert_eq!(
+            client.read_secret_for_credential(RP_ID, &second).await.unwrap().unwrap(),
+            b"second secret"
+        );
+        assert_eq!(parse_array(client.device().large_blob()).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_large_secret_spans_fragments() {
+        let mut authenticator = SoftAuthenticator::new(PIN);
+        let credential = authenticator.add_credential(RP_ID);
+        let mut client = Fido2Client::new(authenticator);
+
+        // Hash chain output doesn't compress, forcing several fragments
+        let mut secret = Vec::new();
+        let mut block = Sha256::digest(b"seed");
+        while secret.len() < 3000 {
+            secret.extend_from_slice(&block);
+            block = Sha256::digest(block);
+        }
+
+        client.store_secret_for_credential(RP_ID, &credential, &secret, PIN).await.unwrap();
+        assert!(client.device().large_blob().len() > 2 * (DEFAULT_MAX_MSG_SIZE - MESSAGE_OVERHEAD));
+        assert_eq!(
+            client.read_secret_for_credential(RP_ID, &credential).await.unwrap().unwrap(),
+            secret
+        );
+    }
+
+    #[tokio::test]
+    async fn test_array_round_trip_in_small_fragments() {
+        // 192-byte fragments, so the array below takes four reads and writes
+        let mut client = Fido2Client::new(SoftAuthenticator::new(PIN).with_max_msg_size(256));
+        assert!(client.read_large_blob_array().await.unwrap().is_empty());
+
+        let entries: Vec<_> = (0..6u8).map(|i| cbor::bytes(&[i; 100])).collect();
+        client.write_large_blob_array(&entries, PIN).await.unwrap();
+        assert!(client.device().large_blob().len() > 3 * 192);
+        assert_eq!(client.read_large_blob_array().await.unwrap(), entries);
+    }
+
+    #[tokio::test]
+    async fn test_read_rejects_checksum_mismatch() {
+        let mut authenticator = SoftAuthenticator::new(PIN);
+        let mut data = serialize_array(&[cbor::bytes(b"entry")]).unwrap();
+        *data.last_mut().unwrap() ^= 0x01;
+        authenticator.set_large_blob(data);
+
+        let mut client = Fido2Client::new(authenticator);
+        let result = client.read_large_blob_array().await;
+        assert!(matches!(result, Err(YKeyError::InvalidCredential(_))));
+    }
+
+    #[tokio::test]
+    async fn test_write_rejects_checksum_mismatch() {
+        let mut client = Fido2Client::new(SoftAuthenticator::new(PIN));
+        let token = client.get_pin_uv_auth_token_with_permissions(PIN, Permissions::LARGE_BLOB_WRITE, None).await.unwrap();
+        let mut data = serialize_array(&[cbor::bytes(b"entry")]).unwrap();
+        *data.last_mut().unwrap() ^= 0x01;
+
+        let result = client.write_serialized_large_blob_array(&data, &token).await;
+        assert!(matches!(result, Err(YKeyError::CtapError { code: 0x3D, .. })));
+        assert_eq!(client.device().large_blob(), serialize_array(&[]).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_update_entry_preserves_others() {
+        let keys = [[0xA1; 32], [0xB2; 32], [0xC3; 32]];
+        let entries: Vec<_> = keys.iter().map(|key| encrypt_entry(key, b"original").unwrap()).collect();
+        let mut client = Fido2Client::new(SoftAuthenticator::new(PIN));
+        client.write_large_blob_array(&entries, PIN).await.unwrap();
+
+        let mut large_blobs = LargeBlobClient::new(client, PIN).await.unwrap();
+        large_blobs.update_entry(&keys[1], encrypt_entry(&keys[1], b"updated").unwrap()).await.unwrap();
+        let missing = large_blobs.update_entry(&[0xD4; 32], encrypt_entry(&[0xD4; 32], b"new").unwrap()).await;
+        assert!(matches!(missing, Err(YKeyError::CredentialNotFound(_))));
+
+        let stored = large_blobs.into_client().read_large_blob_array().await.unwrap();
+        assert_eq!(stored.len(), 3);
+        assert_eq!((&stored[0], &stored[2]), (&entries[0], &entries[2]));
+        assert_eq!(decrypt_entry(&keys[1], &stored[1]).unwrap(), b"updated");
+    }
+
+    #[tokio::test]
+    async fn test_update_detects_concurrent_change() {
+        let key = [0xA1; 32];
+        let entries = [encrypt_entry(&key, b"original").unwrap()];
+        let mut authenticator = SoftAuthenticator::new(PIN);
+        authenticator.set_large_blob(serialize_array(&entries).unwrap());
+        let mut large_blobs = LargeBlobClient::new(Fido2Client::new(authenticator), PIN).await.unwrap();
+
+        // Another client appends an entry after this one read the array
+        let grown = serialize_array(&[entries[0].clone(), cbor::bytes(b"other")]).unwrap();
+        large_blobs.client.device_mut().set_large_blob_after_read(grown.clone());
+
+        let result = large_blobs.update_entry(&key, encrypt_entry(&key, b"updated").unwrap()).await;
+        assert!(matches!(result, Err(YKeyError::DeviceBusy(_))));
+        assert_eq!(large_blobs.into_client().device_mut().large_blob(), grown);
+    }
+
+    #[tokio::test]
+    async fn test_store_secret_with_wrong_pin() {
+        let mut authenticator = SoftAuthenticator::new(PIN);
+        let credential = authenticator.add_credential(RP_ID);
+        let mut client = Fido2Client::new(authenticator);
+
+        let result = client.store_secret_for_credential(RP_ID, &credential, b"secret", "000000").await;
+        assert!(matches!(result, Err(YKeyError::InvalidPin { .. })));
+        assert_eq!(client.read_secret_for_credential(RP_ID, &credential).await.unwrap(), None);
+    }
+}
@@ -6,10 +6,80 @@
 //! This crate provides implementations for various hardware security key protocols,
 //! including FIDO2/WebAuthn and CTAP (Client to Authenticator Protocol).
 
-use ykey_core::{traits::*, types::*, YKeyResult, YKeyError};
+use ykey_core::{traits::*, types::*, Clock, SystemClock, YKeyResult, YKeyError};
 use async_trait::async_trait;
 use std::time::Duration;
 
+pub mod apdu;
+pub mod attestation;
+pub mod bio_enroll;
+pub mod cbor;
+pub mod client_data;
+pub mod config;
+pub mod cose;
+pub mod cred_mgmt;
+pub mod ctaphid;
+pub mod diagnostics;
+pub mod hmac_secret;
+pub mod hybrid;
+pub mod large_blob;
+pub mod management;
+pub mod oath;
+pub mod piv;
+pub mod pin;
+pub mod prelude;
+pub mod quirks;
+pub mod rng;
+pub mod trace;
+pub mod u2f;
+
+#[cfg(test)]
+mod soft;
+#[cfg(test)]
+mod test_support;
+
+use quirks::{DeviceIdentity, DeviceQuirks, QuirkTable};
+use client_data::{ClientData, ClientDataType, OriginAllowList};
+use diagnostics::{CborFieldIssue, FieldIssues};
+
+/// Status ending a transaction aborted by CTAPHID_CANCEL
+pub const CTAP2_ERR_KEEPALIVE_CANCEL: u8 = 0x2D;
+
+/// No touch arrived while the authenticator waited for one
+const CTAP2_ERR_USER_ACTION_TIMEOUT: u8 = 0x2F;
+/// The reset came too long after power-up
+const CTAP2_ERR_NOT_ALLOWED: u8 = 0x30;
+
+/// Statuses with which authenticators refuse a reset outside its window
+const RESET_REFUSED_STATUSES: [u8; 2] = [CTAP2_ERR_USER_ACTION_TIMEOUT, CTAP2_ERR_NOT_ALLOWED];
+
+/// Explanation attached to a refused reset
+const RESET_WINDOW_MESSAGE: &str =
+    "reset must be performed within 10s of inserting the key and requires a touch";
+
+/// Split a message into packet-sized fragments
+///
+/// The first fragment holds up to `first` bytes and every later one up to
+/// `continuation` bytes. An empty message is a single empty fragment.
+pub fn split_message(data: &[u8], first: usize, continuation: usize) -> YKeyResult<Vec<&[u8]>> {
+    if first == 0 || continuation == 0 {
+        return Err(YKeyError::InvalidParameters(format!(
+            "packets carrying {} and {} message bytes cannot hold a message",
+            first, continuation
+        )));
+    }
+
+    let (head, rest) = data.split_at(data.len().min(first));
+    let mut fragments = vec![head];
+    fragments.extend(rest.chunks(continuation));
+    Ok(fragments)
+}
+
+/// Extension storing a small blob with a credential at creation
+pub const CRED_BLOB_EXTENSION: &str = "credBlob";
+/// Extension returning the credBlob with an assertion
+pub const GET_CRED_BLOB_EXTENSION: &str = "getCredBlob";
+
 /// CTAP Command types
 #[derive(Debug, Clone)]
 pub enum CtapCommand {
@@ -37,8 +107,7 @@ pub enum CtapResponse {
     MakeCredential(AttestationObject),
     GetAssertion(AssertionObject),
     Reset,
-    ClientPin,
-    ClientPinToken(Vec<u8>),
+    ClientPin(pin::ClientPinResponse),
     Cancel,
     Error(u8),
 }
@@ -48,59 +117,424 @@ impl CtapCommand {
     pub fn encode(&self) -> YKeyResult<Vec<u8>> {
         match self {
             CtapCommand::GetInfo => Ok(vec![0x04]), // CTAP2 GetInfo command
-            CtapCommand::MakeCredential(_) => Ok(vec![0x01]), // CTAP2 MakeCredential command
-            CtapCommand::GetAssertion(_) => Ok(vec![0x02]), // CTAP2 GetAssertion command
+            CtapCommand::MakeCredential(params) => Self::with_payload(0x01, Self::make_credential_map(params)),
+            CtapCommand::GetAssertion(params) => Self::with_payload(0x02, Self::get_assertion_map(params)),
             CtapCommand::Reset => Ok(vec![0x07]), // CTAP2 Reset command
             CtapCommand::ClientPin(_) => Ok(vec![0x06]), // CTAP2 ClientPin command
             CtapCommand::GetNextAssertion => Ok(vec![0x08]), // CTAP2 GetNextAssertion command
             CtapCommand::Cancel => Ok(vec![0x3F, 0x00, 0x00, 0x00]), // HID Cancel packet
         }
     }
+
+    /// Prefix an encoded CBOR parameter map with the command byte
+    fn with_payload(command: u8, params: ciborium::Value) -> YKeyResult<Vec<u8>> {
+        let mut data = vec![command];
+        data.extend(cbor::encode(&params)?);
+        Ok(data)
+    }
+
+    /// Build the authenticatorGetAssertion parameter map
+    fn get_assertion_map(params: &GetAssertionParams) -> ciborium::Value {
+        let options = cbor::Value::Map(
+            [("up", params.options.up), ("uv", params.options.uv)]
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|v| (cbor::text(key), cbor::Value::Bool(v))))
+                .collect(),
+        );
+
+        cbor::int_map(vec![
+            (0x01, Some(cbor::text(&params.rp_id))),
+            (0x02, Some(cbor::bytes(&params.client_data_hash))),
+            (0x03, params.allow_list.as_ref().map(|list| Self::descriptor_list(list))),
+            (0x04, Self::extensions_map(params.extensions.as_ref(), vec![
+                (GET_CRED_BLOB_EXTENSION, params.get_cred_blob.then_some(cbor::Value::Bool(true))),
+                (large_blob::LARGE_BLOB_KEY_EXTENSION, params.large_blob_key.then_some(cbor::Value::Bool(true))),
+            ])),
+            (0x05, Some(options).filter(|o| !matches!(o, cbor::Value::Map(m) if m.is_empty()))),
+            (0x06, params.pin_uv_auth_param.as_deref().map(cbor::bytes)),
+            (0x07, params.pin_uv_auth_protocol.map(|p| cbor::int(p as i64))),
+        ])
+    }
+
+    /// Build the authenticatorMakeCredential parameter map
+    fn make_credential_map(params: &MakeCredentialParams) -> ciborium::Value {
+        let options = cbor::Value::Map(
+            [("rk", params.options.rk), ("uv", params.options.uv), ("up", params.options.up)]
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|v| (cbor::text(key), cbor::Value::Bool(v))))
+                .collect(),
+        );
+
+        let optional_text = |key: &str, value: &Option<String>| {
+            value.as_deref().map(|v| (cbor::text(key), cbor::text(v)))
+        };
+        let rp = cbor::Value::Map(
+            std::iter::once((cbor::text("id"), cbor::text(&params.rp.id)))
+                .chain(optional_text("name", &params.rp.name))
+                .chain(optional_text("icon", &params.rp.icon))
+                .collect(),
+        );
+        let user = cbor::Value::Map(
+            [
+                (cbor::text("id"), cbor::bytes(&params.user.id)),
+                (cbor::text("name"), cbor::text(&params.user.name)),
+                (cbor::text("displayName"), cbor::text(&params.user.display_name)),
+            ]
+            .into_iter()
+            .chain(optional_text("icon", &params.user.icon))
+            .collect(),
+        );
+        let pub_key_cred_params = cbor::Value::Array(
+            params
+                .pub_key_cred_params
+                .iter()
+                .map(|p| cbor::Value::Map(vec![
+                    (cbor::text("alg"), cbor::int(p.alg)),
+                    (cbor::text("type"), cbor::text(&p.cred_type)),
+                ]))
+                .collect(),
+        );
+
+        cbor::int_map(vec![
+            (0x01, Some(cbor::bytes(&params.client_data_hash))),
+            (0x02, Some(rp)),
+            (0x03, Some(user)),
+            (0x04, Some(pub_key_cred_params)),
+            (0x05, params.exclude_list.as_ref().map(|list| Self::descriptor_list(list))),
+            (0x06, Self::extensions_map(params.extensions.as_ref(), vec![
+                (CRED_BLOB_EXTENSION, params.cred_blob.as_deref().map(cbor::bytes)),
+                (large_blob::LARGE_BLOB_KEY_EXTENSION, params.large_blob_key.then_some(cbor::Value::Bool(true))),
+            ])),
+            (0x07, Some(options).filter(|o| !matches!(o, cbor::Value::Map(m) if m.is_empty()))),
+            (0x08, params.pin_uv_auth_param.as_deref().map(cbor::bytes)),
+            (0x09, params.pin_uv_auth_protocol.map(|p| cbor::int(p as i64))),
+            (0x0A, params.requested_enterprise_attestation().map(|kind| cbor::int(kind as i64))),
+        ])
+    }
+
+    /// Encode a list of credential descriptors
+    fn descriptor_list(list: &[PublicKeyCredentialDescriptor]) -> ciborium::Value {
+        cbor::Value::Array(
+            list.iter()
+                .map(|descriptor| {
+                    let mut entries = vec![
+                        (cbor::text("id"), cbor::bytes(&descriptor.id)),
+                        (cbor::text("type"), cbor::text(&descriptor.cred_type)),
+                    ];
+                    if let Some(transports) = &descriptor.transports {
+                        entries.push((
+                            cbor::text("transports"),
+                            cbor::Value::Array(transports.iter().map(|t| cbor::text(t)).collect()),
+                        ));
+                    }
+                    cbor::Value::Map(entries)
+                })
+                .collect(),
+        )
+    }
+
+    /// Encode the extensions map, adding the extensions set through typed fields
+    fn extensions_map(
+        extensions: Option<&std::collections::HashMap<String, serde_json::Value>>,
+        typed: Vec<(&str, Option<ciborium::Value>)>,
+    ) -> Option<ciborium::Value> {
+        let typed: Vec<_> = typed.into_iter().filter_map(|(name, value)| Some((name, value?))).collect();
+        if extensions.is_none() && typed.is_empty() {
+            return None;
+        }
+        let typed_names: Vec<&str> = typed.iter().map(|(name, _)| *name).collect();
+        Some(cbor::Value::Map(
+            extensions
+                .into_iter()
+                .flatten()
+                .filter(|(name, _)| !typed_names.contains(&name.as_str()))
+                .map(|(name, value)| (cbor::text(name), cbor::from_json(value)))
+                .chain(typed.into_iter().map(|(name, value)| (cbor::text(name), value)))
+                .collect(),
+        ))
+    }
 }
 
 impl CtapResponse {
     /// Decode response from bytes (simplified for now)
     pub fn decode(data: &[u8]) -> YKeyResult<Self> {
-        if data.is_empty() {
-            return Err(YKeyError::communication("Empty response"));
-        }
-
-        // Check for CTAP2 status byte
-        match data[0] {
-            0x00 => {
-                // Success - determine response type based on length and content
-                if data.len() == 1 {
-                    Ok(CtapResponse::Reset)
-                } else {
-                    // For now, return a mock AuthenticatorInfo for GetInfo
-                    Ok(CtapResponse::GetInfo(AuthenticatorInfo {
-                        versions: vec!["FIDO_2_0".to_string()],
-                        extensions: Some(vec!["hmac-secret".to_string()]),
-                        aaguid: vec![0; 16],
-                        options: None,
-                        max_msg_size: Some(1200),
-                        pin_uv_auth_protocols: Some(vec![1]),
-                        max_credential_count_in_list: Some(8),
-                        max_credential_id_length: Some(128),
-                        transports: Some(vec!["usb".to_string()]),
-                        algorithms: None,
-                        max_serialized_large_blob_array: None,
-                        force_pin_change: None,
-                        min_pin_length: Some(4),
-                        firmware_version: None,
-                        max_cred_blob_length: None,
-                        max_rp_ids_for_set_min_pin_length: None,
-                        preferred_platform_uv_attempts: None,
-                        uv_modality: None,
-                        certifications: None,
-                        remaining_discoverable_credentials: None,
-                        vendor_prototype_config_commands: None,
-                    }))
+        Self::decode_with_quirks(data, &DeviceQuirks::default())
+    }
+
+    /// Decode the response to a specific command
+    pub fn decode_for(command: &CtapCommand, data: &[u8], quirks: &DeviceQuirks) -> YKeyResult<Self> {
+        Self::decode_checked(command, data, quirks, &mut FieldIssues::new(Self::response_name(command), false))
+    }
+
+    /// Decode a response as far as possible, listing every malformed field
+    ///
+    /// Missing required fields and fields of the wrong type are reported
+    /// instead of failing the decode, and take a default value in the
+    /// returned response. Meant for bug reports about firmware quirks; the
+    /// response should not be trusted when issues were found.
+    pub fn decode_diagnostic(command: &CtapCommand, data: &[u8]) -> YKeyResult<(Self, Vec<CborFieldIssue>)> {
+        let mut issues = FieldIssues::new(Self::response_name(command), true);
+        let response = Self::decode_checked(command, data, &DeviceQuirks::default(), &mut issues)?;
+        Ok((response, issues.into_issues()))
+    }
+
+    /// Name of the response to a command, as used in field issues
+    fn response_name(command: &CtapCommand) -> &'static str {
+        match command {
+            CtapCommand::MakeCredential(_) => "MakeCredential",
+            CtapCommand::GetAssertion(_) | CtapCommand::GetNextAssertion => "GetAssertion",
+            CtapCommand::ClientPin(_) => "ClientPin",
+            _ => "GetInfo",
+        }
+    }
+
+    fn decode_checked(
+        command: &CtapCommand,
+        data: &[u8],
+        quirks: &DeviceQuirks,
+        issues: &mut FieldIssues,
+    ) -> YKeyResult<Self> {
+        match command {
+            CtapCommand::GetInfo => match Self::split_status(data, quirks)? {
+                Ok(body) => Ok(CtapResponse::GetInfo(Self::parse_info(&body, issues)?)),
+                Err(code) => Ok(CtapResponse::Error(code)),
+            },
+            CtapCommand::MakeCredential(_) => match Self::split_status(data, quirks)? {
+                Ok(body) => Ok(CtapResponse::MakeCredential(Self::parse_attestation(&body, issues)?)),
+                Err(code) => Ok(CtapResponse::Error(code)),
+            },
+            CtapCommand::GetAssertion(_) | CtapCommand::GetNextAssertion => {
+                match Self::split_status(data, quirks)? {
+                    Ok(body) => Ok(CtapResponse::GetAssertion(Self::parse_assertion(&body, issues)?)),
+                    Err(code) => Ok(CtapResponse::Error(code)),
                 }
+            }
+            // Subcommands such as setPIN answer with a bare success status
+            CtapCommand::ClientPin(_) if data == [0x00] => Ok(CtapResponse::ClientPin(pin::ClientPinResponse::default())),
+            CtapCommand::ClientPin(_) => match Self::split_status(data, quirks)? {
+                Ok(body) => Ok(CtapResponse::ClientPin(pin::ClientPinResponse::from_cbor(Some(&body))?)),
+                Err(code) => Ok(CtapResponse::Error(code)),
             },
-            0x01..=0xFF => Ok(CtapResponse::Error(data[0])),
+            _ => Self::decode_untyped(data, quirks, issues),
+        }
+    }
+
+    /// Split a response into its CBOR body or the error status
+    fn split_status(data: &[u8], quirks: &DeviceQuirks) -> YKeyResult<Result<ciborium::Value, u8>> {
+        match data.first() {
+            None => Err(YKeyError::communication("Empty response")),
+            Some(0x00) if data.len() > 1 => Ok(Ok(cbor::decode(&data[1..], quirks.lenient_cbor)?)),
+            Some(0x00) => Err(YKeyError::communication("Missing response body")),
+            Some(&code) => Ok(Err(code)),
         }
     }
+
+    /// Parse an authenticatorMakeCredential response map
+    fn parse_attestation(body: &ciborium::Value, issues: &mut FieldIssues) -> YKeyResult<AttestationObject> {
+        let map = cbor::as_map(body)?;
+
+        let fmt = issues.required("fmt", cbor::get_int(map, 0x01).map(cbor::to_text).transpose())?;
+        let auth_data = issues.required("authData", cbor::get_int(map, 0x02).map(cbor::to_bytes).transpose())?;
+        let att_stmt = match cbor::get_int(map, 0x03).map(cbor::to_json) {
+            Some(serde_json::Value::Object(entries)) => Ok(Some(entries.into_iter().collect())),
+            Some(_) => Err(YKeyError::communication("Attestation attStmt is not a map")),
+            None => Ok(None),
+        };
+        let att_stmt = issues.required("attStmt", att_stmt)?;
+
+        let cred_blob_stored = Self::extension_output(&auth_data, CRED_BLOB_EXTENSION).as_ref().map(cbor::to_bool).transpose();
+        let cred_blob_stored = issues.optional("extensions.credBlob", cred_blob_stored)?;
+        let large_blob_key = issues.optional("largeBlobKey", cbor::get_int(map, 0x05).map(cbor::to_bytes).transpose())?;
+
+        Ok(AttestationObject {
+            fmt,
+            att_stmt,
+            auth_data,
+            cred_blob_stored,
+            large_blob_key,
+        })
+    }
+
+    /// Parse an authenticatorGetAssertion response map
+    fn parse_assertion(body: &ciborium::Value, issues: &mut FieldIssues) -> YKeyResult<AssertionObject> {
+        let map = cbor::as_map(body)?;
+
+        let credential_id = cbor::get_int(map, 0x01)
+            .map(|credential| {
+                let descriptor = cbor::as_map(credential)?;
+                cbor::get_text(descriptor, "id").map(cbor::to_bytes).transpose()
+            })
+            .transpose()
+            .map(Option::flatten);
+        let credential_id = issues.optional("credential", credential_id)?;
+
+        let auth_data = issues.required("authData", cbor::get_int(map, 0x02).map(cbor::to_bytes).transpose())?;
+        let signature = issues.required("signature", cbor::get_int(map, 0x03).map(cbor::to_bytes).transpose())?;
+
+        let user = match cbor::get_int(map, 0x04).map(cbor::as_map) {
+            Some(Ok(user)) => {
+                let mut text_field = |key: &str| -> YKeyResult<String> {
+                    let value = cbor::get_text(user, key).map(cbor::to_text).transpose();
+                    Ok(issues.optional(&format!("user.{}", key), value)?.unwrap_or_default())
+                };
+                let (name, display_name) = (text_field("name")?, text_field("displayName")?);
+                Some(User {
+                    id: issues.required("user.id", cbor::get_text(user, "id").map(cbor::to_bytes).transpose())?,
+                    name,
+                    display_name,
+                    icon: None,
+                })
+            }
+            Some(Err(error)) => issues.optional::<User>("user", Err(error))?,
+            None => None,
+        };
+
+        let number_of_credentials = cbor::get_int(map, 0x05).map(cbor::to_u64).transpose();
+        let number_of_credentials = issues.optional("numberOfCredentials", number_of_credentials)?.map(|n| n as u32);
+
+        let cred_blob = Self::extension_output(&auth_data, CRED_BLOB_EXTENSION).as_ref().map(cbor::to_bytes).transpose();
+        let cred_blob = issues.optional("extensions.credBlob", cred_blob)?;
+        let large_blob_key = issues.optional("largeBlobKey", cbor::get_int(map, 0x07).map(cbor::to_bytes).transpose())?;
+
+        Ok(AssertionObject {
+            credential_id,
+            auth_data,
+            signature,
+            user,
+            number_of_credentials,
+            cred_blob,
+            large_blob_key,
+        })
+    }
+
+    /// Output of an extension in authenticator data
+    ///
+    /// Authenticator data that does not parse is left to the caller to
+    /// verify, so it yields no outputs here.
+    fn extension_output(auth_data: &[u8], name: &str) -> Option<ciborium::Value> {
+        let extensions = cose::AuthenticatorData::parse(auth_data).ok()?.extensions?;
+        cbor::get_text(cbor::as_map(&extensions).ok()?, name).cloned()
+    }
+
+    /// Parse an authenticatorGetInfo response map
+    /// 
+    /// Unknown keys are ignored and absent optional keys map to `None`.
+    fn parse_info(body: &ciborium::Value, issues: &mut FieldIssues) -> YKeyResult<AuthenticatorInfo> {
+        let map = cbor::as_map(body)?;
+        let field = |key: i64| cbor::get_int(map, key);
+        let uint = |key: i64| field(key).map(cbor::to_u64).transpose();
+        let strings = |key: i64| -> YKeyResult<Option<Vec<String>>> {
+            field(key)
+                .map(|v| cbor::to_array(v)?.iter().map(cbor::to_text).collect())
+                .transpose()
+        };
+        let uints = |key: i64| -> YKeyResult<Option<Vec<u64>>> {
+            field(key)
+                .map(|v| cbor::to_array(v)?.iter().map(cbor::to_u64).collect())
+                .transpose()
+        };
+
+        let options = field(0x04)
+            .map(|v| {
+                cbor::as_map(v)?
+                    .iter()
+                    .map(|(k, v)| Ok((cbor::to_text(k)?, cbor::to_bool(v)?)))
+                    .collect::<YKeyResult<std::collections::HashMap<_, _>>>()
+            })
+            .transpose();
+
+        let algorithms = field(0x0A)
+            .map(|v| {
+                cbor::to_array(v)?
+                    .iter()
+                    .map(|param| {
+                        let param = cbor::as_map(param)?;
+                        Ok(PublicKeyCredentialParameter {
+                            cred_type: cbor::get_text(param, "type")
+                                .ok_or_else(|| YKeyError::communication("Algorithm missing type"))
+                                .and_then(cbor::to_text)?,
+                            alg: cbor::get_text(param, "alg")
+                                .ok_or_else(|| YKeyError::communication("Algorithm missing alg"))
+                                .and_then(cbor::to_i64)?,
+                        })
+                    })
+                    .collect::<YKeyResult<Vec<_>>>()
+            })
+            .transpose();
+
+        let certifications = field(0x13)
+            .map(|v| {
+                cbor::as_map(v)?
+                    .iter()
+                    .map(|(k, v)| Ok((cbor::to_text(k)?, cbor::to_json(v))))
+                    .collect::<YKeyResult<std::collections::HashMap<_, _>>>()
+            })
+            .transpose();
+
+        let aaguid = field(0x03).map(|v| cbor::to_bytes(v).and_then(|bytes| Aaguid::from_slice(&bytes))).transpose();
+
+        Ok(AuthenticatorInfo {
+            versions: issues.required("versions", strings(0x01))?,
+            extensions: issues.optional("extensions", strings(0x02))?,
+            aaguid: issues.required("aaguid", aaguid)?,
+            options: issues.optional("options", options)?,
+            max_msg_size: issues.optional("maxMsgSize", uint(0x05))?,
+            pin_uv_auth_protocols: issues.optional("pinUvAuthProtocols", uints(0x06))?,
+            max_credential_count_in_list: issues.optional("maxCredentialCountInList", uint(0x07))?,
+            max_credential_id_length: issues.optional("maxCredentialIdLength", uint(0x08))?,
+            transports: issues.optional("transports", strings(0x09))?,
+            algorithms: issues.optional("algorithms", algorithms)?,
+            max_serialized_large_blob_array: issues.optional("maxSerializedLargeBlobArray", uint(0x0B))?,
+            force_pin_change: issues.optional("forcePINChange", field(0x0C).map(cbor::to_bool).transpose())?,
+            min_pin_length: issues.optional("minPINLength", uint(0x0D))?,
+            firmware_version: issues.optional("firmwareVersion", uint(0x0E))?,
+            max_cred_blob_length: issues.optional("maxCredBlobLength", uint(0x0F))?,
+            max_rp_ids_for_set_min_pin_length: issues.optional("maxRPIDsForSetMinPINLength", uint(0x10))?,
+            preferred_platform_uv_attempts: issues.optional("preferredPlatformUvAttempts", uint(0x11))?,
+            uv_modality: issues.optional("uvModality", uint(0x12))?,
+            certifications: issues.optional("certifications", certifications)?,
+            remaining_discoverable_credentials: issues.optional("remainingDiscoverableCredentials", uint(0x14))?,
+            vendor_prototype_config_commands: issues.optional("vendorPrototypeConfigCommands", uints(0x15))?,
+        })
+    }
+
+    /// Decode response from bytes, applying device-specific workarounds
+    /// 
+    /// Without the originating command, a success response carrying a body is
+    /// treated as GetInfo; use `decode_for` when the command is known.
+    pub fn decode_with_quirks(data: &[u8], quirks: &DeviceQuirks) -> YKeyResult<Self> {
+        Self::decode_untyped(data, quirks, &mut FieldIssues::new("GetInfo", false))
+    }
+
+    fn decode_untyped(data: &[u8], quirks: &DeviceQuirks, issues: &mut FieldIssues) -> YKeyResult<Self> {
+        match Self::split_status(data, quirks) {
+            Ok(Ok(body)) => Ok(CtapResponse::GetInfo(Self::parse_info(&body, issues)?)),
+            Ok(Err(code)) => Ok(CtapResponse::Error(code)),
+            // A bare success status carries no body
+            Err(_) if data == [0x00] => Ok(CtapResponse::Reset),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Replace an attestation with the `none` format, as browsers do
+/// 
+/// The AAGUID in attested credential data is zeroed as well, since it
+/// identifies the authenticator model.
+fn strip_attestation(attestation: &mut AttestationObject) {
+    const FLAGS_OFFSET: usize = 32;
+    const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+    const AAGUID_RANGE: std::ops::Range<usize> = 37..53;
+
+    attestation.fmt = "none".to_string();
+    attestation.att_stmt.clear();
+
+    let auth_data = &mut attestation.auth_data;
+    if auth_data.len() >= AAGUID_RANGE.end
+        && auth_data[FLAGS_OFFSET] & FLAG_ATTESTED_CREDENTIAL_DATA != 0
+    {
+        auth_data[AAGUID_RANGE].fill(0);
+    }
 }
 
 /// FIDO2 protocol client implementation
@@ -110,18 +544,25 @@ pub struct Fido2Client<D: Device> {
     device: D,
     pin_token: Option<Vec<u8>>,
     pin_protocol_version: Option<u8>,
+    /// Permissions granted to `pin_token`
+    pin_permissions: pin::Permissions,
+    /// RP ID `pin_token` is bound to, if any
+    pin_rp_id: Option<String>,
     timeout: Duration,
+    quirk_table: QuirkTable,
+    identity: DeviceIdentity,
+    quirks: Option<DeviceQuirks>,
+    origin_allow_list: Option<OriginAllowList>,
+    /// Response to the last GetInfo, for limits such as maxMsgSize
+    authenticator_info: Option<AuthenticatorInfo>,
+    rng: Box<dyn rng::RngSource>,
+    tracer: Option<Box<dyn trace::Tracer>>,
 }
 
 impl<D: Device> Fido2Client<D> {
     /// Create a new FIDO2 client with the given device
     pub fn new(device: D) -> Self {
-        Self {
-            device,
-            pin_token: None,
-            pin_protocol_version: None,
-            timeout: Duration::from_secs(30),
-        }
+        Self::with_timeout(device, Duration::from_secs(30))
     }
 
     /// Create a new FIDO2 client with custom timeout
@@ -130,7 +571,28 @@ impl<D: Device> Fido2Client<D> {
             device,
             pin_token: None,
             pin_protocol_version: None,
+            pin_permissions: pin::Permissions::empty(),
+            pin_rp_id: None,
             timeout,
+            quirk_table: QuirkTable::new(),
+            identity: DeviceIdentity::default(),
+            quirks: None,
+            origin_allow_list: None,
+            authenticator_info: None,
+            rng: rng::os_rng(),
+            tracer: None,
+        }
+    }
+
+    /// Create a client drawing randomness from `rng` instead of the OS
+    ///
+    /// Only available to tests, so production clients can never end up
+    /// with a deterministic generator.
+    #[cfg(test)]
+    pub(crate) fn with_rng(device: D, rng: impl rng::RngSource + 'static) -> Self {
+        Self {
+            rng: Box::new(rng),
+            ..Self::new(device)
         }
     }
 
@@ -139,6 +601,90 @@ impl<D: Device> Fido2Client<D> {
         self.timeout = timeout;
     }
 
+    /// Replace the quirk table used to resolve device workarounds
+    pub fn set_quirk_table(&mut self, table: QuirkTable) {
+        self.quirk_table = table;
+        self.quirks = None;
+    }
+
+    /// Get the workarounds currently applied to the device, once resolved
+    pub fn quirks(&self) -> Option<&DeviceQuirks> {
+        self.quirks.as_ref()
+    }
+
+    /// Restrict the origins `client_data` builds client data for
+    pub fn set_origin_allow_list(&mut self, allow_list: Option<OriginAllowList>) {
+        self.origin_allow_list = allow_list;
+    }
+
+    /// Observe every command and response exchanged with the device
+    ///
+    /// Use [`trace::HexTracer`] to log the traffic, or `None` to stop tracing.
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn trace::Tracer>>) {
+        self.tracer = tracer;
+    }
+
+    /// Build client data for a ceremony, enforcing the origin allow-list if set
+    pub fn client_data(
+        &self,
+        kind: ClientDataType,
+        challenge: Vec<u8>,
+        origin: impl Into<String>,
+    ) -> YKeyResult<ClientData> {
+        match &self.origin_allow_list {
+            Some(allow_list) => allow_list.client_data(kind, challenge, origin),
+            None => Ok(ClientData::new(kind, challenge, origin)),
+        }
+    }
+
+    /// Verify an assertion signature over authData || clientDataHash
+    pub fn verify_assertion(
+        &self,
+        assertion: &AssertionObject,
+        client_data_hash: &[u8],
+        public_key: &cose::CosePublicKey,
+    ) -> YKeyResult<()> {
+        let mut signed_data = assertion.auth_data.clone();
+        signed_data.extend_from_slice(client_data_hash);
+        cose::verify_signature(public_key, &signed_data, &assertion.signature)
+    }
+
+    /// Iterate over every assertion the authenticator has for `params`
+    ///
+    /// The first call to `next` sends GetAssertion, later ones
+    /// GetNextAssertion until all reported credentials are returned.
+    pub fn assertion_iter(&mut self, params: GetAssertionParams) -> AssertionIter<'_, D> {
+        AssertionIter {
+            client: self,
+            params: Some(params),
+            index: 0,
+            total: 0,
+        }
+    }
+
+    /// Fetch every assertion the authenticator has for `params`, in its order
+    ///
+    /// Stops after the numberOfCredentials reported with the first assertion.
+    pub async fn get_all_assertions(&mut self, params: GetAssertionParams) -> YKeyResult<Vec<AssertionObject>> {
+        let mut assertions = Vec::new();
+        let mut iter = self.assertion_iter(params);
+        while let Some(result) = iter.next().await {
+            let (_, _, assertion) = result?;
+            assertions.push(assertion);
+        }
+        Ok(assertions)
+    }
+
+    /// Reset the authenticator, telling the caller when to ask for a touch
+    ///
+    /// `on_touch_required` runs just before the command is sent, when the
+    /// authenticator starts waiting for user presence, so a UI can prompt
+    /// while the reset window is still open.
+    pub async fn reset_with_prompt(&mut self, on_touch_required: impl FnOnce() + Send) -> YKeyResult<()> {
+        on_touch_required();
+        self.reset().await
+    }
+
     /// Get current PIN token if available
     pub fn pin_token(&self) -> Option<&Vec<u8>> {
         self.pin_token.as_ref()
@@ -148,6 +694,42 @@ impl<D: Device> Fido2Client<D> {
     pub fn clear_pin_token(&mut self) {
         self.pin_token = None;
         self.pin_protocol_version = None;
+        self.pin_permissions = pin::Permissions::empty();
+    }
+}
+
+/// Assertions for one request, fetched from the device on demand
+pub struct AssertionIter<'a, D: Device> {
+    client: &'a mut Fido2Client<D>,
+    params: Option<GetAssertionParams>,
+    index: usize,
+    total: usize,
+}
+
+impl<D: Device> AssertionIter<'_, D> {
+    /// Fetch the next assertion as `(index, total, assertion)`
+    ///
+    /// `index` counts from zero. Devices that omit numberOfCredentials
+    /// report a total of one. Iteration ends after the first error.
+    pub async fn next(&mut self) -> Option<YKeyResult<(usize, usize, AssertionObject)>> {
+        let result = match self.params.take() {
+            Some(params) => self.client.get_assertion(params).await.inspect(|assertion| {
+                self.total = assertion.number_of_credentials.map_or(1, |n| n.max(1) as usize);
+            }),
+            None if self.index < self.total => self.client.get_next_assertion().await,
+            None => return None,
+        };
+
+        match result {
+            Ok(assertion) => {
+                self.index += 1;
+                Some(Ok((self.index - 1, self.total, assertion)))
+            }
+            Err(error) => {
+                self.total = 0;
+                Some(Err(error))
+            }
+        }
     }
 }
 
@@ -158,7 +740,14 @@ impl<D: Device> Fido2Protocol for Fido2Client<D> {
         let response = self.send_ctap_command(command).await?;
         
         match response {
-            CtapResponse::GetInfo(info) => Ok(info),
+            CtapResponse::GetInfo(info) => {
+                // GetInfo identifies the exact model, so refine the quirks
+                self.identity.aaguid = Some(info.aaguid);
+                self.identity.firmware_version = info.firmware_version;
+                self.quirks = Some(self.quirk_table.lookup(&self.identity));
+                self.authenticator_info = Some(info.clone());
+                Ok(info)
+            },
             CtapResponse::Error(code) => Err(YKeyError::ctap_error(code)),
             _ => Err(YKeyError::UnexpectedResponse),
         }
@@ -168,11 +757,42 @@ impl<D: Device> Fido2Protocol for Fido2Client<D> {
         &mut self, 
         params: MakeCredentialParams
     ) -> YKeyResult<AttestationObject> {
+        params.validate()?;
+        if let Some(blob) = &params.cred_blob {
+            let info = self.authenticator_info().await?;
+            if !info.extensions.as_ref().is_some_and(|e| e.iter().any(|e| e == CRED_BLOB_EXTENSION)) {
+                return Err(YKeyError::unsupported(CRED_BLOB_EXTENSION, "authenticator does not advertise the extension"));
+            }
+            let max = info.max_cred_blob_length.unwrap_or(0);
+            if blob.len() as u64 > max {
+                return Err(YKeyError::InvalidParameters(format!(
+                    "credBlob of {} bytes exceeds the authenticator's maximum of {}",
+                    blob.len(),
+                    max
+                )));
+            }
+        }
+        if let Some(kind) = params.requested_enterprise_attestation() {
+            if !(ENTERPRISE_ATTESTATION_VENDOR_FACILITATED..=ENTERPRISE_ATTESTATION_PLATFORM_MANAGED).contains(&kind) {
+                return Err(YKeyError::InvalidParameters(format!("Unknown enterprise attestation kind {}", kind)));
+            }
+            if !self.authenticator_info().await?.option_enabled("ep") {
+                return Err(YKeyError::InvalidParameters(
+                    "Authenticator does not have enterprise attestation enabled".to_string(),
+                ));
+            }
+        }
+        let preference = params.attestation_preference;
         let command = CtapCommand::MakeCredential(params);
         let response = self.send_ctap_command(command).await?;
         
         match response {
-            CtapResponse::MakeCredential(attestation) => Ok(attestation),
+            CtapResponse::MakeCredential(mut attestation) => {
+                if preference == AttestationConveyance::None {
+                    strip_attestation(&mut attestation);
+                }
+                Ok(attestation)
+            },
             CtapResponse::Error(code) => Err(YKeyError::ctap_error(code)),
             _ => Err(YKeyError::UnexpectedResponse),
         }
@@ -180,8 +800,17 @@ impl<D: Device> Fido2Protocol for Fido2Client<D> {
     
     async fn get_assertion(
         &mut self, 
-        params: GetAssertionParams
+        mut params: GetAssertionParams
     ) -> YKeyResult<AssertionObject> {
+        params.validate()?;
+        // CTAP2.1 only allows silent assertions under a pinUvAuthToken
+        if params.options.up == Some(false) && params.pin_uv_auth_param.is_none() {
+            let token = self.permitted_pin_token(pin::Permissions::GET_ASSERTION)?;
+            params.pin_uv_auth_param = Some(token.authenticate(&params.client_data_hash));
+            params.pin_uv_auth_protocol = Some(token.protocol().version());
+            params.options.uv = None;
+        }
+
         let command = CtapCommand::GetAssertion(params);
         let response = self.send_ctap_command(command).await?;
         
@@ -198,69 +827,45 @@ impl<D: Device> Fido2Protocol for Fido2Client<D> {
         
         match response {
             CtapResponse::Reset => {
-                // Clear any stored PIN tokens after reset
+                // Clear any stored PIN tokens and the now stale GetInfo after reset
                 self.clear_pin_token();
+                self.invalidate_info_cache();
+
+                // Some devices need to settle before answering again
+                if let Some(delay) = self.quirks.as_ref().and_then(|q| q.post_reset_delay) {
+                    tokio::time::sleep(delay).await;
+                }
                 Ok(())
             },
+            CtapResponse::Error(code) if RESET_REFUSED_STATUSES.contains(&code) => Err(YKeyError::CtapError {
+                code,
+                message: RESET_WINDOW_MESSAGE.to_string(),
+            }),
             CtapResponse::Error(code) => Err(YKeyError::ctap_error(code)),
             _ => Err(YKeyError::UnexpectedResponse),
         }
     }
     
     async fn set_pin(&mut self, pin: &str) -> YKeyResult<()> {
-        if pin.len() < 4 || pin.len() > 8 {
-            return Err(YKeyError::InvalidParameters("PIN must be 4-8 characters".to_string()));
-        }
-        
-        let command = CtapCommand::ClientPin(ClientPinCommand::SetPin {
-            pin: pin.to_string(),
-        });
-        let response = self.send_ctap_command(command).await?;
+        pin::validate_new_pin(pin)?;
         
-        match response {
-            CtapResponse::ClientPin => Ok(()),
-            CtapResponse::Error(code) => Err(YKeyError::ctap_error(code)),
-            _ => Err(YKeyError::UnexpectedResponse),
-        }
+        self.set_pin_with(pin::PinUvAuthProtocol::One, pin).await
     }
     
     async fn change_pin(&mut self, old_pin: &str, new_pin: &str) -> YKeyResult<()> {
-        if new_pin.len() < 4 || new_pin.len() > 8 {
-            return Err(YKeyError::InvalidParameters("PIN must be 4-8 characters".to_string()));
-        }
+        pin::validate_new_pin(new_pin)?;
         
-        let command = CtapCommand::ClientPin(ClientPinCommand::ChangePin {
-            old_pin: old_pin.to_string(),
-            new_pin: new_pin.to_string(),
-        });
-        let response = self.send_ctap_command(command).await?;
+        self.change_pin_with(pin::PinUvAuthProtocol::One, old_pin, new_pin).await?;
         
-        match response {
-            CtapResponse::ClientPin => {
-                // Clear stored PIN token after PIN change
-                self.clear_pin_token();
-                Ok(())
-            },
-            CtapResponse::Error(code) => Err(YKeyError::ctap_error(code)),
-            _ => Err(YKeyError::UnexpectedResponse),
-        }
+        // Clear stored PIN token after PIN change
+        self.clear_pin_token();
+        Ok(())
     }
     
     async fn verify_pin(&mut self, pin: &str) -> YKeyResult<Vec<u8>> {
-        let command = CtapCommand::ClientPin(ClientPinCommand::GetPinToken {
-            pin: pin.to_string(),
-        });
-        let response = self.send_ctap_command(command).await?;
-        
-        match response {
-            CtapResponse::ClientPinToken(token) => {
-                self.pin_token = Some(token.clone());
-                self.pin_protocol_version = Some(1); // CTAP2.0 PIN protocol
-                Ok(token)
-            },
-            CtapResponse::Error(code) => Err(YKeyError::ctap_error(code)),
-            _ => Err(YKeyError::UnexpectedResponse),
-        }
+        // CTAP2.0 PIN protocol, supported by every PIN-capable authenticator
+        let token = self.get_pin_token_with(pin::PinUvAuthProtocol::One, pin).await?;
+        Ok(token.into_bytes())
     }
     
     async fn get_next_assertion(&mut self) -> YKeyResult<AssertionObject> {
@@ -274,17 +879,14 @@ impl<D: Device> Fido2Protocol for Fido2Client<D> {
         }
     }
     
+    /// Send an out-of-band cancel on the device's transport
+    ///
+    /// Nothing is awaited: the aborted operation itself fails with
+    /// `UserCancelled`. An operation pending on this client is cancelled
+    /// through the transport's handle instead, e.g.
+    /// [`HidDevice::cancel_handle`](crate::ctaphid::HidDevice::cancel_handle).
     async fn cancel(&mut self) -> YKeyResult<()> {
-        // CTAP cancel is typically sent as a separate HID packet
-        // For now, we'll implement a basic timeout-based cancel
-        let command = CtapCommand::Cancel;
-        let response = self.send_ctap_command(command).await?;
-        
-        match response {
-            CtapResponse::Cancel => Ok(()),
-            CtapResponse::Error(code) => Err(YKeyError::ctap_error(code)),
-            _ => Err(YKeyError::UnexpectedResponse),
-        }
+        self.device.cancel().await
     }
 }
 
@@ -292,16 +894,128 @@ impl<D: Device> Fido2Client<D> {
     /// Send a CTAP command to the device and parse the response
     async fn send_ctap_command(&mut self, command: CtapCommand) -> YKeyResult<CtapResponse> {
         let data = command.encode()?;
+        let response_data = self.exchange(&data).await?;
         
-        // Add timeout for the operation
-        let response_data = tokio::time::timeout(
-            self.timeout,
-            self.device.send_raw(&data)
-        ).await
-        .map_err(|_| YKeyError::timeout(self.timeout.as_secs()))?
-        .map_err(|e| YKeyError::communication(format!("Device communication failed: {}", e)))?;
+        let quirks = self.quirks.clone().unwrap_or_default();
+        CtapResponse::decode_for(&command, &response_data, &quirks)
+    }
+
+    /// Send a CTAP2 command with a raw CBOR parameter map
+    /// 
+    /// Returns the decoded response map, or `None` for an empty success response.
+    pub(crate) async fn send_cbor(
+        &mut self,
+        command: u8,
+        params: Option<cbor::Value>,
+    ) -> YKeyResult<Option<cbor::Value>> {
+        let mut data = vec![command];
+        if let Some(params) = params {
+            data.extend(cbor::encode(&params)?);
+        }
+
+        let response_data = self.exchange(&data).await?;
+        let quirks = self.quirks.clone().unwrap_or_default();
+        match response_data.first() {
+            None => Err(YKeyError::communication("Empty response")),
+            Some(0x00) if response_data.len() == 1 => Ok(None),
+            Some(0x00) => Ok(Some(cbor::decode(&response_data[1..], quirks.lenient_cbor)?)),
+            Some(&code) => Err(YKeyError::ctap_error(code)),
+        }
+    }
+
+    /// Largest command the device and authenticator both accept
+    ///
+    /// The transport's limit always applies; the authenticator's maxMsgSize
+    /// only once GetInfo has been read, so no command triggers a GetInfo.
+    pub fn max_message_size(&self) -> usize {
+        let advertised = self.authenticator_info.as_ref().and_then(|info| info.max_msg_size);
+        advertised.map_or(self.device.max_message_size(), |size| {
+            self.device.max_message_size().min(usize::try_from(size).unwrap_or(usize::MAX))
+        })
+    }
+
+    /// Send an encoded command and wait for the raw response
+    ///
+    /// Commands over [`max_message_size`](Self::max_message_size) are refused
+    /// before reaching the device. Transports that frame messages into packets
+    /// get the command split to their packet sizes.
+    async fn exchange(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        let limit = self.max_message_size();
+        if data.len() > limit {
+            return Err(YKeyError::InvalidParameters(format!(
+                "request too large: {} bytes exceeds the {} byte message limit",
+                data.len(),
+                limit
+            )));
+        }
+        let fragments = match self.device.packet_payload_sizes() {
+            Some((first, continuation)) => Some(split_message(data, first, continuation)?),
+            None => None,
+        };
+        self.resolve_quirks().await;
+        self.trace(trace::TraceDirection::Command, data);
         
-        CtapResponse::decode(&response_data)
+        let send = match &fragments {
+            Some(fragments) => self.device.send_fragments(fragments),
+            None => self.device.send_raw(data),
+        };
+        // Add timeout for the operation
+        let result = tokio::time::timeout(self.timeout, send).await;
+        let response = match result {
+            Ok(response) => response,
+            Err(_) => {
+                // Stop the authenticator waiting for a user who is gone
+                let _ = self.device.cancel().await;
+                return Err(YKeyError::timeout(self.timeout.as_secs()));
+            }
+        };
+        let response = response
+            .map_err(|e| YKeyError::communication(format!("Device communication failed: {}", e)))?;
+        self.trace(trace::TraceDirection::Response, &response);
+        if response.first() == Some(&CTAP2_ERR_KEEPALIVE_CANCEL) {
+            return Err(YKeyError::UserCancelled);
+        }
+        Ok(response)
+    }
+
+    /// Hand a message to the tracer, if one is set
+    fn trace(&self, direction: trace::TraceDirection, data: &[u8]) {
+        if let Some(tracer) = &self.tracer {
+            tracer.trace(&trace::TraceEvent { direction, data, timestamp: SystemClock.now() });
+        }
+    }
+
+    /// GetInfo response from the last `get_info`, without asking the device
+    pub fn cached_info(&self) -> Option<&AuthenticatorInfo> {
+        self.authenticator_info.as_ref()
+    }
+
+    /// Forget the cached GetInfo response, so the next use fetches it again
+    pub fn invalidate_info_cache(&mut self) {
+        self.authenticator_info = None;
+    }
+
+    /// GetInfo response, fetched once and then reused
+    pub(crate) async fn authenticator_info(&mut self) -> YKeyResult<&AuthenticatorInfo> {
+        if self.authenticator_info.is_none() {
+            self.get_info().await?;
+        }
+        self.authenticator_info
+            .as_ref()
+            .ok_or_else(|| YKeyError::communication("GetInfo response missing"))
+    }
+
+    /// Resolve device quirks from the USB identity if not done yet
+    async fn resolve_quirks(&mut self) {
+        if self.quirks.is_some() {
+            return;
+        }
+
+        if let Ok(info) = self.device.info().await {
+            self.identity.vendor_id = Some(info.vendor_id);
+            self.identity.product_id = Some(info.product_id);
+        }
+        self.quirks = Some(self.quirk_table.lookup(&self.identity));
     }
     
     /// Get underlying device reference
@@ -323,6 +1037,11 @@ impl<D: Device> Fido2Client<D> {
     pub fn pin_protocol_version(&self) -> Option<u8> {
         self.pin_protocol_version
     }
+
+    /// Permissions granted to the stored PIN token
+    pub fn pin_permissions(&self) -> pin::Permissions {
+        self.pin_permissions
+    }
 }
 
 #[cfg(test)]
@@ -333,14 +1052,23 @@ mod tests {
     // Mock device for testing
     struct MockDevice {
         responses: std::collections::VecDeque<Vec<u8>>,
+        requests: Vec<Vec<u8>>,
         connected: bool,
+        max_message_size: usize,
+        packet_payload_sizes: Option<(usize, usize)>,
+        /// Fragment lengths of each request sent through `send_fragments`
+        fragments: Vec<Vec<usize>>,
     }
 
     impl MockDevice {
         fn new() -> Self {
             Self {
                 responses: std::collections::VecDeque::new(),
+                requests: Vec::new(),
                 connected: false,
+                max_message_size: 7609,
+                packet_payload_sizes: None,
+                fragments: Vec::new(),
             }
         }
         
@@ -378,14 +1106,28 @@ mod tests {
             self.connected
         }
         
-        async fn send_raw(&mut self, _data: &[u8]) -> YKeyResult<Vec<u8>> {
+        async fn send_raw(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
             if !self.connected {
                 return Err(YKeyError::communication("Device not connected"));
             }
             
+            self.requests.push(data.to_vec());
             self.responses.pop_front()
                 .ok_or_else(|| YKeyError::communication("No response available"))
         }
+
+        fn max_message_size(&self) -> usize {
+            self.max_message_size
+        }
+
+        fn packet_payload_sizes(&self) -> Option<(usize, usize)> {
+            self.packet_payload_sizes
+        }
+
+        async fn send_fragments(&mut self, fragments: &[&[u8]]) -> YKeyResult<Vec<u8>> {
+            self.fragments.push(fragments.iter().map(|fragment| fragment.len()).collect());
+            self.send_raw(&fragments.concat()).await
+        }
     }
 
     #[tokio::test]
@@ -452,4 +1194,761 @@ mod tests {
         assert_eq!(client.pin_token(), None);
         assert_eq!(client.pin_protocol_version(), None);
     }
+
+    /// Build a success response with a small CBOR map body and optional trailing bytes
+    fn cbor_response(trailing: &[u8]) -> Vec<u8> {
+        let body = ciborium::Value::Map(vec![
+            (
+                ciborium::Value::Integer(1.into()),
+                ciborium::Value::Array(vec![ciborium::Value::Text("FIDO_2_0".to_string())]),
+            ),
+            (ciborium::Value::Integer(3.into()), ciborium::Value::Bytes(vec![0; 16])),
+        ]);
+        let mut data = vec![0x00];
+        ciborium::ser::into_writer(&body, &mut data).unwrap();
+        data.extend_from_slice(trailing);
+        data
+    }
+
+    #[test]
+    fn test_trailing_bytes_rejected_without_quirk() {
+        let data = cbor_response(&[0x00, 0x00]);
+        let result = CtapResponse::decode(&data);
+        assert!(matches!(result, Err(YKeyError::CommunicationError(_))));
+    }
+
+    #[test]
+    fn test_aaguid_quirk_takes_lenient_path() {
+        let aaguid = Aaguid::new([0x42; 16]);
+        let mut table = QuirkTable::empty();
+        table.add(quirks::QuirkEntry {
+            matcher: quirks::QuirkMatch::Aaguid(aaguid),
+            firmware: quirks::FirmwareRange::ANY,
+            quirks: vec![quirks::Quirk::LenientCbor],
+            description: "pads responses",
+        });
+
+        let identity = DeviceIdentity {
+            aaguid: Some(aaguid),
+            ..Default::default()
+        };
+        let device_quirks = table.lookup(&identity);
+        assert!(device_quirks.lenient_cbor);
+
+        let data = cbor_response(&[0x00, 0x00]);
+        assert!(CtapResponse::decode_with_quirks(&data, &device_quirks).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_applies_vid_pid_quirks() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(cbor_response(&[0x00]));
+
+        let mut client = Fido2Client::new(device);
+        let mut table = QuirkTable::empty();
+        table.add(quirks::QuirkEntry {
+            matcher: quirks::QuirkMatch::VidPid { vendor_id: 0x1234, product_id: 0x5678 },
+            firmware: quirks::FirmwareRange::ANY,
+            quirks: vec![quirks::Quirk::LenientCbor],
+            description: "pads responses",
+        });
+        client.set_quirk_table(table);
+
+        assert!(client.get_info().await.is_ok());
+        assert!(client.quirks().unwrap().lenient_cbor);
+    }
+
+    #[tokio::test]
+    async fn test_reset_applies_settle_delay() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(vec![0x00]);
+
+        let mut client = Fido2Client::new(device);
+        let mut table = QuirkTable::empty();
+        table.add(quirks::QuirkEntry {
+            matcher: quirks::QuirkMatch::Vendor(0x1234),
+            firmware: quirks::FirmwareRange::ANY,
+            quirks: vec![quirks::Quirk::PostResetDelay(Duration::from_millis(50))],
+            description: "slow to recover",
+        });
+        client.set_quirk_table(table);
+
+        let start = std::time::Instant::now();
+        client.reset().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    fn excluding(count: u8) -> MakeCredentialParams {
+        let mut params = make_credential_params(AttestationConveyance::Direct);
+        params.exclude_list = Some(
+            (0..count)
+                .map(|i| PublicKeyCredentialDescriptor {
+                    cred_type: "public-key".to_string(),
+                    id: vec![i; 64],
+                    transports: None,
+                })
+                .collect(),
+        );
+        params
+    }
+
+    #[tokio::test]
+    async fn test_capability_checks_share_cached_info() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        let body = cbor::int_map(vec![
+            (0x01, Some(cbor::Value::Array(vec![cbor::text("FIDO_2_1")]))),
+            (0x03, Some(cbor::bytes(&[0; 16]))),
+            (0x04, Some(cbor::Value::Map(vec![(cbor::text("ep"), cbor::Value::Bool(true))]))),
+        ]);
+        device.add_response([vec![0x00], cbor::encode(&body).unwrap()].concat());
+        device.add_response(make_credential_response());
+        device.add_response(make_credential_response());
+        device.add_response(vec![0x00]);
+        let mut client = Fido2Client::new(device);
+        client.set_quirk_table(QuirkTable::empty());
+        assert!(client.cached_info().is_none());
+
+        let mut params = make_credential_params(AttestationConveyance::Direct);
+        params.enterprise_attestation = Some(ENTERPRISE_ATTESTATION_VENDOR_FACILITATED);
+        client.make_credential(params.clone()).await.unwrap();
+        assert!(client.cached_info().unwrap().option_enabled("ep"));
+        client.make_credential(params).await.unwrap();
+
+        // GetInfo went out once, ahead of both MakeCredential commands
+        let commands: Vec<u8> = client.device().requests.iter().map(|r| r[0]).collect();
+        assert_eq!(commands, vec![0x04, 0x01, 0x01]);
+
+        client.reset().await.unwrap();
+        assert!(client.cached_info().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_request_rejected_by_transport_limit() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.max_message_size = 512;
+        device.add_response(make_credential_response());
+        let mut client = Fido2Client::new(device);
+
+        let result = client.make_credential(excluding(10)).await;
+        assert!(matches!(result, Err(YKeyError::InvalidParameters(m)) if m.contains("request too large")));
+        assert!(client.device().requests.is_empty());
+
+        client.make_credential(excluding(2)).await.unwrap();
+        assert_eq!(client.device().requests.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_split_into_packets() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.packet_payload_sizes = Some((57, 59));
+        device.add_response(make_credential_response());
+        let mut client = Fido2Client::new(device);
+
+        client.make_credential(excluding(3)).await.unwrap();
+        let request = &client.device().requests[0];
+        let fragments = &client.device().fragments[0];
+        assert!(fragments.len() > 2);
+        assert_eq!(fragments[0], 57);
+        assert!(fragments[1..fragments.len() - 1].iter().all(|&length| length == 59));
+        assert_eq!(fragments.iter().sum::<usize>(), request.len());
+    }
+
+    #[test]
+    fn test_split_message() {
+        let data = [0xAB; 10];
+        let fragments = split_message(&data, 4, 3).unwrap();
+        assert_eq!(fragments, vec![&data[..4], &data[4..7], &data[7..]]);
+
+        assert_eq!(split_message(&[], 4, 3).unwrap(), vec![&[] as &[u8]]);
+        assert!(matches!(split_message(&data, 4, 0), Err(YKeyError::InvalidParameters(_))));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_request_rejected_by_advertised_limit() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        let body = cbor::int_map(vec![
+            (0x01, Some(cbor::Value::Array(vec![cbor::text("FIDO_2_1")]))),
+            (0x03, Some(cbor::bytes(&[0; 16]))),
+            (0x05, Some(cbor::int(300))),
+        ]);
+        device.add_response([vec![0x00], cbor::encode(&body).unwrap()].concat());
+        let mut client = Fido2Client::new(device);
+        assert_eq!(client.max_message_size(), 7609);
+
+        client.get_info().await.unwrap();
+        assert_eq!(client.max_message_size(), 300);
+        let result = client.make_credential(excluding(5)).await;
+        assert!(matches!(result, Err(YKeyError::InvalidParameters(_))));
+        assert_eq!(client.device().requests.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reset_outside_window_is_explained() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(vec![CTAP2_ERR_NOT_ALLOWED]);
+        device.add_response(vec![CTAP2_ERR_USER_ACTION_TIMEOUT]);
+        device.add_response(vec![0x00]);
+
+        let mut client = Fido2Client::new(device);
+        client.set_quirk_table(QuirkTable::empty());
+        let mut prompts = 0;
+        let result = client.reset_with_prompt(|| prompts += 1).await;
+        let Err(YKeyError::CtapError { code, message }) = result else {
+            panic!("expected a refused reset, got {:?}", result);
+        };
+        assert_eq!(code, CTAP2_ERR_NOT_ALLOWED);
+        assert!(message.contains("within 10s"));
+        assert_eq!(prompts, 1);
+
+        let result = client.reset_with_prompt(|| prompts += 1).await;
+        assert!(matches!(result, Err(YKeyError::CtapError { code: CTAP2_ERR_USER_ACTION_TIMEOUT, message }) if message.contains("touch")));
+
+        client.reset_with_prompt(|| prompts += 1).await.unwrap();
+        assert_eq!(prompts, 3);
+        assert_eq!(client.device().requests, vec![vec![0x07], vec![0x07], vec![0x07]]);
+    }
+
+    #[tokio::test]
+    async fn test_reset_clears_pin_token() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(vec![0x00]);
+        device.add_response(vec![0x30]);
+
+        let mut client = Fido2Client::new(device);
+        client.set_quirk_table(QuirkTable::empty());
+        client.pin_token = Some(vec![0x11; 32]);
+        client.pin_protocol_version = Some(2);
+
+        client.reset().await.unwrap();
+        assert!(client.pin_token().is_none());
+        assert!(client.pin_protocol_version.is_none());
+
+        // A refused reset leaves nothing to clear and must not wait
+        client.pin_token = Some(vec![0x11; 32]);
+        assert!(client.reset().await.is_err());
+        assert!(client.pin_token().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_pin_status_codes_map_to_typed_errors() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(vec![pin::CTAP2_ERR_PIN_INVALID]);
+        device.add_response(vec![0x36]);
+        device.add_response(vec![0x3B]);
+
+        let mut client = Fido2Client::new(device);
+        client.set_quirk_table(QuirkTable::empty());
+        let result = client.send_cbor(pin::CLIENT_PIN_COMMAND, None).await;
+        assert!(matches!(result, Err(YKeyError::InvalidPin { retries_remaining: None, .. })));
+        let result = client.send_cbor(pin::CLIENT_PIN_COMMAND, None).await;
+        assert!(matches!(result, Err(YKeyError::PinRequired)));
+        let result = client.send_cbor(pin::CLIENT_PIN_COMMAND, None).await;
+        assert!(matches!(result, Err(YKeyError::UserVerificationRequired)));
+    }
+
+    /// GetInfo response captured from a YubiKey 5 NFC (firmware 5.4.3)
+    const YUBIKEY5_GET_INFO: &str = concat!(
+        "00ab0183665532465f5632684649444f5f325f306c4649444f5f325f315f5052",
+        "4502826b6372656450726f746563746b686d61632d7365637265740350ee8828",
+        "79721c491397753dfcce97072a04a562726bf5627570f564706c6174f469636c",
+        "69656e7450696ef57563726564656e7469616c4d676d7450726576696577f505",
+        "1904b006810107080818800982636e6663637573620a82a263616c6726647479",
+        "70656a7075626c69632d6b6579a263616c672764747970656a7075626c69632d",
+        "6b65790e1a00050403",
+    );
+
+    #[test]
+    fn test_decode_yubikey5_get_info() {
+        let data = hex::decode(YUBIKEY5_GET_INFO).unwrap();
+        let response = CtapResponse::decode_for(&CtapCommand::GetInfo, &data, &DeviceQuirks::default());
+        let CtapResponse::GetInfo(info) = response.unwrap() else {
+            panic!("expected GetInfo response");
+        };
+
+        assert_eq!(info.versions, vec!["U2F_V2", "FIDO_2_0", "FIDO_2_1_PRE"]);
+        assert_eq!(info.aaguid.to_string(), "ee882879-721c-4913-9775-3dfcce97072a");
+        assert_eq!(info.extensions.unwrap(), vec!["credProtect", "hmac-secret"]);
+        assert_eq!(info.options.as_ref().unwrap().get("clientPin"), Some(&true));
+        assert_eq!(info.options.as_ref().unwrap().get("plat"), Some(&false));
+        assert_eq!(info.max_msg_size, Some(1200));
+        assert_eq!(info.pin_uv_auth_protocols, Some(vec![1]));
+        assert_eq!(info.transports.unwrap(), vec!["nfc", "usb"]);
+        assert_eq!(info.algorithms.unwrap()[1].alg, -8);
+        assert_eq!(info.firmware_version, Some(0x050403));
+        assert_eq!(info.min_pin_length, None);
+        assert_eq!(info.max_serialized_large_blob_array, None);
+    }
+
+    #[test]
+    fn test_get_info_ignores_unknown_keys() {
+        let body = cbor::int_map(vec![
+            (0x01, Some(cbor::Value::Array(vec![cbor::text("FIDO_2_1")]))),
+            (0x03, Some(cbor::bytes(&[0x11; 16]))),
+            (0x7F, Some(cbor::text("vendor specific"))),
+        ]);
+        let mut data = vec![0x00];
+        data.extend(cbor::encode(&body).unwrap());
+
+        let CtapResponse::GetInfo(info) = CtapResponse::decode(&data).unwrap() else {
+            panic!("expected GetInfo response");
+        };
+        assert_eq!(info.versions, vec!["FIDO_2_1"]);
+        assert_eq!(info.aaguid, Aaguid::new([0x11; 16]));
+        assert!(info.options.is_none());
+    }
+
+    #[test]
+    fn test_get_info_requires_aaguid() {
+        let body = cbor::int_map(vec![(0x01, Some(cbor::Value::Array(vec![cbor::text("FIDO_2_0")])))]);
+        let mut data = vec![0x00];
+        data.extend(cbor::encode(&body).unwrap());
+
+        assert!(CtapResponse::decode(&data).is_err());
+    }
+
+    #[test]
+    fn test_decode_diagnostic_reports_every_issue() {
+        // aaguid is missing and maxMsgSize is a string
+        let body = cbor::int_map(vec![
+            (0x01, Some(cbor::Value::Array(vec![cbor::text("FIDO_2_0")]))),
+            (0x05, Some(cbor::text("1200"))),
+            (0x06, Some(cbor::Value::Array(vec![cbor::int(1)]))),
+        ]);
+        let mut data = vec![0x00];
+        data.extend(cbor::encode(&body).unwrap());
+
+        let (response, issues) = CtapResponse::decode_diagnostic(&CtapCommand::GetInfo, &data).unwrap();
+        let CtapResponse::GetInfo(info) = response else {
+            panic!("expected GetInfo response");
+        };
+        assert_eq!(info.versions, vec!["FIDO_2_0"]);
+        assert_eq!(info.pin_uv_auth_protocols, Some(vec![1]));
+        assert!(info.aaguid.is_zero());
+        assert_eq!(info.max_msg_size, None);
+
+        let fields: Vec<_> = issues.iter().map(|issue| issue.field.as_str()).collect();
+        assert_eq!(fields, ["aaguid", "maxMsgSize"]);
+        assert_eq!(issues[0].problem, "missing required field");
+        assert_eq!(issues[0].to_string(), "GetInfo aaguid: missing required field");
+
+        // Normal decoding stops at the first problem
+        let error = CtapResponse::decode_for(&CtapCommand::GetInfo, &data, &DeviceQuirks::default()).unwrap_err();
+        assert_eq!(error.to_string(), "Device communication error: GetInfo missing aaguid");
+
+        let clean = hex::decode(YUBIKEY5_GET_INFO).unwrap();
+        assert!(CtapResponse::decode_diagnostic(&CtapCommand::GetInfo, &clean).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn test_decode_diagnostic_assertion() {
+        let body = cbor::int_map(vec![
+            (0x02, Some(cbor::text("not bytes"))),
+            (0x04, Some(cbor::Value::Map(vec![(cbor::text("name"), cbor::text("alice"))]))),
+        ]);
+        let mut data = vec![0x00];
+        data.extend(cbor::encode(&body).unwrap());
+
+        let (_, issues) = CtapResponse::decode_diagnostic(&CtapCommand::GetNextAssertion, &data).unwrap();
+        let fields: Vec<_> = issues.iter().map(|issue| (issue.response.as_str(), issue.field.as_str())).collect();
+        assert_eq!(fields, [("GetAssertion", "authData"), ("GetAssertion", "signature"), ("GetAssertion", "user.id")]);
+    }
+
+    fn make_credential_params(preference: AttestationConveyance) -> MakeCredentialParams {
+        MakeCredentialParams {
+            client_data_hash: vec![0xCD; 32],
+            rp: RelyingParty {
+                id: "example.com".to_string(),
+                name: Some("Example".to_string()),
+                icon: None,
+            },
+            user: User {
+                id: vec![1, 2, 3],
+                name: "alice".to_string(),
+                display_name: "Alice".to_string(),
+                icon: None,
+            },
+            pub_key_cred_params: vec![PublicKeyCredentialParameter {
+                cred_type: "public-key".to_string(),
+                alg: -7,
+            }],
+            exclude_list: None,
+            extensions: None,
+            options: MakeCredentialOptions::default(),
+            pin_uv_auth_param: None,
+            pin_uv_auth_protocol: None,
+            attestation_preference: preference,
+            cred_blob: None,
+            large_blob_key: false,
+            enterprise_attestation: None,
+        }
+    }
+
+    /// Packed attestation with attested credential data for AAGUID 0x42..
+    fn make_credential_response() -> Vec<u8> {
+        let mut auth_data = vec![0xAA; 32];
+        auth_data.push(0x41); // UP | AT
+        auth_data.extend_from_slice(&1u32.to_be_bytes());
+        auth_data.extend_from_slice(&[0x42; 16]);
+        auth_data.extend_from_slice(&[0x00, 0x02, 0xC1, 0xC2]);
+
+        let body = cbor::int_map(vec![
+            (0x01, Some(cbor::text("packed"))),
+            (0x02, Some(cbor::bytes(&auth_data))),
+            (0x03, Some(cbor::Value::Map(vec![
+                (cbor::text("alg"), cbor::int(-7)),
+                (cbor::text("sig"), cbor::bytes(&[0x30, 0x44])),
+                (cbor::text("x5c"), cbor::Value::Array(vec![cbor::bytes(&[0x30, 0x82])])),
+            ]))),
+        ]);
+        let mut data = vec![0x00];
+        data.extend(cbor::encode(&body).unwrap());
+        data
+    }
+
+    async fn make_credential_with(preference: AttestationConveyance) -> AttestationObject {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(make_credential_response());
+        let mut client = Fido2Client::new(device);
+        client.make_credential(make_credential_params(preference)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_attestation_none_strips_statement() {
+        let attestation = make_credential_with(AttestationConveyance::None).await;
+
+        assert_eq!(attestation.fmt, "none");
+        assert!(attestation.att_stmt.is_empty());
+        assert_eq!(&attestation.auth_data[37..53], &[0; 16]);
+        assert_eq!(&attestation.auth_data[53..], &[0x00, 0x02, 0xC1, 0xC2]);
+    }
+
+    #[tokio::test]
+    async fn test_attestation_direct_left_intact() {
+        let attestation = make_credential_with(AttestationConveyance::Direct).await;
+
+        assert_eq!(attestation.fmt, "packed");
+        assert_eq!(attestation.att_stmt.get("alg"), Some(&serde_json::json!(-7)));
+        assert!(attestation.att_stmt.contains_key("sig"));
+        assert!(attestation.att_stmt.contains_key("x5c"));
+        assert_eq!(&attestation.auth_data[37..53], &[0x42; 16]);
+    }
+
+    #[test]
+    fn test_enterprise_attestation_requested() {
+        let encode = |preference| {
+            let data = CtapCommand::MakeCredential(make_credential_params(preference)).encode().unwrap();
+            assert_eq!(data[0], 0x01);
+            cbor::decode(&data[1..], false).unwrap()
+        };
+
+        let enterprise = encode(AttestationConveyance::Enterprise);
+        let map = cbor::as_map(&enterprise).unwrap();
+        assert_eq!(cbor::get_int(map, 0x0A), Some(&cbor::int(1)));
+
+        let direct = encode(AttestationConveyance::Direct);
+        assert_eq!(cbor::get_int(cbor::as_map(&direct).unwrap(), 0x0A), None);
+
+        let mut params = make_credential_params(AttestationConveyance::Direct);
+        params.enterprise_attestation = Some(ENTERPRISE_ATTESTATION_PLATFORM_MANAGED);
+        let data = CtapCommand::MakeCredential(params).encode().unwrap();
+        let platform_managed = cbor::decode(&data[1..], false).unwrap();
+        assert_eq!(cbor::get_int(cbor::as_map(&platform_managed).unwrap(), 0x0A), Some(&cbor::int(2)));
+    }
+
+    #[tokio::test]
+    async fn test_enterprise_attestation_requires_ep() {
+        let get_info = |ep: bool| {
+            let body = cbor::int_map(vec![
+                (0x01, Some(cbor::Value::Array(vec![cbor::text("FIDO_2_1")]))),
+                (0x03, Some(cbor::bytes(&[0; 16]))),
+                (0x04, Some(cbor::Value::Map(vec![(cbor::text("ep"), cbor::Value::Bool(ep))]))),
+            ]);
+            [vec![0x00], cbor::encode(&body).unwrap()].concat()
+        };
+        let mut params = make_credential_params(AttestationConveyance::Direct);
+        params.enterprise_attestation = Some(ENTERPRISE_ATTESTATION_VENDOR_FACILITATED);
+
+        // Disabled enterprise attestation fails before MakeCredential is sent
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(get_info(false));
+        let mut client = Fido2Client::new(device);
+        let result = client.make_credential(params.clone()).await;
+        assert!(matches!(result, Err(YKeyError::InvalidParameters(_))));
+        assert_eq!(client.device().requests.len(), 1);
+
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(get_info(true));
+        device.add_response(make_credential_response());
+        let mut client = Fido2Client::new(device);
+        client.make_credential(params.clone()).await.unwrap();
+        let request = &client.device().requests[1];
+        assert_eq!(request[0], 0x01);
+        let sent = cbor::decode(&request[1..], false).unwrap();
+        assert_eq!(cbor::get_int(cbor::as_map(&sent).unwrap(), 0x0A), Some(&cbor::int(1)));
+
+        params.enterprise_attestation = Some(3);
+        let result = client.make_credential(params).await;
+        assert!(matches!(result, Err(YKeyError::InvalidParameters(_))));
+    }
+
+    fn user() -> User {
+        User { id: vec![1, 2, 3], name: "alice".to_string(), display_name: "Alice".to_string(), icon: None }
+    }
+
+    #[tokio::test]
+    async fn test_cred_blob_round_trip() {
+        let mut client = Fido2Client::new(soft::SoftAuthenticator::blank());
+        let params = MakeCredentialParams::builder()
+            .rp_id("example.com")
+            .user(user())
+            .client_data_hash(vec![0xCD; 32])
+            .cred_blob(b"ssh-ed25519".to_vec())
+            .build()
+            .unwrap();
+        let attestation = client.make_credential(params).await.unwrap();
+        assert_eq!(attestation.cred_blob_stored, Some(true));
+        assert_eq!(attestation.large_blob_key, None);
+        let credential_id = cose::AuthenticatorData::parse(&attestation.auth_data)
+            .unwrap()
+            .attested_credential
+            .unwrap()
+            .credential_id;
+
+        let params = GetAssertionParams::builder()
+            .rp_id("example.com")
+            .allow(credential_id)
+            .client_data_hash(vec![0xCD; 32])
+            .get_cred_blob(true)
+            .build()
+            .unwrap();
+        let assertion = client.get_assertion(params).await.unwrap();
+        assert_eq!(assertion.cred_blob.as_deref(), Some(&b"ssh-ed25519"[..]));
+
+        let oversize = MakeCredentialParams::builder()
+            .rp_id("example.com")
+            .user(user())
+            .client_data_hash(vec![0xCD; 32])
+            .cred_blob(vec![0; 33])
+            .build()
+            .unwrap();
+        let result = client.make_credential(oversize).await;
+        assert!(matches!(result, Err(YKeyError::InvalidParameters(_))));
+    }
+
+    #[tokio::test]
+    async fn test_large_blob_key_round_trip() {
+        let mut client = Fido2Client::new(soft::SoftAuthenticator::blank());
+        let params = MakeCredentialParams::builder()
+            .rp_id("example.com")
+            .user(user())
+            .client_data_hash(vec![0xCD; 32])
+            .large_blob_key(true)
+            .build()
+            .unwrap();
+        let attestation = client.make_credential(params).await.unwrap();
+        let key = attestation.large_blob_key.expect("largeBlobKey returned");
+        assert_eq!(key.len(), 32);
+        assert_eq!(attestation.cred_blob_stored, None);
+
+        let assertion_params = |large_blob_key| {
+            GetAssertionParams::builder()
+                .rp_id("example.com")
+                .client_data_hash(vec![0xCD; 32])
+                .large_blob_key(large_blob_key)
+                .build()
+                .unwrap()
+        };
+        let assertion = client.get_assertion(assertion_params(true)).await.unwrap();
+        assert_eq!(assertion.large_blob_key, Some(key));
+        assert_eq!(assertion.cred_blob, None);
+        let assertion = client.get_assertion(assertion_params(false)).await.unwrap();
+        assert_eq!(assertion.large_blob_key, None);
+    }
+
+    fn silent_assertion_params() -> GetAssertionParams {
+        GetAssertionParams {
+            rp_id: "example.com".to_string(),
+            client_data_hash: vec![0xCD; 32],
+            allow_list: Some(vec![PublicKeyCredentialDescriptor {
+                cred_type: "public-key".to_string(),
+                id: vec![0xA1; 16],
+                transports: None,
+            }]),
+            extensions: None,
+            options: GetAssertionOptions { up: Some(false), uv: Some(true) },
+            pin_uv_auth_param: None,
+            pin_uv_auth_protocol: None,
+            get_cred_blob: false,
+            large_blob_key: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_silent_assertion_attaches_pin_token() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        let body = cbor::int_map(vec![
+            (0x02, Some(cbor::bytes(&[0xAA; 37]))),
+            (0x03, Some(cbor::bytes(&[0x30, 0x44]))),
+        ]);
+        device.add_response([vec![0x00], cbor::encode(&body).unwrap()].concat());
+
+        let mut client = Fido2Client::new(device);
+        client.pin_token = Some(vec![0x22; 32]);
+        client.pin_protocol_version = Some(2);
+        client.pin_permissions = pin::Permissions::GET_ASSERTION;
+        client.get_assertion(silent_assertion_params()).await.unwrap();
+
+        let request = &client.device().requests[0];
+        assert_eq!(request[0], 0x02);
+        let request = cbor::decode(&request[1..], false).unwrap();
+        let map = cbor::as_map(&request).unwrap();
+        let token = pin::PinUvAuthToken::new(pin::PinUvAuthProtocol::Two, vec![0x22; 32]);
+        assert_eq!(cbor::get_int(map, 0x06), Some(&cbor::bytes(&token.authenticate(&[0xCD; 32]))));
+        assert_eq!(cbor::get_int(map, 0x07), Some(&cbor::int(2)));
+        assert_eq!(
+            cbor::get_int(map, 0x05),
+            Some(&cbor::Value::Map(vec![(cbor::text("up"), cbor::Value::Bool(false))]))
+        );
+    }
+
+    fn assertion_response(number_of_credentials: Option<i64>, user: u8) -> Vec<u8> {
+        let body = cbor::int_map(vec![
+            (0x02, Some(cbor::bytes(&[0xAA; 37]))),
+            (0x03, Some(cbor::bytes(&[0x30, user]))),
+            (0x04, Some(cbor::Value::Map(vec![(cbor::text("id"), cbor::bytes(&[user]))]))),
+            (0x05, number_of_credentials.map(cbor::int)),
+        ]);
+        [vec![0x00], cbor::encode(&body).unwrap()].concat()
+    }
+
+    #[tokio::test]
+    async fn test_assertion_iter_yields_index_and_total() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(assertion_response(Some(3), 1));
+        device.add_response(assertion_response(None, 2));
+        device.add_response(assertion_response(None, 3));
+
+        let mut client = Fido2Client::new(device);
+        let mut params = silent_assertion_params();
+        params.options = GetAssertionOptions::default();
+        let mut assertions = client.assertion_iter(params);
+        let mut seen = Vec::new();
+        while let Some(result) = assertions.next().await {
+            let (index, total, assertion) = result.unwrap();
+            seen.push((index, total, assertion.user.unwrap().id[0]));
+        }
+
+        assert_eq!(seen, vec![(0, 3, 1), (1, 3, 2), (2, 3, 3)]);
+        let commands: Vec<u8> = client.device().requests.iter().map(|r| r[0]).collect();
+        assert_eq!(commands, vec![0x02, 0x08, 0x08]);
+    }
+
+    #[tokio::test]
+    async fn test_get_all_assertions_stops_at_reported_count() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(assertion_response(Some(3), 1));
+        device.add_response(assertion_response(None, 2));
+        device.add_response(assertion_response(None, 3));
+        // Never requested, since only three credentials were reported
+        device.add_response(assertion_response(None, 4));
+
+        let mut client = Fido2Client::new(device);
+        let mut params = silent_assertion_params();
+        params.options = GetAssertionOptions::default();
+        let assertions = client.get_all_assertions(params).await.unwrap();
+
+        let users: Vec<u8> = assertions.iter().map(|a| a.user.as_ref().unwrap().id[0]).collect();
+        assert_eq!(users, vec![1, 2, 3]);
+        assert_eq!(assertions[0].number_of_credentials, Some(3));
+        assert_eq!(client.device().requests.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_assertion_iter_without_count_yields_one() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        device.add_response(assertion_response(None, 1));
+
+        let mut client = Fido2Client::new(device);
+        let mut params = silent_assertion_params();
+        params.options = GetAssertionOptions::default();
+        let mut assertions = client.assertion_iter(params);
+
+        assert_eq!(assertions.next().await.unwrap().unwrap().1, 1);
+        assert!(assertions.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_silent_assertion_requires_pin_token() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        let mut client = Fido2Client::new(device);
+
+        let result = client.get_assertion(silent_assertion_params()).await;
+        assert!(matches!(result, Err(YKeyError::PinRequired)));
+        assert!(client.device().requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_assertion_rejects_bad_client_data_hash() {
+        let mut device = MockDevice::new();
+        device.connect().await.unwrap();
+        let mut client = Fido2Client::new(device);
+
+        let mut params = silent_assertion_params();
+        params.client_data_hash = vec![0xCD; 20];
+        let result = client.get_assertion(params).await;
+        assert!(matches!(result, Err(YKeyError::InvalidParameters(_))));
+        assert!(client.device().requests.is_empty());
+    }
+
+    #[test]
+    fn test_verify_assertion_over_auth_data_and_client_data_hash() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let signing_key = SigningKey::from_bytes(&[0x07; 32]);
+        let public_key = cose::CosePublicKey::Okp {
+            alg: cose::ALG_EDDSA,
+            curve: cose::CURVE_ED25519,
+            x: signing_key.verifying_key().to_bytes().to_vec(),
+        };
+        let auth_data = vec![0xAB; 37];
+        let client_data_hash = [0xCD; 32];
+        let signed_data = [auth_data.as_slice(), &client_data_hash].concat();
+        let mut assertion = AssertionObject {
+            credential_id: Some(vec![0x01; 16]),
+            auth_data,
+            signature: signing_key.sign(&signed_data).to_bytes().to_vec(),
+            user: None,
+            number_of_credentials: None,
+            cred_blob: None,
+            large_blob_key: None,
+        };
+
+        let client = Fido2Client::new(MockDevice::new());
+        assert!(client.verify_assertion(&assertion, &client_data_hash, &public_key).is_ok());
+        assert!(client.verify_assertion(&assertion, &[0x00; 32], &public_key).is_err());
+
+        // Bumping the sign counter invalidates the signature
+        assertion.auth_data[36] = 0x01;
+        assert!(matches!(
+            client.verify_assertion(&assertion, &client_data_hash, &public_key),
+            Err(YKeyError::AuthenticationFailed(_))
+        ));
+    }
 }
@@ -0,0 +1,164 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! YubiKey management application support
+//!
+//! Talks to the management applet over ISO 7816 APDUs. For now this covers
+//! reading device-generated random bytes with GET CHALLENGE, which FIPS
+//! workflows use to sample the key's RNG. Firmware without the command
+//! answers with "instruction not supported".
+
+use crate::apdu::{self, CommandApdu, SW_SUCCESS};
+use ykey_core::{traits::Device, YKeyError, YKeyResult};
+
+/// Management applet AID
+const MANAGEMENT_AID: [u8; 8] = [0xA0, 0x00, 0x00, 0x05, 0x27, 0x47, 0x11, 0x17];
+
+const INS_SELECT: u8 = 0xA4;
+const INS_GET_CHALLENGE: u8 = 0x84;
+
+/// Most random bytes one GET CHALLENGE can return, the largest short Le
+pub const MAX_RANDOM_LENGTH: usize = 256;
+
+/// Bytes requested when probing for GET CHALLENGE support
+const PROBE_LENGTH: usize = 8;
+
+/// YubiKey management protocol client
+pub struct ManagementClient<D: Device> {
+    device: D,
+    selected: bool,
+    random_supported: Option<bool>,
+}
+
+impl<D: Device> ManagementClient<D> {
+    /// Create a new management client with the given device
+    pub fn new(device: D) -> Self {
+        Self { device, selected: false, random_supported: None }
+    }
+
+    /// Get underlying device reference
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// Read `length` random bytes generated by the device
+    ///
+    /// `length` must be between 1 and [`MAX_RANDOM_LENGTH`]. Devices without
+    /// GET CHALLENGE return `UnsupportedOperation`.
+    pub async fn get_random(&mut self, length: usize) -> YKeyResult<Vec<u8>> {
+        if length == 0 || length > MAX_RANDOM_LENGTH {
+            return Err(YKeyError::InvalidParameters(format!(
+                "Random length must be 1 to {} bytes",
+                MAX_RANDOM_LENGTH
+            )));
+        }
+        if self.random_supported == Some(false) {
+            return Err(random_unsupported());
+        }
+        self.select().await?;
+
+        let command = CommandApdu::new(0x00, INS_GET_CHALLENGE, 0x00, 0x00).with_le(length);
+        let response = apdu::transceive(&mut self.device, &command).await?;
+        let random = match response.status() {
+            SW_SUCCESS => response.data,
+            0x6D00 | 0x6E00 => {
+                self.random_supported = Some(false);
+                return Err(random_unsupported());
+            }
+            status => {
+                return Err(YKeyError::communication(format!(
+                    "GET CHALLENGE failed with status {:04X}",
+                    status
+                )))
+            }
+        };
+        self.random_supported = Some(true);
+
+        if random.len() != length {
+            return Err(YKeyError::communication(format!(
+                "Device returned {} random bytes, expected {}",
+                random.len(),
+                length
+            )));
+        }
+        Ok(random)
+    }
+
+    /// Whether the device hands out random bytes through [`get_random`](Self::get_random)
+    ///
+    /// The first call probes the device with a short request; the answer is
+    /// remembered for the lifetime of the client.
+    pub async fn supports_random(&mut self) -> YKeyResult<bool> {
+        if let Some(supported) = self.random_supported {
+            return Ok(supported);
+        }
+        match self.get_random(PROBE_LENGTH).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.is_unsupported() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Select the management applet if not done yet
+    async fn select(&mut self) -> YKeyResult<()> {
+        if self.selected {
+            return Ok(());
+        }
+        let command = CommandApdu::new(0x00, INS_SELECT, 0x04, 0x00).with_data(MANAGEMENT_AID);
+        let response = apdu::transceive(&mut self.device, &command).await?;
+        match response.status() {
+            SW_SUCCESS => {
+                self.selected = true;
+                Ok(())
+            }
+            0x6A82 => {
+                self.random_supported = Some(false);
+                Err(YKeyError::unsupported("management", "device has no management applet"))
+            }
+            status => Err(YKeyError::communication(format!(
+                "Selecting the management applet failed with status {:04X}",
+                status
+            ))),
+        }
+    }
+}
+
+fn random_unsupported() -> YKeyError {
+    YKeyError::unsupported("get_random", "device does not support GET CHALLENGE")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ReplayDevice;
+
+    #[tokio::test]
+    async fn test_get_random() {
+        let random: Vec<u8> = (0..32).collect();
+        let mut client = ManagementClient::new(ReplayDevice::new(vec![
+            vec![0x90, 0x00],
+            [random.clone(), vec![0x90, 0x00]].concat(),
+        ]));
+
+        assert_eq!(client.get_random(32).await.unwrap(), random);
+        assert!(client.supports_random().await.unwrap());
+
+        let commands = &client.device().requests;
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0], [&[0x00, INS_SELECT, 0x04, 0x00, 0x08][..], &MANAGEMENT_AID].concat());
+        assert_eq!(commands[1], [0x00, INS_GET_CHALLENGE, 0x00, 0x00, 0x20]);
+
+        let too_long = client.get_random(MAX_RANDOM_LENGTH + 1).await;
+        assert!(matches!(too_long, Err(YKeyError::InvalidParameters(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_random_unsupported() {
+        let mut client = ManagementClient::new(ReplayDevice::new(vec![vec![0x90, 0x00], vec![0x6D, 0x00]]));
+
+        assert!(!client.supports_random().await.unwrap());
+        assert!(client.get_random(16).await.unwrap_err().is_unsupported());
+        // The answer is remembered rather than asked for again
+        assert_eq!(client.device().requests.len(), 2);
+    }
+}
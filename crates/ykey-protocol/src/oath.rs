@@ -0,0 +1,540 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! YKOATH application support
+//!
+//! Talks to the OATH applet over ISO 7816 APDUs to enumerate the configured
+//! TOTP/HOTP credentials and to provision new ones.
+//!
+//! The applet never reports the live counter of an HOTP credential, and
+//! calculating a code advances it. Resynchronizing with a server therefore
+//! means provisioning the credential again with the wanted counter, which
+//! needs the original secret.
+
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use ykey_core::{traits::Device, YKeyError, YKeyResult};
+
+/// OATH applet AID
+const OATH_AID: [u8; 7] = [0xA0, 0x00, 0x00, 0x05, 0x27, 0x21, 0x01];
+
+const INS_SELECT: u8 = 0xA4;
+const INS_PUT: u8 = 0x01;
+const INS_LIST: u8 = 0xA1;
+const INS_CALCULATE_ALL: u8 = 0xA4;
+const INS_SEND_REMAINING: u8 = 0xA5;
+
+const TAG_NAME: u8 = 0x71;
+const TAG_NAME_LIST: u8 = 0x72;
+const TAG_KEY: u8 = 0x73;
+const TAG_CHALLENGE: u8 = 0x74;
+const TAG_RESPONSE: u8 = 0x75;
+const TAG_TRUNCATED_RESPONSE: u8 = 0x76;
+const TAG_HOTP: u8 = 0x77;
+const TAG_PROPERTY: u8 = 0x78;
+const TAG_IMF: u8 = 0x7A;
+const TAG_TOUCH: u8 = 0x7C;
+
+/// PUT property requiring a touch for every code
+const PROP_REQUIRE_TOUCH: u8 = 0x02;
+
+/// Longest credential name the applet stores
+const MAX_NAME_LENGTH: usize = 64;
+/// Secrets shorter than this are zero-padded, as the applet requires
+const MIN_SECRET_LENGTH: usize = 14;
+
+/// Default TOTP time step in seconds
+const DEFAULT_PERIOD: u32 = 30;
+
+/// OATH credential type
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OathType {
+    /// Counter-based one-time password
+    Hotp,
+    /// Time-based one-time password
+    Totp,
+}
+
+/// OATH hash algorithm
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OathAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// Credential as listed by the OATH applet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OathCredential {
+    /// Full credential name as stored, including any period prefix
+    pub name: String,
+    /// Credential type
+    pub oath_type: OathType,
+    /// Hash algorithm
+    pub algorithm: OathAlgorithm,
+}
+
+/// Everything needed to provision a credential, secret included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OathCredentialData {
+    /// Full name to store, including any period prefix
+    pub name: String,
+    /// Credential type
+    pub oath_type: OathType,
+    /// Hash algorithm
+    pub algorithm: OathAlgorithm,
+    /// Number of digits in generated codes
+    pub digits: u8,
+    /// Shared secret, as decoded from the provisioning URI
+    pub secret: Vec<u8>,
+    /// Whether generating a code requires touching the key
+    pub touch_required: bool,
+    /// Initial moving factor of an HOTP credential
+    pub counter: u32,
+}
+
+/// Inventory entry describing how a credential is configured
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OathInventoryEntry {
+    /// Full credential name as stored on the device
+    pub name: String,
+    /// Issuer part of the name, if present
+    pub issuer: Option<String>,
+    /// Account part of the name
+    pub account: String,
+    /// Credential type
+    pub oath_type: OathType,
+    /// Hash algorithm
+    pub algorithm: OathAlgorithm,
+    /// Number of digits, when the device reveals it without a touch
+    pub digits: Option<u8>,
+    /// TOTP time step in seconds
+    pub period: Option<u32>,
+    /// Whether generating a code requires touching the key
+    pub touch_required: bool,
+}
+
+/// Inventory of OATH credentials for re-provisioning
+///
+/// Secrets cannot be read back from the device, so the inventory only records
+/// how each credential is configured. Restoring it requires the original
+/// secrets.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OathInventory {
+    /// Always false: the OATH applet never releases secrets
+    pub secrets_included: bool,
+    /// Configured credentials
+    pub credentials: Vec<OathInventoryEntry>,
+}
+
+impl OathInventory {
+    /// Serialize the inventory to pretty-printed JSON
+    pub fn to_json(&self) -> YKeyResult<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// OATH protocol client
+pub struct OathClient<D: Device> {
+    device: D,
+    selected: bool,
+}
+
+impl<D: Device> OathClient<D> {
+    /// Create a new OATH client with the given device
+    pub fn new(device: D) -> Self {
+        Self { device, selected: false }
+    }
+
+    /// Get underlying device reference
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// List the credentials stored on the device
+    pub async fn list_credentials(&mut self) -> YKeyResult<Vec<OathCredential>> {
+        self.select().await?;
+        let response = self.transmit(INS_LIST, 0x00, 0x00, &[]).await?;
+
+        parse_tlvs(&response)?
+            .into_iter()
+            .filter(|(tag, _)| *tag == TAG_NAME_LIST)
+            .map(|(_, value)| {
+                let (&kind, name) = value
+                    .split_first()
+                    .ok_or_else(|| YKeyError::communication("Empty OATH list entry"))?;
+                Ok(OathCredential {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    oath_type: parse_type(kind)?,
+                    algorithm: parse_algorithm(kind)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Store a credential, replacing any credential with the same name
+    pub async fn put_credential(&mut self, credential: &OathCredentialData) -> YKeyResult<()> {
+        let data = encode_put(credential)?;
+        self.select().await?;
+        self.transmit(INS_PUT, 0x00, 0x00, &data).await?;
+        Ok(())
+    }
+
+    /// Set the counter of an HOTP credential for resynchronization
+    ///
+    /// The applet has no command to change a counter, so the credential is
+    /// stored again from `credential` with `counter` as its moving factor.
+    /// `credential.name` selects the credential to replace; its secret and
+    /// settings must match the original or the codes will change.
+    pub async fn set_counter(&mut self, credential: &OathCredentialData, counter: u32) -> YKeyResult<()> {
+        if credential.oath_type != OathType::Hotp {
+            return Err(YKeyError::InvalidParameters(format!(
+                "{} is not an HOTP credential",
+                credential.name
+            )));
+        }
+        self.put_credential(&OathCredentialData { counter, ..credential.clone() }).await
+    }
+
+    /// Export the configured credentials without their secrets
+    ///
+    /// Combines LIST with a CALCULATE ALL pass to learn digit counts and touch
+    /// requirements. No codes are kept and no secrets are exported.
+    pub async fn export_inventory(&mut self) -> YKeyResult<OathInventory> {
+        let credentials = self.list_credentials().await?;
+
+        let challenge = (chrono::Utc::now().timestamp() as u64 / DEFAULT_PERIOD as u64).to_be_bytes();
+        let mut data = vec![TAG_CHALLENGE, challenge.len() as u8];
+        data.extend_from_slice(&challenge);
+        let response = self.transmit(INS_CALCULATE_ALL, 0x00, 0x01, &data).await?;
+
+        let mut details = std::collections::HashMap::new();
+        let mut current_name = None;
+        for (tag, value) in parse_tlvs(&response)? {
+            match tag {
+                TAG_NAME => current_name = Some(String::from_utf8_lossy(&value).into_owned()),
+                TAG_TRUNCATED_RESPONSE | TAG_RESPONSE | TAG_HOTP | TAG_TOUCH => {
+                    if let Some(name) = current_name.take() {
+                        let digits = matches!(tag, TAG_TRUNCATED_RESPONSE | TAG_RESPONSE)
+                            .then(|| value.first().copied())
+                            .flatten();
+                        details.insert(name, (digits, tag == TAG_TOUCH));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let credentials = credentials
+            .into_iter()
+            .map(|credential| {
+                let (digits, touch_required) = details.get(&credential.name).copied().unwrap_or_default();
+                let (period, issuer, account) = split_name(&credential.name, credential.oath_type);
+                OathInventoryEntry {
+                    issuer,
+                    account,
+                    oath_type: credential.oath_type,
+                    algorithm: credential.algorithm,
+                    digits,
+                    period,
+                    touch_required,
+                    name: credential.name,
+                }
+            })
+            .collect();
+
+        Ok(OathInventory {
+            secrets_included: false,
+            credentials,
+        })
+    }
+
+    /// Select the OATH applet if not done yet
+    async fn select(&mut self) -> YKeyResult<()> {
+        if !self.selected {
+            self.transmit(INS_SELECT, 0x04, 0x00, &OATH_AID).await?;
+            self.selected = true;
+        }
+        Ok(())
+    }
+
+    /// Send a short APDU, following SEND REMAINING chaining
+    async fn transmit(&mut self, ins: u8, p1: u8, p2: u8, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        let mut apdu = vec![0x00, ins, p1, p2];
+        if !data.is_empty() {
+            let length = u8::try_from(data.len())
+                .map_err(|_| YKeyError::InvalidParameters("APDU data too long".to_string()))?;
+            apdu.push(length);
+            apdu.extend_from_slice(data);
+        }
+
+        let mut output = Vec::new();
+        loop {
+            let mut response = self.device.send_raw(&apdu).await?;
+            if response.len() < 2 {
+                return Err(YKeyError::communication("APDU response missing status word"));
+            }
+            let status = response.split_off(response.len() - 2);
+            output.extend(response);
+
+            match (status[0], status[1]) {
+                (0x90, 0x00) => return Ok(output),
+                (0x61, _) => apdu = vec![0x00, INS_SEND_REMAINING, 0x00, 0x00],
+                (sw1, sw2) => {
+                    return Err(YKeyError::communication(format!(
+                        "OATH command failed with status {:02X}{:02X}",
+                        sw1, sw2
+                    )))
+                }
+            }
+        }
+    }
+}
+
+/// Encode the PUT data for a credential
+fn encode_put(credential: &OathCredentialData) -> YKeyResult<Vec<u8>> {
+    let name = credential.name.as_bytes();
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(YKeyError::InvalidParameters(format!(
+            "OATH name must be 1 to {} bytes",
+            MAX_NAME_LENGTH
+        )));
+    }
+    if !(6..=8).contains(&credential.digits) {
+        return Err(YKeyError::InvalidParameters("OATH codes must have 6 to 8 digits".to_string()));
+    }
+
+    let kind = match credential.oath_type {
+        OathType::Hotp => 0x10,
+        OathType::Totp => 0x20,
+    } | match credential.algorithm {
+        OathAlgorithm::Sha1 => 0x01,
+        OathAlgorithm::Sha256 => 0x02,
+        OathAlgorithm::Sha512 => 0x03,
+    };
+    let key = [&[kind, credential.digits][..], &shorten_secret(&credential.secret, credential.algorithm)].concat();
+
+    let mut data = [tlv(TAG_NAME, name), tlv(TAG_KEY, &key)].concat();
+    if credential.touch_required {
+        // The property tag carries its value without a length byte
+        data.extend_from_slice(&[TAG_PROPERTY, PROP_REQUIRE_TOUCH]);
+    }
+    if credential.oath_type == OathType::Hotp && credential.counter > 0 {
+        data.extend(tlv(TAG_IMF, &credential.counter.to_be_bytes()));
+    }
+    Ok(data)
+}
+
+/// Hash secrets longer than the HMAC block and pad short ones
+fn shorten_secret(secret: &[u8], algorithm: OathAlgorithm) -> Vec<u8> {
+    let (digest_algorithm, block_size) = match algorithm {
+        OathAlgorithm::Sha1 => (&digest::SHA1_FOR_LEGACY_USE_ONLY, 64),
+        OathAlgorithm::Sha256 => (&digest::SHA256, 64),
+        OathAlgorithm::Sha512 => (&digest::SHA512, 128),
+    };
+    let mut secret = if secret.len() > block_size {
+        digest::digest(digest_algorithm, secret).as_ref().to_vec()
+    } else {
+        secret.to_vec()
+    };
+    if secret.len() < MIN_SECRET_LENGTH {
+        secret.resize(MIN_SECRET_LENGTH, 0);
+    }
+    secret
+}
+
+fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    [&[tag, value.len() as u8][..], value].concat()
+}
+
+/// Split a TLV sequence with single-byte tags and lengths
+fn parse_tlvs(data: &[u8]) -> YKeyResult<Vec<(u8, Vec<u8>)>> {
+    let mut items = Vec::new();
+    let mut rest = data;
+    while let [tag, length, tail @ ..] = rest {
+        let length = *length as usize;
+        if tail.len() < length {
+            return Err(YKeyError::communication("Truncated OATH TLV"));
+        }
+        items.push((*tag, tail[..length].to_vec()));
+        rest = &tail[length..];
+    }
+
+    if !rest.is_empty() {
+        return Err(YKeyError::communication("Trailing bytes in OATH response"));
+    }
+    Ok(items)
+}
+
+fn parse_type(kind: u8) -> YKeyResult<OathType> {
+    match kind & 0xF0 {
+        0x10 => Ok(OathType::Hotp),
+        0x20 => Ok(OathType::Totp),
+        other => Err(YKeyError::communication(format!("Unknown OATH type {:#04x}", other))),
+    }
+}
+
+fn parse_algorithm(kind: u8) -> YKeyResult<OathAlgorithm> {
+    match kind & 0x0F {
+        0x01 => Ok(OathAlgorithm::Sha1),
+        0x02 => Ok(OathAlgorithm::Sha256),
+        0x03 => Ok(OathAlgorithm::Sha512),
+        other => Err(YKeyError::communication(format!("Unknown OATH algorithm {:#04x}", other))),
+    }
+}
+
+/// Split a stored name into period, issuer and account
+///
+/// TOTP credentials with a non-default period are stored as `period/issuer:account`.
+fn split_name(name: &str, oath_type: OathType) -> (Option<u32>, Option<String>, String) {
+    let (period, rest) = match (oath_type, name.split_once('/')) {
+        (OathType::Totp, Some((prefix, rest))) => match prefix.parse() {
+            Ok(period) => (Some(period), rest),
+            Err(_) => (Some(DEFAULT_PERIOD), name),
+        },
+        (OathType::Totp, None) => (Some(DEFAULT_PERIOD), name),
+        (OathType::Hotp, _) => (None, name),
+    };
+
+    match rest.split_once(':') {
+        Some((issuer, account)) => (period, Some(issuer.to_string()), account.to_string()),
+        None => (period, None, rest.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ReplayDevice;
+
+    fn with_status(mut data: Vec<u8>, sw1: u8, sw2: u8) -> Vec<u8> {
+        data.extend_from_slice(&[sw1, sw2]);
+        data
+    }
+
+    #[tokio::test]
+    async fn test_export_inventory() {
+        let list = [
+            tlv(TAG_NAME_LIST, &[&[0x21][..], b"GitHub:alice"].concat()),
+            tlv(TAG_NAME_LIST, &[&[0x22][..], b"60/Example:bob"].concat()),
+            tlv(TAG_NAME_LIST, &[&[0x11][..], b"counter"].concat()),
+        ]
+        .concat();
+        let calculate = [
+            tlv(TAG_NAME, b"GitHub:alice"),
+            tlv(TAG_TRUNCATED_RESPONSE, &[6, 0x01, 0x02, 0x03, 0x04]),
+            tlv(TAG_NAME, b"60/Example:bob"),
+            tlv(TAG_TOUCH, &[8]),
+            tlv(TAG_NAME, b"counter"),
+            tlv(TAG_HOTP, &[6]),
+        ]
+        .concat();
+
+        // LIST arrives in two chunks to exercise SEND REMAINING
+        let (first, second) = list.split_at(10);
+        let card = ReplayDevice::new(vec![
+            with_status(Vec::new(), 0x90, 0x00),
+            with_status(first.to_vec(), 0x61, 0x20),
+            with_status(second.to_vec(), 0x90, 0x00),
+            with_status(calculate, 0x90, 0x00),
+        ]);
+
+        let mut client = OathClient::new(card);
+        let inventory = client.export_inventory().await.unwrap();
+
+        assert!(!inventory.secrets_included);
+        assert_eq!(inventory.credentials.len(), 3);
+
+        let github = &inventory.credentials[0];
+        assert_eq!(github.issuer.as_deref(), Some("GitHub"));
+        assert_eq!(github.account, "alice");
+        assert_eq!(github.oath_type, OathType::Totp);
+        assert_eq!(github.algorithm, OathAlgorithm::Sha1);
+        assert_eq!(github.digits, Some(6));
+        assert_eq!(github.period, Some(30));
+        assert!(!github.touch_required);
+
+        let example = &inventory.credentials[1];
+        assert_eq!(example.algorithm, OathAlgorithm::Sha256);
+        assert_eq!(example.period, Some(60));
+        assert_eq!(example.account, "bob");
+        assert!(example.touch_required);
+        assert_eq!(example.digits, None);
+
+        let counter = &inventory.credentials[2];
+        assert_eq!(counter.oath_type, OathType::Hotp);
+        assert_eq!(counter.issuer, None);
+        assert_eq!(counter.period, None);
+
+        let commands = &client.device().requests;
+        assert_eq!(commands[0][..5], [0x00, INS_SELECT, 0x04, 0x00, OATH_AID.len() as u8]);
+        assert_eq!(commands[1][1], INS_LIST);
+        assert_eq!(commands[2][1], INS_SEND_REMAINING);
+        assert_eq!(commands[3][..4], [0x00, INS_CALCULATE_ALL, 0x00, 0x01]);
+
+        let json = inventory.to_json().unwrap();
+        assert!(json.contains("\"secrets_included\": false"));
+    }
+
+    #[tokio::test]
+    async fn test_list_error_status() {
+        let card = ReplayDevice::new(vec![with_status(Vec::new(), 0x6A, 0x82)]);
+
+        let mut client = OathClient::new(card);
+        assert!(matches!(client.list_credentials().await, Err(YKeyError::CommunicationError(_))));
+    }
+
+    fn hotp_credential() -> OathCredentialData {
+        OathCredentialData {
+            name: "Example:alice".to_string(),
+            oath_type: OathType::Hotp,
+            algorithm: OathAlgorithm::Sha1,
+            digits: 6,
+            secret: b"12345678901234567890".to_vec(),
+            touch_required: false,
+            counter: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_counter_reputs_with_counter() {
+        let card = ReplayDevice::new(vec![with_status(Vec::new(), 0x90, 0x00); 2]);
+        let mut client = OathClient::new(card);
+        client.set_counter(&hotp_credential(), 0x0102_0304).await.unwrap();
+
+        let put = &client.device().requests[1];
+        let expected = [
+            tlv(TAG_NAME, b"Example:alice"),
+            tlv(TAG_KEY, &[&[0x11, 6][..], b"12345678901234567890"].concat()),
+            tlv(TAG_IMF, &[0x01, 0x02, 0x03, 0x04]),
+        ]
+        .concat();
+        assert_eq!(put[..5], [0x00, INS_PUT, 0x00, 0x00, expected.len() as u8]);
+        assert_eq!(put[5..], expected);
+    }
+
+    #[tokio::test]
+    async fn test_set_counter_rejects_totp() {
+        let card = ReplayDevice::new(Vec::new());
+        let mut client = OathClient::new(card);
+        let totp = OathCredentialData { oath_type: OathType::Totp, ..hotp_credential() };
+        assert!(matches!(client.set_counter(&totp, 5).await, Err(YKeyError::InvalidParameters(_))));
+        assert!(client.device().requests.is_empty());
+    }
+
+    #[test]
+    fn test_encode_put_secret_and_touch() {
+        // Short secrets are padded, and counter zero is left out
+        let short = OathCredentialData { secret: vec![0xAB; 10], touch_required: true, ..hotp_credential() };
+        let data = encode_put(&short).unwrap();
+        let key = &data[2 + 13..];
+        assert_eq!(key[..4], [TAG_KEY, 2 + 14, 0x11, 6]);
+        assert_eq!(key[4..18], [&[0xAB; 10][..], &[0; 4]].concat());
+        assert_eq!(key[18..], [TAG_PROPERTY, PROP_REQUIRE_TOUCH]);
+
+        // Secrets longer than the block size are hashed
+        let long = OathCredentialData { algorithm: OathAlgorithm::Sha256, secret: vec![0x01; 65], ..hotp_credential() };
+        assert_eq!(shorten_secret(&long.secret, long.algorithm), digest::digest(&digest::SHA256, &[0x01; 65]).as_ref());
+        assert_eq!(encode_put(&long).unwrap().len(), 2 + 13 + 2 + 2 + 32);
+    }
+}
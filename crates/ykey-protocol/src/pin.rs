@@ -0,0 +1,1127 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! PIN/UV auth protocol support
+//!
+//! Implements the key agreement and symmetric primitives of CTAP2 PIN/UV auth
+//! protocols one and two, and uses them to set and change the PIN and to obtain
+//! a pinUvAuthToken from the device.
+//!
+//! PINs are normalized to Unicode NFC before they are padded or hashed, so a
+//! PIN typed with decomposed accents matches the one it was set with.
+
+use crate::{cbor, rng::RngSource, CtapCommand, Fido2Client};
+use aes::cipher::{block_padding::NoPadding, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use p256::{
+    ecdh::EphemeralSecret,
+    elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint},
+    EncodedPoint, PublicKey,
+};
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
+use ykey_core::{
+    traits::Device,
+    types::PublicKeyCredentialDescriptor,
+    YKeyError, YKeyResult,
+};
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+/// authenticatorClientPIN command byte
+pub const CLIENT_PIN_COMMAND: u8 = 0x06;
+
+/// getPINRetries subcommand
+const SUBCOMMAND_GET_RETRIES: u8 = 0x01;
+/// getKeyAgreement subcommand
+const SUBCOMMAND_GET_KEY_AGREEMENT: u8 = 0x02;
+/// setPIN subcommand
+const SUBCOMMAND_SET_PIN: u8 = 0x03;
+/// changePIN subcommand
+const SUBCOMMAND_CHANGE_PIN: u8 = 0x04;
+/// getPinToken subcommand (CTAP2.0)
+const SUBCOMMAND_GET_PIN_TOKEN: u8 = 0x05;
+/// getPinUvAuthTokenUsingUvWithPermissions subcommand
+const SUBCOMMAND_GET_TOKEN_USING_UV: u8 = 0x06;
+/// getPinUvAuthTokenUsingPinWithPermissions subcommand
+const SUBCOMMAND_GET_TOKEN_WITH_PERMISSIONS: u8 = 0x09;
+
+bitflags::bitflags! {
+    /// Operations a pinUvAuthToken is scoped to
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Permissions: u8 {
+        /// makeCredential (mc)
+        const MAKE_CREDENTIAL = 0x01;
+        /// getAssertion (ga)
+        const GET_ASSERTION = 0x02;
+        /// authenticatorCredentialManagement (cm)
+        const CREDENTIAL_MANAGEMENT = 0x04;
+        /// authenticatorBioEnrollment (be)
+        const BIO_ENROLLMENT = 0x08;
+        /// Writing the large blob array (lbw)
+        const LARGE_BLOB_WRITE = 0x10;
+        /// authenticatorConfig (acfg)
+        const AUTHENTICATOR_CONFIG = 0x20;
+    }
+}
+
+impl Permissions {
+    /// Permissions implied by a token from the CTAP2.0 getPinToken subcommand
+    pub const LEGACY: Self = Self::MAKE_CREDENTIAL.union(Self::GET_ASSERTION);
+}
+
+/// The PIN did not match
+pub const CTAP2_ERR_PIN_INVALID: u8 = 0x31;
+
+/// The pinUvAuthParam did not verify, e.g. under a token the authenticator forgot
+pub const CTAP2_ERR_PIN_AUTH_INVALID: u8 = 0x33;
+
+/// The pinUvAuthToken outlived its usage window
+pub const CTAP2_ERR_PIN_TOKEN_EXPIRED: u8 = 0x38;
+
+/// No credential matched the request
+const CTAP2_ERR_NO_CREDENTIALS: u8 = 0x2E;
+
+/// RP ID of the assertion used to probe a token not bound to an RP
+const PROBE_RP_ID: &str = "token-probe.invalid";
+
+/// Built-in user verification is blocked until the PIN is entered
+pub const CTAP2_ERR_UV_BLOCKED: u8 = 0x3C;
+
+/// Built-in user verification failed
+pub const CTAP2_ERR_UV_INVALID: u8 = 0x3F;
+
+/// Length new PINs are zero-padded to before encryption
+const PADDED_PIN_LENGTH: usize = 64;
+
+/// Fewest code points CTAP allows in a PIN
+const MIN_PIN_CODE_POINTS: usize = 4;
+/// Most code points accepted for a new PIN
+const MAX_PIN_CODE_POINTS: usize = 8;
+
+/// PIN/UV auth protocol versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinUvAuthProtocol {
+    /// Protocol one (CTAP 2.0)
+    One,
+    /// Protocol two (CTAP 2.1)
+    Two,
+}
+
+impl PinUvAuthProtocol {
+    /// Protocol number as sent on the wire
+    pub fn version(self) -> u8 {
+        match self {
+            PinUvAuthProtocol::One => 1,
+            PinUvAuthProtocol::Two => 2,
+        }
+    }
+
+    /// Look up a protocol by its wire number
+    pub fn from_version(version: u8) -> YKeyResult<Self> {
+        match version {
+            1 => Ok(PinUvAuthProtocol::One),
+            2 => Ok(PinUvAuthProtocol::Two),
+            other => Err(YKeyError::UnsupportedProtocolVersion(format!(
+                "PIN/UV auth protocol {}",
+                other
+            ))),
+        }
+    }
+
+    /// Compute a pinUvAuthParam over a message with the given key
+    pub fn authenticate(self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        match self {
+            PinUvAuthProtocol::One => hmac_sha256(key, message)[..16].to_vec(),
+            PinUvAuthProtocol::Two => hmac_sha256(key, message).to_vec(),
+        }
+    }
+}
+
+/// Shared secret established with the authenticator's key agreement key
+pub struct SharedSecret {
+    protocol: PinUvAuthProtocol,
+    hmac_key: [u8; 32],
+    aes_key: [u8; 32],
+}
+
+impl SharedSecret {
+    /// Derive the shared secret from the ECDH x-coordinate
+    pub fn derive(protocol: PinUvAuthProtocol, z: &[u8]) -> Self {
+        match protocol {
+            PinUvAuthProtocol::One => {
+                let key: [u8; 32] = Sha256::digest(z).into();
+                Self { protocol, hmac_key: key, aes_key: key }
+            }
+            PinUvAuthProtocol::Two => {
+                let hkdf = Hkdf::<Sha256>::new(Some(&[0u8; 32]), z);
+                let mut hmac_key = [0u8; 32];
+                let mut aes_key = [0u8; 32];
+                // Output lengths are fixed and valid for SHA-256
+                hkdf.expand(b"CTAP2 HMAC key", &mut hmac_key).expect("valid HKDF length");
+                hkdf.expand(b"CTAP2 AES key", &mut aes_key).expect("valid HKDF length");
+                Self { protocol, hmac_key, aes_key }
+            }
+        }
+    }
+
+    /// Protocol this secret was derived for
+    pub fn protocol(&self) -> PinUvAuthProtocol {
+        self.protocol
+    }
+
+    /// Encrypt a block-aligned plaintext
+    ///
+    /// Protocol one uses an all-zero IV; protocol two prefixes a random IV.
+    pub fn encrypt(&self, plaintext: &[u8]) -> YKeyResult<Vec<u8>> {
+        self.encrypt_with_rng(&mut OsRng, plaintext)
+    }
+
+    /// Encrypt a block-aligned plaintext, drawing the IV from `rng`
+    pub fn encrypt_with_rng(&self, rng: &mut dyn RngSource, plaintext: &[u8]) -> YKeyResult<Vec<u8>> {
+        if !plaintext.len().is_multiple_of(16) {
+            return Err(YKeyError::InvalidParameters(
+                "PIN protocol plaintext must be a multiple of 16 bytes".to_string(),
+            ));
+        }
+
+        match self.protocol {
+            PinUvAuthProtocol::One => Ok(cbc_encrypt(&self.aes_key, &[0; 16], plaintext)),
+            PinUvAuthProtocol::Two => {
+                let mut iv = [0u8; 16];
+                rng.fill_bytes(&mut iv);
+                let mut output = iv.to_vec();
+                output.extend(cbc_encrypt(&self.aes_key, &iv, plaintext));
+                Ok(output)
+            }
+        }
+    }
+
+    /// Decrypt a ciphertext produced by the peer
+    pub fn decrypt(&self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        if data.len() < 16 || !data.len().is_multiple_of(16) {
+            return Err(YKeyError::communication("Invalid PIN protocol ciphertext length"));
+        }
+
+        match self.protocol {
+            PinUvAuthProtocol::One => cbc_decrypt(&self.aes_key, &[0; 16], data),
+            PinUvAuthProtocol::Two => {
+                let (iv, ciphertext) = data.split_at(16);
+                cbc_decrypt(&self.aes_key, iv.try_into().expect("16-byte IV"), ciphertext)
+            }
+        }
+    }
+
+    /// Compute a pinUvAuthParam keyed by the shared secret
+    pub fn authenticate(&self, message: &[u8]) -> Vec<u8> {
+        self.protocol.authenticate(&self.hmac_key, message)
+    }
+}
+
+/// Decoded authenticatorClientPIN response
+///
+/// Each subcommand fills in only the fields it returns.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientPinResponse {
+    /// Authenticator's key agreement key as a COSE key, from getKeyAgreement
+    pub key_agreement: Option<cbor::Value>,
+    /// Encrypted pinUvAuthToken, from the token subcommands
+    pub pin_uv_auth_token: Option<Vec<u8>>,
+    /// PIN attempts left before the PIN is blocked
+    pub pin_retries: Option<u8>,
+    /// Whether a power cycle is needed before the PIN can be tried again
+    pub power_cycle_state: Option<bool>,
+    /// Built-in UV attempts left before UV is blocked
+    pub uv_retries: Option<u8>,
+}
+
+impl ClientPinResponse {
+    /// Decode a response body; a bare success status has none
+    pub fn from_cbor(body: Option<&cbor::Value>) -> YKeyResult<Self> {
+        let Some(body) = body else {
+            return Ok(Self::default());
+        };
+        let map = cbor::as_map(body)?;
+        let retries = |key: i64, name: &str| -> YKeyResult<Option<u8>> {
+            cbor::get_int(map, key)
+                .map(|value| {
+                    let count = cbor::to_u64(value)?;
+                    u8::try_from(count)
+                        .map_err(|_| YKeyError::communication(format!("{} of {} is out of range", name, count)))
+                })
+                .transpose()
+        };
+
+        Ok(Self {
+            key_agreement: cbor::get_int(map, 0x01).cloned(),
+            pin_uv_auth_token: cbor::get_int(map, 0x02).map(cbor::to_bytes).transpose()?,
+            pin_retries: retries(0x03, "pinRetries")?,
+            power_cycle_state: cbor::get_int(map, 0x04).map(cbor::to_bool).transpose()?,
+            uv_retries: retries(0x05, "uvRetries")?,
+        })
+    }
+}
+
+/// pinUvAuthToken obtained from the authenticator
+#[derive(Clone)]
+pub struct PinUvAuthToken {
+    protocol: PinUvAuthProtocol,
+    token: Vec<u8>,
+    permissions: Permissions,
+}
+
+impl PinUvAuthToken {
+    /// Wrap a decrypted token, granted no permissions
+    pub fn new(protocol: PinUvAuthProtocol, token: Vec<u8>) -> Self {
+        Self { protocol, token, permissions: Permissions::empty() }
+    }
+
+    /// Record the permissions the token was granted
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Permissions the token was granted
+    pub fn permissions(&self) -> Permissions {
+        self.permissions
+    }
+
+    /// Protocol the token was issued under
+    pub fn protocol(&self) -> PinUvAuthProtocol {
+        self.protocol
+    }
+
+    /// Compute a pinUvAuthParam over a message with this token
+    pub fn authenticate(&self, message: &[u8]) -> Vec<u8> {
+        self.protocol.authenticate(&self.token, message)
+    }
+
+    /// Raw token bytes
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.token
+    }
+}
+
+impl std::fmt::Debug for PinUvAuthToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PinUvAuthToken")
+            .field("protocol", &self.protocol)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Run ECDH against the authenticator's public key
+///
+/// Returns the platform's COSE public key to send back alongside the derived
+/// shared secret.
+pub fn encapsulate(
+    protocol: PinUvAuthProtocol,
+    peer_key: &cbor::Value,
+) -> YKeyResult<(cbor::Value, SharedSecret)> {
+    encapsulate_with_rng(protocol, peer_key, &mut OsRng)
+}
+
+/// Run ECDH with an ephemeral key drawn from `rng`
+pub fn encapsulate_with_rng(
+    protocol: PinUvAuthProtocol,
+    peer_key: &cbor::Value,
+    mut rng: &mut dyn RngSource,
+) -> YKeyResult<(cbor::Value, SharedSecret)> {
+    let peer = parse_cose_key(peer_key)?;
+    let secret = EphemeralSecret::random(&mut rng);
+    let shared = secret.diffie_hellman(&peer);
+
+    Ok((
+        cose_key(&secret.public_key()),
+        SharedSecret::derive(protocol, shared.raw_secret_bytes()),
+    ))
+}
+
+/// Encode a P-256 public key as a COSE_Key for ECDH-ES+HKDF-256
+pub fn cose_key(key: &PublicKey) -> cbor::Value {
+    let point = key.to_encoded_point(false);
+    cbor::Value::Map(vec![
+        (cbor::int(1), cbor::int(2)),
+        (cbor::int(3), cbor::int(-25)),
+        (cbor::int(-1), cbor::int(1)),
+        (cbor::int(-2), cbor::bytes(point.x().map(|x| x.as_slice()).unwrap_or_default())),
+        (cbor::int(-3), cbor::bytes(point.y().map(|y| y.as_slice()).unwrap_or_default())),
+    ])
+}
+
+/// Decode a COSE_Key holding a P-256 public key
+pub fn parse_cose_key(value: &cbor::Value) -> YKeyResult<PublicKey> {
+    let map = cbor::as_map(value)?;
+    let coordinate = |key: i64| -> YKeyResult<Vec<u8>> {
+        cbor::get_int(map, key)
+            .ok_or_else(|| YKeyError::communication("COSE key missing coordinate"))
+            .and_then(cbor::to_bytes)
+    };
+
+    let (x, y) = (coordinate(-2)?, coordinate(-3)?);
+    if x.len() != 32 || y.len() != 32 {
+        return Err(YKeyError::communication("Invalid COSE key coordinate length"));
+    }
+
+    let point = EncodedPoint::from_affine_coordinates(x.as_slice().into(), y.as_slice().into(), false);
+    Option::from(PublicKey::from_encoded_point(&point))
+        .ok_or_else(|| YKeyError::communication("COSE key is not a valid P-256 point"))
+}
+
+/// Normalize a PIN to Unicode NFC
+pub fn normalize_pin(pin: &str) -> String {
+    pin.nfc().collect()
+}
+
+/// Check that a new PIN has 4 to 8 Unicode code points
+///
+/// CTAP counts code points rather than UTF-8 bytes, so a PIN of four emoji
+/// is as long as "1234".
+pub fn validate_new_pin(pin: &str) -> YKeyResult<()> {
+    let code_points = normalize_pin(pin).chars().count();
+    if !(MIN_PIN_CODE_POINTS..=MAX_PIN_CODE_POINTS).contains(&code_points) {
+        return Err(YKeyError::InvalidParameters("PIN must be 4-8 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// Zero-pad the UTF-8 bytes of a new PIN to the length the authenticator expects
+pub fn pad_pin(pin: &str) -> YKeyResult<[u8; PADDED_PIN_LENGTH]> {
+    let pin = normalize_pin(pin);
+    let bytes = pin.as_bytes();
+    if bytes.len() >= PADDED_PIN_LENGTH {
+        return Err(YKeyError::InvalidParameters(
+            "PIN must be shorter than 64 bytes".to_string(),
+        ));
+    }
+
+    let mut padded = [0u8; PADDED_PIN_LENGTH];
+    padded[..bytes.len()].copy_from_slice(bytes);
+    Ok(padded)
+}
+
+/// Left 16 bytes of SHA-256 over the PIN
+pub fn pin_hash(pin: &str) -> [u8; 16] {
+    let digest = Sha256::digest(normalize_pin(pin).as_bytes());
+    let mut hash = [0u8; 16];
+    hash.copy_from_slice(&digest[..16]);
+    hash
+}
+
+/// HMAC-SHA-256
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// AES-256-CBC encrypt block-aligned data
+fn cbc_encrypt(key: &[u8; 32], iv: &[u8; 16], plaintext: &[u8]) -> Vec<u8> {
+    Aes256CbcEnc::new(key.into(), iv.into()).encrypt_padded_vec_mut::<NoPadding>(plaintext)
+}
+
+/// AES-256-CBC decrypt block-aligned data
+fn cbc_decrypt(key: &[u8; 32], iv: &[u8; 16], ciphertext: &[u8]) -> YKeyResult<Vec<u8>> {
+    Aes256CbcDec::new(key.into(), iv.into())
+        .decrypt_padded_vec_mut::<NoPadding>(ciphertext)
+        .map_err(|_| YKeyError::communication("Failed to decrypt PIN protocol data"))
+}
+
+impl<D: Device> Fido2Client<D> {
+    /// Set the initial PIN on an authenticator that has none
+    pub(crate) async fn set_pin_with(&mut self, protocol: PinUvAuthProtocol, pin: &str) -> YKeyResult<()> {
+        let (platform_key, shared) = self.key_agreement(protocol).await?;
+        let new_pin_enc = shared.encrypt_with_rng(self.rng.as_mut(), &pad_pin(pin)?)?;
+        let pin_uv_auth_param = shared.authenticate(&new_pin_enc);
+
+        self.client_pin(cbor::int_map(vec![
+            (0x01, Some(cbor::int(protocol.version() as i64))),
+            (0x02, Some(cbor::int(SUBCOMMAND_SET_PIN as i64))),
+            (0x03, Some(platform_key)),
+            (0x04, Some(cbor::bytes(&pin_uv_auth_param))),
+            (0x05, Some(cbor::bytes(&new_pin_enc))),
+        ]))
+        .await?;
+        // GetInfo now reports clientPin as set
+        self.invalidate_info_cache();
+        Ok(())
+    }
+
+    /// Replace the current PIN, proving knowledge of the old one
+    pub(crate) async fn change_pin_with(
+        &mut self,
+        protocol: PinUvAuthProtocol,
+        old_pin: &str,
+        new_pin: &str,
+    ) -> YKeyResult<()> {
+        let (platform_key, shared) = self.key_agreement(protocol).await?;
+        let new_pin_enc = shared.encrypt_with_rng(self.rng.as_mut(), &pad_pin(new_pin)?)?;
+        let pin_hash_enc = shared.encrypt_with_rng(self.rng.as_mut(), &pin_hash(old_pin))?;
+        let pin_uv_auth_param = shared.authenticate(&[new_pin_enc.as_slice(), &pin_hash_enc].concat());
+
+        self.send_pin_check(cbor::int_map(vec![
+            (0x01, Some(cbor::int(protocol.version() as i64))),
+            (0x02, Some(cbor::int(SUBCOMMAND_CHANGE_PIN as i64))),
+            (0x03, Some(platform_key)),
+            (0x04, Some(cbor::bytes(&pin_uv_auth_param))),
+            (0x05, Some(cbor::bytes(&new_pin_enc))),
+            (0x06, Some(cbor::bytes(&pin_hash_enc))),
+        ]))
+        .await?;
+        // A forced PIN change clears forcePINChange in GetInfo
+        self.invalidate_info_cache();
+        Ok(())
+    }
+
+    /// Obtain a pinUvAuthToken with the CTAP2.0 getPinToken subcommand
+    pub(crate) async fn get_pin_token_with(
+        &mut self,
+        protocol: PinUvAuthProtocol,
+        pin: &str,
+    ) -> YKeyResult<PinUvAuthToken> {
+        let (platform_key, shared) = self.key_agreement(protocol).await?;
+        let pin_hash_enc = shared.encrypt_with_rng(self.rng.as_mut(), &pin_hash(pin))?;
+
+        let response = self
+            .send_pin_check(cbor::int_map(vec![
+                (0x01, Some(cbor::int(protocol.version() as i64))),
+                (0x02, Some(cbor::int(SUBCOMMAND_GET_PIN_TOKEN as i64))),
+                (0x03, Some(platform_key)),
+                (0x06, Some(cbor::bytes(&pin_hash_enc))),
+            ]))
+            .await?;
+        self.store_pin_token(protocol, &shared, response, Permissions::LEGACY, None)
+    }
+
+    /// Obtain a pinUvAuthToken scoped to the given permissions
+    ///
+    /// Uses getPinUvAuthTokenUsingPinWithPermissions over protocol two. The
+    /// token is kept together with its permissions, so later commands can
+    /// check it is allowed to authenticate them before they are sent.
+    pub async fn get_pin_uv_auth_token_with_permissions(
+        &mut self,
+        pin: &str,
+        permissions: Permissions,
+        rp_id: Option<&str>,
+    ) -> YKeyResult<PinUvAuthToken> {
+        let protocol = PinUvAuthProtocol::Two;
+        let (platform_key, shared) = self.key_agreement(protocol).await?;
+        let pin_hash_enc = shared.encrypt_with_rng(self.rng.as_mut(), &pin_hash(pin))?;
+
+        let response = self
+            .send_pin_check(cbor::int_map(vec![
+                (0x01, Some(cbor::int(protocol.version() as i64))),
+                (0x02, Some(cbor::int(SUBCOMMAND_GET_TOKEN_WITH_PERMISSIONS as i64))),
+                (0x03, Some(platform_key)),
+                (0x06, Some(cbor::bytes(&pin_hash_enc))),
+                (0x09, Some(cbor::int(permissions.bits() as i64))),
+                (0x0A, rp_id.map(cbor::text)),
+            ]))
+            .await?;
+        self.store_pin_token(protocol, &shared, response, permissions, rp_id)
+    }
+
+    /// Obtain a pinUvAuthToken through the authenticator's built-in UV
+    pub(crate) async fn acquire_uv_token(
+        &mut self,
+        permissions: Permissions,
+        rp_id: Option<&str>,
+    ) -> YKeyResult<PinUvAuthToken> {
+        let protocol = PinUvAuthProtocol::Two;
+        let (platform_key, shared) = self.key_agreement(protocol).await?;
+
+        let response = self
+            .client_pin(cbor::int_map(vec![
+                (0x01, Some(cbor::int(protocol.version() as i64))),
+                (0x02, Some(cbor::int(SUBCOMMAND_GET_TOKEN_USING_UV as i64))),
+                (0x03, Some(platform_key)),
+                (0x09, Some(cbor::int(permissions.bits() as i64))),
+                (0x0A, rp_id.map(cbor::text)),
+            ]))
+            .await?;
+        self.store_pin_token(protocol, &shared, response, permissions, rp_id)
+    }
+
+    /// Obtain a pinUvAuthToken, preferring built-in UV over the PIN
+    ///
+    /// Built-in UV is retried up to the authenticator's
+    /// `preferredPlatformUvAttempts` before falling back to `pin`, so a
+    /// misread fingerprint does not send the user straight to the PIN prompt.
+    /// Without a PIN to fall back to, the last UV failure is returned.
+    pub async fn obtain_uv(
+        &mut self,
+        pin: Option<&str>,
+        permissions: Permissions,
+        rp_id: Option<&str>,
+    ) -> YKeyResult<PinUvAuthToken> {
+        let info = self.authenticator_info().await?.clone();
+        let mut uv_error = None;
+        if info.option_enabled("uv") && info.option_enabled("pinUvAuthToken") {
+            for _ in 0..info.platform_uv_attempts() {
+                match self.acquire_uv_token(permissions, rp_id).await {
+                    Err(YKeyError::CtapError { code: CTAP2_ERR_UV_INVALID, .. }) => {
+                        uv_error = Some(YKeyError::ctap_error(CTAP2_ERR_UV_INVALID));
+                    }
+                    Err(YKeyError::CtapError { code: CTAP2_ERR_UV_BLOCKED, .. }) => {
+                        uv_error = Some(YKeyError::ctap_error(CTAP2_ERR_UV_BLOCKED));
+                        break;
+                    }
+                    result => return result,
+                }
+            }
+        }
+
+        match pin {
+            Some(pin) => self.get_pin_uv_auth_token_with_permissions(pin, permissions, rp_id).await,
+            None => Err(uv_error.unwrap_or(YKeyError::PinRequired)),
+        }
+    }
+
+    /// The most recently acquired pinUvAuthToken
+    pub(crate) fn stored_pin_token(&self) -> YKeyResult<PinUvAuthToken> {
+        match (&self.pin_token, self.pin_protocol_version) {
+            (Some(token), Some(version)) => Ok(PinUvAuthToken::new(
+                PinUvAuthProtocol::from_version(version)?,
+                token.clone(),
+            )
+            .with_permissions(self.pin_permissions)),
+            _ => Err(YKeyError::PinRequired),
+        }
+    }
+
+    /// The stored pinUvAuthToken, if it was granted `required`
+    pub(crate) fn permitted_pin_token(&self, required: Permissions) -> YKeyResult<PinUvAuthToken> {
+        let token = self.stored_pin_token()?;
+        let missing = required.difference(token.permissions());
+        if !missing.is_empty() {
+            return Err(YKeyError::PermissionDenied(format!(
+                "pinUvAuthToken lacks the {:?} permission",
+                missing
+            )));
+        }
+        Ok(token)
+    }
+
+    /// Check the stored pinUvAuthToken is still accepted before relying on it
+    ///
+    /// Sends a silent GetAssertion for a random credential under the token,
+    /// which costs no touch: "no credentials" means the token verified, while
+    /// an auth or expiry error means the authenticator no longer takes it.
+    /// A rejected token is dropped and, where built-in UV is available,
+    /// replaced with a fresh one of the same permissions, so check
+    /// `has_pin_token` after `false`. Without a stored token this is `false`.
+    pub async fn validate_token(&mut self) -> YKeyResult<bool> {
+        let Ok(token) = self.stored_pin_token() else {
+            return Ok(false);
+        };
+        if !token.permissions().contains(Permissions::GET_ASSERTION) {
+            return Err(YKeyError::unsupported(
+                "validate_token",
+                "token lacks the getAssertion permission the probe needs",
+            ));
+        }
+
+        let mut client_data_hash = [0u8; 32];
+        self.rng.fill_bytes(&mut client_data_hash);
+        let mut credential_id = [0u8; 16];
+        self.rng.fill_bytes(&mut credential_id);
+        let rp_id = self.pin_rp_id.clone();
+        let probe = self
+            .send_cbor(
+                0x02,
+                Some(cbor::int_map(vec![
+                    (0x01, Some(cbor::text(rp_id.as_deref().unwrap_or(PROBE_RP_ID)))),
+                    (0x02, Some(cbor::bytes(&client_data_hash))),
+                    (0x03, Some(CtapCommand::descriptor_list(&[PublicKeyCredentialDescriptor {
+                        cred_type: "public-key".to_string(),
+                        id: credential_id.to_vec(),
+                        transports: None,
+                    }]))),
+                    (0x05, Some(cbor::Value::Map(vec![(cbor::text("up"), cbor::Value::Bool(false))]))),
+                    (0x06, Some(cbor::bytes(&token.authenticate(&client_data_hash)))),
+                    (0x07, Some(cbor::int(token.protocol().version() as i64))),
+                ])),
+            )
+            .await;
+
+        match probe {
+            Ok(_) | Err(YKeyError::CtapError { code: CTAP2_ERR_NO_CREDENTIALS, .. }) => Ok(true),
+            Err(YKeyError::CtapError { code: CTAP2_ERR_PIN_AUTH_INVALID | CTAP2_ERR_PIN_TOKEN_EXPIRED, .. }) => {
+                self.clear_pin_token();
+                let info = self.authenticator_info().await?;
+                if info.option_enabled("uv") && info.option_enabled("pinUvAuthToken") {
+                    self.acquire_uv_token(token.permissions(), rp_id.as_deref()).await?;
+                }
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Number of wrong PINs the authenticator accepts before blocking the PIN
+    pub async fn pin_retries(&mut self) -> YKeyResult<u8> {
+        self.client_pin(cbor::int_map(vec![
+            (0x01, Some(cbor::int(PinUvAuthProtocol::One.version() as i64))),
+            (0x02, Some(cbor::int(SUBCOMMAND_GET_RETRIES as i64))),
+        ]))
+        .await?
+        .pin_retries
+        .ok_or_else(|| YKeyError::communication("Missing pinRetries"))
+    }
+
+    /// Send a ClientPIN subcommand and decode its response
+    pub(crate) async fn client_pin(&mut self, request: cbor::Value) -> YKeyResult<ClientPinResponse> {
+        let response = self.send_cbor(CLIENT_PIN_COMMAND, Some(request)).await?;
+        ClientPinResponse::from_cbor(response.as_ref())
+    }
+
+    /// Send a ClientPIN subcommand that checks the PIN
+    ///
+    /// A wrong PIN becomes [`YKeyError::InvalidPin`] carrying the retries
+    /// left, queried with getPINRetries. The count is left out if that query
+    /// fails, rather than hiding the wrong-PIN error behind it.
+    async fn send_pin_check(&mut self, request: cbor::Value) -> YKeyResult<ClientPinResponse> {
+        match self.client_pin(request).await {
+            Err(YKeyError::InvalidPin { retries_remaining: None, .. }) => {
+                Err(YKeyError::invalid_pin("PIN invalid", self.pin_retries().await.ok()))
+            }
+            result => result,
+        }
+    }
+
+    /// Fetch the authenticator's key agreement key and run ECDH against it
+    pub(crate) async fn key_agreement(&mut self, protocol: PinUvAuthProtocol) -> YKeyResult<(cbor::Value, SharedSecret)> {
+        let peer_key = self
+            .client_pin(cbor::int_map(vec![
+                (0x01, Some(cbor::int(protocol.version() as i64))),
+                (0x02, Some(cbor::int(SUBCOMMAND_GET_KEY_AGREEMENT as i64))),
+            ]))
+            .await?
+            .key_agreement
+            .ok_or_else(|| YKeyError::communication("Missing key agreement key"))?;
+
+        encapsulate_with_rng(protocol, &peer_key, self.rng.as_mut())
+    }
+
+    /// Decrypt the token from a PIN token response and remember it
+    fn store_pin_token(
+        &mut self,
+        protocol: PinUvAuthProtocol,
+        shared: &SharedSecret,
+        response: ClientPinResponse,
+        permissions: Permissions,
+        rp_id: Option<&str>,
+    ) -> YKeyResult<PinUvAuthToken> {
+        let encrypted = response
+            .pin_uv_auth_token
+            .ok_or_else(|| YKeyError::communication("Missing pinUvAuthToken"))?;
+
+        let token = shared.decrypt(&encrypted)?;
+        self.pin_token = Some(token.clone());
+        self.pin_protocol_version = Some(protocol.version());
+        self.pin_permissions = permissions;
+        self.pin_rp_id = rp_id.map(str::to_string);
+
+        Ok(PinUvAuthToken::new(protocol, token).with_permissions(permissions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::SecretKey;
+
+    #[test]
+    fn test_shared_secret_round_trip() {
+        let authenticator = SecretKey::random(&mut OsRng);
+        let (platform_key, platform_secret) =
+            encapsulate(PinUvAuthProtocol::Two, &cose_key(&authenticator.public_key())).unwrap();
+
+        // The authenticator derives the same secret from the platform key
+        let peer = parse_cose_key(&platform_key).unwrap();
+        let z = p256::ecdh::diffie_hellman(authenticator.to_nonzero_scalar(), peer.as_affine());
+        let authenticator_secret = SharedSecret::derive(PinUvAuthProtocol::Two, z.raw_secret_bytes());
+
+        let ciphertext = platform_secret.encrypt(&pin_hash("123456")).unwrap();
+        assert_eq!(ciphertext.len(), 32);
+        assert_eq!(authenticator_secret.decrypt(&ciphertext).unwrap(), pin_hash("123456"));
+        assert_eq!(platform_secret.authenticate(b"msg"), authenticator_secret.authenticate(b"msg"));
+    }
+
+    #[test]
+    fn test_encrypt_rejects_unaligned_plaintext() {
+        let secret = SharedSecret::derive(PinUvAuthProtocol::Two, &[7; 32]);
+        assert!(matches!(secret.encrypt(&[0; 15]), Err(YKeyError::InvalidParameters(_))));
+    }
+
+    #[test]
+    fn test_hmac_rfc4231_vectors() {
+        // Test case 2
+        assert_eq!(
+            hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        // Test case 5, truncated to 128 bits as protocol one does
+        assert_eq!(
+            hex::encode(PinUvAuthProtocol::One.authenticate(&[0x0c; 20], b"Test With Truncation")),
+            "a3b6167473100ee06e0c796c2955552b"
+        );
+    }
+
+    #[test]
+    fn test_aes_cbc_sp800_38a_vectors() {
+        // F.2.5 / F.2.6 CBC-AES256
+        let key: [u8; 32] = hex::decode("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let iv: [u8; 16] = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap().try_into().unwrap();
+        let plaintext = hex::decode("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51").unwrap();
+        let ciphertext = hex::decode("f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d").unwrap();
+
+        assert_eq!(cbc_encrypt(&key, &iv, &plaintext), ciphertext);
+        assert_eq!(cbc_decrypt(&key, &iv, &ciphertext).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn test_protocol_one_shared_secret() {
+        let z = [0x5a; 32];
+        let secret = SharedSecret::derive(PinUvAuthProtocol::One, &z);
+        let key: [u8; 32] = Sha256::digest(z).into();
+
+        // Zero IV and no prefix, so encryption is deterministic
+        let ciphertext = secret.encrypt(&pin_hash("1234")).unwrap();
+        assert_eq!(ciphertext, cbc_encrypt(&key, &[0; 16], &pin_hash("1234")));
+        assert_eq!(secret.decrypt(&ciphertext).unwrap(), pin_hash("1234"));
+        assert_eq!(secret.authenticate(b"msg"), hmac_sha256(&key, b"msg")[..16].to_vec());
+    }
+
+    #[test]
+    fn test_pad_pin() {
+        let padded = pad_pin("1234").unwrap();
+        assert_eq!(&padded[..4], b"1234");
+        assert!(padded[4..].iter().all(|&b| b == 0));
+        assert!(pad_pin(&"1".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_validate_new_pin_counts_code_points() {
+        // Four emoji are 16 bytes but only four code points
+        let emoji = "\u{1F511}\u{1F511}\u{1F511}\u{1F511}";
+        assert!(validate_new_pin(emoji).is_ok());
+        assert_eq!(&pad_pin(emoji).unwrap()[..16], emoji.as_bytes());
+
+        // Four bytes, three code points
+        assert!(validate_new_pin("\u{e9}12").is_err());
+        assert!(validate_new_pin("\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}").is_ok());
+        assert!(validate_new_pin("123456789").is_err());
+    }
+
+    #[test]
+    fn test_pin_is_nfc_normalized() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        assert!(validate_new_pin(decomposed).is_ok());
+        assert_eq!(pin_hash(composed), pin_hash(decomposed));
+        assert_eq!(pad_pin(composed).unwrap(), pad_pin(decomposed).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_pin_lifecycle_against_soft_authenticator() {
+        use crate::soft::SoftAuthenticator;
+        use ykey_core::traits::Fido2Protocol;
+
+        let mut client = Fido2Client::new(SoftAuthenticator::blank());
+        client.set_pin("1234").await.unwrap();
+        assert!(matches!(client.set_pin("5678").await, Err(YKeyError::CtapError { .. })));
+
+        client.change_pin("1234", "87654321").await.unwrap();
+        assert!(matches!(client.verify_pin("1234").await, Err(YKeyError::InvalidPin { .. })));
+
+        let token = client.verify_pin("87654321").await.unwrap();
+        assert_eq!(client.pin_token(), Some(&token));
+        assert_eq!(client.pin_protocol_version(), Some(1));
+        assert_eq!(client.device().pin_token(), Some(token.as_slice()));
+    }
+
+    #[tokio::test]
+    async fn test_set_pin_refreshes_cached_info() {
+        use crate::soft::SoftAuthenticator;
+        use ykey_core::traits::Fido2Protocol;
+
+        let mut client = Fido2Client::new(SoftAuthenticator::blank());
+        client.get_info().await.unwrap();
+        assert!(!client.cached_info().unwrap().option_enabled("clientPin"));
+
+        client.set_pin("1234").await.unwrap();
+        assert!(client.cached_info().is_none());
+        assert!(client.authenticator_info().await.unwrap().option_enabled("clientPin"));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_pin_reports_retries() {
+        use crate::soft::SoftAuthenticator;
+        use ykey_core::traits::Fido2Protocol;
+
+        let mut client = Fido2Client::new(SoftAuthenticator::new("1234"));
+        assert_eq!(client.pin_retries().await.unwrap(), 8);
+
+        for expected in [7, 6, 5] {
+            match client.verify_pin("0000").await {
+                Err(YKeyError::InvalidPin { retries_remaining, .. }) => assert_eq!(retries_remaining, Some(expected)),
+                other => panic!("expected InvalidPin, got {:?}", other),
+            }
+        }
+        let Err(error) = client.change_pin("9999", "5678").await else {
+            panic!("change_pin accepted a wrong PIN");
+        };
+        assert_eq!(error.to_string(), "Invalid PIN: PIN invalid, 4 attempts remaining");
+        let Err(error) = client.get_pin_uv_auth_token_with_permissions("9999", Permissions::GET_ASSERTION, None).await else {
+            panic!("token issued for a wrong PIN");
+        };
+        assert!(matches!(error, YKeyError::InvalidPin { retries_remaining: Some(3), .. }));
+
+        // A correct PIN resets the counter
+        client.verify_pin("1234").await.unwrap();
+        assert_eq!(client.pin_retries().await.unwrap(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_obtain_uv_retries_preferred_attempts() {
+        use crate::soft::SoftAuthenticator;
+
+        let authenticator = SoftAuthenticator::new("1234").with_builtin_uv(Some(3), 2);
+        let mut client = Fido2Client::new(authenticator);
+        let token = client
+            .obtain_uv(Some("1234"), Permissions::CREDENTIAL_MANAGEMENT, None)
+            .await
+            .unwrap();
+
+        // Two misreads then a match, without falling back to the PIN
+        assert_eq!(client.device().uv_attempts(), 3);
+        assert_eq!(client.device().pin_token(), Some(token.into_bytes().as_slice()));
+        assert_eq!(client.pin_protocol_version(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_obtain_uv_falls_back_to_pin() {
+        use crate::soft::SoftAuthenticator;
+
+        // Without a preference a single UV failure goes to the PIN
+        let mut client = Fido2Client::new(SoftAuthenticator::new("1234").with_builtin_uv(None, 2));
+        client.obtain_uv(Some("1234"), Permissions::CREDENTIAL_MANAGEMENT, None).await.unwrap();
+        assert_eq!(client.device().uv_attempts(), 1);
+
+        let mut client = Fido2Client::new(SoftAuthenticator::new("1234").with_builtin_uv(Some(2), 2));
+        let error = client.obtain_uv(None, Permissions::CREDENTIAL_MANAGEMENT, None).await.unwrap_err();
+        assert!(matches!(error, YKeyError::CtapError { code: CTAP2_ERR_UV_INVALID, .. }));
+        assert_eq!(client.device().uv_attempts(), 2);
+
+        // Authenticators without built-in UV go straight to the PIN
+        let mut client = Fido2Client::new(SoftAuthenticator::new("1234"));
+        let error = client.obtain_uv(None, Permissions::CREDENTIAL_MANAGEMENT, None).await.unwrap_err();
+        assert!(matches!(error, YKeyError::PinRequired));
+    }
+
+    #[test]
+    fn test_permission_bits() {
+        assert_eq!(Permissions::LEGACY.bits(), 0x03);
+        assert_eq!((Permissions::CREDENTIAL_MANAGEMENT | Permissions::LARGE_BLOB_WRITE).bits(), 0x14);
+        assert_eq!(Permissions::all().bits(), 0x3F);
+        assert_eq!(Permissions::from_bits_truncate(0xC8), Permissions::BIO_ENROLLMENT);
+    }
+
+    #[tokio::test]
+    async fn test_token_permissions_are_requested_and_kept() {
+        use crate::soft::SoftAuthenticator;
+
+        let mut client = Fido2Client::new(FixedKeyDevice {
+            key_agreement: SecretKey::from_slice(&[0x24; 32]).unwrap(),
+            requests: Vec::new(),
+        });
+        let permissions = Permissions::GET_ASSERTION | Permissions::LARGE_BLOB_WRITE;
+        // The fixed device answers without a token, so only the request is checked
+        let _ = client.get_pin_uv_auth_token_with_permissions("1234", permissions, Some("example.com")).await;
+        let request = &client.device().requests[1];
+        let params = cbor::decode(&request[1..], false).unwrap();
+        let map = cbor::as_map(&params).unwrap();
+        assert_eq!(cbor::get_int(map, 0x02), Some(&cbor::int(SUBCOMMAND_GET_TOKEN_WITH_PERMISSIONS as i64)));
+        assert_eq!(cbor::get_int(map, 0x09), Some(&cbor::int(0x12)));
+        assert_eq!(cbor::get_int(map, 0x0A), Some(&cbor::text("example.com")));
+
+        let mut client = Fido2Client::new(SoftAuthenticator::new("1234"));
+        let token = client
+            .get_pin_uv_auth_token_with_permissions("1234", Permissions::CREDENTIAL_MANAGEMENT, None)
+            .await
+            .unwrap();
+        assert_eq!(token.permissions(), Permissions::CREDENTIAL_MANAGEMENT);
+        assert_eq!(client.pin_permissions(), Permissions::CREDENTIAL_MANAGEMENT);
+        assert!(client.permitted_pin_token(Permissions::CREDENTIAL_MANAGEMENT).is_ok());
+        assert!(matches!(
+            client.permitted_pin_token(Permissions::BIO_ENROLLMENT),
+            Err(YKeyError::PermissionDenied(_))
+        ));
+
+        client.clear_pin_token();
+        assert!(client.pin_permissions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token() {
+        use crate::soft::SoftAuthenticator;
+
+        let mut client = Fido2Client::new(SoftAuthenticator::new("1234"));
+        assert!(!client.validate_token().await.unwrap());
+
+        client
+            .get_pin_uv_auth_token_with_permissions("1234", Permissions::GET_ASSERTION, Some("example.com"))
+            .await
+            .unwrap();
+        assert!(client.validate_token().await.unwrap());
+
+        // A PIN token the authenticator forgot is dropped; the PIN is needed again
+        client.device_mut().expire_token();
+        assert!(!client.validate_token().await.unwrap());
+        assert!(!client.has_pin_token());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_refreshes_with_builtin_uv() {
+        use crate::soft::SoftAuthenticator;
+
+        let mut client = Fido2Client::new(SoftAuthenticator::blank().with_builtin_uv(None, 0));
+        client.obtain_uv(None, Permissions::GET_ASSERTION, None).await.unwrap();
+        let expired = client.stored_pin_token().unwrap();
+
+        client.device_mut().expire_token();
+        assert!(!client.validate_token().await.unwrap());
+        let refreshed = client.stored_pin_token().unwrap();
+        assert_ne!(refreshed.clone().into_bytes(), expired.into_bytes());
+        assert_eq!(refreshed.permissions(), Permissions::GET_ASSERTION);
+        assert!(client.validate_token().await.unwrap());
+    }
+
+    /// Authenticator with a fixed key agreement key that accepts every command
+    struct FixedKeyDevice {
+        key_agreement: SecretKey,
+        requests: Vec<Vec<u8>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Device for FixedKeyDevice {
+        async fn info(&self) -> YKeyResult<ykey_core::types::DeviceInfo> {
+            Err(YKeyError::communication("No device info"))
+        }
+
+        async fn connect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        async fn disconnect(&mut self) -> YKeyResult<()> {
+            Ok(())
+        }
+
+        fn is_connected(&self) -> bool {
+            true
+        }
+
+        async fn send_raw(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+            self.requests.push(data.to_vec());
+            let params = cbor::decode(&data[1..], false)?;
+            let subcommand = cbor::get_int(cbor::as_map(&params)?, 0x02).cloned();
+            if subcommand != Some(cbor::int(SUBCOMMAND_GET_KEY_AGREEMENT as i64)) {
+                return Ok(vec![0x00]);
+            }
+
+            let mut response = vec![0x00];
+            let key = cose_key(&self.key_agreement.public_key());
+            response.extend(cbor::encode(&cbor::int_map(vec![(0x01, Some(key))]))?);
+            Ok(response)
+        }
+    }
+
+    /// setPIN request sent by a client seeded with `seed`
+    async fn seeded_set_pin_request(seed: u64) -> Vec<u8> {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let device = FixedKeyDevice {
+            key_agreement: SecretKey::from_slice(&[0x24; 32]).unwrap(),
+            requests: Vec::new(),
+        };
+        let mut client = Fido2Client::with_rng(device, StdRng::seed_from_u64(seed));
+        client.set_pin_with(PinUvAuthProtocol::Two, "1234").await.unwrap();
+        client.device().requests[1].clone()
+    }
+
+    #[tokio::test]
+    async fn test_seeded_rng_reproduces_pin_uv_auth_param() {
+        let request = seeded_set_pin_request(7).await;
+        assert_eq!(request, seeded_set_pin_request(7).await);
+        assert_ne!(request, seeded_set_pin_request(8).await);
+
+        let params = cbor::decode(&request[1..], false).unwrap();
+        let map = cbor::as_map(&params).unwrap();
+        let param = cbor::to_bytes(cbor::get_int(map, 0x04).unwrap()).unwrap();
+        assert_eq!(hex::encode(&param), "b80e2eb466b0b99cbf915d76c372bb7be481e5e975ee263e59b00823a9a12bab");
+
+        // The authenticator accepts the param with its side of the ECDH
+        let peer = parse_cose_key(cbor::get_int(map, 0x03).unwrap()).unwrap();
+        let authenticator = SecretKey::from_slice(&[0x24; 32]).unwrap();
+        let z = p256::ecdh::diffie_hellman(authenticator.to_nonzero_scalar(), peer.as_affine());
+        let shared = SharedSecret::derive(PinUvAuthProtocol::Two, z.raw_secret_bytes());
+        let new_pin_enc = cbor::to_bytes(cbor::get_int(map, 0x05).unwrap()).unwrap();
+        assert_eq!(shared.authenticate(&new_pin_enc), param);
+        assert_eq!(&shared.decrypt(&new_pin_enc).unwrap()[..4], b"1234");
+    }
+
+    #[test]
+    fn test_decode_key_agreement_response() {
+        let authenticator = SecretKey::random(&mut OsRng);
+        let body = cbor::int_map(vec![(0x01, Some(cose_key(&authenticator.public_key())))]);
+        let data = [vec![0x00], cbor::encode(&body).unwrap()].concat();
+        let command = CtapCommand::ClientPin(crate::ClientPinCommand::GetPinToken { pin: String::new() });
+
+        let crate::CtapResponse::ClientPin(response) =
+            crate::CtapResponse::decode_for(&command, &data, &Default::default()).unwrap()
+        else {
+            panic!("expected a ClientPin response");
+        };
+        let key = parse_cose_key(response.key_agreement.as_ref().unwrap()).unwrap();
+        assert_eq!(key, authenticator.public_key());
+        assert_eq!(response.pin_uv_auth_token, None);
+        assert_eq!(response.pin_retries, None);
+    }
+
+    #[test]
+    fn test_decode_retries_response() {
+        let body = cbor::int_map(vec![
+            (0x03, Some(cbor::int(5))),
+            (0x04, Some(cbor::Value::Bool(true))),
+            (0x05, Some(cbor::int(2))),
+        ]);
+        let response = ClientPinResponse::from_cbor(Some(&body)).unwrap();
+        assert_eq!(response.pin_retries, Some(5));
+        assert_eq!(response.power_cycle_state, Some(true));
+        assert_eq!(response.uv_retries, Some(2));
+        assert_eq!(response.key_agreement, None);
+
+        let out_of_range = cbor::int_map(vec![(0x03, Some(cbor::int(300)))]);
+        assert!(ClientPinResponse::from_cbor(Some(&out_of_range)).is_err());
+        assert_eq!(ClientPinResponse::from_cbor(None).unwrap(), ClientPinResponse::default());
+    }
+
+    #[test]
+    fn test_unknown_protocol_version() {
+        assert!(PinUvAuthProtocol::from_version(1).is_ok());
+        assert!(PinUvAuthProtocol::from_version(2).is_ok());
+        assert!(matches!(
+            PinUvAuthProtocol::from_version(9),
+            Err(YKeyError::UnsupportedProtocolVersion(_))
+        ));
+    }
+}
@@ -0,0 +1,662 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! PIV application support
+//!
+//! Reads PIV data objects (CHUID, CCC, printed information, key history and
+//! certificates), verifies the PIN and signs with slot keys over ISO 7816
+//! APDUs.
+
+use crate::apdu::{self, CommandApdu, SW_SUCCESS};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use x509_cert::{der::Decode, Certificate};
+use ykey_core::{traits::Device, YKeyError, YKeyResult};
+
+/// PIV applet AID
+const PIV_AID: [u8; 5] = [0xA0, 0x00, 0x00, 0x03, 0x08];
+
+const INS_SELECT: u8 = 0xA4;
+const INS_GET_DATA: u8 = 0xCB;
+const INS_VERIFY: u8 = 0x20;
+const INS_GENERAL_AUTHENTICATE: u8 = 0x87;
+
+/// Key reference of the PIV card application PIN
+const PIN_REFERENCE: u8 = 0x80;
+/// PINs are padded with 0xFF to this length
+const PIN_LENGTH: usize = 8;
+const MIN_PIN_LENGTH: usize = 6;
+
+const TAG_OBJECT_ID: u8 = 0x5C;
+const TAG_OBJECT_DATA: u8 = 0x53;
+
+const TAG_FASC_N: u8 = 0x30;
+const TAG_GUID: u8 = 0x34;
+const TAG_EXPIRATION: u8 = 0x35;
+const TAG_SIGNATURE: u8 = 0x3E;
+
+const TAG_ON_CARD_CERTS: u8 = 0xC1;
+const TAG_OFF_CARD_CERTS: u8 = 0xC2;
+const TAG_OFF_CARD_URL: u8 = 0xF3;
+
+const TAG_DYNAMIC_AUTH: u8 = 0x7C;
+const TAG_AUTH_CHALLENGE: u8 = 0x81;
+const TAG_AUTH_RESPONSE: u8 = 0x82;
+
+const TAG_CERTIFICATE: u8 = 0x70;
+const TAG_CERT_INFO: u8 = 0x71;
+/// CertInfo flag marking a gzip-compressed certificate
+const CERT_INFO_GZIP: u8 = 0x01;
+
+/// Card Holder Unique Identifier
+pub const OBJECT_CHUID: u32 = 0x5FC102;
+/// Card Capability Container
+pub const OBJECT_CCC: u32 = 0x5FC107;
+/// Printed information
+pub const OBJECT_PRINTED: u32 = 0x5FC109;
+/// Key history
+pub const OBJECT_KEY_HISTORY: u32 = 0x5FC10C;
+/// Discovery object
+pub const OBJECT_DISCOVERY: u32 = 0x7E;
+
+/// Key slots holding a certificate
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Slot {
+    /// 9A, PIV authentication
+    Authentication,
+    /// 9C, digital signature
+    Signature,
+    /// 9D, key management
+    KeyManagement,
+    /// 9E, card authentication
+    CardAuthentication,
+    /// 82 to 95, retired key management slots numbered from 1
+    Retired(u8),
+}
+
+impl Slot {
+    /// Slots present on every PIV card
+    pub const STANDARD: [Slot; 4] = [
+        Slot::Authentication,
+        Slot::Signature,
+        Slot::KeyManagement,
+        Slot::CardAuthentication,
+    ];
+
+    /// Key reference as used in APDUs
+    pub fn key_reference(self) -> u8 {
+        match self {
+            Slot::Authentication => 0x9A,
+            Slot::Signature => 0x9C,
+            Slot::KeyManagement => 0x9D,
+            Slot::CardAuthentication => 0x9E,
+            Slot::Retired(n) => 0x81 + n,
+        }
+    }
+
+    /// Data object holding the slot's certificate
+    pub fn certificate_object(self) -> u32 {
+        match self {
+            Slot::Authentication => 0x5FC105,
+            Slot::Signature => 0x5FC10A,
+            Slot::KeyManagement => 0x5FC10B,
+            Slot::CardAuthentication => 0x5FC101,
+            Slot::Retired(n) => 0x5FC10C + n as u32,
+        }
+    }
+}
+
+/// Algorithm of the key held in a slot
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Algorithm {
+    Rsa1024,
+    Rsa2048,
+    EccP256,
+    EccP384,
+}
+
+impl Algorithm {
+    /// Algorithm reference as used in APDUs
+    pub fn id(self) -> u8 {
+        match self {
+            Algorithm::Rsa1024 => 0x06,
+            Algorithm::Rsa2048 => 0x07,
+            Algorithm::EccP256 => 0x11,
+            Algorithm::EccP384 => 0x14,
+        }
+    }
+}
+
+/// Card Holder Unique Identifier object
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Chuid {
+    /// Federal Agency Smart Credential Number
+    pub fasc_n: Vec<u8>,
+    /// Card UUID
+    pub guid: Option<[u8; 16]>,
+    /// Expiration date as `YYYYMMDD`
+    pub expiration: Option<String>,
+    /// Whether an issuer signature is present
+    pub signed: bool,
+}
+
+/// Retired key management slots, as recorded in the key history object
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyHistory {
+    /// Retired keys whose certificates are stored on the card
+    pub on_card_certs: u8,
+    /// Retired keys whose certificates are stored elsewhere
+    pub off_card_certs: u8,
+    /// Where off-card certificates can be fetched from
+    pub off_card_url: Option<String>,
+}
+
+/// PIV protocol client
+pub struct PivClient<D: Device> {
+    device: D,
+    selected: bool,
+}
+
+impl<D: Device> PivClient<D> {
+    /// Create a new PIV client with the given device
+    pub fn new(device: D) -> Self {
+        Self { device, selected: false }
+    }
+
+    /// Get underlying device reference
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// Read a data object by its BER-TLV tag
+    ///
+    /// Returns the TLV-encoded object content, or `None` if the object is not
+    /// present. Objects that need the PIN fail with `PinRequired` until it
+    /// has been verified.
+    pub async fn get_object(&mut self, object_id: u32) -> YKeyResult<Option<Vec<u8>>> {
+        self.select().await?;
+
+        let tag = encode_tag(object_id);
+        let mut data = vec![TAG_OBJECT_ID, tag.len() as u8];
+        data.extend_from_slice(&tag);
+
+        let response = match self.transmit(INS_GET_DATA, 0x3F, 0xFF, &data).await {
+            Ok(response) => response,
+            Err(YKeyError::CredentialNotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        match parse_tlvs(&response)?.as_slice() {
+            [(TAG_OBJECT_DATA, value)] => Ok(Some(value.clone())),
+            _ => Err(YKeyError::communication("Unexpected PIV object encoding")),
+        }
+    }
+
+    /// Read the Card Holder Unique Identifier
+    pub async fn read_chuid(&mut self) -> YKeyResult<Option<Chuid>> {
+        self.get_object(OBJECT_CHUID).await?.map(|data| parse_chuid(&data)).transpose()
+    }
+
+    /// Read the key history object
+    pub async fn read_key_history(&mut self) -> YKeyResult<Option<KeyHistory>> {
+        self.get_object(OBJECT_KEY_HISTORY)
+            .await?
+            .map(|data| parse_key_history(&data))
+            .transpose()
+    }
+
+    /// Read the DER certificate stored in a slot, or `None` if it is empty
+    pub async fn read_certificate(&mut self, slot: Slot) -> YKeyResult<Option<Vec<u8>>> {
+        self.get_object(slot.certificate_object())
+            .await?
+            .map(|data| parse_certificate(&data))
+            .transpose()
+    }
+
+    /// Read and parse the X.509 certificate stored in a slot
+    pub async fn read_x509_certificate(&mut self, slot: Slot) -> YKeyResult<Option<Certificate>> {
+        self.read_certificate(slot)
+            .await?
+            .map(|der| {
+                Certificate::from_der(&der)
+                    .map_err(|e| YKeyError::InvalidCredential(format!("Invalid PIV certificate: {}", e)))
+            })
+            .transpose()
+    }
+
+    /// Verify the PIN, unlocking PIN-protected objects and keys for this session
+    ///
+    /// A wrong PIN fails with `InvalidPin` naming the retries left.
+    pub async fn verify_pin(&mut self, pin: &str) -> YKeyResult<()> {
+        if !(MIN_PIN_LENGTH..=PIN_LENGTH).contains(&pin.len()) {
+            return Err(YKeyError::InvalidParameters(format!(
+                "PIV PIN must be {} to {} bytes",
+                MIN_PIN_LENGTH, PIN_LENGTH
+            )));
+        }
+        self.select().await?;
+
+        let mut padded = [0xFF; PIN_LENGTH];
+        padded[..pin.len()].copy_from_slice(pin.as_bytes());
+        self.transmit(INS_VERIFY, 0x00, PIN_REFERENCE, &padded).await?;
+        Ok(())
+    }
+
+    /// Sign a challenge with the key in a slot
+    ///
+    /// The challenge is passed to the card as is: a digest for ECC keys, or a
+    /// fully padded block of the key size for RSA keys. Returns the signature
+    /// exactly as the card produced it.
+    pub async fn sign(&mut self, slot: Slot, algorithm: Algorithm, challenge: &[u8]) -> YKeyResult<Vec<u8>> {
+        self.select().await?;
+
+        let template = [encode_tlv(TAG_AUTH_RESPONSE, &[]), encode_tlv(TAG_AUTH_CHALLENGE, challenge)].concat();
+        let response = self
+            .transmit(
+                INS_GENERAL_AUTHENTICATE,
+                algorithm.id(),
+                slot.key_reference(),
+                &encode_tlv(TAG_DYNAMIC_AUTH, &template),
+            )
+            .await?;
+
+        let template = match parse_tlvs(&response)?.as_slice() {
+            [(TAG_DYNAMIC_AUTH, template)] => parse_tlvs(template)?,
+            _ => return Err(YKeyError::communication("Unexpected PIV authentication response")),
+        };
+        template
+            .into_iter()
+            .find(|(tag, _)| *tag == TAG_AUTH_RESPONSE)
+            .map(|(_, signature)| signature)
+            .ok_or_else(|| YKeyError::communication("PIV authentication response without signature"))
+    }
+
+    /// Read the certificates of every populated slot in one session
+    ///
+    /// Covers the standard slots and the retired slots counted in the key
+    /// history. The applet is selected once; empty slots are skipped and a
+    /// failure in one slot does not stop the others from being read.
+    pub async fn read_all_certificates(&mut self) -> YKeyResult<Vec<(Slot, YKeyResult<Vec<u8>>)>> {
+        self.select().await?;
+
+        let retired = match self.read_key_history().await {
+            Ok(history) => history.map_or(0, |h| h.on_card_certs.min(20)),
+            Err(YKeyError::PinRequired) => 0,
+            Err(e) => return Err(e),
+        };
+        let slots = Slot::STANDARD.into_iter().chain((1..=retired).map(Slot::Retired));
+
+        let mut certificates = Vec::new();
+        for slot in slots {
+            match self.read_certificate(slot).await {
+                Ok(Some(certificate)) => certificates.push((slot, Ok(certificate))),
+                Ok(None) => {}
+                Err(e) => certificates.push((slot, Err(e))),
+            }
+        }
+        Ok(certificates)
+    }
+
+    /// Select the PIV applet if not done yet
+    async fn select(&mut self) -> YKeyResult<()> {
+        if !self.selected {
+            self.transmit(INS_SELECT, 0x04, 0x00, &PIV_AID).await?;
+            self.selected = true;
+        }
+        Ok(())
+    }
+
+    /// Send an APDU, following GET RESPONSE chaining
+    async fn transmit(&mut self, ins: u8, p1: u8, p2: u8, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        let command = CommandApdu::new(0x00, ins, p1, p2).with_data(data);
+        let response = apdu::transceive(&mut self.device, &command).await?;
+        match response.status() {
+            SW_SUCCESS => Ok(response.data),
+            0x6982 => Err(YKeyError::PinRequired),
+            0x6983 => Err(YKeyError::DeviceLocked),
+            status if status & 0xFFF0 == 0x63C0 => Err(YKeyError::invalid_pin(
+                "Incorrect PIV PIN",
+                Some((status & 0x000F) as u8),
+            )),
+            0x6A82 => Err(YKeyError::CredentialNotFound("PIV object not found".to_string())),
+            status => Err(YKeyError::communication(format!("PIV command failed with status {:04X}", status))),
+        }
+    }
+}
+
+/// Encode an object ID as the minimal big-endian tag bytes
+fn encode_tag(object_id: u32) -> Vec<u8> {
+    let bytes = object_id.to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len() - 1);
+    bytes[start..].to_vec()
+}
+
+/// Encode a TLV with a single-byte tag and BER length
+fn encode_tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut data = vec![tag];
+    match value.len() {
+        length @ 0..=0x7F => data.push(length as u8),
+        length @ 0x80..=0xFF => data.extend_from_slice(&[0x81, length as u8]),
+        length => {
+            data.push(0x82);
+            data.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+    }
+    data.extend_from_slice(value);
+    data
+}
+
+/// Split a TLV sequence with single-byte tags and BER lengths
+fn parse_tlvs(data: &[u8]) -> YKeyResult<Vec<(u8, Vec<u8>)>> {
+    let mut items = Vec::new();
+    let mut rest = data;
+    while let [tag, tail @ ..] = rest {
+        let (length, tail) = match tail {
+            [0x81, length, tail @ ..] => (*length as usize, tail),
+            [0x82, high, low, tail @ ..] => (u16::from_be_bytes([*high, *low]) as usize, tail),
+            [length, tail @ ..] if *length < 0x80 => (*length as usize, tail),
+            _ => return Err(YKeyError::communication("Invalid PIV TLV length")),
+        };
+        if tail.len() < length {
+            return Err(YKeyError::communication("Truncated PIV TLV"));
+        }
+        items.push((*tag, tail[..length].to_vec()));
+        rest = &tail[length..];
+    }
+    Ok(items)
+}
+
+fn parse_chuid(data: &[u8]) -> YKeyResult<Chuid> {
+    let mut chuid = Chuid {
+        fasc_n: Vec::new(),
+        guid: None,
+        expiration: None,
+        signed: false,
+    };
+
+    for (tag, value) in parse_tlvs(data)? {
+        match tag {
+            TAG_FASC_N => chuid.fasc_n = value,
+            TAG_GUID => {
+                chuid.guid = Some(
+                    value
+                        .try_into()
+                        .map_err(|_| YKeyError::communication("CHUID GUID must be 16 bytes"))?,
+                )
+            }
+            TAG_EXPIRATION => chuid.expiration = Some(String::from_utf8_lossy(&value).into_owned()),
+            TAG_SIGNATURE => chuid.signed = !value.is_empty(),
+            _ => {}
+        }
+    }
+    Ok(chuid)
+}
+
+/// Extract the DER certificate from a certificate object, inflating it if compressed
+fn parse_certificate(data: &[u8]) -> YKeyResult<Vec<u8>> {
+    let items = parse_tlvs(data)?;
+    let certificate = items
+        .iter()
+        .find(|(tag, _)| *tag == TAG_CERTIFICATE)
+        .map(|(_, value)| value)
+        .ok_or_else(|| YKeyError::communication("PIV certificate object without certificate"))?;
+    let compressed = items
+        .iter()
+        .any(|(tag, value)| *tag == TAG_CERT_INFO && value.first() == Some(&CERT_INFO_GZIP));
+
+    if !compressed {
+        return Ok(certificate.clone());
+    }
+    let mut inflated = Vec::new();
+    GzDecoder::new(certificate.as_slice())
+        .read_to_end(&mut inflated)
+        .map_err(|e| YKeyError::communication(format!("Failed to inflate PIV certificate: {}", e)))?;
+    Ok(inflated)
+}
+
+fn parse_key_history(data: &[u8]) -> YKeyResult<KeyHistory> {
+    let mut history = KeyHistory {
+        on_card_certs: 0,
+        off_card_certs: 0,
+        off_card_url: None,
+    };
+
+    for (tag, value) in parse_tlvs(data)? {
+        match tag {
+            TAG_ON_CARD_CERTS => history.on_card_certs = value.first().copied().unwrap_or_default(),
+            TAG_OFF_CARD_CERTS => history.off_card_certs = value.first().copied().unwrap_or_default(),
+            TAG_OFF_CARD_URL => history.off_card_url = Some(String::from_utf8_lossy(&value).into_owned()),
+            _ => {}
+        }
+    }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apdu::INS_GET_RESPONSE, test_support::ReplayDevice};
+
+    fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+        encode_tlv(tag, value)
+    }
+
+    fn with_status(mut data: Vec<u8>, sw1: u8, sw2: u8) -> Vec<u8> {
+        data.extend_from_slice(&[sw1, sw2]);
+        data
+    }
+
+    #[tokio::test]
+    async fn test_read_chuid() {
+        let guid: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let content = [
+            tlv(TAG_FASC_N, &[0xD4; 25]),
+            tlv(TAG_GUID, &guid),
+            tlv(TAG_EXPIRATION, b"20301231"),
+            tlv(TAG_SIGNATURE, &[0x30; 100]),
+            tlv(0xFE, &[]),
+        ]
+        .concat();
+        let object = tlv(TAG_OBJECT_DATA, &content);
+
+        // The object arrives in two chunks to exercise GET RESPONSE
+        let (first, second) = object.split_at(100);
+        let mut client = PivClient::new(ReplayDevice::new(vec![
+            with_status(Vec::new(), 0x90, 0x00),
+            with_status(first.to_vec(), 0x61, second.len() as u8),
+            with_status(second.to_vec(), 0x90, 0x00),
+        ]));
+
+        let chuid = client.read_chuid().await.unwrap().unwrap();
+        assert_eq!(chuid.fasc_n, vec![0xD4; 25]);
+        assert_eq!(chuid.guid, Some(guid));
+        assert_eq!(chuid.expiration.as_deref(), Some("20301231"));
+        assert!(chuid.signed);
+
+        let commands = &client.device().requests;
+        assert_eq!(commands[0][..5], [0x00, INS_SELECT, 0x04, 0x00, PIV_AID.len() as u8]);
+        assert_eq!(commands[1], [0x00, INS_GET_DATA, 0x3F, 0xFF, 0x05, 0x5C, 0x03, 0x5F, 0xC1, 0x02]);
+        assert_eq!(commands[2], [0x00, INS_GET_RESPONSE, 0x00, 0x00, second.len() as u8]);
+    }
+
+    #[tokio::test]
+    async fn test_get_object_custom_tag_encoding() {
+        let mut client = PivClient::new(ReplayDevice::new(vec![
+            with_status(Vec::new(), 0x90, 0x00),
+            with_status(tlv(TAG_OBJECT_DATA, &[0x01, 0x01, 0xAA]), 0x90, 0x00),
+            with_status(tlv(TAG_OBJECT_DATA, &[]), 0x90, 0x00),
+        ]));
+
+        assert_eq!(client.get_object(0x5FFF01).await.unwrap(), Some(vec![0x01, 0x01, 0xAA]));
+        assert_eq!(client.get_object(OBJECT_DISCOVERY).await.unwrap(), Some(Vec::new()));
+
+        let commands = &client.device().requests;
+        assert_eq!(commands[1][4..], [0x05, TAG_OBJECT_ID, 0x03, 0x5F, 0xFF, 0x01]);
+        assert_eq!(commands[2][4..], [0x03, TAG_OBJECT_ID, 0x01, 0x7E]);
+    }
+
+    #[tokio::test]
+    async fn test_get_object_status_words() {
+        let mut client = PivClient::new(ReplayDevice::new(vec![
+            with_status(Vec::new(), 0x90, 0x00),
+            with_status(Vec::new(), 0x69, 0x82),
+            with_status(Vec::new(), 0x6A, 0x82),
+        ]));
+
+        assert!(matches!(client.get_object(OBJECT_PRINTED).await, Err(YKeyError::PinRequired)));
+        assert_eq!(client.read_key_history().await.unwrap(), None);
+    }
+
+    fn certificate_object(certificate: &[u8]) -> Vec<u8> {
+        let content = [tlv(TAG_CERTIFICATE, certificate), tlv(TAG_CERT_INFO, &[0x00]), tlv(0xFE, &[])].concat();
+        with_status(tlv(TAG_OBJECT_DATA, &content), 0x90, 0x00)
+    }
+
+    #[tokio::test]
+    async fn test_read_all_certificates_in_one_session() {
+        // Key history records one retired certificate on the card
+        let history = [tlv(TAG_ON_CARD_CERTS, &[1]), tlv(TAG_OFF_CARD_CERTS, &[0])].concat();
+        let mut client = PivClient::new(ReplayDevice::new(vec![
+            with_status(Vec::new(), 0x90, 0x00),
+            with_status(tlv(TAG_OBJECT_DATA, &history), 0x90, 0x00),
+            certificate_object(&[0x30, 0x9A]),
+            with_status(Vec::new(), 0x6A, 0x82),
+            with_status(Vec::new(), 0x6F, 0x00),
+            with_status(Vec::new(), 0x6A, 0x82),
+            certificate_object(&[0x30, 0x82]),
+        ]));
+
+        let certificates = client.read_all_certificates().await.unwrap();
+        let slots: Vec<Slot> = certificates.iter().map(|(slot, _)| *slot).collect();
+        assert_eq!(slots, vec![Slot::Authentication, Slot::KeyManagement, Slot::Retired(1)]);
+        assert_eq!(certificates[0].1.as_ref().unwrap(), &vec![0x30, 0x9A]);
+        assert!(matches!(certificates[1].1, Err(YKeyError::CommunicationError(_))));
+        assert_eq!(certificates[2].1.as_ref().unwrap(), &vec![0x30, 0x82]);
+
+        // One SELECT, then key history and one GET DATA per slot
+        let commands = &client.device().requests;
+        assert_eq!(commands.len(), 7);
+        assert_eq!(commands.iter().filter(|c| c[1] == INS_SELECT).count(), 1);
+        assert_eq!(commands[6][4..], [0x05, TAG_OBJECT_ID, 0x03, 0x5F, 0xC1, 0x0D]);
+    }
+
+    #[tokio::test]
+    async fn test_verify_pin_reports_retries() {
+        let mut client = PivClient::new(ReplayDevice::new(vec![
+            with_status(Vec::new(), 0x90, 0x00),
+            with_status(Vec::new(), 0x63, 0xC2),
+            with_status(Vec::new(), 0x90, 0x00),
+            with_status(Vec::new(), 0x69, 0x83),
+        ]));
+
+        match client.verify_pin("123456").await {
+            Err(error @ YKeyError::InvalidPin { retries_remaining: Some(2), .. }) => {
+                assert!(error.to_string().contains("2 attempts remaining"))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        client.verify_pin("12345678").await.unwrap();
+        assert!(matches!(client.verify_pin("123456").await, Err(YKeyError::DeviceLocked)));
+        assert!(matches!(client.verify_pin("12345").await, Err(YKeyError::InvalidParameters(_))));
+
+        let commands = &client.device().requests;
+        assert_eq!(
+            commands[1],
+            [0x00, INS_VERIFY, 0x00, PIN_REFERENCE, 0x08, b'1', b'2', b'3', b'4', b'5', b'6', 0xFF, 0xFF]
+        );
+        assert_eq!(commands[2][5..], *b"12345678");
+    }
+
+    #[tokio::test]
+    async fn test_sign_with_slot_key() {
+        let signature = vec![0x30; 70];
+        let response = tlv(TAG_DYNAMIC_AUTH, &tlv(TAG_AUTH_RESPONSE, &signature));
+        let mut client = PivClient::new(ReplayDevice::new(vec![
+            with_status(Vec::new(), 0x90, 0x00),
+            with_status(response, 0x90, 0x00),
+        ]));
+
+        let digest = [0xAB; 32];
+        assert_eq!(client.sign(Slot::Signature, Algorithm::EccP256, &digest).await.unwrap(), signature);
+
+        let expected = [
+            vec![0x00, INS_GENERAL_AUTHENTICATE, 0x11, 0x9C, 0x26, TAG_DYNAMIC_AUTH, 0x24],
+            vec![TAG_AUTH_RESPONSE, 0x00, TAG_AUTH_CHALLENGE, 0x20],
+            digest.to_vec(),
+        ]
+        .concat();
+        assert_eq!(client.device().requests[1], expected);
+    }
+
+    #[tokio::test]
+    async fn test_read_x509_certificate() {
+        use p256::ecdsa::{DerSignature, SigningKey};
+        use std::{str::FromStr, time::Duration};
+        use x509_cert::{
+            builder::{Builder, CertificateBuilder, Profile},
+            der::Encode,
+            name::Name,
+            serial_number::SerialNumber,
+            spki::SubjectPublicKeyInfoOwned,
+            time::Validity,
+        };
+
+        let key = SigningKey::from_slice(&[0x33; 32]).unwrap();
+        let der = CertificateBuilder::new(
+            Profile::Root,
+            SerialNumber::from(7u32),
+            Validity::from_now(Duration::from_secs(3600)).unwrap(),
+            Name::from_str("CN=PIV Authentication").unwrap(),
+            SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).unwrap(),
+            &key,
+        )
+        .unwrap()
+        .build::<DerSignature>()
+        .unwrap()
+        .to_der()
+        .unwrap();
+
+        let content = [tlv(TAG_CERTIFICATE, &der), tlv(TAG_CERT_INFO, &[0x00]), tlv(0xFE, &[])].concat();
+        let mut client = PivClient::new(ReplayDevice::new(vec![
+            with_status(Vec::new(), 0x90, 0x00),
+            with_status(tlv(TAG_OBJECT_DATA, &content), 0x90, 0x00),
+            with_status(Vec::new(), 0x6A, 0x82),
+        ]));
+
+        let certificate = client.read_x509_certificate(Slot::Authentication).await.unwrap().unwrap();
+        assert_eq!(certificate.tbs_certificate.subject.to_string(), "CN=PIV Authentication");
+        assert_eq!(certificate.tbs_certificate.serial_number, SerialNumber::from(7u32));
+        assert!(client.read_x509_certificate(Slot::Signature).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_compressed_certificate() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0x30; 64]).unwrap();
+        let data = [tlv(TAG_CERTIFICATE, &encoder.finish().unwrap()), tlv(TAG_CERT_INFO, &[CERT_INFO_GZIP])].concat();
+
+        assert_eq!(parse_certificate(&data).unwrap(), vec![0x30; 64]);
+        assert_eq!(Slot::Retired(20).key_reference(), 0x95);
+        assert_eq!(Slot::Retired(20).certificate_object(), 0x5FC120);
+    }
+
+    #[test]
+    fn test_parse_key_history() {
+        let data = [
+            tlv(TAG_ON_CARD_CERTS, &[2]),
+            tlv(TAG_OFF_CARD_CERTS, &[1]),
+            tlv(TAG_OFF_CARD_URL, b"https://example.com/certs"),
+            tlv(0xFE, &[]),
+        ]
+        .concat();
+
+        let history = parse_key_history(&data).unwrap();
+        assert_eq!(history.on_card_certs, 2);
+        assert_eq!(history.off_card_certs, 1);
+        assert_eq!(history.off_card_url.as_deref(), Some("https://example.com/certs"));
+    }
+}
@@ -0,0 +1,11 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! The core prelude plus the protocol clients
+
+pub use ykey_core::prelude::*;
+
+pub use crate::{
+    bio_enroll::BioEnrollClient, cred_mgmt::CredMgmtClient, management::ManagementClient, oath::OathClient,
+    piv::PivClient, u2f::U2fClient, Fido2Client,
+};
@@ -0,0 +1,264 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Device-specific quirk handling
+//!
+//! Some authenticator firmware deviates from the CTAP specification in small,
+//! well-known ways. Rather than sprinkling special cases through the protocol
+//! code, the known deviations are described in a [`QuirkTable`] and resolved
+//! into a [`DeviceQuirks`] set that the client consults where it matters.
+
+use std::time::Duration;
+use ykey_core::types::Aaguid;
+
+/// How a quirk entry identifies the devices it applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuirkMatch {
+    /// Match by the AAGUID reported in GetInfo
+    Aaguid(Aaguid),
+    /// Match by USB vendor and product ID
+    VidPid { vendor_id: u16, product_id: u16 },
+    /// Match every product from a USB vendor
+    Vendor(u16),
+}
+
+/// Inclusive firmware version range an entry applies to
+///
+/// Versions are compared against the `firmwareVersion` field from GetInfo.
+/// A bounded range never matches a device whose firmware version is unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FirmwareRange {
+    /// Lowest affected firmware version
+    pub min: Option<u64>,
+    /// Highest affected firmware version
+    pub max: Option<u64>,
+}
+
+impl FirmwareRange {
+    /// Range covering every firmware version
+    pub const ANY: FirmwareRange = FirmwareRange { min: None, max: None };
+
+    /// Check whether a firmware version falls within this range
+    pub fn contains(&self, firmware_version: Option<u64>) -> bool {
+        if self.min.is_none() && self.max.is_none() {
+            return true;
+        }
+
+        match firmware_version {
+            Some(version) => {
+                self.min.is_none_or(|min| version >= min)
+                    && self.max.is_none_or(|max| version <= max)
+            }
+            None => false,
+        }
+    }
+}
+
+/// A single workaround for a known firmware deviation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Quirk {
+    /// Accept CBOR responses followed by trailing bytes instead of rejecting them
+    LenientCbor,
+    /// Wait this long after a successful reset before the device is usable again
+    PostResetDelay(Duration),
+}
+
+/// Entry in the quirk table
+#[derive(Debug, Clone)]
+pub struct QuirkEntry {
+    /// Devices this entry applies to
+    pub matcher: QuirkMatch,
+    /// Affected firmware versions
+    pub firmware: FirmwareRange,
+    /// Workarounds to apply
+    pub quirks: Vec<Quirk>,
+    /// Human-readable explanation of the deviation
+    pub description: &'static str,
+}
+
+/// Identity of a device used to look up applicable quirks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    /// AAGUID from GetInfo, once known
+    pub aaguid: Option<Aaguid>,
+    /// USB vendor ID
+    pub vendor_id: Option<u16>,
+    /// USB product ID
+    pub product_id: Option<u16>,
+    /// Firmware version from GetInfo, once known
+    pub firmware_version: Option<u64>,
+}
+
+impl QuirkMatch {
+    fn matches(&self, identity: &DeviceIdentity) -> bool {
+        match self {
+            QuirkMatch::Aaguid(aaguid) => identity.aaguid.as_ref() == Some(aaguid),
+            QuirkMatch::VidPid { vendor_id, product_id } => {
+                identity.vendor_id == Some(*vendor_id) && identity.product_id == Some(*product_id)
+            }
+            QuirkMatch::Vendor(vendor_id) => identity.vendor_id == Some(*vendor_id),
+        }
+    }
+}
+
+/// Resolved set of workarounds for one device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceQuirks {
+    /// Tolerate trailing bytes after a CBOR response body
+    pub lenient_cbor: bool,
+    /// Settle delay to apply after a successful reset
+    pub post_reset_delay: Option<Duration>,
+}
+
+impl DeviceQuirks {
+    /// Check whether no workarounds apply
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn apply(&mut self, quirk: &Quirk) {
+        match quirk {
+            Quirk::LenientCbor => self.lenient_cbor = true,
+            Quirk::PostResetDelay(delay) => {
+                // Several entries may match; the longest delay wins
+                let current = self.post_reset_delay.unwrap_or_default();
+                self.post_reset_delay = Some(current.max(*delay));
+            }
+        }
+    }
+}
+
+/// Table of known device quirks
+#[derive(Debug, Clone)]
+pub struct QuirkTable {
+    entries: Vec<QuirkEntry>,
+}
+
+impl QuirkTable {
+    /// Create an empty quirk table
+    pub fn empty() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    /// Create a quirk table with the built-in entries
+    pub fn new() -> Self {
+        let mut table = Self::empty();
+
+        table.add(QuirkEntry {
+            matcher: QuirkMatch::Vendor(0x1050),
+            firmware: FirmwareRange::ANY,
+            quirks: vec![Quirk::PostResetDelay(Duration::from_millis(500))],
+            description: "YubiKeys need a moment after reset before answering reliably",
+        });
+
+        table
+    }
+
+    /// Add an entry to the table
+    pub fn add(&mut self, entry: QuirkEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Get all entries in the table
+    pub fn entries(&self) -> &[QuirkEntry] {
+        &self.entries
+    }
+
+    /// Resolve the workarounds that apply to a device
+    pub fn lookup(&self, identity: &DeviceIdentity) -> DeviceQuirks {
+        let mut quirks = DeviceQuirks::default();
+
+        for entry in &self.entries {
+            if entry.matcher.matches(identity) && entry.firmware.contains(identity.firmware_version) {
+                for quirk in &entry.quirks {
+                    quirks.apply(quirk);
+                }
+            }
+        }
+
+        quirks
+    }
+}
+
+impl Default for QuirkTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_AAGUID: Aaguid = Aaguid::new([0xAB; 16]);
+
+    fn lenient_entry(firmware: FirmwareRange) -> QuirkEntry {
+        QuirkEntry {
+            matcher: QuirkMatch::Aaguid(TEST_AAGUID),
+            firmware,
+            quirks: vec![Quirk::LenientCbor],
+            description: "test entry",
+        }
+    }
+
+    #[test]
+    fn test_firmware_range() {
+        let range = FirmwareRange { min: Some(10), max: Some(20) };
+        assert!(range.contains(Some(10)));
+        assert!(range.contains(Some(20)));
+        assert!(!range.contains(Some(21)));
+        assert!(!range.contains(None));
+
+        assert!(FirmwareRange::ANY.contains(None));
+        assert!(FirmwareRange::ANY.contains(Some(1)));
+    }
+
+    #[test]
+    fn test_lookup_by_aaguid() {
+        let mut table = QuirkTable::empty();
+        table.add(lenient_entry(FirmwareRange::ANY));
+
+        let identity = DeviceIdentity {
+            aaguid: Some(TEST_AAGUID),
+            ..Default::default()
+        };
+        assert!(table.lookup(&identity).lenient_cbor);
+
+        let other = DeviceIdentity {
+            aaguid: Some(Aaguid::default()),
+            ..Default::default()
+        };
+        assert!(table.lookup(&other).is_empty());
+    }
+
+    #[test]
+    fn test_lookup_respects_firmware_range() {
+        let mut table = QuirkTable::empty();
+        table.add(lenient_entry(FirmwareRange { min: None, max: Some(5) }));
+
+        let old = DeviceIdentity {
+            aaguid: Some(TEST_AAGUID),
+            firmware_version: Some(4),
+            ..Default::default()
+        };
+        let new = DeviceIdentity {
+            firmware_version: Some(6),
+            ..old.clone()
+        };
+
+        assert!(table.lookup(&old).lenient_cbor);
+        assert!(!table.lookup(&new).lenient_cbor);
+    }
+
+    #[test]
+    fn test_builtin_yubikey_reset_delay() {
+        let table = QuirkTable::new();
+        let identity = DeviceIdentity {
+            vendor_id: Some(0x1050),
+            product_id: Some(0x0407),
+            ..Default::default()
+        };
+
+        assert!(table.lookup(&identity).post_reset_delay.is_some());
+    }
+}
@@ -0,0 +1,20 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Randomness used by the protocol clients
+//!
+//! Ephemeral key agreement keys, IVs and throwaway client data hashes are
+//! drawn from an `RngSource`. Clients use the operating system RNG; only
+//! tests can swap in a seeded generator to reproduce exact outputs.
+
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+
+/// Cryptographically secure random number generator
+pub trait RngSource: RngCore + CryptoRng + Send + Sync {}
+
+impl<R: RngCore + CryptoRng + Send + Sync> RngSource for R {}
+
+/// The operating system RNG, used by every client outside tests
+pub fn os_rng() -> Box<dyn RngSource> {
+    Box::new(OsRng)
+}
@@ -0,0 +1,672 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Software authenticator for exercising protocol flows in tests
+//!
+//! Speaks just enough CTAP2 over `send_raw` to cover PIN management, token
+//! acquisition, MakeCredential and GetAssertion with the hmac-secret, credBlob
+//! and largeBlobKey extensions, credential management and the large blob
+//! store, with real cryptography on both sides.
+
+use crate::{
+    cbor,
+    cred_mgmt::CREDENTIAL_MANAGEMENT_COMMAND,
+    hmac_secret::HMAC_SECRET_EXTENSION,
+    large_blob::{self, LARGE_BLOBS_COMMAND},
+    pin::{
+        self, Permissions, PinUvAuthProtocol, SharedSecret, CLIENT_PIN_COMMAND,
+    },
+    CRED_BLOB_EXTENSION, GET_CRED_BLOB_EXTENSION,
+};
+use async_trait::async_trait;
+use p256::SecretKey;
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use ykey_core::{traits::Device, types::*, YKeyError, YKeyResult};
+
+const CTAP2_ERR_INVALID_PARAMETER: u8 = 0x02;
+const CTAP2_ERR_INVALID_LENGTH: u8 = 0x03;
+const CTAP2_ERR_INVALID_SEQ: u8 = 0x04;
+const CTAP2_ERR_INTEGRITY_FAILURE: u8 = 0x3D;
+const CTAP2_ERR_MISSING_PARAMETER: u8 = 0x14;
+const CTAP2_ERR_NO_CREDENTIALS: u8 = 0x2E;
+const CTAP2_ERR_NOT_ALLOWED: u8 = 0x30;
+const CTAP2_ERR_PIN_INVALID: u8 = 0x31;
+const CTAP2_ERR_PIN_BLOCKED: u8 = 0x32;
+const CTAP2_ERR_PIN_AUTH_INVALID: u8 = 0x33;
+const CTAP2_ERR_PIN_NOT_SET: u8 = 0x35;
+const CTAP2_ERR_PIN_POLICY_VIOLATION: u8 = 0x37;
+const CTAP2_ERR_UV_INVALID: u8 = 0x3F;
+const CTAP1_ERR_INVALID_COMMAND: u8 = 0x01;
+
+/// PIN attempts allowed before the PIN blocks
+const MAX_PIN_RETRIES: u8 = 8;
+
+/// maxMsgSize advertised unless overridden
+const DEFAULT_MAX_MSG_SIZE: usize = 1024;
+
+/// Longest credBlob stored, advertised as maxCredBlobLength
+const MAX_CRED_BLOB_LENGTH: usize = 32;
+
+struct SoftToken {
+    protocol: PinUvAuthProtocol,
+    token: Vec<u8>,
+    permissions: Permissions,
+}
+
+/// Built-in user verification, failing a set number of times first
+struct SoftUv {
+    preferred_attempts: Option<u64>,
+    failures_left: u32,
+    attempts: u32,
+}
+
+struct SoftCredential {
+    rp_id: String,
+    id: Vec<u8>,
+    large_blob_key: [u8; 32],
+    /// hmac-secret keys for requests without and with user verification
+    cred_random: [[u8; 32]; 2],
+    cred_blob: Option<Vec<u8>>,
+}
+
+/// In-memory CTAP2.1 authenticator
+pub(crate) struct SoftAuthenticator {
+    key_agreement: SecretKey,
+    pin_hash: Option<[u8; 16]>,
+    pin_retries: u8,
+    token: Option<SoftToken>,
+    credentials: Vec<SoftCredential>,
+    large_blob: Vec<u8>,
+    pending_blob: Option<(usize, Vec<u8>)>,
+    blob_after_read: Option<Vec<u8>>,
+    pending_rps: Vec<String>,
+    uv: Option<SoftUv>,
+    max_msg_size: usize,
+    connected: bool,
+}
+
+type CommandResult = Result<Option<cbor::Value>, u8>;
+
+impl SoftAuthenticator {
+    /// Create an authenticator with the given PIN set
+    pub(crate) fn new(pin: &str) -> Self {
+        Self {
+            pin_hash: Some(pin::pin_hash(pin)),
+            ..Self::blank()
+        }
+    }
+
+    /// Create an authenticator with no PIN set
+    pub(crate) fn blank() -> Self {
+        Self {
+            key_agreement: SecretKey::random(&mut OsRng),
+            pin_hash: None,
+            pin_retries: MAX_PIN_RETRIES,
+            token: None,
+            credentials: Vec::new(),
+            large_blob: large_blob::serialize_array(&[]).unwrap(),
+            pending_blob: None,
+            blob_after_read: None,
+            pending_rps: Vec::new(),
+            uv: None,
+            max_msg_size: DEFAULT_MAX_MSG_SIZE,
+            connected: true,
+        }
+    }
+
+    /// Add built-in UV that rejects the first `failures` attempts
+    ///
+    /// `preferred_attempts` is advertised as preferredPlatformUvAttempts.
+    pub(crate) fn with_builtin_uv(mut self, preferred_attempts: Option<u64>, failures: u32) -> Self {
+        self.uv = Some(SoftUv {
+            preferred_attempts,
+            failures_left: failures,
+            attempts: 0,
+        });
+        self
+    }
+
+    /// Advertise a different maxMsgSize, which also bounds large blob fragments
+    pub(crate) fn with_max_msg_size(mut self, max_msg_size: usize) -> Self {
+        self.max_msg_size = max_msg_size;
+        self
+    }
+
+    /// Number of built-in UV attempts made so far
+    pub(crate) fn uv_attempts(&self) -> u32 {
+        self.uv.as_ref().map_or(0, |uv| uv.attempts)
+    }
+
+    /// Register a discoverable credential and return its ID
+    pub(crate) fn add_credential(&mut self, rp_id: &str) -> Vec<u8> {
+        let id = rand::random::<[u8; 16]>().to_vec();
+        self.credentials.push(SoftCredential {
+            rp_id: rp_id.to_string(),
+            id: id.clone(),
+            large_blob_key: rand::random(),
+            cred_random: rand::random(),
+            cred_blob: None,
+        });
+        id
+    }
+
+    /// hmac-secret output a credential gives for the SHA-256 of `app_salt`
+    pub(crate) fn hmac_secret_output(&self, credential_id: &[u8], verified: bool, app_salt: &[u8]) -> [u8; 32] {
+        let credential = self.credentials.iter().find(|c| c.id == credential_id).expect("known credential");
+        pin::hmac_sha256(&credential.cred_random[verified as usize], &Sha256::digest(app_salt))
+    }
+
+    /// IDs of the credentials registered for an RP
+    pub(crate) fn credentials_for(&self, rp_id: &str) -> Vec<&[u8]> {
+        self.credentials
+            .iter()
+            .filter(|c| c.rp_id == rp_id)
+            .map(|c| c.id.as_slice())
+            .collect()
+    }
+
+    /// Most recently issued pinUvAuthToken
+    pub(crate) fn pin_token(&self) -> Option<&[u8]> {
+        self.token.as_ref().map(|t| t.token.as_slice())
+    }
+
+    /// Forget the issued pinUvAuthToken, as a power cycle or timeout would
+    pub(crate) fn expire_token(&mut self) {
+        self.token = None;
+    }
+
+    /// Current serialized large blob array
+    pub(crate) fn large_blob(&self) -> &[u8] {
+        &self.large_blob
+    }
+
+    /// Overwrite the stored large blob array, e.g. with corrupted data
+    pub(crate) fn set_large_blob(&mut self, data: Vec<u8>) {
+        self.large_blob = data;
+    }
+
+    /// Overwrite the large blob array once the next full read finishes,
+    /// as another client writing in between would
+    pub(crate) fn set_large_blob_after_read(&mut self, data: Vec<u8>) {
+        self.blob_after_read = Some(data);
+    }
+
+    /// Longest large blob fragment accepted in either direction
+    fn max_fragment_length(&self) -> usize {
+        self.max_msg_size - 64
+    }
+
+    fn handle(&mut self, command: u8, params: Option<cbor::Value>) -> CommandResult {
+        let params = params.unwrap_or(cbor::Value::Map(Vec::new()));
+        let map = cbor::as_map(&params).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?;
+
+        match command {
+            0x01 => self.make_credential(map),
+            0x02 => self.get_assertion(map),
+            0x04 => Ok(Some(self.get_info())),
+            CLIENT_PIN_COMMAND => self.client_pin(map),
+            CREDENTIAL_MANAGEMENT_COMMAND => self.credential_management(map),
+            LARGE_BLOBS_COMMAND => self.large_blobs(map),
+            _ => Err(CTAP1_ERR_INVALID_COMMAND),
+        }
+    }
+
+    fn get_info(&self) -> cbor::Value {
+        let mut options = vec![
+            (cbor::text("clientPin"), cbor::Value::Bool(self.pin_hash.is_some())),
+            (cbor::text("pinUvAuthToken"), cbor::Value::Bool(true)),
+        ];
+        if self.uv.is_some() {
+            options.push((cbor::text("uv"), cbor::Value::Bool(true)));
+        }
+        cbor::int_map(vec![
+            (0x01, Some(cbor::Value::Array(vec![cbor::text("FIDO_2_0"), cbor::text("FIDO_2_1")]))),
+            (0x03, Some(cbor::bytes(&[0; 16]))),
+            (0x04, Some(cbor::Value::Map(options))),
+            (0x02, Some(cbor::Value::Array(vec![
+                cbor::text(HMAC_SECRET_EXTENSION),
+                cbor::text(CRED_BLOB_EXTENSION),
+                cbor::text(large_blob::LARGE_BLOB_KEY_EXTENSION),
+            ]))),
+            (0x05, Some(cbor::int(self.max_msg_size as i64))),
+            (0x06, Some(cbor::Value::Array(vec![cbor::int(2), cbor::int(1)]))),
+            (0x0F, Some(cbor::int(MAX_CRED_BLOB_LENGTH as i64))),
+            (0x11, self.uv.as_ref().and_then(|uv| uv.preferred_attempts).map(|n| cbor::int(n as i64))),
+        ])
+    }
+
+    /// Create a credential without user verification, with "none" attestation
+    fn make_credential(&mut self, map: &[(cbor::Value, cbor::Value)]) -> CommandResult {
+        let rp = required(map, 0x02).and_then(|v| cbor::as_map(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))?;
+        let rp_id = cbor::get_text(rp, "id")
+            .ok_or(CTAP2_ERR_MISSING_PARAMETER)
+            .and_then(|v| cbor::to_text(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))?;
+        let extensions = match cbor::get_int(map, 0x06) {
+            Some(extensions) => cbor::as_map(extensions).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?,
+            None => &[],
+        };
+        let cred_blob = cbor::get_text(extensions, CRED_BLOB_EXTENSION)
+            .map(|v| cbor::to_bytes(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))
+            .transpose()?;
+        let wants_large_blob_key = cbor::get_text(extensions, large_blob::LARGE_BLOB_KEY_EXTENSION)
+            .is_some_and(|v| v == &cbor::Value::Bool(true));
+
+        let id = self.add_credential(&rp_id);
+        let credential = self.credentials.last_mut().expect("credential just added");
+        // A credBlob over the limit is not stored, which the output reports
+        let cred_blob_stored = cred_blob.map(|blob| {
+            let fits = blob.len() <= MAX_CRED_BLOB_LENGTH;
+            if fits {
+                credential.cred_blob = Some(blob);
+            }
+            fits
+        });
+
+        let mut auth_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        auth_data.push(if cred_blob_stored.is_some() { 0xC1 } else { 0x41 });
+        auth_data.extend_from_slice(&0u32.to_be_bytes());
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&(id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&id);
+        let public_key = pin::cose_key(&SecretKey::random(&mut OsRng).public_key());
+        auth_data.extend(cbor::encode(&public_key).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?);
+        if let Some(stored) = cred_blob_stored {
+            let extensions = cbor::Value::Map(vec![(cbor::text(CRED_BLOB_EXTENSION), cbor::Value::Bool(stored))]);
+            auth_data.extend(cbor::encode(&extensions).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?);
+        }
+
+        Ok(Some(cbor::int_map(vec![
+            (0x01, Some(cbor::text("none"))),
+            (0x02, Some(cbor::bytes(&auth_data))),
+            (0x03, Some(cbor::Value::Map(Vec::new()))),
+            (0x05, wants_large_blob_key.then(|| cbor::bytes(&credential.large_blob_key))),
+        ])))
+    }
+
+    fn get_assertion(&mut self, map: &[(cbor::Value, cbor::Value)]) -> CommandResult {
+        let rp_id = required(map, 0x01).and_then(|v| cbor::to_text(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))?;
+        let allow_list = match cbor::get_int(map, 0x03) {
+            Some(list) => cbor::to_array(list)
+                .map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?
+                .iter()
+                .filter_map(|descriptor| cbor::as_map(descriptor).ok()?.iter().find_map(|(k, v)| {
+                    (k == &cbor::text("id")).then(|| cbor::to_bytes(v).ok()).flatten()
+                }))
+                .collect(),
+            None => Vec::new(),
+        };
+        let extensions = match cbor::get_int(map, 0x04) {
+            Some(extensions) => cbor::as_map(extensions).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?,
+            None => &[],
+        };
+        let wants_large_blob_key = cbor::get_text(extensions, large_blob::LARGE_BLOB_KEY_EXTENSION)
+            .is_some_and(|v| v == &cbor::Value::Bool(true));
+        let wants_cred_blob = cbor::get_text(extensions, GET_CRED_BLOB_EXTENSION)
+            .is_some_and(|v| v == &cbor::Value::Bool(true));
+
+        let verified = match cbor::get_int(map, 0x06) {
+            Some(auth_param) => {
+                let token = self.token.as_ref().ok_or(CTAP2_ERR_PIN_AUTH_INVALID)?;
+                let client_data_hash = bytes_param(map, 0x02)?;
+                if Some(token.protocol.authenticate(&token.token, &client_data_hash)) != cbor::to_bytes(auth_param).ok()
+                    || !token.permissions.contains(Permissions::GET_ASSERTION)
+                {
+                    return Err(CTAP2_ERR_PIN_AUTH_INVALID);
+                }
+                true
+            }
+            None => false,
+        };
+
+        let credential = self
+            .credentials
+            .iter()
+            .find(|c| c.rp_id == rp_id && (allow_list.is_empty() || allow_list.contains(&c.id)))
+            .ok_or(CTAP2_ERR_NO_CREDENTIALS)?;
+
+        let mut outputs = Vec::new();
+        if let Some(input) = cbor::get_text(extensions, HMAC_SECRET_EXTENSION) {
+            outputs.push((cbor::text(HMAC_SECRET_EXTENSION), cbor::bytes(&self.hmac_secret(credential, verified, input)?)));
+        }
+        if wants_cred_blob {
+            // Credentials without a credBlob return an empty one
+            let blob = credential.cred_blob.as_deref().unwrap_or_default();
+            outputs.push((cbor::text(CRED_BLOB_EXTENSION), cbor::bytes(blob)));
+        }
+
+        let mut auth_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        let mut flags = 0x01; // User present
+        if verified {
+            flags |= 0x04;
+        }
+        if !outputs.is_empty() {
+            flags |= 0x80;
+        }
+        auth_data.push(flags);
+        auth_data.extend_from_slice(&1u32.to_be_bytes());
+        if !outputs.is_empty() {
+            auth_data.extend(cbor::encode(&cbor::Value::Map(outputs)).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?);
+        }
+
+        Ok(Some(cbor::int_map(vec![
+            (0x01, Some(cbor::Value::Map(vec![
+                (cbor::text("id"), cbor::bytes(&credential.id)),
+                (cbor::text("type"), cbor::text("public-key")),
+            ]))),
+            (0x02, Some(cbor::bytes(&auth_data))),
+            (0x03, Some(cbor::bytes(&[0x30, 0x00]))),
+            (0x07, wants_large_blob_key.then(|| cbor::bytes(&credential.large_blob_key))),
+        ])))
+    }
+
+    fn client_pin(&mut self, map: &[(cbor::Value, cbor::Value)]) -> CommandResult {
+        let protocol = required(map, 0x01)
+            .and_then(|v| cbor::to_u64(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))
+            .and_then(|v| PinUvAuthProtocol::from_version(v as u8).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))?;
+        let subcommand = required(map, 0x02).and_then(|v| cbor::to_u64(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))?;
+
+        match subcommand {
+            0x01 => Ok(Some(cbor::int_map(vec![(0x03, Some(cbor::int(self.pin_retries as i64)))]))),
+            0x02 => Ok(Some(cbor::int_map(vec![(
+                0x01,
+                Some(pin::cose_key(&self.key_agreement.public_key())),
+            )]))),
+            0x03 => {
+                if self.pin_hash.is_some() {
+                    return Err(CTAP2_ERR_NOT_ALLOWED);
+                }
+                let shared = self.shared_secret(protocol, map)?;
+                let new_pin_enc = bytes_param(map, 0x05)?;
+                if shared.authenticate(&new_pin_enc) != bytes_param(map, 0x04)? {
+                    return Err(CTAP2_ERR_PIN_AUTH_INVALID);
+                }
+                self.pin_hash = Some(decrypt_new_pin(&shared, &new_pin_enc)?);
+                Ok(None)
+            }
+            0x04 => {
+                let shared = self.shared_secret(protocol, map)?;
+                let new_pin_enc = bytes_param(map, 0x05)?;
+                let pin_hash_enc = bytes_param(map, 0x06)?;
+                if shared.authenticate(&[new_pin_enc.as_slice(), &pin_hash_enc].concat()) != bytes_param(map, 0x04)? {
+                    return Err(CTAP2_ERR_PIN_AUTH_INVALID);
+                }
+                self.check_pin(&shared, &pin_hash_enc)?;
+                self.pin_hash = Some(decrypt_new_pin(&shared, &new_pin_enc)?);
+                self.token = None;
+                Ok(None)
+            }
+            0x05 | 0x06 | 0x09 => {
+                let shared = self.shared_secret(protocol, map)?;
+                let permissions = match subcommand {
+                    0x06 | 0x09 => Permissions::from_bits_truncate(
+                        required(map, 0x09).and_then(|v| cbor::to_u64(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))? as u8,
+                    ),
+                    _ => Permissions::LEGACY,
+                };
+                if subcommand == 0x06 {
+                    let uv = self.uv.as_mut().ok_or(CTAP2_ERR_INVALID_PARAMETER)?;
+                    uv.attempts += 1;
+                    if uv.failures_left > 0 {
+                        uv.failures_left -= 1;
+                        return Err(CTAP2_ERR_UV_INVALID);
+                    }
+                } else {
+                    self.check_pin(&shared, &bytes_param(map, 0x06)?)?;
+                }
+
+                let token = rand::random::<[u8; 32]>().to_vec();
+                let encrypted = shared.encrypt(&token).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?;
+                self.token = Some(SoftToken { protocol, token, permissions });
+                Ok(Some(cbor::int_map(vec![(0x02, Some(cbor::bytes(&encrypted)))])))
+            }
+            _ => Err(CTAP2_ERR_INVALID_PARAMETER),
+        }
+    }
+
+    /// Encrypted hmac-secret output for the salts in an extension input
+    fn hmac_secret(&self, credential: &SoftCredential, verified: bool, input: &cbor::Value) -> Result<Vec<u8>, u8> {
+        let input = cbor::as_map(input).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?;
+        let protocol = match cbor::get_int(input, 0x04) {
+            Some(version) => cbor::to_u64(version)
+                .ok()
+                .and_then(|v| PinUvAuthProtocol::from_version(v as u8).ok())
+                .ok_or(CTAP2_ERR_INVALID_PARAMETER)?,
+            None => PinUvAuthProtocol::One,
+        };
+        let shared = self.shared_secret_with(protocol, required(input, 0x01)?)?;
+        let salt_enc = bytes_param(input, 0x02)?;
+        if shared.authenticate(&salt_enc) != bytes_param(input, 0x03)? {
+            return Err(CTAP2_ERR_PIN_AUTH_INVALID);
+        }
+        let salts = shared.decrypt(&salt_enc).map_err(|_| CTAP2_ERR_INVALID_LENGTH)?;
+        if salts.len() != 32 && salts.len() != 64 {
+            return Err(CTAP2_ERR_INVALID_LENGTH);
+        }
+        let key = &credential.cred_random[verified as usize];
+        let output: Vec<u8> = salts.chunks(32).flat_map(|salt| pin::hmac_sha256(key, salt)).collect();
+        shared.encrypt(&output).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)
+    }
+
+    /// Derive the shared secret from the platform key agreement key in the request
+    fn shared_secret(&self, protocol: PinUvAuthProtocol, map: &[(cbor::Value, cbor::Value)]) -> Result<SharedSecret, u8> {
+        self.shared_secret_with(protocol, required(map, 0x03)?)
+    }
+
+    fn shared_secret_with(&self, protocol: PinUvAuthProtocol, platform_key: &cbor::Value) -> Result<SharedSecret, u8> {
+        let platform_key = pin::parse_cose_key(platform_key).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?;
+        let z = p256::ecdh::diffie_hellman(self.key_agreement.to_nonzero_scalar(), platform_key.as_affine());
+        Ok(SharedSecret::derive(protocol, z.raw_secret_bytes()))
+    }
+
+    fn check_pin(&mut self, shared: &SharedSecret, pin_hash_enc: &[u8]) -> Result<(), u8> {
+        let expected = self.pin_hash.ok_or(CTAP2_ERR_PIN_NOT_SET)?;
+        if self.pin_retries == 0 {
+            return Err(CTAP2_ERR_PIN_BLOCKED);
+        }
+        if shared.decrypt(pin_hash_enc).ok() != Some(expected.to_vec()) {
+            self.pin_retries -= 1;
+            return Err(CTAP2_ERR_PIN_INVALID);
+        }
+        self.pin_retries = MAX_PIN_RETRIES;
+        Ok(())
+    }
+
+    fn credential_management(&mut self, map: &[(cbor::Value, cbor::Value)]) -> CommandResult {
+        let subcommand = required(map, 0x01).and_then(|v| cbor::to_u64(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))? as u8;
+
+        // Continuations are not authenticated
+        if subcommand == 0x03 {
+            if self.pending_rps.is_empty() {
+                return Err(CTAP2_ERR_NOT_ALLOWED);
+            }
+            let rp_id = self.pending_rps.remove(0);
+            return Ok(Some(cbor::int_map(rp_entry(&rp_id, None))));
+        }
+
+        let mut message = vec![subcommand];
+        if let Some(params) = cbor::get_int(map, 0x02) {
+            message.extend(cbor::encode(params).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)?);
+        }
+        let token = self.token.as_ref().ok_or(CTAP2_ERR_PIN_AUTH_INVALID)?;
+        if token.protocol.authenticate(&token.token, &message) != bytes_param(map, 0x04)?
+            || !token.permissions.contains(Permissions::CREDENTIAL_MANAGEMENT)
+        {
+            return Err(CTAP2_ERR_PIN_AUTH_INVALID);
+        }
+
+        match subcommand {
+            0x02 => {
+                let mut rp_ids: Vec<String> = Vec::new();
+                for credential in &self.credentials {
+                    if !rp_ids.contains(&credential.rp_id) {
+                        rp_ids.push(credential.rp_id.clone());
+                    }
+                }
+                if rp_ids.is_empty() {
+                    return Err(CTAP2_ERR_NO_CREDENTIALS);
+                }
+                let first = rp_ids.remove(0);
+                let total = rp_ids.len() as i64 + 1;
+                self.pending_rps = rp_ids;
+                Ok(Some(cbor::int_map(rp_entry(&first, Some(total)))))
+            }
+            0x04 => {
+                let params = required(map, 0x02).and_then(|v| cbor::as_map(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))?;
+                let rp_id_hash = bytes_param(params, 0x01)?;
+                let matching: Vec<_> = self
+                    .credentials
+                    .iter()
+                    .filter(|c| Sha256::digest(c.rp_id.as_bytes()).as_slice() == rp_id_hash)
+                    .collect();
+                let first = matching.first().ok_or(CTAP2_ERR_NO_CREDENTIALS)?;
+                Ok(Some(cbor::int_map(vec![
+                    (0x06, Some(cbor::Value::Map(vec![(cbor::text("id"), cbor::bytes(&first.id))]))),
+                    (0x07, Some(cbor::Value::Map(vec![
+                        (cbor::text("id"), cbor::bytes(&first.id)),
+                        (cbor::text("type"), cbor::text("public-key")),
+                    ]))),
+                    (0x09, Some(cbor::int(matching.len() as i64))),
+                ])))
+            }
+            _ => Err(CTAP2_ERR_INVALID_PARAMETER),
+        }
+    }
+
+    fn large_blobs(&mut self, map: &[(cbor::Value, cbor::Value)]) -> CommandResult {
+        let offset = required(map, 0x03).and_then(|v| cbor::to_u64(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))? as usize;
+
+        if let Some(count) = cbor::get_int(map, 0x01) {
+            let count = cbor::to_u64(count).map_err(|_| CTAP2_ERR_INVALID_PARAMETER)? as usize;
+            if count > self.max_fragment_length() {
+                return Err(CTAP2_ERR_INVALID_LENGTH);
+            }
+            let start = offset.min(self.large_blob.len());
+            let end = (offset + count).min(self.large_blob.len());
+            let response = cbor::int_map(vec![(0x01, Some(cbor::bytes(&self.large_blob[start..end])))]);
+            if end - start < count {
+                if let Some(data) = self.blob_after_read.take() {
+                    self.large_blob = data;
+                }
+            }
+            return Ok(Some(response));
+        }
+
+        let fragment = required(map, 0x02).and_then(|v| cbor::to_bytes(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))?;
+        if fragment.len() > self.max_fragment_length() {
+            return Err(CTAP2_ERR_INVALID_LENGTH);
+        }
+
+        let auth_param = bytes_param(map, 0x05)?;
+        let token = self.token.as_ref().ok_or(CTAP2_ERR_PIN_AUTH_INVALID)?;
+        let mut message = vec![0xFF; 32];
+        message.extend_from_slice(&[LARGE_BLOBS_COMMAND, 0x00]);
+        message.extend_from_slice(&(offset as u32).to_le_bytes());
+        message.extend_from_slice(&Sha256::digest(&fragment));
+        if token.protocol.authenticate(&token.token, &message) != auth_param
+            || !token.permissions.contains(Permissions::LARGE_BLOB_WRITE)
+        {
+            return Err(CTAP2_ERR_PIN_AUTH_INVALID);
+        }
+
+        if offset == 0 {
+            let length = required(map, 0x04).and_then(|v| cbor::to_u64(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))?;
+            self.pending_blob = Some((length as usize, Vec::new()));
+        }
+
+        let (expected, buffer) = self.pending_blob.as_mut().ok_or(CTAP2_ERR_INVALID_SEQ)?;
+        if offset != buffer.len() || buffer.len() + fragment.len() > *expected {
+            return Err(CTAP2_ERR_INVALID_SEQ);
+        }
+        buffer.extend(fragment);
+
+        if buffer.len() == *expected {
+            let (_, data) = self.pending_blob.take().unwrap_or_default();
+            large_blob::parse_array(&data).map_err(|_| CTAP2_ERR_INTEGRITY_FAILURE)?;
+            self.large_blob = data;
+        }
+        Ok(None)
+    }
+}
+
+fn required(map: &[(cbor::Value, cbor::Value)], key: i64) -> Result<&cbor::Value, u8> {
+    cbor::get_int(map, key).ok_or(CTAP2_ERR_MISSING_PARAMETER)
+}
+
+/// rp, rpIDHash and optionally totalRPs of an enumerateRPs response
+fn rp_entry(rp_id: &str, total: Option<i64>) -> Vec<(i64, Option<cbor::Value>)> {
+    vec![
+        (0x03, Some(cbor::Value::Map(vec![(cbor::text("id"), cbor::text(rp_id))]))),
+        (0x04, Some(cbor::bytes(&Sha256::digest(rp_id.as_bytes())))),
+        (0x05, total.map(cbor::int)),
+    ]
+}
+
+fn bytes_param(map: &[(cbor::Value, cbor::Value)], key: i64) -> Result<Vec<u8>, u8> {
+    required(map, key).and_then(|v| cbor::to_bytes(v).map_err(|_| CTAP2_ERR_INVALID_PARAMETER))
+}
+
+/// Decrypt a padded newPinEnc and hash the PIN it carries
+fn decrypt_new_pin(shared: &SharedSecret, new_pin_enc: &[u8]) -> Result<[u8; 16], u8> {
+    let padded = shared.decrypt(new_pin_enc).map_err(|_| CTAP2_ERR_PIN_AUTH_INVALID)?;
+    let length = padded.iter().position(|&b| b == 0).unwrap_or(padded.len());
+    let pin = std::str::from_utf8(&padded[..length]).map_err(|_| CTAP2_ERR_PIN_POLICY_VIOLATION)?;
+    if padded.len() < 64 || pin.chars().count() < 4 {
+        return Err(CTAP2_ERR_PIN_POLICY_VIOLATION);
+    }
+    Ok(pin::pin_hash(pin))
+}
+
+#[async_trait]
+impl Device for SoftAuthenticator {
+    async fn info(&self) -> YKeyResult<DeviceInfo> {
+        Ok(DeviceInfo::new(
+            "soft".to_string(),
+            "Software Authenticator".to_string(),
+            "YKey".to_string(),
+            "Soft FIDO2".to_string(),
+            0x0000,
+            0x0000,
+            DeviceType::Generic,
+            TransportType::Usb,
+        ))
+    }
+
+    async fn connect(&mut self) -> YKeyResult<()> {
+        self.connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> YKeyResult<()> {
+        self.connected = false;
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    async fn send_raw(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        let (&command, body) = data
+            .split_first()
+            .ok_or_else(|| YKeyError::communication("Empty request"))?;
+        let params = if body.is_empty() {
+            None
+        } else {
+            match cbor::decode(body, false) {
+                Ok(value) => Some(value),
+                Err(_) => return Ok(vec![0x12]), // CTAP2_ERR_INVALID_CBOR
+            }
+        };
+
+        Ok(match self.handle(command, params) {
+            Ok(None) => vec![0x00],
+            Ok(Some(response)) => {
+                let mut data = vec![0x00];
+                data.extend(cbor::encode(&response)?);
+                data
+            }
+            Err(code) => vec![code],
+        })
+    }
+}
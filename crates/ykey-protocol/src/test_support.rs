@@ -0,0 +1,79 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Test doubles shared by the protocol module tests
+
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use ykey_core::{traits::Device, types::*, YKeyError, YKeyResult};
+
+/// Device replaying canned responses and recording the requests it receives
+///
+/// Serves CTAP2 messages and smart card APDUs alike through `send_raw`, and
+/// U2F APDUs from a queue of their own through `send_u2f`.
+pub(crate) struct ReplayDevice {
+    pub(crate) responses: VecDeque<Vec<u8>>,
+    pub(crate) requests: Vec<Vec<u8>>,
+    pub(crate) u2f_responses: VecDeque<YKeyResult<Vec<u8>>>,
+    pub(crate) u2f_requests: Vec<Vec<u8>>,
+}
+
+impl ReplayDevice {
+    /// Device answering `send_raw` with `responses` in order
+    pub(crate) fn new(responses: Vec<Vec<u8>>) -> Self {
+        Self {
+            responses: responses.into(),
+            requests: Vec::new(),
+            u2f_responses: VecDeque::new(),
+            u2f_requests: Vec::new(),
+        }
+    }
+
+    /// Answer `send_u2f` with `responses` in order
+    pub(crate) fn with_u2f(mut self, responses: Vec<YKeyResult<Vec<u8>>>) -> Self {
+        self.u2f_responses = responses.into();
+        self
+    }
+}
+
+#[async_trait]
+impl Device for ReplayDevice {
+    async fn info(&self) -> YKeyResult<DeviceInfo> {
+        Ok(DeviceInfo::new(
+            "replay".to_string(),
+            "Replay".to_string(),
+            "Yubico".to_string(),
+            "YubiKey 5".to_string(),
+            0x1050,
+            0x0407,
+            DeviceType::YubiKey,
+            TransportType::Usb,
+        ))
+    }
+
+    async fn connect(&mut self) -> YKeyResult<()> {
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> YKeyResult<()> {
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    async fn send_raw(&mut self, data: &[u8]) -> YKeyResult<Vec<u8>> {
+        self.requests.push(data.to_vec());
+        self.responses
+            .pop_front()
+            .ok_or_else(|| YKeyError::communication("No response available"))
+    }
+
+    async fn send_u2f(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>> {
+        self.u2f_requests.push(apdu.to_vec());
+        self.u2f_responses
+            .pop_front()
+            .unwrap_or_else(|| Err(YKeyError::communication("No response available")))
+    }
+}
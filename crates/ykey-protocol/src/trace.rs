@@ -0,0 +1,227 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! Tracing of the raw CTAP traffic between a client and its authenticator
+//!
+//! A [`Tracer`] attached with `Fido2Client::set_tracer` sees every command
+//! as sent and every response as received, which makes firmware problems
+//! reproducible from a captured session. Without a tracer nothing is
+//! recorded.
+
+use chrono::{DateTime, Utc};
+
+/// Which way a traced message went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceDirection {
+    /// Command sent to the authenticator
+    Command,
+    /// Response received from the authenticator
+    Response,
+}
+
+/// One message exchanged with the authenticator
+#[derive(Debug, Clone, Copy)]
+pub struct TraceEvent<'a> {
+    /// Which way the message went
+    pub direction: TraceDirection,
+    /// Command byte and CBOR parameters, or status byte and CBOR body
+    pub data: &'a [u8],
+    /// When the message was sent or received
+    pub timestamp: DateTime<Utc>,
+}
+
+impl TraceEvent<'_> {
+    /// Name of the command, or of the status for a response
+    pub fn name(&self) -> String {
+        match (self.direction, self.data.first()) {
+            (_, None) => "empty".to_string(),
+            (TraceDirection::Command, Some(&command)) => command_name(command).to_string(),
+            (TraceDirection::Response, Some(&status)) => match status_name(status) {
+                Some(name) => name.to_string(),
+                None => format!("unknown status {:#04x}", status),
+            },
+        }
+    }
+}
+
+/// Observer of the CTAP traffic of a client
+pub trait Tracer: Send + Sync {
+    /// Called for every command before it is sent and every response once received
+    fn trace(&self, event: &TraceEvent<'_>);
+}
+
+/// Tracer logging each message as hex through `tracing` at debug level
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HexTracer;
+
+impl Tracer for HexTracer {
+    fn trace(&self, event: &TraceEvent<'_>) {
+        let arrow = match event.direction {
+            TraceDirection::Command => "->",
+            TraceDirection::Response => "<-",
+        };
+        tracing::debug!(
+            target: "ykey::ctap",
+            timestamp = %event.timestamp.to_rfc3339(),
+            "{} {} ({} bytes) {}",
+            arrow,
+            event.name(),
+            event.data.len(),
+            hex::encode(event.data)
+        );
+    }
+}
+
+/// CTAP2 command name for a command byte
+fn command_name(command: u8) -> &'static str {
+    match command {
+        0x01 => "authenticatorMakeCredential",
+        0x02 => "authenticatorGetAssertion",
+        0x04 => "authenticatorGetInfo",
+        0x06 => "authenticatorClientPIN",
+        0x07 => "authenticatorReset",
+        0x08 => "authenticatorGetNextAssertion",
+        0x09 => "authenticatorBioEnrollment",
+        0x0A => "authenticatorCredentialManagement",
+        0x0B => "authenticatorSelection",
+        0x0C => "authenticatorLargeBlobs",
+        0x0D => "authenticatorConfig",
+        0x40 => "authenticatorBioEnrollment (preview)",
+        0x41 => "authenticatorCredentialManagement (preview)",
+        _ => "unknown command",
+    }
+}
+
+/// CTAP2.1 name of a response status byte
+fn status_name(status: u8) -> Option<&'static str> {
+    let name = match status {
+        0x00 => "CTAP2_OK",
+        0x01 => "CTAP1_ERR_INVALID_COMMAND",
+        0x02 => "CTAP1_ERR_INVALID_PARAMETER",
+        0x03 => "CTAP1_ERR_INVALID_LENGTH",
+        0x04 => "CTAP1_ERR_INVALID_SEQ",
+        0x05 => "CTAP1_ERR_TIMEOUT",
+        0x06 => "CTAP1_ERR_CHANNEL_BUSY",
+        0x0A => "CTAP1_ERR_LOCK_REQUIRED",
+        0x0B => "CTAP1_ERR_INVALID_CHANNEL",
+        0x11 => "CTAP2_ERR_CBOR_UNEXPECTED_TYPE",
+        0x12 => "CTAP2_ERR_INVALID_CBOR",
+        0x14 => "CTAP2_ERR_MISSING_PARAMETER",
+        0x15 => "CTAP2_ERR_LIMIT_EXCEEDED",
+        0x16 => "CTAP2_ERR_UNSUPPORTED_EXTENSION",
+        0x17 => "CTAP2_ERR_FP_DATABASE_FULL",
+        0x18 => "CTAP2_ERR_LARGE_BLOB_STORAGE_FULL",
+        0x19 => "CTAP2_ERR_CREDENTIAL_EXCLUDED",
+        0x21 => "CTAP2_ERR_PROCESSING",
+        0x22 => "CTAP2_ERR_INVALID_CREDENTIAL",
+        0x23 => "CTAP2_ERR_USER_ACTION_PENDING",
+        0x24 => "CTAP2_ERR_OPERATION_PENDING",
+        0x25 => "CTAP2_ERR_NO_OPERATIONS",
+        0x26 => "CTAP2_ERR_UNSUPPORTED_ALGORITHM",
+        0x27 => "CTAP2_ERR_OPERATION_DENIED",
+        0x28 => "CTAP2_ERR_KEY_STORE_FULL",
+        0x2A => "CTAP2_ERR_NO_OPERATION_PENDING",
+        0x2B => "CTAP2_ERR_UNSUPPORTED_OPTION",
+        0x2C => "CTAP2_ERR_INVALID_OPTION",
+        0x2D => "CTAP2_ERR_KEEPALIVE_CANCEL",
+        0x2E => "CTAP2_ERR_NO_CREDENTIALS",
+        0x2F => "CTAP2_ERR_USER_ACTION_TIMEOUT",
+        0x30 => "CTAP2_ERR_NOT_ALLOWED",
+        0x31 => "CTAP2_ERR_PIN_INVALID",
+        0x32 => "CTAP2_ERR_PIN_BLOCKED",
+        0x33 => "CTAP2_ERR_PIN_AUTH_INVALID",
+        0x34 => "CTAP2_ERR_PIN_AUTH_BLOCKED",
+        0x35 => "CTAP2_ERR_PIN_NOT_SET",
+        0x36 => "CTAP2_ERR_PUAT_REQUIRED",
+        0x37 => "CTAP2_ERR_PIN_POLICY_VIOLATION",
+        0x38 => "CTAP2_ERR_PIN_TOKEN_EXPIRED",
+        0x39 => "CTAP2_ERR_REQUEST_TOO_LARGE",
+        0x3A => "CTAP2_ERR_ACTION_TIMEOUT",
+        0x3B => "CTAP2_ERR_UP_REQUIRED",
+        0x3C => "CTAP2_ERR_UV_BLOCKED",
+        0x3D => "CTAP2_ERR_INTEGRITY_FAILURE",
+        0x3E => "CTAP2_ERR_INVALID_SUBCOMMAND",
+        0x3F => "CTAP2_ERR_UV_INVALID",
+        0x40 => "CTAP2_ERR_UNAUTHORIZED_PERMISSION",
+        0x7F => "CTAP1_ERR_OTHER",
+        0xE0..=0xEF => "CTAP2_ERR_EXTENSION",
+        0xF0..=0xFF => "CTAP2_ERR_VENDOR",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{soft::SoftAuthenticator, Fido2Client};
+    use std::sync::{Arc, Mutex};
+    use ykey_core::traits::Fido2Protocol;
+
+    /// Owned copy of a traced message
+    #[derive(Debug, Clone, PartialEq)]
+    struct RecordedEvent {
+        direction: TraceDirection,
+        name: String,
+        data: Vec<u8>,
+    }
+
+    /// Tracer keeping every message it sees
+    #[derive(Clone, Default)]
+    struct RecordingTracer {
+        events: Arc<Mutex<Vec<RecordedEvent>>>,
+    }
+
+    impl Tracer for RecordingTracer {
+        fn trace(&self, event: &TraceEvent<'_>) {
+            self.events.lock().unwrap().push(RecordedEvent {
+                direction: event.direction,
+                name: event.name(),
+                data: event.data.to_vec(),
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tracer_sees_both_directions() {
+        let tracer = RecordingTracer::default();
+        let mut client = Fido2Client::new(SoftAuthenticator::blank());
+        client.set_tracer(Some(Box::new(tracer.clone())));
+        client.get_info().await.unwrap();
+
+        let events = tracer.events.lock().unwrap().clone();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0],
+            RecordedEvent {
+                direction: TraceDirection::Command,
+                name: "authenticatorGetInfo".to_string(),
+                data: vec![0x04],
+            }
+        );
+        assert_eq!(events[1].direction, TraceDirection::Response);
+        assert_eq!(events[1].name, "CTAP2_OK");
+        assert!(events[1].data.len() > 1);
+
+        // Detached tracers see nothing more
+        client.set_tracer(None);
+        client.get_info().await.unwrap();
+        assert_eq!(tracer.events.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_event_names() {
+        let timestamp = Utc::now();
+        let command = TraceEvent { direction: TraceDirection::Command, data: &[0x06, 0xA1], timestamp };
+        assert_eq!(command.name(), "authenticatorClientPIN");
+
+        let wrong_pin = TraceEvent { direction: TraceDirection::Response, data: &[0x31, 0xA1], timestamp };
+        assert_eq!(wrong_pin.name(), "CTAP2_ERR_PIN_INVALID");
+        HexTracer.trace(&wrong_pin);
+
+        let integrity = TraceEvent { direction: TraceDirection::Response, data: &[0x3D], timestamp };
+        assert_eq!(integrity.name(), "CTAP2_ERR_INTEGRITY_FAILURE");
+        let reserved = TraceEvent { direction: TraceDirection::Response, data: &[0x50], timestamp };
+        assert_eq!(reserved.name(), "unknown status 0x50");
+    }
+}
@@ -0,0 +1,463 @@
+// Copyright 2025 AprilNEA LLC
+// SPDX-License-Identifier: MIT
+
+//! CTAP1/U2F support
+//!
+//! [`U2fClient`] speaks the U2F register, authenticate and version APDUs for
+//! legacy relying parties and keys without CTAP2.
+//!
+//! Only authenticators without CTAP2 are driven over U2F. Many FIDO2 keys
+//! also expose a U2F interface that is disabled or broken, so a key listing
+//! any `FIDO_2_x` version is never sent U2F messages, and a U2F failure while
+//! probing sends the client back to CTAP2 rather than declaring the device
+//! broken.
+
+use crate::{
+    apdu::{CommandApdu, ResponseApdu, SW_SUCCESS},
+    Fido2Client,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use ykey_core::{traits::*, YKeyError, YKeyResult};
+
+/// Version string of U2F authenticators
+pub const U2F_VERSION: &str = "U2F_V2";
+
+/// U2F VERSION request in extended-length encoding
+const VERSION_APDU: [u8; 7] = [0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+/// ISO 7816 status word for success
+const SW_NO_ERROR: [u8; 2] = [0x90, 0x00];
+
+/// CTAP1_ERR_INVALID_COMMAND, returned by authenticators without CTAP2
+const CTAP1_ERR_INVALID_COMMAND: u8 = 0x01;
+
+const INS_REGISTER: u8 = 0x01;
+const INS_AUTHENTICATE: u8 = 0x02;
+const INS_VERSION: u8 = 0x03;
+
+/// Authenticate control byte requiring a touch and signing
+const P1_ENFORCE_USER_PRESENCE: u8 = 0x03;
+/// Authenticate control byte only checking the key handle belongs to the key
+const P1_CHECK_ONLY: u8 = 0x07;
+
+/// Test of user presence required: the key is waiting for a touch
+const SW_CONDITIONS_NOT_SATISFIED: u16 = 0x6985;
+/// Bad key handle or a key handle for another application
+const SW_WRONG_DATA: u16 = 0x6A80;
+
+/// First byte of a registration response
+const REGISTER_RESERVED: u8 = 0x05;
+/// Length of an uncompressed P-256 public key
+const PUBLIC_KEY_LENGTH: usize = 65;
+
+/// Delay between touch polls unless configured
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// How long to wait for a touch unless configured
+pub const DEFAULT_TOUCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Result of a U2F registration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct U2fRegistration {
+    /// Uncompressed P-256 public key of the new credential
+    pub public_key: Vec<u8>,
+    /// Opaque handle to pass back when authenticating
+    pub key_handle: Vec<u8>,
+    /// DER encoded attestation certificate
+    pub attestation_certificate: Vec<u8>,
+    /// ECDSA signature over the registration data
+    pub signature: Vec<u8>,
+}
+
+/// Result of a U2F authentication
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct U2fSignature {
+    /// Whether the user touched the key
+    pub user_presence: bool,
+    /// Signature counter
+    pub counter: u32,
+    /// ECDSA signature over the authentication data
+    pub signature: Vec<u8>,
+}
+
+/// Outcome of a U2F command the key did not reject
+#[derive(Debug)]
+enum U2fStatus {
+    /// The command completed with this response data
+    Complete(Vec<u8>),
+    /// Test of user presence required: the key is waiting for a touch
+    UserPresenceRequired,
+}
+
+/// CTAP1/U2F client
+///
+/// Register and authenticate wait for a touch by repeating the command while
+/// the key answers "test of user presence required", as U2F keys do not
+/// block on user presence themselves.
+pub struct U2fClient<D: Device> {
+    device: D,
+    poll_interval: Duration,
+    timeout: Duration,
+}
+
+impl<D: Device> U2fClient<D> {
+    /// Create a new U2F client with the given device
+    pub fn new(device: D) -> Self {
+        Self { device, poll_interval: DEFAULT_POLL_INTERVAL, timeout: DEFAULT_TOUCH_TIMEOUT }
+    }
+
+    /// Poll for a touch every `poll_interval`
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Give up waiting for a touch after `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Get underlying device reference
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// Protocol version reported by the key, `U2F_V2` for every U2F key
+    pub async fn version(&mut self) -> YKeyResult<String> {
+        let U2fStatus::Complete(response) = self.transmit(&CommandApdu::new(0x00, INS_VERSION, 0x00, 0x00)).await? else {
+            return Err(YKeyError::UnexpectedResponse);
+        };
+        String::from_utf8(response).map_err(|_| YKeyError::communication("U2F version is not UTF-8"))
+    }
+
+    /// Register a new credential, waiting for a touch
+    ///
+    /// `challenge` and `application` are the SHA-256 hashes of the client
+    /// data and the application ID.
+    pub async fn register(&mut self, challenge: &[u8; 32], application: &[u8; 32]) -> YKeyResult<U2fRegistration> {
+        let command = CommandApdu::new(0x00, INS_REGISTER, P1_ENFORCE_USER_PRESENCE, 0x00)
+            .with_data([challenge.as_slice(), application].concat());
+        parse_registration(&self.transmit_with_touch(&command).await?)
+    }
+
+    /// Sign a challenge with an existing credential, waiting for a touch
+    ///
+    /// A key handle that does not belong to this key for `application` fails
+    /// with `InvalidCredential`.
+    pub async fn authenticate(
+        &mut self,
+        challenge: &[u8; 32],
+        application: &[u8; 32],
+        key_handle: &[u8],
+    ) -> YKeyResult<U2fSignature> {
+        let command = authenticate_command(P1_ENFORCE_USER_PRESENCE, challenge, application, key_handle)?;
+        parse_signature(&self.transmit_with_touch(&command).await?)
+    }
+
+    /// Whether `key_handle` was issued by this key for `application`
+    ///
+    /// Needs no touch, which makes it suitable for picking the right key
+    /// before asking the user for one.
+    pub async fn check_key_handle(&mut self, application: &[u8; 32], key_handle: &[u8]) -> YKeyResult<bool> {
+        let command = authenticate_command(P1_CHECK_ONLY, &[0; 32], application, key_handle)?;
+        // A known handle is reported as "user presence required"
+        match self.transmit(&command).await {
+            Ok(U2fStatus::UserPresenceRequired) => Ok(true),
+            Err(YKeyError::InvalidCredential(_)) => Ok(false),
+            Ok(U2fStatus::Complete(_)) => Err(YKeyError::UnexpectedResponse),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Repeat a command until the user touches the key or the timeout passes
+    async fn transmit_with_touch(&mut self, command: &CommandApdu) -> YKeyResult<Vec<u8>> {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            match self.transmit(command).await? {
+                U2fStatus::Complete(response) => return Ok(response),
+                U2fStatus::UserPresenceRequired if tokio::time::Instant::now() < deadline => {
+                    tokio::time::sleep(self.poll_interval).await;
+                }
+                U2fStatus::UserPresenceRequired => return Err(YKeyError::timeout(self.timeout.as_secs())),
+            }
+        }
+    }
+
+    /// Send an APDU and map its status word
+    async fn transmit(&mut self, command: &CommandApdu) -> YKeyResult<U2fStatus> {
+        let response = ResponseApdu::parse(self.device.send_u2f(&command.clone().with_le(65536).encode_extended()?).await?)?;
+        match response.status() {
+            SW_SUCCESS => Ok(U2fStatus::Complete(response.data)),
+            SW_CONDITIONS_NOT_SATISFIED => Ok(U2fStatus::UserPresenceRequired),
+            SW_WRONG_DATA => Err(YKeyError::InvalidCredential("Key handle not recognized".to_string())),
+            status => Err(YKeyError::communication(format!("U2F command failed with status {:04X}", status))),
+        }
+    }
+}
+
+/// Protocol to speak to an authenticator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthenticatorProtocol {
+    /// CTAP2 over CBOR messages
+    Ctap2,
+    /// CTAP1/U2F over raw APDUs
+    U2f,
+}
+
+impl<D: Device> Fido2Client<D> {
+    /// Work out whether the authenticator speaks CTAP2 or only U2F
+    ///
+    /// GetInfo decides for any authenticator that answers it. Only when it
+    /// fails as an unknown command is the U2F interface probed, and if that
+    /// fails too GetInfo is tried once more before giving up.
+    pub async fn negotiate_protocol(&mut self) -> YKeyResult<AuthenticatorProtocol> {
+        let error = match self.authenticator_info().await {
+            Ok(info) => return protocol_for_versions(&info.versions),
+            Err(error) => error,
+        };
+        if !matches!(error, YKeyError::CtapError { code: CTAP1_ERR_INVALID_COMMAND, .. }) {
+            return Err(error);
+        }
+
+        match self.u2f_version().await {
+            Ok(_) => Ok(AuthenticatorProtocol::U2f),
+            Err(_) => {
+                let info = self.get_info().await?;
+                protocol_for_versions(&info.versions)
+            }
+        }
+    }
+
+    /// Send a U2F APDU to an authenticator that only speaks U2F
+    ///
+    /// FIDO2 authenticators are refused even when they advertise U2F, since
+    /// their U2F interface may be disabled; use the CTAP2 commands instead.
+    pub async fn send_u2f(&mut self, apdu: &[u8]) -> YKeyResult<Vec<u8>> {
+        if self.negotiate_protocol().await? == AuthenticatorProtocol::Ctap2 {
+            return Err(YKeyError::unsupported("U2F", "authenticator speaks CTAP2"));
+        }
+        self.device.send_u2f(apdu).await
+    }
+
+    /// Ask the U2F interface for its protocol version
+    pub async fn u2f_version(&mut self) -> YKeyResult<String> {
+        let response = self.device.send_u2f(&VERSION_APDU).await?;
+        match response.split_last_chunk::<2>() {
+            Some((version, &SW_NO_ERROR)) => String::from_utf8(version.to_vec())
+                .map_err(|_| YKeyError::communication("U2F version is not UTF-8")),
+            Some((_, [sw1, sw2])) => Err(YKeyError::communication(format!(
+                "U2F VERSION failed with status {:02x}{:02x}",
+                sw1, sw2
+            ))),
+            None => Err(YKeyError::communication("Truncated U2F response")),
+        }
+    }
+}
+
+fn authenticate_command(
+    control: u8,
+    challenge: &[u8; 32],
+    application: &[u8; 32],
+    key_handle: &[u8],
+) -> YKeyResult<CommandApdu> {
+    let length = u8::try_from(key_handle.len())
+        .map_err(|_| YKeyError::InvalidParameters("U2F key handle longer than 255 bytes".to_string()))?;
+    let data = [challenge.as_slice(), application, &[length], key_handle].concat();
+    Ok(CommandApdu::new(0x00, INS_AUTHENTICATE, control, 0x00).with_data(data))
+}
+
+/// Split a registration response into its fields
+fn parse_registration(response: &[u8]) -> YKeyResult<U2fRegistration> {
+    let truncated = || YKeyError::communication("Truncated U2F registration response");
+    let (&reserved, rest) = response.split_first().ok_or_else(truncated)?;
+    if reserved != REGISTER_RESERVED {
+        return Err(YKeyError::communication(format!("Unexpected U2F registration marker {:#04x}", reserved)));
+    }
+    let (public_key, rest) = rest.split_at_checked(PUBLIC_KEY_LENGTH).ok_or_else(truncated)?;
+    let (&handle_length, rest) = rest.split_first().ok_or_else(truncated)?;
+    let (key_handle, rest) = rest.split_at_checked(handle_length as usize).ok_or_else(truncated)?;
+    let (certificate, signature) = rest.split_at_checked(der_length(rest)?).ok_or_else(truncated)?;
+    if signature.is_empty() {
+        return Err(truncated());
+    }
+
+    Ok(U2fRegistration {
+        public_key: public_key.to_vec(),
+        key_handle: key_handle.to_vec(),
+        attestation_certificate: certificate.to_vec(),
+        signature: signature.to_vec(),
+    })
+}
+
+/// Split an authentication response into its fields
+fn parse_signature(response: &[u8]) -> YKeyResult<U2fSignature> {
+    match response {
+        [flags, c0, c1, c2, c3, signature @ ..] if !signature.is_empty() => Ok(U2fSignature {
+            user_presence: flags & 0x01 != 0,
+            counter: u32::from_be_bytes([*c0, *c1, *c2, *c3]),
+            signature: signature.to_vec(),
+        }),
+        _ => Err(YKeyError::communication("Truncated U2F authentication response")),
+    }
+}
+
+/// Total length of the DER element at the start of `data`, header included
+fn der_length(data: &[u8]) -> YKeyResult<usize> {
+    let invalid = || YKeyError::communication("Invalid DER attestation certificate");
+    match data {
+        [_, length, ..] if *length < 0x80 => Ok(2 + *length as usize),
+        [_, 0x81, length, ..] => Ok(3 + *length as usize),
+        [_, 0x82, high, low, ..] => Ok(4 + u16::from_be_bytes([*high, *low]) as usize),
+        _ => Err(invalid()),
+    }
+}
+
+/// Protocol implied by the versions listed in GetInfo
+fn protocol_for_versions(versions: &[String]) -> YKeyResult<AuthenticatorProtocol> {
+    if versions.iter().any(|v| v.starts_with("FIDO_2")) {
+        Ok(AuthenticatorProtocol::Ctap2)
+    } else if versions.iter().any(|v| v == U2F_VERSION) {
+        Ok(AuthenticatorProtocol::U2f)
+    } else {
+        Err(YKeyError::UnsupportedProtocolVersion(versions.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cbor, test_support::ReplayDevice};
+
+    fn u2f_only(u2f: Vec<Vec<u8>>) -> ReplayDevice {
+        ReplayDevice::new(Vec::new()).with_u2f(u2f.into_iter().map(Ok).collect())
+    }
+
+    fn get_info(versions: &[&str]) -> Vec<u8> {
+        let info = cbor::int_map(vec![
+            (0x01, Some(cbor::Value::Array(versions.iter().map(|v| cbor::text(v)).collect()))),
+            (0x03, Some(cbor::bytes(&[0; 16]))),
+        ]);
+        [vec![0x00], cbor::encode(&info).unwrap()].concat()
+    }
+
+    fn broken_u2f() -> YKeyResult<Vec<u8>> {
+        Err(YKeyError::communication("U2F interface disabled"))
+    }
+
+    #[tokio::test]
+    async fn test_fido2_device_never_falls_back_to_u2f() {
+        let device = ReplayDevice::new(vec![get_info(&["U2F_V2", "FIDO_2_0"])]).with_u2f(vec![broken_u2f()]);
+        let mut client = Fido2Client::new(device);
+
+        assert_eq!(client.negotiate_protocol().await.unwrap(), AuthenticatorProtocol::Ctap2);
+        assert!(client.send_u2f(&VERSION_APDU).await.unwrap_err().is_unsupported());
+        assert_eq!(client.device().u2f_requests.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_broken_u2f_probe_retries_ctap2() {
+        // GetInfo fails once, the U2F probe errors, and the retried GetInfo works
+        let device = ReplayDevice::new(vec![vec![CTAP1_ERR_INVALID_COMMAND], get_info(&["FIDO_2_1"])]).with_u2f(vec![broken_u2f()]);
+        let mut client = Fido2Client::new(device);
+
+        assert_eq!(client.negotiate_protocol().await.unwrap(), AuthenticatorProtocol::Ctap2);
+        assert_eq!(client.device().u2f_requests.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_u2f_only_device_uses_u2f() {
+        let version = [U2F_VERSION.as_bytes(), &SW_NO_ERROR].concat();
+        let device = ReplayDevice::new(vec![vec![CTAP1_ERR_INVALID_COMMAND], vec![CTAP1_ERR_INVALID_COMMAND]])
+            .with_u2f(vec![Ok(version.clone()), Ok(version.clone()), Ok(version.clone())]);
+        let mut client = Fido2Client::new(device);
+
+        assert_eq!(client.negotiate_protocol().await.unwrap(), AuthenticatorProtocol::U2f);
+        assert_eq!(client.send_u2f(&VERSION_APDU).await.unwrap(), version);
+        assert!(matches!(
+            protocol_for_versions(&["FIDO_3".to_string()]),
+            Err(YKeyError::UnsupportedProtocolVersion(_))
+        ));
+    }
+
+    fn touch_required() -> Vec<u8> {
+        vec![0x69, 0x85]
+    }
+
+    /// Registration response: marker, public key, key handle, certificate and signature
+    fn registration_response() -> Vec<u8> {
+        let mut certificate = vec![0x30, 0x81, 0x90];
+        certificate.extend([0xCE; 0x90]);
+        [
+            vec![REGISTER_RESERVED, 0x04],
+            vec![0x11; 64],
+            vec![0x40],
+            vec![0x22; 64],
+            certificate,
+            vec![0x30, 0x44],
+            vec![0x33; 0x44],
+            SW_NO_ERROR.to_vec(),
+        ]
+        .concat()
+    }
+
+    #[tokio::test]
+    async fn test_register_waits_for_touch() {
+        let device = u2f_only(vec![touch_required(), touch_required(), registration_response()]);
+        let mut client = U2fClient::new(device).with_poll_interval(Duration::from_millis(1));
+
+        let registration = client.register(&[0xAA; 32], &[0xBB; 32]).await.unwrap();
+        assert_eq!(registration.public_key, [vec![0x04], vec![0x11; 64]].concat());
+        assert_eq!(registration.key_handle, vec![0x22; 64]);
+        assert_eq!(registration.attestation_certificate.len(), 3 + 0x90);
+        assert_eq!(registration.signature, [vec![0x30, 0x44], vec![0x33; 0x44]].concat());
+
+        // Every attempt repeats the same extended-length request
+        let requests = &client.device().u2f_requests;
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|r| r == &requests[0]));
+        assert_eq!(requests[0][..7], [0x00, INS_REGISTER, P1_ENFORCE_USER_PRESENCE, 0x00, 0x00, 0x00, 0x40]);
+        assert_eq!(requests[0][7..71], [[0xAA; 32], [0xBB; 32]].concat());
+        assert_eq!(requests[0][71..], [0x00, 0x00]);
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_parses_signature() {
+        let response = [vec![0x01, 0x00, 0x00, 0x01, 0x2C, 0x30, 0x45], vec![0x44; 0x45], SW_NO_ERROR.to_vec()].concat();
+        let device = u2f_only(vec![touch_required(), response, vec![0x6A, 0x80]]);
+        let mut client = U2fClient::new(device).with_poll_interval(Duration::from_millis(1));
+
+        let signature = client.authenticate(&[0xAA; 32], &[0xBB; 32], &[0x22; 64]).await.unwrap();
+        assert!(signature.user_presence);
+        assert_eq!(signature.counter, 300);
+        assert_eq!(signature.signature.len(), 2 + 0x45);
+
+        let request = &client.device().u2f_requests[0];
+        assert_eq!(request[..4], [0x00, INS_AUTHENTICATE, P1_ENFORCE_USER_PRESENCE, 0x00]);
+        assert_eq!(request[7 + 64], 64);
+        assert_eq!(request[7 + 65..7 + 129], [0x22; 64]);
+
+        let unknown = client.authenticate(&[0xAA; 32], &[0xBB; 32], &[0x99; 64]).await;
+        assert!(matches!(unknown, Err(YKeyError::InvalidCredential(_))));
+    }
+
+    #[tokio::test]
+    async fn test_touch_timeout_and_key_handle_check() {
+        let device = u2f_only(vec![touch_required(); 8]);
+        let mut client = U2fClient::new(device)
+            .with_poll_interval(Duration::from_millis(1))
+            .with_timeout(Duration::ZERO);
+        let result = client.authenticate(&[0xAA; 32], &[0xBB; 32], &[0x22; 64]).await;
+        assert!(matches!(result, Err(YKeyError::Timeout { .. })));
+
+        // Check-only answers "touch required" for its own key handles
+        assert!(client.check_key_handle(&[0xBB; 32], &[0x22; 64]).await.unwrap());
+        assert_eq!(client.device().u2f_requests.last().unwrap()[2], P1_CHECK_ONLY);
+
+        let device = u2f_only(vec![vec![0x6A, 0x80], [U2F_VERSION.as_bytes(), &SW_NO_ERROR].concat()]);
+        let mut client = U2fClient::new(device);
+        assert!(!client.check_key_handle(&[0xBB; 32], &[0x22; 64]).await.unwrap());
+        assert_eq!(client.version().await.unwrap(), U2F_VERSION);
+        assert_eq!(client.device().u2f_requests[1], VERSION_APDU);
+    }
+}
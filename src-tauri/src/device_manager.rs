@@ -1,9 +1,6 @@
-use ykey_device::DeviceManager;
-use ykey_core::{DeviceInfo, DeviceType, TransportType, Capability, YKeyResult, DeviceEventStream};
-use async_trait::async_trait;
-use std::process::Command;
-use serde_json::Value;
-use tokio::sync::mpsc;
+use ykey_device::{DeviceManager, DeviceSnapshot};
+use ykey_core::{Capability, DeviceEvent, DeviceId, DeviceInfo};
+use tokio::sync::{broadcast, mpsc};
 use serde::{Deserialize, Serialize};
 
 /// Device information for frontend
@@ -24,161 +21,127 @@ pub struct FrontendDeviceInfo {
 impl From<DeviceInfo> for FrontendDeviceInfo {
     fn from(info: DeviceInfo) -> Self {
         Self {
-            id: info.id,
+            id: info.id.into(),
             name: info.name,
             manufacturer: info.manufacturer,
             product_name: info.product_name,
-            device_type: format!("{:?}", info.device_type),
-            transport: format!("{:?}", info.transport),
+            device_type: info.device_type.to_string(),
+            transport: info.transport.to_string(),
             vendor_id: info.vendor_id,
             product_id: info.product_id,
-            capabilities: info.capabilities.iter().map(|c| format!("{:?}", c)).collect(),
+            capabilities: info.capabilities.iter().map(Capability::to_string).collect(),
             is_connected: false,
         }
     }
 }
 
-/// macOS-specific USB device discovery using system_profiler
-pub struct MacOSUsbDiscovery;
-
-impl MacOSUsbDiscovery {
-    pub fn new() -> Self {
-        Self
+impl From<DeviceSnapshot> for FrontendDeviceInfo {
+    fn from(snapshot: DeviceSnapshot) -> Self {
+        Self {
+            is_connected: snapshot.connected,
+            ..Self::from(snapshot.info)
+        }
     }
+}
 
-    async fn scan_usb_devices(&self) -> YKeyResult<Vec<DeviceInfo>> {
-        let output = Command::new("system_profiler")
-            .args(&["SPUSBDataType", "-json"])
-            .output()
-            .map_err(|e| ykey_core::YKeyError::communication(&format!("Failed to run system_profiler: {}", e)))?;
-
-        let json: Value = serde_json::from_slice(&output.stdout)
-            .map_err(|e| ykey_core::YKeyError::communication(&format!("Failed to parse system_profiler output: {}", e)))?;
+/// Tauri Device Manager wrapper
+pub struct TauriDeviceManager {
+    manager: DeviceManager,
+    changes: mpsc::Sender<()>,
+    pending_changes: Option<mpsc::Receiver<()>>,
+    events: broadcast::Sender<DeviceEvent>,
+    watching: bool,
+}
 
-        let mut devices = Vec::new();
-        
-        if let Some(usb_data) = json.get("SPUSBDataType") {
-            Self::parse_usb_data(usb_data, &mut devices);
+impl TauriDeviceManager {
+    pub fn new() -> Self {
+        let mut manager = DeviceManager::new();
+        manager.add_discovery(ykey_platform::create_platform_discovery());
+        let (changes, pending_changes) = mpsc::channel(32);
+        let (events, _) = broadcast::channel(32);
+        Self { manager, changes, pending_changes: Some(pending_changes), events, watching: false }
+    }
+
+    /// Start the single hotplug watch shared by every subscriber
+    /// 
+    /// Platform discoveries only support one watch at a time, so their
+    /// events are fanned out through a broadcast channel instead of
+    /// watching again for each consumer.
+    async fn ensure_watching(&mut self) -> Result<(), String> {
+        if self.watching {
+            return Ok(());
         }
 
-        Ok(devices)
-    }
-
-    fn parse_usb_data(data: &Value, devices: &mut Vec<DeviceInfo>) {
-        if let Some(array) = data.as_array() {
-            for entry in array {
-                Self::parse_usb_item(entry, devices);
+        let mut events = self.manager.watch_devices().await
+            .map_err(|e| format!("Failed to watch devices: {}", e))?;
+        let tx = self.events.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                // No subscribers is fine, the event is simply dropped
+                let _ = tx.send(event);
             }
-        } else if let Some(_obj) = data.as_object() {
-            Self::parse_usb_item(data, devices);
-        }
+        });
+        self.watching = true;
+        Ok(())
     }
 
-    fn parse_usb_item(item: &Value, devices: &mut Vec<DeviceInfo>) {
-        if let Some(product_id) = item.get("product_id") {
-            if let Some(vendor_id) = item.get("vendor_id") {
-                if let (Some(pid), Some(vid)) = (product_id.as_str(), vendor_id.as_str()) {
-                    let pid = pid.trim_start_matches("0x");
-                    let vid = vid.trim_start_matches("0x");
-                    if let (Ok(product_id), Ok(vendor_id)) = (u16::from_str_radix(pid, 16), u16::from_str_radix(vid, 16)) {
-                        if let Some(device_type) = Self::identify_device_type(vendor_id, product_id) {
-                            let name = item.get("_name")
-                                .and_then(|n| n.as_str())
-                                .unwrap_or("Unknown Device")
-                                .to_string();
-                            let manufacturer = item.get("manufacturer")
-                                .and_then(|m| m.as_str())
-                                .unwrap_or("Unknown")
-                                .to_string();
-                            let product = item.get("_name")
-                                .and_then(|n| n.as_str())
-                                .unwrap_or("Unknown")
-                                .to_string();
-                            let device_id = format!("{}-{:04x}-{:04x}", format!("{:?}", device_type).to_lowercase(), vendor_id, product_id);
-                            let mut info = DeviceInfo::new(
-                                device_id,
-                                name.clone(),
-                                manufacturer,
-                                product,
-                                vendor_id,
-                                product_id,
-                                device_type,
-                                TransportType::Usb,
-                            );
-                            info.add_capability(Capability::Fido2);
-                            if matches!(device_type, DeviceType::YubiKey) {
-                                info.add_capability(Capability::Fido1);
-                                info.add_capability(Capability::Oath);
-                                info.add_capability(Capability::Piv);
-                                info.add_capability(Capability::Otp);
-                            }
-                            devices.push(info);
+    /// Take the stream of device set and connection changes
+    /// 
+    /// Combines discovery events with connects and disconnects made through
+    /// this manager. Can only be taken once.
+    pub async fn take_changes(&mut self) -> Result<mpsc::Receiver<()>, String> {
+        let changes = self.pending_changes.take()
+            .ok_or_else(|| "Device changes are already being watched".to_string())?;
+        self.ensure_watching().await?;
+
+        let mut events = self.events.subscribe();
+        let tx = self.changes.clone();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    // Missed events still mean the device set changed
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                        if tx.send(()).await.is_err() {
+                            break;
                         }
                     }
+                    Err(broadcast::error::RecvError::Closed) => break,
                 }
             }
-        }
-        
-        if let Some(children) = item.get("_items") {
-            if let Some(children_array) = children.as_array() {
-                for child in children_array {
-                    Self::parse_usb_item(child, devices);
-                }
-            }
-        }
-    }
-
-    fn identify_device_type(vendor_id: u16, product_id: u16) -> Option<DeviceType> {
-        match (vendor_id, product_id) {
-            // YubiKey devices
-            (0x1050, 0x0010) | (0x1050, 0x0018) | (0x1050, 0x0030) | 
-            (0x1050, 0x0407) | (0x1050, 0x0410) => Some(DeviceType::YubiKey),
-            
-            // CanoKey devices
-            (0x20A0, 0x42D4) => Some(DeviceType::CanoKey),
-            
-            // Nitrokey devices
-            (0x20A0, 0x42B1) | (0x20A0, 0x42B2) => Some(DeviceType::Nitrokey),
-            
-            // SoloKeys devices
-            (0x0483, 0xA2CA) | (0x1209, 0x5070) => Some(DeviceType::SoloKey),
-            
-            _ => None,
-        }
-    }
-}
-
-#[async_trait]
-impl ykey_core::traits::DeviceDiscovery for MacOSUsbDiscovery {
-    async fn scan(&self) -> YKeyResult<Vec<DeviceInfo>> {
-        self.scan_usb_devices().await
-    }
-
-    async fn watch(&self) -> YKeyResult<DeviceEventStream> {
-        let (_tx, rx) = mpsc::channel(10);
-        Ok(rx)
-    }
-
-    async fn stop_watch(&self) -> YKeyResult<()> {
+        });
+        Ok(changes)
+    }
+
+    /// Subscribe to hotplug events from every discovery
+    /// 
+    /// Starts the shared watch if it is not running yet.
+    pub async fn watch_events(&mut self) -> Result<broadcast::Receiver<DeviceEvent>, String> {
+        self.ensure_watching().await?;
+        Ok(self.events.subscribe())
+    }
+
+    /// Stop the shared hotplug watch
+    /// 
+    /// Device changes stop reflecting hotplug events until the watch is
+    /// started again by `watch_events`.
+    pub async fn stop_watching(&mut self) -> Result<(), String> {
+        self.manager.stop_watching().await
+            .map_err(|e| format!("Failed to stop watching devices: {}", e))?;
+        self.watching = false;
         Ok(())
     }
 
-    async fn is_device_available(&self, device_id: &str) -> YKeyResult<bool> {
-        let devices = self.scan_usb_devices().await?;
-        Ok(devices.iter().any(|d| d.id == device_id))
-    }
-}
+    /// Full device list with connection status
+    pub async fn snapshot(&self) -> Result<Vec<FrontendDeviceInfo>, String> {
+        let snapshot = self.manager.snapshot().await
+            .map_err(|e| format!("Failed to snapshot devices: {}", e))?;
 
-/// Tauri Device Manager wrapper
-pub struct TauriDeviceManager {
-    manager: DeviceManager,
-}
+        Ok(snapshot.into_iter().map(FrontendDeviceInfo::from).collect())
+    }
 
-impl TauriDeviceManager {
-    pub fn new() -> Self {
-        let mut manager = DeviceManager::new();
-        manager.add_discovery(Box::new(MacOSUsbDiscovery::new()));
-        Self { manager }
+    fn notify_changed(&self) {
+        // A full queue already carries a pending change
+        let _ = self.changes.try_send(());
     }
 
     pub async fn scan_devices(&mut self) -> Result<Vec<FrontendDeviceInfo>, String> {
@@ -188,31 +151,32 @@ impl TauriDeviceManager {
         Ok(devices.into_iter().map(FrontendDeviceInfo::from).collect())
     }
 
-    pub async fn connect_device(&mut self, device_id: &str) -> Result<(), String> {
+    pub async fn connect_device(&mut self, device_id: &DeviceId) -> Result<(), String> {
         self.manager.connect_device(device_id).await
-            .map_err(|e| format!("Failed to connect device {}: {}", device_id, e))
+            .map_err(|e| format!("Failed to connect device {}: {}", device_id, e))?;
+        self.notify_changed();
+        Ok(())
     }
 
-    pub async fn disconnect_device(&mut self, device_id: &str) -> Result<(), String> {
+    pub async fn disconnect_device(&mut self, device_id: &DeviceId) -> Result<(), String> {
         self.manager.disconnect_device(device_id).await
-            .map_err(|e| format!("Failed to disconnect device {}: {}", device_id, e))
+            .map_err(|e| format!("Failed to disconnect device {}: {}", device_id, e))?;
+        self.notify_changed();
+        Ok(())
     }
 
-    pub async fn get_device_info(&mut self, device_id: &str) -> Result<FrontendDeviceInfo, String> {
-        let result = self.manager.with_device(device_id, |device| {
-            Box::pin(async move {
-                let info = device.info().await?;
-                Ok(info)
-            })
-        }).await;
+    /// Metadata of any discovered device, whether or not it is connected
+    pub async fn get_device_info(&mut self, device_id: &DeviceId) -> Result<FrontendDeviceInfo, String> {
+        let info = self.manager.device_info(device_id).await
+            .map_err(|e| format!("Failed to get device info for {}: {}", device_id, e))?;
 
-        match result {
-            Ok(info) => Ok(FrontendDeviceInfo::from(info)),
-            Err(e) => Err(format!("Failed to get device info for {}: {}", device_id, e))
-        }
+        Ok(FrontendDeviceInfo {
+            is_connected: self.manager.is_device_connected(device_id).await,
+            ..FrontendDeviceInfo::from(info)
+        })
     }
 
-    pub async fn send_raw_command(&mut self, device_id: &str, command: Vec<u8>) -> Result<Vec<u8>, String> {
+    pub async fn send_raw_command(&mut self, device_id: &DeviceId, command: Vec<u8>) -> Result<Vec<u8>, String> {
         let result = self.manager.with_device(device_id, |device| {
             Box::pin(async move {
                 let response = device.send_raw(&command).await?;
@@ -227,11 +191,48 @@ impl TauriDeviceManager {
     }
 
     pub async fn get_connected_devices(&self) -> Vec<String> {
-        self.manager.connected_device_ids().await
+        self.manager.connected_device_ids().await.into_iter().map(String::from).collect()
     }
 
     pub async fn disconnect_all(&mut self) -> Result<(), String> {
         self.manager.disconnect_all().await
-            .map_err(|e| format!("Failed to disconnect all devices: {}", e))
+            .map_err(|e| format!("Failed to disconnect all devices: {}", e))?;
+        self.notify_changed();
+        Ok(())
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ykey_core::{DeviceType, TransportType};
+
+    #[test]
+    fn test_device_event_json_for_frontend() {
+        let info = DeviceInfo::new(
+            "yubikey-1050-0407",
+            "YubiKey 5".to_string(),
+            "Yubico".to_string(),
+            "YubiKey 5 NFC".to_string(),
+            0x1050,
+            0x0407,
+            DeviceType::YubiKey,
+            TransportType::Usb,
+        );
+
+        // The frontend tells the variants apart by the `type` field
+        let json = serde_json::to_value(DeviceEvent::Connected(info)).unwrap();
+        assert_eq!(json["type"], "connected");
+        assert_eq!(json["device"]["id"], "yubikey-1050-0407");
+        assert_eq!(json["device"]["vendor_id"], 0x1050);
+
+        let json = serde_json::to_value(DeviceEvent::Disconnected("yubikey-1050-0407".to_string())).unwrap();
+        assert_eq!(json, serde_json::json!({ "type": "disconnected", "device_id": "yubikey-1050-0407" }));
+
+        let error = DeviceEvent::Error { device_id: "key".to_string(), error: "unplugged".to_string() };
+        assert_eq!(
+            serde_json::to_value(error).unwrap(),
+            serde_json::json!({ "type": "error", "device_id": "key", "error": "unplugged" })
+        );
+    }
+}
@@ -1,9 +1,10 @@
 // Copyright 2025 AprilNEA LLC
 // SPDX-License-Identifier: MIT
 
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use tauri::State;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{broadcast, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+use ykey_core::DeviceId;
 
 mod device_manager;
 use device_manager::{TauriDeviceManager, FrontendDeviceInfo};
@@ -11,6 +12,18 @@ use device_manager::{TauriDeviceManager, FrontendDeviceInfo};
 // Global device manager state
 type DeviceManagerState = Arc<Mutex<TauriDeviceManager>>;
 
+/// Task forwarding hotplug events to the frontend, while running
+type DeviceWatchState = Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>;
+
+/// Event carrying a single hotplug event
+const DEVICE_EVENT: &str = "device-event";
+
+/// Event carrying the full device list after any change
+const DEVICE_SNAPSHOT_EVENT: &str = "device-snapshot";
+
+/// Quiet period that coalesces bursts of device changes into one snapshot
+const SNAPSHOT_DEBOUNCE: Duration = Duration::from_millis(250);
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -29,7 +42,7 @@ async fn scan_devices(
 /// Connect to a specific device
 #[tauri::command]
 async fn connect_device(
-    device_id: String,
+    device_id: DeviceId,
     device_manager: State<'_, DeviceManagerState>,
 ) -> Result<(), String> {
     let mut manager = device_manager.lock().await;
@@ -39,7 +52,7 @@ async fn connect_device(
 /// Disconnect from a specific device
 #[tauri::command]
 async fn disconnect_device(
-    device_id: String,
+    device_id: DeviceId,
     device_manager: State<'_, DeviceManagerState>,
 ) -> Result<(), String> {
     let mut manager = device_manager.lock().await;
@@ -49,7 +62,7 @@ async fn disconnect_device(
 /// Get detailed information about a connected device
 #[tauri::command]
 async fn get_device_info(
-    device_id: String,
+    device_id: DeviceId,
     device_manager: State<'_, DeviceManagerState>,
 ) -> Result<FrontendDeviceInfo, String> {
     let mut manager = device_manager.lock().await;
@@ -59,7 +72,7 @@ async fn get_device_info(
 /// Send raw command to device
 #[tauri::command]
 async fn send_raw_command(
-    device_id: String,
+    device_id: DeviceId,
     command: Vec<u8>,
     device_manager: State<'_, DeviceManagerState>,
 ) -> Result<Vec<u8>, String> {
@@ -76,6 +89,15 @@ async fn get_connected_devices(
     Ok(manager.get_connected_devices().await)
 }
 
+/// Get the full device list with connection status
+#[tauri::command]
+async fn get_device_snapshot(
+    device_manager: State<'_, DeviceManagerState>,
+) -> Result<Vec<FrontendDeviceInfo>, String> {
+    let manager = device_manager.lock().await;
+    manager.snapshot().await
+}
+
 /// Disconnect all devices
 #[tauri::command]
 async fn disconnect_all_devices(
@@ -85,11 +107,91 @@ async fn disconnect_all_devices(
     manager.disconnect_all().await
 }
 
+/// Forward hotplug events to the frontend as `device-event`
+/// 
+/// Does nothing if the watch is already running.
+#[tauri::command]
+async fn start_device_watch(
+    app: AppHandle,
+    device_manager: State<'_, DeviceManagerState>,
+    device_watch: State<'_, DeviceWatchState>,
+) -> Result<(), String> {
+    let mut watch = device_watch.lock().await;
+    if watch.is_some() {
+        return Ok(());
+    }
+
+    let mut events = device_manager.lock().await.watch_events().await?;
+    *watch = Some(tauri::async_runtime::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Err(e) = app.emit(DEVICE_EVENT, event) {
+                        tracing::error!("Failed to emit device event: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Device event forwarder fell behind and dropped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }));
+    Ok(())
+}
+
+/// Stop forwarding hotplug events started by `start_device_watch`
+/// 
+/// Also stops the platform hotplug monitor, so snapshots only follow
+/// connects and disconnects until the watch is started again.
+#[tauri::command]
+async fn stop_device_watch(
+    device_manager: State<'_, DeviceManagerState>,
+    device_watch: State<'_, DeviceWatchState>,
+) -> Result<(), String> {
+    let mut watch = device_watch.lock().await;
+    if let Some(task) = watch.take() {
+        task.abort();
+        device_manager.lock().await.stop_watching().await?;
+    }
+    Ok(())
+}
+
+/// Emit a debounced device snapshot whenever the device set changes
+async fn emit_device_snapshots(app: AppHandle, device_manager: DeviceManagerState) {
+    let changes = match device_manager.lock().await.take_changes().await {
+        Ok(changes) => changes,
+        Err(e) => {
+            tracing::warn!("Device snapshots disabled: {}", e);
+            return;
+        }
+    };
+
+    let mut changes = ykey_device::debounce(changes, SNAPSHOT_DEBOUNCE);
+    while changes.recv().await.is_some() {
+        let snapshot = device_manager.lock().await.snapshot().await;
+        match snapshot {
+            Ok(devices) => {
+                if let Err(e) = app.emit(DEVICE_SNAPSHOT_EVENT, devices) {
+                    tracing::error!("Failed to emit device snapshot: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("Device snapshot scan failed: {}", e),
+        }
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .manage(Arc::new(Mutex::new(TauriDeviceManager::new())))
+        .manage(DeviceWatchState::default())
+        .setup(|app| {
+            let device_manager = app.state::<DeviceManagerState>().inner().clone();
+            tauri::async_runtime::spawn(emit_device_snapshots(app.handle().clone(), device_manager));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             greet,
             scan_devices,
@@ -98,7 +200,10 @@ pub fn run() {
             get_device_info,
             send_raw_command,
             get_connected_devices,
-            disconnect_all_devices
+            get_device_snapshot,
+            disconnect_all_devices,
+            start_device_watch,
+            stop_device_watch
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");